@@ -2,28 +2,760 @@
 pub enum ValueType {
     I32,
     I64,
+    F32,
+    F64,
+    V128,
+    FuncRef,
+    ExternRef,
+    ExnRef,
+    /// A reference to a function of the type `type_idx`, which can only be
+    /// null if it is `nullable`. All of them are also a `funcref`.
+    Ref {
+        nullable: bool,
+        type_idx: usize,
+    },
+}
+
+impl ValueType {
+    /// Whether locals of the type start out with a default value. Others
+    /// have to be set before they are read.
+    pub fn is_defaultable(&self) -> bool {
+        !matches!(
+            self,
+            ValueType::Ref {
+                nullable: false,
+                ..
+            }
+        )
+    }
+}
+
+/// Type of the references stored in a table.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub enum RefType {
+    FuncRef,
+    ExternRef,
+    /// A caught exception, which can be rethrown.
+    ExnRef,
+}
+
+impl From<RefType> for ValueType {
+    fn from(ref_type: RefType) -> Self {
+        match ref_type {
+            RefType::FuncRef => ValueType::FuncRef,
+            RefType::ExternRef => ValueType::ExternRef,
+            RefType::ExnRef => ValueType::ExnRef,
+        }
+    }
+}
+
+/// Heap type of `ref.null`, which is either abstract or, for typed
+/// function references, a type index.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub enum HeapType {
+    Func,
+    Extern,
+    Exn,
+    Type(usize),
+}
+
+impl From<HeapType> for ValueType {
+    fn from(heap_type: HeapType) -> Self {
+        match heap_type {
+            HeapType::Func => ValueType::FuncRef,
+            HeapType::Extern => ValueType::ExternRef,
+            HeapType::Exn => ValueType::ExnRef,
+            HeapType::Type(type_idx) => ValueType::Ref {
+                nullable: true,
+                type_idx,
+            },
+        }
+    }
 }
 
 pub type StackType = Vec<ValueType>;
 pub type FuncType = (StackType, StackType);
 pub type Type = FuncType;
 
-#[derive(Debug, PartialEq, Clone, Eq)]
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub enum BlockType {
+    Empty,
+    Value(ValueType),
+    /// Index of a function type, for blocks with parameters or several
+    /// results.
+    TypeIdx(usize),
+}
+
+/// A catch clause of `try_table`, which branches to its label when it
+/// catches an exception.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub enum Catch {
+    /// Catches exceptions with the tag and pushes their payload.
+    Tag(usize, usize),
+    /// Like `Tag`, but also pushes the exception as an `exnref`.
+    TagRef(usize, usize),
+    /// Catches all exceptions.
+    All(usize),
+    /// Catches all exceptions and pushes them as an `exnref`.
+    AllRef(usize),
+}
+
+/// Immediates of a memory access. `align` is the exponent of the alignment
+/// hint, e.g. 2 for a 4-byte alignment, and `memory` the accessed memory.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub struct MemArg {
+    pub align: u32,
+    pub offset: u64,
+    pub memory: usize,
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum Instr {
+    Unreachable,
+    Nop,
+    Block(BlockType, Vec<Instr>),
+    Loop(BlockType, Vec<Instr>),
+    If(BlockType, Vec<Instr>, Vec<Instr>),
+    Br(usize),
+    BrIf(usize),
+    BrTable(Vec<usize>, usize),
+    Return,
+    Call(usize),
+    /// Calls the function in a table, given the type index and table index.
+    CallIndirect(usize, usize),
+    /// Tail calls, which replace the frame of the calling function.
+    ReturnCall(usize),
+    ReturnCallIndirect(usize, usize),
+    /// Calls the function referenced on top of the stack, which has to be of
+    /// the given type.
+    CallRef(usize),
+    ReturnCallRef(usize),
+    /// Throws an exception with the tag, whose parameters are the payload.
+    Throw(usize),
+    ThrowRef,
+    /// A block whose catch clauses handle exceptions thrown inside of it.
+    /// Their labels are relative to the enclosing block.
+    TryTable(BlockType, Vec<Catch>, Vec<Instr>),
+
+    Drop,
+    /// Selects one of two operands. The type is only given explicitly for
+    /// typed `select`.
+    Select(Option<ValueType>),
+
+    RefNull(HeapType),
+    RefIsNull,
+    RefFunc(usize),
+    /// Traps if the reference is null.
+    RefAsNonNull,
+    /// Branches if the reference is null and keeps it otherwise.
+    BrOnNull(usize),
+    /// Branches with the reference if it isn't null and drops it otherwise.
+    BrOnNonNull(usize),
+
     LocalGet(usize),
+    LocalSet(usize),
+    LocalTee(usize),
+    GlobalGet(usize),
+    GlobalSet(usize),
+    TableGet(usize),
+    TableSet(usize),
+    TableSize(usize),
+    TableGrow(usize),
+    TableFill(usize),
+    /// Copies between tables, given the destination and source table index.
+    TableCopy(usize, usize),
+    /// Copies an element segment into a table, given the table index and
+    /// element index.
+    TableInit(usize, usize),
+    ElemDrop(usize),
+    I32Load(MemArg),
+    I64Load(MemArg),
+    F32Load(MemArg),
+    F64Load(MemArg),
+    I32Load8S(MemArg),
+    I32Load8U(MemArg),
+    I32Load16S(MemArg),
+    I32Load16U(MemArg),
+    I64Load8S(MemArg),
+    I64Load8U(MemArg),
+    I64Load16S(MemArg),
+    I64Load16U(MemArg),
+    I64Load32S(MemArg),
+    I64Load32U(MemArg),
+    I32Store(MemArg),
+    I64Store(MemArg),
+    F32Store(MemArg),
+    F64Store(MemArg),
+    I32Store8(MemArg),
+    I32Store16(MemArg),
+    I64Store8(MemArg),
+    I64Store16(MemArg),
+    I64Store32(MemArg),
+    MemorySize(usize),
+    MemoryGrow(usize),
+    /// Copies a data segment into a memory, given the memory index and data
+    /// index.
+    MemoryInit(usize, usize),
+    DataDrop(usize),
+    /// Copies between memories, given the destination and source memory
+    /// index.
+    MemoryCopy(usize, usize),
+    MemoryFill(usize),
+
+    I32Const(i32),
+    I64Const(i64),
+    F32Const(f32),
+    F64Const(f64),
+
+    I32Eqz,
+    I32Eq,
+    I32Ne,
+    I32LtS,
+    I32LtU,
+    I32GtS,
+    I32GtU,
+    I32LeS,
+    I32LeU,
+    I32GeS,
+    I32GeU,
+
+    I32Clz,
+    I32Ctz,
+    I32Popcnt,
     I32Add,
+    I32Sub,
+    I32Mul,
+    I32DivS,
+    I32DivU,
+    I32RemS,
+    I32RemU,
+    I32And,
+    I32Or,
+    I32Xor,
+    I32Shl,
+    I32ShrS,
+    I32ShrU,
+    I32Rotl,
+    I32Rotr,
+
+    I64Eqz,
+    I64Eq,
+    I64Ne,
+    I64LtS,
+    I64LtU,
+    I64GtS,
+    I64GtU,
+    I64LeS,
+    I64LeU,
+    I64GeS,
+    I64GeU,
+
+    I64Clz,
+    I64Ctz,
+    I64Popcnt,
+    I64Add,
+    I64Sub,
+    I64Mul,
+    I64DivS,
+    I64DivU,
+    I64RemS,
+    I64RemU,
+    I64And,
+    I64Or,
+    I64Xor,
+    I64Shl,
+    I64ShrS,
+    I64ShrU,
+    I64Rotl,
+    I64Rotr,
+
+    F32Eq,
+    F32Ne,
+    F32Lt,
+    F32Gt,
+    F32Le,
+    F32Ge,
+
+    F64Eq,
+    F64Ne,
+    F64Lt,
+    F64Gt,
+    F64Le,
+    F64Ge,
+
+    F32Abs,
+    F32Neg,
+    F32Ceil,
+    F32Floor,
+    F32Trunc,
+    F32Nearest,
+    F32Sqrt,
+    F32Add,
+    F32Sub,
+    F32Mul,
+    F32Div,
+    F32Min,
+    F32Max,
+    F32Copysign,
+
+    F64Abs,
+    F64Neg,
+    F64Ceil,
+    F64Floor,
+    F64Trunc,
+    F64Nearest,
+    F64Sqrt,
+    F64Add,
+    F64Sub,
+    F64Mul,
+    F64Div,
+    F64Min,
+    F64Max,
+    F64Copysign,
+
+    I32WrapI64,
+    I32TruncF32S,
+    I32TruncF32U,
+    I32TruncF64S,
+    I32TruncF64U,
+    I64ExtendI32S,
+    I64ExtendI32U,
+    I64TruncF32S,
+    I64TruncF32U,
+    I64TruncF64S,
+    I64TruncF64U,
+    F32ConvertI32S,
+    F32ConvertI32U,
+    F32ConvertI64S,
+    F32ConvertI64U,
+    F32DemoteF64,
+    F64ConvertI32S,
+    F64ConvertI32U,
+    F64ConvertI64S,
+    F64ConvertI64U,
+    F64PromoteF32,
+    I32ReinterpretF32,
+    I64ReinterpretF64,
+    F32ReinterpretI32,
+    F64ReinterpretI64,
+
+    I32Extend8S,
+    I32Extend16S,
+    I64Extend8S,
+    I64Extend16S,
+    I64Extend32S,
+
+    I32TruncSatF32S,
+    I32TruncSatF32U,
+    I32TruncSatF64S,
+    I32TruncSatF64U,
+    I64TruncSatF32S,
+    I64TruncSatF32U,
+    I64TruncSatF64S,
+    I64TruncSatF64U,
+
+    V128Load(MemArg),
+    V128Load8x8S(MemArg),
+    V128Load8x8U(MemArg),
+    V128Load16x4S(MemArg),
+    V128Load16x4U(MemArg),
+    V128Load32x2S(MemArg),
+    V128Load32x2U(MemArg),
+    V128Load8Splat(MemArg),
+    V128Load16Splat(MemArg),
+    V128Load32Splat(MemArg),
+    V128Load64Splat(MemArg),
+    V128Load32Zero(MemArg),
+    V128Load64Zero(MemArg),
+    V128Store(MemArg),
+    /// Loads into or stores from the lane with the given index.
+    V128Load8Lane(MemArg, u8),
+    V128Load16Lane(MemArg, u8),
+    V128Load32Lane(MemArg, u8),
+    V128Load64Lane(MemArg, u8),
+    V128Store8Lane(MemArg, u8),
+    V128Store16Lane(MemArg, u8),
+    V128Store32Lane(MemArg, u8),
+    V128Store64Lane(MemArg, u8),
+    /// The lanes are stored in little-endian order, so lane 0 is in the
+    /// least significant bits.
+    V128Const(u128),
+    /// Selects each byte of the result by its index into the bytes of both
+    /// operands.
+    I8x16Shuffle([u8; 16]),
+    I8x16ExtractLaneS(u8),
+    I8x16ExtractLaneU(u8),
+    I8x16ReplaceLane(u8),
+    I16x8ExtractLaneS(u8),
+    I16x8ExtractLaneU(u8),
+    I16x8ReplaceLane(u8),
+    I32x4ExtractLane(u8),
+    I32x4ReplaceLane(u8),
+    I64x2ExtractLane(u8),
+    I64x2ReplaceLane(u8),
+    F32x4ExtractLane(u8),
+    F32x4ReplaceLane(u8),
+    F64x2ExtractLane(u8),
+    F64x2ReplaceLane(u8),
+    Simd(SimdOp),
+
+    MemoryAtomicNotify(MemArg),
+    MemoryAtomicWait32(MemArg),
+    MemoryAtomicWait64(MemArg),
+    AtomicFence,
+    AtomicLoad(AtomicWidth, MemArg),
+    AtomicStore(AtomicWidth, MemArg),
+    AtomicRmw(AtomicRmwOp, AtomicWidth, MemArg),
 }
 
-#[derive(Debug, PartialEq, Clone, Eq)]
+/// The SIMD instructions without immediates, which operate on the lanes of
+/// `v128` values.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub enum SimdOp {
+    I8x16Swizzle,
+    I8x16Splat,
+    I16x8Splat,
+    I32x4Splat,
+    I64x2Splat,
+    F32x4Splat,
+    F64x2Splat,
+    I8x16Eq,
+    I8x16Ne,
+    I8x16LtS,
+    I8x16LtU,
+    I8x16GtS,
+    I8x16GtU,
+    I8x16LeS,
+    I8x16LeU,
+    I8x16GeS,
+    I8x16GeU,
+    I16x8Eq,
+    I16x8Ne,
+    I16x8LtS,
+    I16x8LtU,
+    I16x8GtS,
+    I16x8GtU,
+    I16x8LeS,
+    I16x8LeU,
+    I16x8GeS,
+    I16x8GeU,
+    I32x4Eq,
+    I32x4Ne,
+    I32x4LtS,
+    I32x4LtU,
+    I32x4GtS,
+    I32x4GtU,
+    I32x4LeS,
+    I32x4LeU,
+    I32x4GeS,
+    I32x4GeU,
+    F32x4Eq,
+    F32x4Ne,
+    F32x4Lt,
+    F32x4Gt,
+    F32x4Le,
+    F32x4Ge,
+    F64x2Eq,
+    F64x2Ne,
+    F64x2Lt,
+    F64x2Gt,
+    F64x2Le,
+    F64x2Ge,
+    V128Not,
+    V128And,
+    V128AndNot,
+    V128Or,
+    V128Xor,
+    V128Bitselect,
+    V128AnyTrue,
+    F32x4DemoteF64x2Zero,
+    F64x2PromoteLowF32x4,
+    I8x16Abs,
+    I8x16Neg,
+    I8x16Popcnt,
+    I8x16AllTrue,
+    I8x16Bitmask,
+    I8x16NarrowI16x8S,
+    I8x16NarrowI16x8U,
+    F32x4Ceil,
+    F32x4Floor,
+    F32x4Trunc,
+    F32x4Nearest,
+    I8x16Shl,
+    I8x16ShrS,
+    I8x16ShrU,
+    I8x16Add,
+    I8x16AddSatS,
+    I8x16AddSatU,
+    I8x16Sub,
+    I8x16SubSatS,
+    I8x16SubSatU,
+    F64x2Ceil,
+    F64x2Floor,
+    I8x16MinS,
+    I8x16MinU,
+    I8x16MaxS,
+    I8x16MaxU,
+    F64x2Trunc,
+    I8x16AvgrU,
+    I16x8ExtaddPairwiseI8x16S,
+    I16x8ExtaddPairwiseI8x16U,
+    I32x4ExtaddPairwiseI16x8S,
+    I32x4ExtaddPairwiseI16x8U,
+    I16x8Abs,
+    I16x8Neg,
+    I16x8Q15mulrSatS,
+    I16x8AllTrue,
+    I16x8Bitmask,
+    I16x8NarrowI32x4S,
+    I16x8NarrowI32x4U,
+    I16x8ExtendLowI8x16S,
+    I16x8ExtendHighI8x16S,
+    I16x8ExtendLowI8x16U,
+    I16x8ExtendHighI8x16U,
+    I16x8Shl,
+    I16x8ShrS,
+    I16x8ShrU,
+    I16x8Add,
+    I16x8AddSatS,
+    I16x8AddSatU,
+    I16x8Sub,
+    I16x8SubSatS,
+    I16x8SubSatU,
+    F64x2Nearest,
+    I16x8Mul,
+    I16x8MinS,
+    I16x8MinU,
+    I16x8MaxS,
+    I16x8MaxU,
+    I16x8AvgrU,
+    I16x8ExtmulLowI8x16S,
+    I16x8ExtmulHighI8x16S,
+    I16x8ExtmulLowI8x16U,
+    I16x8ExtmulHighI8x16U,
+    I32x4Abs,
+    I32x4Neg,
+    I32x4AllTrue,
+    I32x4Bitmask,
+    I32x4ExtendLowI16x8S,
+    I32x4ExtendHighI16x8S,
+    I32x4ExtendLowI16x8U,
+    I32x4ExtendHighI16x8U,
+    I32x4Shl,
+    I32x4ShrS,
+    I32x4ShrU,
+    I32x4Add,
+    I32x4Sub,
+    I32x4Mul,
+    I32x4MinS,
+    I32x4MinU,
+    I32x4MaxS,
+    I32x4MaxU,
+    I32x4DotI16x8S,
+    I32x4ExtmulLowI16x8S,
+    I32x4ExtmulHighI16x8S,
+    I32x4ExtmulLowI16x8U,
+    I32x4ExtmulHighI16x8U,
+    I64x2Abs,
+    I64x2Neg,
+    I64x2AllTrue,
+    I64x2Bitmask,
+    I64x2ExtendLowI32x4S,
+    I64x2ExtendHighI32x4S,
+    I64x2ExtendLowI32x4U,
+    I64x2ExtendHighI32x4U,
+    I64x2Shl,
+    I64x2ShrS,
+    I64x2ShrU,
+    I64x2Add,
+    I64x2Sub,
+    I64x2Mul,
+    I64x2Eq,
+    I64x2Ne,
+    I64x2LtS,
+    I64x2GtS,
+    I64x2LeS,
+    I64x2GeS,
+    I64x2ExtmulLowI32x4S,
+    I64x2ExtmulHighI32x4S,
+    I64x2ExtmulLowI32x4U,
+    I64x2ExtmulHighI32x4U,
+    F32x4Abs,
+    F32x4Neg,
+    F32x4Sqrt,
+    F32x4Add,
+    F32x4Sub,
+    F32x4Mul,
+    F32x4Div,
+    F32x4Min,
+    F32x4Max,
+    F32x4Pmin,
+    F32x4Pmax,
+    F64x2Abs,
+    F64x2Neg,
+    F64x2Sqrt,
+    F64x2Add,
+    F64x2Sub,
+    F64x2Mul,
+    F64x2Div,
+    F64x2Min,
+    F64x2Max,
+    F64x2Pmin,
+    F64x2Pmax,
+    I32x4TruncSatF32x4S,
+    I32x4TruncSatF32x4U,
+    F32x4ConvertI32x4S,
+    F32x4ConvertI32x4U,
+    I32x4TruncSatF64x2SZero,
+    I32x4TruncSatF64x2UZero,
+    F64x2ConvertLowI32x4S,
+    F64x2ConvertLowI32x4U,
+}
+
+/// Value type and access size of an atomic memory instruction. Narrow
+/// accesses zero-extend the value they load.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub enum AtomicWidth {
+    I32,
+    I64,
+    I32U8,
+    I32U16,
+    I64U8,
+    I64U16,
+    I64U32,
+}
+
+impl AtomicWidth {
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            AtomicWidth::I32 | AtomicWidth::I32U8 | AtomicWidth::I32U16 => ValueType::I32,
+            _ => ValueType::I64,
+        }
+    }
+
+    /// Number of bytes accessed in memory.
+    pub fn bytes(&self) -> usize {
+        match self {
+            AtomicWidth::I32U8 | AtomicWidth::I64U8 => 1,
+            AtomicWidth::I32U16 | AtomicWidth::I64U16 => 2,
+            AtomicWidth::I32 | AtomicWidth::I64U32 => 4,
+            AtomicWidth::I64 => 8,
+        }
+    }
+}
+
+/// Atomic read-modify-write operations, which all return the value that was
+/// in memory before.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub enum AtomicRmwOp {
+    Add,
+    Sub,
+    And,
+    Or,
+    Xor,
+    Xchg,
+    Cmpxchg,
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct Func {
     pub f_type: i32,
     pub locals: Vec<ValueType>,
     pub body: Vec<Instr>,
 }
 
+/// Size limits in units of pages of a memory or in elements of a table.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub struct Limits {
+    pub min: u64,
+    pub max: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub struct Memory {
+    pub limits: Limits,
+    /// Shared memories can be used by several threads at once.
+    pub shared: bool,
+    /// 64-bit memories are addressed by `i64` instead of `i32` operands.
+    pub memory64: bool,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub struct Table {
+    pub limits: Limits,
+    pub ref_type: RefType,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub struct GlobalType {
+    pub value_type: ValueType,
+    pub mutable: bool,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Global {
+    pub g_type: GlobalType,
+    /// Constant expression computing the initial value.
+    pub init: Vec<Instr>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum DataMode {
+    /// The segment can only be copied into a memory with `memory.init`.
+    Passive,
+    /// The segment is copied into `memory` at `offset` during instantiation.
+    Active { memory: usize, offset: Vec<Instr> },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ElemMode {
+    /// The segment can only be copied into a table with `table.init`.
+    Passive,
+    /// The segment is copied into `table` at `offset` during instantiation.
+    Active { table: usize, offset: Vec<Instr> },
+    /// The segment only declares functions that may be referenced.
+    Declarative,
+}
+
+/// An element segment, which initializes a table with references of type
+/// `ref_type`. Every element is given by a constant expression, e.g.
+/// `ref.func` or `ref.null`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Elem {
+    pub ref_type: RefType,
+    pub init: Vec<Vec<Instr>>,
+    pub mode: ElemMode,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Data {
+    pub init: Vec<u8>,
+    pub mode: DataMode,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ImportDesc {
+    /// A function with the given type index.
+    Func(usize),
+    Table(Table),
+    Memory(Memory),
+    Global(GlobalType),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Import {
+    pub module: String,
+    pub name: String,
+    pub desc: ImportDesc,
+}
+
 #[derive(Debug, PartialEq, Clone, Eq)]
 pub enum EDesc {
     FuncExport(usize),
+    TableExport(usize),
+    MemoryExport(usize),
+    GlobalExport(usize),
+    TagExport(usize),
 }
 
 #[derive(Debug, PartialEq, Clone, Eq)]
@@ -35,6 +767,64 @@ pub struct Export {
 #[derive(Debug, PartialEq)]
 pub struct Module {
     pub types: Vec<Type>,
+    pub imports: Vec<Import>,
     pub funcs: Vec<Func>,
+    pub tables: Vec<Table>,
+    pub memories: Vec<Memory>,
+    /// Type indices of the exception tags. The parameters of the type are
+    /// the payload of the exceptions.
+    pub tags: Vec<usize>,
+    pub globals: Vec<Global>,
+    pub elems: Vec<Elem>,
+    pub datas: Vec<Data>,
     pub exports: Vec<Export>,
+    /// Function that runs when the module is instantiated.
+    pub start: Option<usize>,
+}
+
+/// Imported entities come first in their index space, followed by the ones
+/// the module defines.
+impl Module {
+    pub fn num_imported_funcs(&self) -> usize {
+        self.imports
+            .iter()
+            .filter(|i| matches!(i.desc, ImportDesc::Func(_)))
+            .count()
+    }
+
+    /// Type index of every function in the function index space.
+    pub fn func_types(&self) -> Vec<usize> {
+        let imported = self.imports.iter().filter_map(|i| match i.desc {
+            ImportDesc::Func(type_idx) => Some(type_idx),
+            _ => None,
+        });
+        let defined = self.funcs.iter().map(|f| f.f_type as usize);
+        imported.chain(defined).collect()
+    }
+
+    pub fn table_types(&self) -> Vec<Table> {
+        let imported = self.imports.iter().filter_map(|i| match i.desc {
+            ImportDesc::Table(table) => Some(table),
+            _ => None,
+        });
+        imported.chain(self.tables.iter().copied()).collect()
+    }
+
+    pub fn memory_types(&self) -> Vec<Memory> {
+        let imported = self.imports.iter().filter_map(|i| match i.desc {
+            ImportDesc::Memory(memory) => Some(memory),
+            _ => None,
+        });
+        imported.chain(self.memories.iter().copied()).collect()
+    }
+
+    pub fn global_types(&self) -> Vec<GlobalType> {
+        let imported = self.imports.iter().filter_map(|i| match i.desc {
+            ImportDesc::Global(g_type) => Some(g_type),
+            _ => None,
+        });
+        imported
+            .chain(self.globals.iter().map(|g| g.g_type))
+            .collect()
+    }
 }
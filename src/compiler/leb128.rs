@@ -1,13 +1,76 @@
 pub fn from_u32(value: u32) -> Vec<u8> {
-    fn encode(i: u32, r: &[u8]) -> Vec<u8> {
-        let b = i & 0x7fu32;
+    from_u64(value as u64)
+}
+
+pub fn from_u64(value: u64) -> Vec<u8> {
+    fn encode(i: u64, r: &[u8]) -> Vec<u8> {
+        let b = i & 0x7fu64;
         let ii = i >> 7;
         if ii == 0 {
             [r, &[b as u8]].concat()
         } else {
-            let r = [r, &[(0x80u32 | b) as u8]].concat();
+            let r = [r, &[(0x80u64 | b) as u8]].concat();
             encode(ii, &r)
         }
     }
     encode(value, &[]).to_vec()
 }
+
+pub fn from_i32(value: i32) -> Vec<u8> {
+    from_i64(value as i64)
+}
+
+pub fn from_i64(value: i64) -> Vec<u8> {
+    fn encode(i: i64, r: &[u8]) -> Vec<u8> {
+        let b = i & 0x7fi64;
+        // Arithmetic shift, so negative values converge to -1.
+        let ii = i >> 7;
+        let sign_bit_clear = b & 0x40 == 0;
+        if (ii == 0 && sign_bit_clear) || (ii == -1 && !sign_bit_clear) {
+            [r, &[b as u8]].concat()
+        } else {
+            let r = [r, &[(0x80i64 | b) as u8]].concat();
+            encode(ii, &r)
+        }
+    }
+    encode(value, &[]).to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsigned() {
+        assert_eq!(from_u32(0), [0x00]);
+        assert_eq!(from_u32(127), [0x7f]);
+        assert_eq!(from_u32(128), [0x80, 0x01]);
+        assert_eq!(from_u32(624485), [0xe5, 0x8e, 0x26]);
+        assert_eq!(from_u32(u32::MAX), [0xff, 0xff, 0xff, 0xff, 0x0f]);
+        assert_eq!(
+            from_u64(u64::MAX),
+            [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]
+        );
+    }
+
+    #[test]
+    fn signed() {
+        assert_eq!(from_i32(0), [0x00]);
+        assert_eq!(from_i32(63), [0x3f]);
+        assert_eq!(from_i32(64), [0xc0, 0x00]);
+        assert_eq!(from_i32(-1), [0x7f]);
+        assert_eq!(from_i32(-64), [0x40]);
+        assert_eq!(from_i32(-65), [0xbf, 0x7f]);
+        assert_eq!(from_i32(-123456), [0xc0, 0xbb, 0x78]);
+        assert_eq!(from_i32(i32::MIN), [0x80, 0x80, 0x80, 0x80, 0x78]);
+        assert_eq!(from_i32(i32::MAX), [0xff, 0xff, 0xff, 0xff, 0x07]);
+        assert_eq!(
+            from_i64(i64::MIN),
+            [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7f]
+        );
+        assert_eq!(
+            from_i64(i64::MAX),
+            [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00]
+        );
+    }
+}
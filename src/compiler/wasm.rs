@@ -1,15 +1,35 @@
-use crate::ast::{EDesc, Export, Func, Instr, Module, Type};
-use crate::compiler::leb128::from_u32;
+use crate::ast::{
+    BlockType, Catch, Data, DataMode, EDesc, Elem, ElemMode, Export, Func, Global, GlobalType,
+    HeapType, Import, ImportDesc, Instr, Limits, MemArg, Memory, Module, RefType, Table, Type,
+    ValueType,
+};
+use crate::compiler::leb128::{from_i32, from_i64, from_u32, from_u64};
 use crate::op_codes::*;
 
+/// Encodes `vt`, whose type index, if any, is a positive signed 33 bit
+/// integer like in block types.
+fn encode_value_type(vt: &ValueType) -> Vec<u8> {
+    match vt {
+        ValueType::Ref { type_idx, .. } => {
+            [vec![val_type(vt)], from_i64(*type_idx as i64)].concat()
+        }
+        _ => vec![val_type(vt)],
+    }
+}
+
+fn encode_section(code: u8, num_items: usize, body: Vec<u8>) -> Vec<u8> {
+    let body = [from_u32(num_items as u32), body].concat();
+    [vec![code], from_u32(body.len() as u32), body].concat()
+}
+
 fn encode_type_section(ast: &Module) -> Vec<u8> {
     fn encode_type(t: &Type) -> Vec<u8> {
-        vec![
+        [
             vec![control_flow::FUNC],
-            vec![t.0.len() as u8],
-            t.0.iter().map(val_type).collect::<Vec<u8>>(),
-            vec![t.1.len() as u8],
-            t.1.iter().map(val_type).collect::<Vec<u8>>(),
+            from_u32(t.0.len() as u32),
+            t.0.iter().flat_map(encode_value_type).collect::<Vec<u8>>(),
+            from_u32(t.1.len() as u32),
+            t.1.iter().flat_map(encode_value_type).collect::<Vec<u8>>(),
         ]
         .concat()
     }
@@ -17,17 +37,44 @@ fn encode_type_section(ast: &Module) -> Vec<u8> {
     let body: Vec<u8> = ast
         .types
         .iter()
-        .map(|t| encode_type(t))
+        .map(encode_type)
         .collect::<Vec<Vec<u8>>>()
         .concat();
 
-    vec![
-        vec![section::TYPE],
-        from_u32((body.len() + 1) as u32),
-        from_u32(ast.types.len() as u32),
-        body,
-    ]
-    .concat()
+    encode_section(section::TYPE, ast.types.len(), body)
+}
+
+fn encode_name(name: &str) -> Vec<u8> {
+    [from_u32(name.len() as u32), name.as_bytes().to_vec()].concat()
+}
+
+fn encode_import_section(ast: &Module) -> Vec<u8> {
+    fn encode_import(import: &Import) -> Vec<u8> {
+        let desc = match &import.desc {
+            ImportDesc::Func(type_idx) => {
+                [vec![indices::FUNC], from_u32(*type_idx as u32)].concat()
+            }
+            ImportDesc::Table(table) => [vec![indices::TABLE], encode_table(table)].concat(),
+            ImportDesc::Memory(memory) => [vec![indices::MEMORY], encode_memory(memory)].concat(),
+            ImportDesc::Global(g_type) => {
+                [vec![indices::GLOBAL], encode_global_type(g_type)].concat()
+            }
+        };
+
+        [encode_name(&import.module), encode_name(&import.name), desc].concat()
+    }
+
+    if ast.imports.is_empty() {
+        vec![]
+    } else {
+        let body = ast
+            .imports
+            .iter()
+            .map(encode_import)
+            .collect::<Vec<Vec<u8>>>()
+            .concat();
+        encode_section(section::IMPORT, ast.imports.len(), body)
+    }
 }
 
 fn encode_func_section(ast: &Module) -> Vec<u8> {
@@ -37,28 +84,132 @@ fn encode_func_section(ast: &Module) -> Vec<u8> {
         let body = ast
             .funcs
             .iter()
-            .map(|f| f.f_type as u8)
-            .collect::<Vec<u8>>();
-        vec![
-            vec![section::FUNC],
-            from_u32((body.len() + 1) as u32),
-            from_u32(ast.funcs.len() as u32),
-            body,
+            .map(|f| from_u32(f.f_type as u32))
+            .collect::<Vec<Vec<u8>>>()
+            .concat();
+        encode_section(section::FUNC, ast.funcs.len(), body)
+    }
+}
+
+fn encode_limits(limits: &Limits) -> Vec<u8> {
+    match limits.max {
+        None => [vec![limits::MIN], from_u64(limits.min)].concat(),
+        Some(max) => [vec![limits::MIN_MAX], from_u64(limits.min), from_u64(max)].concat(),
+    }
+}
+
+fn encode_memory(memory: &Memory) -> Vec<u8> {
+    let mut bytes = encode_limits(&memory.limits);
+    if memory.shared {
+        bytes[0] |= limits::SHARED;
+    }
+    if memory.memory64 {
+        bytes[0] |= limits::MEMORY64;
+    }
+    bytes
+}
+
+fn encode_table(table: &Table) -> Vec<u8> {
+    [
+        vec![ref_type(&table.ref_type)],
+        encode_limits(&table.limits),
+    ]
+    .concat()
+}
+
+fn encode_table_section(ast: &Module) -> Vec<u8> {
+    if ast.tables.is_empty() {
+        vec![]
+    } else {
+        let body = ast
+            .tables
+            .iter()
+            .map(encode_table)
+            .collect::<Vec<Vec<u8>>>()
+            .concat();
+        encode_section(section::TABLE, ast.tables.len(), body)
+    }
+}
+
+fn encode_memory_section(ast: &Module) -> Vec<u8> {
+    if ast.memories.is_empty() {
+        vec![]
+    } else {
+        let body = ast
+            .memories
+            .iter()
+            .map(encode_memory)
+            .collect::<Vec<Vec<u8>>>()
+            .concat();
+        encode_section(section::MEMORY, ast.memories.len(), body)
+    }
+}
+
+fn encode_tag_section(ast: &Module) -> Vec<u8> {
+    if ast.tags.is_empty() {
+        vec![]
+    } else {
+        let body = ast
+            .tags
+            .iter()
+            .map(|type_idx| [vec![TAG_EXCEPTION], from_u32(*type_idx as u32)].concat())
+            .collect::<Vec<Vec<u8>>>()
+            .concat();
+        encode_section(section::TAG, ast.tags.len(), body)
+    }
+}
+
+fn encode_global_type(g_type: &GlobalType) -> Vec<u8> {
+    [
+        encode_value_type(&g_type.value_type),
+        vec![match g_type.mutable {
+            false => mutability::CONST,
+            true => mutability::VAR,
+        }],
+    ]
+    .concat()
+}
+
+fn encode_global_section(ast: &Module) -> Vec<u8> {
+    fn encode_global(global: &Global) -> Vec<u8> {
+        [
+            encode_global_type(&global.g_type),
+            encode_instrs(&global.init),
+            vec![control_flow::END],
         ]
         .concat()
     }
+
+    if ast.globals.is_empty() {
+        vec![]
+    } else {
+        let body = ast
+            .globals
+            .iter()
+            .map(encode_global)
+            .collect::<Vec<Vec<u8>>>()
+            .concat();
+        encode_section(section::GLOBAL, ast.globals.len(), body)
+    }
 }
 
 fn encode_export_section(ast: &Module) -> Vec<u8> {
     fn encode_export(export: &Export) -> Vec<u8> {
-        vec![
-            from_u32(export.name.len() as u32),
-            export.name.as_bytes().to_vec(),
+        [
+            encode_name(&export.name),
             match export.e_desc {
                 EDesc::FuncExport(_) => vec![indices::FUNC],
+                EDesc::TableExport(_) => vec![indices::TABLE],
+                EDesc::MemoryExport(_) => vec![indices::MEMORY],
+                EDesc::GlobalExport(_) => vec![indices::GLOBAL],
+                EDesc::TagExport(_) => vec![indices::TAG],
             },
             match export.e_desc {
-                EDesc::FuncExport(idx) => vec![idx as u8],
+                EDesc::FuncExport(idx)
+                | EDesc::TableExport(idx)
+                | EDesc::MemoryExport(idx)
+                | EDesc::GlobalExport(idx)
+                | EDesc::TagExport(idx) => from_u32(idx as u32),
             },
         ]
         .concat()
@@ -73,63 +224,751 @@ fn encode_export_section(ast: &Module) -> Vec<u8> {
             .map(encode_export)
             .collect::<Vec<Vec<u8>>>()
             .concat();
-        vec![
-            vec![section::EXPORT],
-            from_u32((body.len() + 1) as u32),
-            from_u32(ast.exports.len() as u32),
-            body,
+        encode_section(section::EXPORT, ast.exports.len(), body)
+    }
+}
+
+fn encode_block_type(bt: &BlockType) -> Vec<u8> {
+    match bt {
+        BlockType::Empty => vec![control_flow::EMPTY_BLOCK],
+        BlockType::Value(vt) => encode_value_type(vt),
+        BlockType::TypeIdx(idx) => from_i64(*idx as i64),
+    }
+}
+
+fn encode_catch(catch: &Catch) -> Vec<u8> {
+    match catch {
+        Catch::Tag(tag, label) => [
+            vec![catch_kind::CATCH],
+            from_u32(*tag as u32),
+            from_u32(*label as u32),
         ]
-        .concat()
+        .concat(),
+        Catch::TagRef(tag, label) => [
+            vec![catch_kind::CATCH_REF],
+            from_u32(*tag as u32),
+            from_u32(*label as u32),
+        ]
+        .concat(),
+        Catch::All(label) => [vec![catch_kind::CATCH_ALL], from_u32(*label as u32)].concat(),
+        Catch::AllRef(label) => [vec![catch_kind::CATCH_ALL_REF], from_u32(*label as u32)].concat(),
     }
 }
 
-fn encode_code_section(ast: &Module) -> Vec<u8> {
-    fn encode_func(func: &Func) -> Vec<u8> {
-        fn encode_instr(instr: &Instr) -> Vec<u8> {
-            match instr {
-                Instr::LocalGet(idx) => vec![var_instr::LOCAL_GET, (*idx as u8)],
-                Instr::I32Add => vec![num_instr::I32_ADD],
-            }
-        }
+/// Encodes a memory argument. The index of memories other than 0 follows
+/// the alignment, which is flagged accordingly.
+fn encode_memarg(memarg: &MemArg) -> Vec<u8> {
+    match memarg.memory {
+        0 => [from_u32(memarg.align), from_u64(memarg.offset)].concat(),
+        memory => [
+            from_u32(memarg.align | memarg::MEMORY_IDX),
+            from_u32(memory as u32),
+            from_u64(memarg.offset),
+        ]
+        .concat(),
+    }
+}
 
-        let body = vec![
-            vec![func.locals.len() as u8], // local decl count
-            func.body
+fn encode_mem_instr(op_code: u8, memarg: &MemArg) -> Vec<u8> {
+    [vec![op_code], encode_memarg(memarg)].concat()
+}
+
+/// Encodes a SIMD instruction with its immediates.
+fn encode_simd_instr(op_code: u32, immediates: &[u8]) -> Vec<u8> {
+    [vec![prefix::SIMD], from_u32(op_code), immediates.to_vec()].concat()
+}
+
+fn encode_simd_mem_instr(op_code: u32, memarg: &MemArg, lane: Option<u8>) -> Vec<u8> {
+    let immediates = [encode_memarg(memarg), lane.into_iter().collect()].concat();
+    encode_simd_instr(op_code, &immediates)
+}
+
+fn encode_atomic_instr(op_code: u32, memarg: &MemArg) -> Vec<u8> {
+    [
+        vec![prefix::THREADS],
+        from_u32(op_code),
+        encode_memarg(memarg),
+    ]
+    .concat()
+}
+
+fn encode_instrs(instrs: &[Instr]) -> Vec<u8> {
+    instrs
+        .iter()
+        .map(encode_instr)
+        .collect::<Vec<Vec<u8>>>()
+        .concat()
+}
+
+/// Encodes an instruction. Only blocks are encoded here, since they recurse
+/// and every level of nesting would otherwise pay for the frame of the
+/// large match in `encode_plain_instr`.
+fn encode_instr(instr: &Instr) -> Vec<u8> {
+    match instr {
+        Instr::Block(bt, body) => [
+            vec![control_flow::BLOCK],
+            encode_block_type(bt),
+            encode_instrs(body),
+            vec![control_flow::END],
+        ]
+        .concat(),
+        Instr::Loop(bt, body) => [
+            vec![control_flow::LOOP],
+            encode_block_type(bt),
+            encode_instrs(body),
+            vec![control_flow::END],
+        ]
+        .concat(),
+        Instr::If(bt, then, els) => [
+            vec![control_flow::IF],
+            encode_block_type(bt),
+            encode_instrs(then),
+            if els.is_empty() {
+                vec![]
+            } else {
+                [vec![control_flow::ELSE], encode_instrs(els)].concat()
+            },
+            vec![control_flow::END],
+        ]
+        .concat(),
+        Instr::TryTable(bt, catches, body) => [
+            vec![control_flow::TRY_TABLE],
+            encode_block_type(bt),
+            from_u32(catches.len() as u32),
+            catches
+                .iter()
+                .map(encode_catch)
+                .collect::<Vec<Vec<u8>>>()
+                .concat(),
+            encode_instrs(body),
+            vec![control_flow::END],
+        ]
+        .concat(),
+        instr => encode_plain_instr(instr),
+    }
+}
+
+#[inline(never)]
+fn encode_plain_instr(instr: &Instr) -> Vec<u8> {
+    match instr {
+        Instr::Unreachable => vec![control_flow::UNREACHABLE],
+        Instr::Nop => vec![control_flow::NOP],
+        Instr::Br(label) => [vec![control_flow::BR], from_u32(*label as u32)].concat(),
+        Instr::BrIf(label) => [vec![control_flow::BR_IF], from_u32(*label as u32)].concat(),
+        Instr::BrTable(labels, default) => [
+            vec![control_flow::BR_TABLE],
+            from_u32(labels.len() as u32),
+            labels
                 .iter()
-                .map(encode_instr)
+                .map(|l| from_u32(*l as u32))
                 .collect::<Vec<Vec<u8>>>()
                 .concat(),
+            from_u32(*default as u32),
+        ]
+        .concat(),
+        Instr::Return => vec![control_flow::RETURN],
+        Instr::Call(idx) => [vec![control_flow::CALL], from_u32(*idx as u32)].concat(),
+        Instr::CallIndirect(type_idx, table) => [
+            vec![control_flow::CALL_INDIRECT],
+            from_u32(*type_idx as u32),
+            from_u32(*table as u32),
+        ]
+        .concat(),
+        Instr::ReturnCall(idx) => [vec![control_flow::RETURN_CALL], from_u32(*idx as u32)].concat(),
+        Instr::ReturnCallIndirect(type_idx, table) => [
+            vec![control_flow::RETURN_CALL_INDIRECT],
+            from_u32(*type_idx as u32),
+            from_u32(*table as u32),
+        ]
+        .concat(),
+        Instr::CallRef(type_idx) => {
+            [vec![control_flow::CALL_REF], from_u32(*type_idx as u32)].concat()
+        }
+        Instr::ReturnCallRef(type_idx) => [
+            vec![control_flow::RETURN_CALL_REF],
+            from_u32(*type_idx as u32),
+        ]
+        .concat(),
+        Instr::Throw(tag) => [vec![control_flow::THROW], from_u32(*tag as u32)].concat(),
+        Instr::ThrowRef => vec![control_flow::THROW_REF],
+        Instr::Drop => vec![parametric::DROP],
+        Instr::Select(None) => vec![parametric::SELECT],
+        Instr::Select(Some(vt)) => {
+            [vec![parametric::SELECT_TYPED, 0x01], encode_value_type(vt)].concat()
+        }
+
+        Instr::RefNull(HeapType::Type(type_idx)) => {
+            [vec![ref_instr::REF_NULL], from_i64(*type_idx as i64)].concat()
+        }
+        Instr::RefNull(ht) => vec![ref_instr::REF_NULL, val_type(&(*ht).into())],
+        Instr::RefIsNull => vec![ref_instr::REF_IS_NULL],
+        Instr::RefFunc(idx) => [vec![ref_instr::REF_FUNC], from_u32(*idx as u32)].concat(),
+        Instr::RefAsNonNull => vec![ref_instr::REF_AS_NON_NULL],
+        Instr::BrOnNull(label) => [vec![ref_instr::BR_ON_NULL], from_u32(*label as u32)].concat(),
+        Instr::BrOnNonNull(label) => {
+            [vec![ref_instr::BR_ON_NON_NULL], from_u32(*label as u32)].concat()
+        }
+
+        Instr::LocalGet(idx) => [vec![var_instr::LOCAL_GET], from_u32(*idx as u32)].concat(),
+        Instr::LocalSet(idx) => [vec![var_instr::LOCAL_SET], from_u32(*idx as u32)].concat(),
+        Instr::LocalTee(idx) => [vec![var_instr::LOCAL_TEE], from_u32(*idx as u32)].concat(),
+        Instr::GlobalGet(idx) => [vec![var_instr::GLOBAL_GET], from_u32(*idx as u32)].concat(),
+        Instr::GlobalSet(idx) => [vec![var_instr::GLOBAL_SET], from_u32(*idx as u32)].concat(),
+        Instr::TableGet(idx) => [vec![table_instr::TABLE_GET], from_u32(*idx as u32)].concat(),
+        Instr::TableSet(idx) => [vec![table_instr::TABLE_SET], from_u32(*idx as u32)].concat(),
+        Instr::TableInit(table, elem) => [
+            vec![prefix::MISC],
+            from_u32(misc_instr::TABLE_INIT),
+            from_u32(*elem as u32),
+            from_u32(*table as u32),
+        ]
+        .concat(),
+        Instr::ElemDrop(idx) => [
+            vec![prefix::MISC],
+            from_u32(misc_instr::ELEM_DROP),
+            from_u32(*idx as u32),
+        ]
+        .concat(),
+        Instr::TableCopy(dst, src) => [
+            vec![prefix::MISC],
+            from_u32(misc_instr::TABLE_COPY),
+            from_u32(*dst as u32),
+            from_u32(*src as u32),
+        ]
+        .concat(),
+        Instr::TableGrow(idx) => [
+            vec![prefix::MISC],
+            from_u32(misc_instr::TABLE_GROW),
+            from_u32(*idx as u32),
+        ]
+        .concat(),
+        Instr::TableSize(idx) => [
+            vec![prefix::MISC],
+            from_u32(misc_instr::TABLE_SIZE),
+            from_u32(*idx as u32),
+        ]
+        .concat(),
+        Instr::TableFill(idx) => [
+            vec![prefix::MISC],
+            from_u32(misc_instr::TABLE_FILL),
+            from_u32(*idx as u32),
+        ]
+        .concat(),
+        Instr::I32Load(m) => encode_mem_instr(mem_instr::I32_LOAD, m),
+        Instr::I64Load(m) => encode_mem_instr(mem_instr::I64_LOAD, m),
+        Instr::F32Load(m) => encode_mem_instr(mem_instr::F32_LOAD, m),
+        Instr::F64Load(m) => encode_mem_instr(mem_instr::F64_LOAD, m),
+        Instr::I32Load8S(m) => encode_mem_instr(mem_instr::I32_LOAD8_S, m),
+        Instr::I32Load8U(m) => encode_mem_instr(mem_instr::I32_LOAD8_U, m),
+        Instr::I32Load16S(m) => encode_mem_instr(mem_instr::I32_LOAD16_S, m),
+        Instr::I32Load16U(m) => encode_mem_instr(mem_instr::I32_LOAD16_U, m),
+        Instr::I64Load8S(m) => encode_mem_instr(mem_instr::I64_LOAD8_S, m),
+        Instr::I64Load8U(m) => encode_mem_instr(mem_instr::I64_LOAD8_U, m),
+        Instr::I64Load16S(m) => encode_mem_instr(mem_instr::I64_LOAD16_S, m),
+        Instr::I64Load16U(m) => encode_mem_instr(mem_instr::I64_LOAD16_U, m),
+        Instr::I64Load32S(m) => encode_mem_instr(mem_instr::I64_LOAD32_S, m),
+        Instr::I64Load32U(m) => encode_mem_instr(mem_instr::I64_LOAD32_U, m),
+        Instr::I32Store(m) => encode_mem_instr(mem_instr::I32_STORE, m),
+        Instr::I64Store(m) => encode_mem_instr(mem_instr::I64_STORE, m),
+        Instr::F32Store(m) => encode_mem_instr(mem_instr::F32_STORE, m),
+        Instr::F64Store(m) => encode_mem_instr(mem_instr::F64_STORE, m),
+        Instr::I32Store8(m) => encode_mem_instr(mem_instr::I32_STORE8, m),
+        Instr::I32Store16(m) => encode_mem_instr(mem_instr::I32_STORE16, m),
+        Instr::I64Store8(m) => encode_mem_instr(mem_instr::I64_STORE8, m),
+        Instr::I64Store16(m) => encode_mem_instr(mem_instr::I64_STORE16, m),
+        Instr::I64Store32(m) => encode_mem_instr(mem_instr::I64_STORE32, m),
+        Instr::MemorySize(idx) => [vec![mem_instr::MEMORY_SIZE], from_u32(*idx as u32)].concat(),
+        Instr::MemoryGrow(idx) => [vec![mem_instr::MEMORY_GROW], from_u32(*idx as u32)].concat(),
+        Instr::I32Const(value) => [vec![num_instr::I32_CONST], from_i32(*value)].concat(),
+        Instr::I64Const(value) => [vec![num_instr::I64_CONST], from_i64(*value)].concat(),
+        Instr::F32Const(value) => [&[num_instr::F32_CONST], &value.to_le_bytes()[..]].concat(),
+        Instr::F64Const(value) => [&[num_instr::F64_CONST], &value.to_le_bytes()[..]].concat(),
+        Instr::I32Eqz => vec![num_instr::I32_EQZ],
+        Instr::I32Eq => vec![num_instr::I32_EQ],
+        Instr::I32Ne => vec![num_instr::I32_NE],
+        Instr::I32LtS => vec![num_instr::I32_LT_S],
+        Instr::I32LtU => vec![num_instr::I32_LT_U],
+        Instr::I32GtS => vec![num_instr::I32_GT_S],
+        Instr::I32GtU => vec![num_instr::I32_GT_U],
+        Instr::I32LeS => vec![num_instr::I32_LE_S],
+        Instr::I32LeU => vec![num_instr::I32_LE_U],
+        Instr::I32GeS => vec![num_instr::I32_GE_S],
+        Instr::I32GeU => vec![num_instr::I32_GE_U],
+        Instr::I32Clz => vec![num_instr::I32_CLZ],
+        Instr::I32Ctz => vec![num_instr::I32_CTZ],
+        Instr::I32Popcnt => vec![num_instr::I32_POPCNT],
+        Instr::I32Add => vec![num_instr::I32_ADD],
+        Instr::I32Sub => vec![num_instr::I32_SUB],
+        Instr::I32Mul => vec![num_instr::I32_MUL],
+        Instr::I32DivS => vec![num_instr::I32_DIV_S],
+        Instr::I32DivU => vec![num_instr::I32_DIV_U],
+        Instr::I32RemS => vec![num_instr::I32_REM_S],
+        Instr::I32RemU => vec![num_instr::I32_REM_U],
+        Instr::I32And => vec![num_instr::I32_AND],
+        Instr::I32Or => vec![num_instr::I32_OR],
+        Instr::I32Xor => vec![num_instr::I32_XOR],
+        Instr::I32Shl => vec![num_instr::I32_SHL],
+        Instr::I32ShrS => vec![num_instr::I32_SHR_S],
+        Instr::I32ShrU => vec![num_instr::I32_SHR_U],
+        Instr::I32Rotl => vec![num_instr::I32_ROTL],
+        Instr::I32Rotr => vec![num_instr::I32_ROTR],
+        Instr::I64Eqz => vec![num_instr::I64_EQZ],
+        Instr::I64Eq => vec![num_instr::I64_EQ],
+        Instr::I64Ne => vec![num_instr::I64_NE],
+        Instr::I64LtS => vec![num_instr::I64_LT_S],
+        Instr::I64LtU => vec![num_instr::I64_LT_U],
+        Instr::I64GtS => vec![num_instr::I64_GT_S],
+        Instr::I64GtU => vec![num_instr::I64_GT_U],
+        Instr::I64LeS => vec![num_instr::I64_LE_S],
+        Instr::I64LeU => vec![num_instr::I64_LE_U],
+        Instr::I64GeS => vec![num_instr::I64_GE_S],
+        Instr::I64GeU => vec![num_instr::I64_GE_U],
+        Instr::I64Clz => vec![num_instr::I64_CLZ],
+        Instr::I64Ctz => vec![num_instr::I64_CTZ],
+        Instr::I64Popcnt => vec![num_instr::I64_POPCNT],
+        Instr::I64Add => vec![num_instr::I64_ADD],
+        Instr::I64Sub => vec![num_instr::I64_SUB],
+        Instr::I64Mul => vec![num_instr::I64_MUL],
+        Instr::I64DivS => vec![num_instr::I64_DIV_S],
+        Instr::I64DivU => vec![num_instr::I64_DIV_U],
+        Instr::I64RemS => vec![num_instr::I64_REM_S],
+        Instr::I64RemU => vec![num_instr::I64_REM_U],
+        Instr::I64And => vec![num_instr::I64_AND],
+        Instr::I64Or => vec![num_instr::I64_OR],
+        Instr::I64Xor => vec![num_instr::I64_XOR],
+        Instr::I64Shl => vec![num_instr::I64_SHL],
+        Instr::I64ShrS => vec![num_instr::I64_SHR_S],
+        Instr::I64ShrU => vec![num_instr::I64_SHR_U],
+        Instr::I64Rotl => vec![num_instr::I64_ROTL],
+        Instr::I64Rotr => vec![num_instr::I64_ROTR],
+        Instr::F32Eq => vec![num_instr::F32_EQ],
+        Instr::F32Ne => vec![num_instr::F32_NE],
+        Instr::F32Lt => vec![num_instr::F32_LT],
+        Instr::F32Gt => vec![num_instr::F32_GT],
+        Instr::F32Le => vec![num_instr::F32_LE],
+        Instr::F32Ge => vec![num_instr::F32_GE],
+        Instr::F64Eq => vec![num_instr::F64_EQ],
+        Instr::F64Ne => vec![num_instr::F64_NE],
+        Instr::F64Lt => vec![num_instr::F64_LT],
+        Instr::F64Gt => vec![num_instr::F64_GT],
+        Instr::F64Le => vec![num_instr::F64_LE],
+        Instr::F64Ge => vec![num_instr::F64_GE],
+        Instr::F32Abs => vec![num_instr::F32_ABS],
+        Instr::F32Neg => vec![num_instr::F32_NEG],
+        Instr::F32Ceil => vec![num_instr::F32_CEIL],
+        Instr::F32Floor => vec![num_instr::F32_FLOOR],
+        Instr::F32Trunc => vec![num_instr::F32_TRUNC],
+        Instr::F32Nearest => vec![num_instr::F32_NEAREST],
+        Instr::F32Sqrt => vec![num_instr::F32_SQRT],
+        Instr::F32Add => vec![num_instr::F32_ADD],
+        Instr::F32Sub => vec![num_instr::F32_SUB],
+        Instr::F32Mul => vec![num_instr::F32_MUL],
+        Instr::F32Div => vec![num_instr::F32_DIV],
+        Instr::F32Min => vec![num_instr::F32_MIN],
+        Instr::F32Max => vec![num_instr::F32_MAX],
+        Instr::F32Copysign => vec![num_instr::F32_COPYSIGN],
+        Instr::F64Abs => vec![num_instr::F64_ABS],
+        Instr::F64Neg => vec![num_instr::F64_NEG],
+        Instr::F64Ceil => vec![num_instr::F64_CEIL],
+        Instr::F64Floor => vec![num_instr::F64_FLOOR],
+        Instr::F64Trunc => vec![num_instr::F64_TRUNC],
+        Instr::F64Nearest => vec![num_instr::F64_NEAREST],
+        Instr::F64Sqrt => vec![num_instr::F64_SQRT],
+        Instr::F64Add => vec![num_instr::F64_ADD],
+        Instr::F64Sub => vec![num_instr::F64_SUB],
+        Instr::F64Mul => vec![num_instr::F64_MUL],
+        Instr::F64Div => vec![num_instr::F64_DIV],
+        Instr::F64Min => vec![num_instr::F64_MIN],
+        Instr::F64Max => vec![num_instr::F64_MAX],
+        Instr::F64Copysign => vec![num_instr::F64_COPYSIGN],
+        Instr::I32WrapI64 => vec![num_instr::I32_WRAP_I64],
+        Instr::I32TruncF32S => vec![num_instr::I32_TRUNC_F32_S],
+        Instr::I32TruncF32U => vec![num_instr::I32_TRUNC_F32_U],
+        Instr::I32TruncF64S => vec![num_instr::I32_TRUNC_F64_S],
+        Instr::I32TruncF64U => vec![num_instr::I32_TRUNC_F64_U],
+        Instr::I64ExtendI32S => vec![num_instr::I64_EXTEND_I32_S],
+        Instr::I64ExtendI32U => vec![num_instr::I64_EXTEND_I32_U],
+        Instr::I64TruncF32S => vec![num_instr::I64_TRUNC_F32_S],
+        Instr::I64TruncF32U => vec![num_instr::I64_TRUNC_F32_U],
+        Instr::I64TruncF64S => vec![num_instr::I64_TRUNC_F64_S],
+        Instr::I64TruncF64U => vec![num_instr::I64_TRUNC_F64_U],
+        Instr::F32ConvertI32S => vec![num_instr::F32_CONVERT_I32_S],
+        Instr::F32ConvertI32U => vec![num_instr::F32_CONVERT_I32_U],
+        Instr::F32ConvertI64S => vec![num_instr::F32_CONVERT_I64_S],
+        Instr::F32ConvertI64U => vec![num_instr::F32_CONVERT_I64_U],
+        Instr::F32DemoteF64 => vec![num_instr::F32_DEMOTE_F64],
+        Instr::F64ConvertI32S => vec![num_instr::F64_CONVERT_I32_S],
+        Instr::F64ConvertI32U => vec![num_instr::F64_CONVERT_I32_U],
+        Instr::F64ConvertI64S => vec![num_instr::F64_CONVERT_I64_S],
+        Instr::F64ConvertI64U => vec![num_instr::F64_CONVERT_I64_U],
+        Instr::F64PromoteF32 => vec![num_instr::F64_PROMOTE_F32],
+        Instr::I32ReinterpretF32 => vec![num_instr::I32_REINTERPRET_F32],
+        Instr::I64ReinterpretF64 => vec![num_instr::I64_REINTERPRET_F64],
+        Instr::F32ReinterpretI32 => vec![num_instr::F32_REINTERPRET_I32],
+        Instr::F64ReinterpretI64 => vec![num_instr::F64_REINTERPRET_I64],
+        Instr::I32Extend8S => vec![num_instr::I32_EXTEND8_S],
+        Instr::I32Extend16S => vec![num_instr::I32_EXTEND16_S],
+        Instr::I64Extend8S => vec![num_instr::I64_EXTEND8_S],
+        Instr::I64Extend16S => vec![num_instr::I64_EXTEND16_S],
+        Instr::I64Extend32S => vec![num_instr::I64_EXTEND32_S],
+        Instr::I32TruncSatF32S => [
+            vec![prefix::MISC],
+            from_u32(misc_instr::I32_TRUNC_SAT_F32_S),
+        ]
+        .concat(),
+        Instr::I32TruncSatF32U => [
+            vec![prefix::MISC],
+            from_u32(misc_instr::I32_TRUNC_SAT_F32_U),
+        ]
+        .concat(),
+        Instr::I32TruncSatF64S => [
+            vec![prefix::MISC],
+            from_u32(misc_instr::I32_TRUNC_SAT_F64_S),
+        ]
+        .concat(),
+        Instr::I32TruncSatF64U => [
+            vec![prefix::MISC],
+            from_u32(misc_instr::I32_TRUNC_SAT_F64_U),
+        ]
+        .concat(),
+        Instr::I64TruncSatF32S => [
+            vec![prefix::MISC],
+            from_u32(misc_instr::I64_TRUNC_SAT_F32_S),
+        ]
+        .concat(),
+        Instr::I64TruncSatF32U => [
+            vec![prefix::MISC],
+            from_u32(misc_instr::I64_TRUNC_SAT_F32_U),
+        ]
+        .concat(),
+        Instr::I64TruncSatF64S => [
+            vec![prefix::MISC],
+            from_u32(misc_instr::I64_TRUNC_SAT_F64_S),
+        ]
+        .concat(),
+        Instr::I64TruncSatF64U => [
+            vec![prefix::MISC],
+            from_u32(misc_instr::I64_TRUNC_SAT_F64_U),
+        ]
+        .concat(),
+        Instr::MemoryInit(memory, data) => [
+            vec![prefix::MISC],
+            from_u32(misc_instr::MEMORY_INIT),
+            from_u32(*data as u32),
+            from_u32(*memory as u32),
+        ]
+        .concat(),
+        Instr::DataDrop(idx) => [
+            vec![prefix::MISC],
+            from_u32(misc_instr::DATA_DROP),
+            from_u32(*idx as u32),
+        ]
+        .concat(),
+        Instr::MemoryCopy(dst, src) => [
+            vec![prefix::MISC],
+            from_u32(misc_instr::MEMORY_COPY),
+            from_u32(*dst as u32),
+            from_u32(*src as u32),
+        ]
+        .concat(),
+        Instr::MemoryFill(idx) => [
+            vec![prefix::MISC],
+            from_u32(misc_instr::MEMORY_FILL),
+            from_u32(*idx as u32),
+        ]
+        .concat(),
+
+        Instr::V128Load(m) => encode_simd_mem_instr(simd_instr::V128_LOAD, m, None),
+        Instr::V128Load8x8S(m) => encode_simd_mem_instr(simd_instr::V128_LOAD8X8_S, m, None),
+        Instr::V128Load8x8U(m) => encode_simd_mem_instr(simd_instr::V128_LOAD8X8_U, m, None),
+        Instr::V128Load16x4S(m) => encode_simd_mem_instr(simd_instr::V128_LOAD16X4_S, m, None),
+        Instr::V128Load16x4U(m) => encode_simd_mem_instr(simd_instr::V128_LOAD16X4_U, m, None),
+        Instr::V128Load32x2S(m) => encode_simd_mem_instr(simd_instr::V128_LOAD32X2_S, m, None),
+        Instr::V128Load32x2U(m) => encode_simd_mem_instr(simd_instr::V128_LOAD32X2_U, m, None),
+        Instr::V128Load8Splat(m) => encode_simd_mem_instr(simd_instr::V128_LOAD8_SPLAT, m, None),
+        Instr::V128Load16Splat(m) => encode_simd_mem_instr(simd_instr::V128_LOAD16_SPLAT, m, None),
+        Instr::V128Load32Splat(m) => encode_simd_mem_instr(simd_instr::V128_LOAD32_SPLAT, m, None),
+        Instr::V128Load64Splat(m) => encode_simd_mem_instr(simd_instr::V128_LOAD64_SPLAT, m, None),
+        Instr::V128Load32Zero(m) => encode_simd_mem_instr(simd_instr::V128_LOAD32_ZERO, m, None),
+        Instr::V128Load64Zero(m) => encode_simd_mem_instr(simd_instr::V128_LOAD64_ZERO, m, None),
+        Instr::V128Store(m) => encode_simd_mem_instr(simd_instr::V128_STORE, m, None),
+        Instr::V128Load8Lane(m, lane) => {
+            encode_simd_mem_instr(simd_instr::V128_LOAD8_LANE, m, Some(*lane))
+        }
+        Instr::V128Load16Lane(m, lane) => {
+            encode_simd_mem_instr(simd_instr::V128_LOAD16_LANE, m, Some(*lane))
+        }
+        Instr::V128Load32Lane(m, lane) => {
+            encode_simd_mem_instr(simd_instr::V128_LOAD32_LANE, m, Some(*lane))
+        }
+        Instr::V128Load64Lane(m, lane) => {
+            encode_simd_mem_instr(simd_instr::V128_LOAD64_LANE, m, Some(*lane))
+        }
+        Instr::V128Store8Lane(m, lane) => {
+            encode_simd_mem_instr(simd_instr::V128_STORE8_LANE, m, Some(*lane))
+        }
+        Instr::V128Store16Lane(m, lane) => {
+            encode_simd_mem_instr(simd_instr::V128_STORE16_LANE, m, Some(*lane))
+        }
+        Instr::V128Store32Lane(m, lane) => {
+            encode_simd_mem_instr(simd_instr::V128_STORE32_LANE, m, Some(*lane))
+        }
+        Instr::V128Store64Lane(m, lane) => {
+            encode_simd_mem_instr(simd_instr::V128_STORE64_LANE, m, Some(*lane))
+        }
+        Instr::V128Const(value) => encode_simd_instr(simd_instr::V128_CONST, &value.to_le_bytes()),
+        Instr::I8x16Shuffle(lanes) => encode_simd_instr(simd_instr::I8X16_SHUFFLE, lanes),
+        Instr::I8x16ExtractLaneS(lane) => {
+            encode_simd_instr(simd_instr::I8X16_EXTRACT_LANE_S, &[*lane])
+        }
+        Instr::I8x16ExtractLaneU(lane) => {
+            encode_simd_instr(simd_instr::I8X16_EXTRACT_LANE_U, &[*lane])
+        }
+        Instr::I8x16ReplaceLane(lane) => {
+            encode_simd_instr(simd_instr::I8X16_REPLACE_LANE, &[*lane])
+        }
+        Instr::I16x8ExtractLaneS(lane) => {
+            encode_simd_instr(simd_instr::I16X8_EXTRACT_LANE_S, &[*lane])
+        }
+        Instr::I16x8ExtractLaneU(lane) => {
+            encode_simd_instr(simd_instr::I16X8_EXTRACT_LANE_U, &[*lane])
+        }
+        Instr::I16x8ReplaceLane(lane) => {
+            encode_simd_instr(simd_instr::I16X8_REPLACE_LANE, &[*lane])
+        }
+        Instr::I32x4ExtractLane(lane) => {
+            encode_simd_instr(simd_instr::I32X4_EXTRACT_LANE, &[*lane])
+        }
+        Instr::I32x4ReplaceLane(lane) => {
+            encode_simd_instr(simd_instr::I32X4_REPLACE_LANE, &[*lane])
+        }
+        Instr::I64x2ExtractLane(lane) => {
+            encode_simd_instr(simd_instr::I64X2_EXTRACT_LANE, &[*lane])
+        }
+        Instr::I64x2ReplaceLane(lane) => {
+            encode_simd_instr(simd_instr::I64X2_REPLACE_LANE, &[*lane])
+        }
+        Instr::F32x4ExtractLane(lane) => {
+            encode_simd_instr(simd_instr::F32X4_EXTRACT_LANE, &[*lane])
+        }
+        Instr::F32x4ReplaceLane(lane) => {
+            encode_simd_instr(simd_instr::F32X4_REPLACE_LANE, &[*lane])
+        }
+        Instr::F64x2ExtractLane(lane) => {
+            encode_simd_instr(simd_instr::F64X2_EXTRACT_LANE, &[*lane])
+        }
+        Instr::F64x2ReplaceLane(lane) => {
+            encode_simd_instr(simd_instr::F64X2_REPLACE_LANE, &[*lane])
+        }
+        Instr::Simd(op) => encode_simd_instr(simd_op_code(op), &[]),
+
+        Instr::MemoryAtomicNotify(m) => encode_atomic_instr(atomic_instr::MEMORY_ATOMIC_NOTIFY, m),
+        Instr::MemoryAtomicWait32(m) => encode_atomic_instr(atomic_instr::MEMORY_ATOMIC_WAIT32, m),
+        Instr::MemoryAtomicWait64(m) => encode_atomic_instr(atomic_instr::MEMORY_ATOMIC_WAIT64, m),
+        Instr::AtomicFence => [
+            vec![prefix::THREADS],
+            from_u32(atomic_instr::ATOMIC_FENCE),
+            vec![0x00],
+        ]
+        .concat(),
+        Instr::AtomicLoad(width, m) => {
+            let op_code = atomic_instr::ATOMIC_LOAD + atomic_instr::width_idx(width);
+            encode_atomic_instr(op_code, m)
+        }
+        Instr::AtomicStore(width, m) => {
+            let op_code = atomic_instr::ATOMIC_STORE + atomic_instr::width_idx(width);
+            encode_atomic_instr(op_code, m)
+        }
+        Instr::AtomicRmw(op, width, m) => encode_atomic_instr(atomic_instr::rmw_code(op, width), m),
+        Instr::Block(..) | Instr::Loop(..) | Instr::If(..) | Instr::TryTable(..) => {
+            encode_instr(instr)
+        }
+    }
+}
+
+/// Encodes locals as run-length groups of `(count, type)`.
+fn encode_locals(locals: &[ValueType]) -> Vec<u8> {
+    let mut groups: Vec<(u32, ValueType)> = vec![];
+    for local in locals {
+        match groups.last_mut() {
+            Some((count, vt)) if vt == local => *count += 1,
+            _ => groups.push((1, *local)),
+        }
+    }
+
+    let encoded = groups
+        .iter()
+        .map(|(count, vt)| [from_u32(*count), encode_value_type(vt)].concat())
+        .collect::<Vec<Vec<u8>>>()
+        .concat();
+
+    [from_u32(groups.len() as u32), encoded].concat()
+}
+
+fn encode_code_section(ast: &Module) -> Vec<u8> {
+    fn encode_func(func: &Func) -> Vec<u8> {
+        let body = [
+            encode_locals(&func.locals),
+            encode_instrs(&func.body),
             vec![control_flow::END],
         ]
         .concat();
 
-        vec![from_u32(body.len() as u32), body].concat()
+        [from_u32(body.len() as u32), body].concat()
     }
 
     if ast.funcs.is_empty() {
         vec![]
     } else {
-        let body = vec![
-            vec![ast.funcs.len() as u8],
-            ast.funcs
+        let body = ast
+            .funcs
+            .iter()
+            .map(encode_func)
+            .collect::<Vec<Vec<u8>>>()
+            .concat();
+        encode_section(section::CODE, ast.funcs.len(), body)
+    }
+}
+
+fn encode_elem_section(ast: &Module) -> Vec<u8> {
+    /// Function indices of a segment of `funcref`s that only refers to
+    /// functions, which can be encoded without expressions.
+    fn funcs(elem: &Elem) -> Option<Vec<usize>> {
+        if elem.ref_type != RefType::FuncRef {
+            return None;
+        }
+        elem.init
+            .iter()
+            .map(|expr| match expr.as_slice() {
+                [Instr::RefFunc(idx)] => Some(*idx),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn encode_elem(elem: &Elem) -> Vec<u8> {
+        let funcs = funcs(elem);
+        // Functions are preceded by their element kind and expressions by
+        // their reference type.
+        let (flags, kind) = match &funcs {
+            Some(_) => (0, elem_kind::FUNC_REF),
+            None => (elem_mode::EXPRS, ref_type(&elem.ref_type)),
+        };
+        let mode = match &elem.mode {
+            ElemMode::Active { table: 0, offset } if elem.ref_type == RefType::FuncRef => [
+                from_u32(elem_mode::ACTIVE | flags),
+                encode_instrs(offset),
+                vec![control_flow::END],
+            ]
+            .concat(),
+            ElemMode::Passive => [from_u32(elem_mode::PASSIVE | flags), vec![kind]].concat(),
+            ElemMode::Active { table, offset } => [
+                from_u32(elem_mode::ACTIVE_TABLE | flags),
+                from_u32(*table as u32),
+                encode_instrs(offset),
+                vec![control_flow::END],
+                vec![kind],
+            ]
+            .concat(),
+            ElemMode::Declarative => {
+                [from_u32(elem_mode::DECLARATIVE | flags), vec![kind]].concat()
+            }
+        };
+        let init = match funcs {
+            Some(funcs) => funcs
                 .iter()
-                .map(encode_func)
-                .collect::<Vec<Vec<u8>>>()
-                .concat(),
-        ]
-        .concat();
-        vec![vec![section::CODE], from_u32((body.len()) as u32), body].concat()
+                .map(|idx| from_u32(*idx as u32))
+                .collect::<Vec<Vec<u8>>>(),
+            None => elem
+                .init
+                .iter()
+                .map(|expr| [encode_instrs(expr), vec![control_flow::END]].concat())
+                .collect::<Vec<Vec<u8>>>(),
+        };
+
+        [mode, from_u32(elem.init.len() as u32), init.concat()].concat()
+    }
+
+    if ast.elems.is_empty() {
+        vec![]
+    } else {
+        let body = ast
+            .elems
+            .iter()
+            .map(encode_elem)
+            .collect::<Vec<Vec<u8>>>()
+            .concat();
+        encode_section(section::ELEM, ast.elems.len(), body)
+    }
+}
+
+fn encode_start_section(ast: &Module) -> Vec<u8> {
+    match ast.start {
+        Some(func) => encode_section(section::START, func, vec![]),
+        None => vec![],
+    }
+}
+
+fn encode_data_count_section(ast: &Module) -> Vec<u8> {
+    if ast.datas.is_empty() {
+        vec![]
+    } else {
+        encode_section(section::DATA_COUNT, ast.datas.len(), vec![])
+    }
+}
+
+fn encode_data_section(ast: &Module) -> Vec<u8> {
+    fn encode_data(data: &Data) -> Vec<u8> {
+        let mode = match &data.mode {
+            DataMode::Passive => from_u32(data_mode::PASSIVE),
+            DataMode::Active { memory: 0, offset } => [
+                from_u32(data_mode::ACTIVE),
+                encode_instrs(offset),
+                vec![control_flow::END],
+            ]
+            .concat(),
+            DataMode::Active { memory, offset } => [
+                from_u32(data_mode::ACTIVE_MEMORY),
+                from_u32(*memory as u32),
+                encode_instrs(offset),
+                vec![control_flow::END],
+            ]
+            .concat(),
+        };
+
+        [mode, from_u32(data.init.len() as u32), data.init.clone()].concat()
+    }
+
+    if ast.datas.is_empty() {
+        vec![]
+    } else {
+        let body = ast
+            .datas
+            .iter()
+            .map(encode_data)
+            .collect::<Vec<Vec<u8>>>()
+            .concat();
+        encode_section(section::DATA, ast.datas.len(), body)
     }
 }
 
 pub fn compile(ast: &Module) -> Vec<u8> {
-    vec![
+    [
         MAGIC,
         VERSION,
         &encode_type_section(ast),
+        &encode_import_section(ast),
         &encode_func_section(ast),
+        &encode_table_section(ast),
+        &encode_memory_section(ast),
+        &encode_tag_section(ast),
+        &encode_global_section(ast),
         &encode_export_section(ast),
+        &encode_start_section(ast),
+        &encode_elem_section(ast),
+        &encode_data_count_section(ast),
         &encode_code_section(ast),
+        &encode_data_section(ast),
     ]
     .concat()
 }
@@ -138,7 +977,7 @@ pub fn compile(ast: &Module) -> Vec<u8> {
 mod tests {
     use super::*;
     use crate::ast::EDesc::FuncExport;
-    use crate::ast::Instr::{I32Add, LocalGet};
+    use crate::ast::Instr::*;
     use crate::ast::ValueType::*;
     use crate::ast::*;
 
@@ -146,15 +985,23 @@ mod tests {
     fn compile_module_with_add_function() {
         let ast = Module {
             types: vec![(vec![I32, I32], vec![I32])],
+            imports: vec![],
             funcs: vec![Func {
                 f_type: 0,
                 locals: vec![],
                 body: vec![LocalGet(0), LocalGet(1), I32Add],
             }],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
             exports: vec![Export {
                 name: "add".to_string(),
                 e_desc: FuncExport(0),
             }],
+            start: None,
         };
 
         let wasm = [
@@ -213,4 +1060,971 @@ mod tests {
 
         assert_eq!(compile(&ast), wasm);
     }
+
+    #[test]
+    fn encode_i32_numeric_instrs() {
+        let ast = Module {
+            types: vec![(vec![I32, I32], vec![I32])],
+            imports: vec![],
+            funcs: vec![Func {
+                f_type: 0,
+                locals: vec![],
+                body: vec![LocalGet(0), LocalGet(1), I32Sub, I32Eqz, I32Popcnt, I32Rotr],
+            }],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![],
+            start: None,
+        };
+
+        let code = [
+            0x0a, // section code
+            0x0c, // section size
+            0x01, // num functions
+            0x0a, // func body size
+            0x00, // local decl count
+            0x20, 0x00, // local.get 0
+            0x20, 0x01, // local.get 1
+            0x6b, // i32.sub
+            0x45, // i32.eqz
+            0x69, // i32.popcnt
+            0x78, // i32.rotr
+            0x0b, // end
+        ];
+
+        assert_eq!(encode_code_section(&ast), code);
+    }
+
+    #[test]
+    fn encode_const_instrs() {
+        let ast = Module {
+            types: vec![(vec![], vec![I64])],
+            imports: vec![],
+            funcs: vec![Func {
+                f_type: 0,
+                locals: vec![],
+                body: vec![I32Const(-123456), I64Const(624485)],
+            }],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![],
+            start: None,
+        };
+
+        let code = [
+            0x0a, // section code
+            0x0c, // section size
+            0x01, // num functions
+            0x0a, // func body size
+            0x00, // local decl count
+            0x41, 0xc0, 0xbb, 0x78, // i32.const -123456
+            0x42, 0xe5, 0x8e, 0x26, // i64.const 624485
+            0x0b, // end
+        ];
+
+        assert_eq!(encode_code_section(&ast), code);
+    }
+
+    #[test]
+    fn encode_float_instrs() {
+        let ast = Module {
+            types: vec![(vec![], vec![F64])],
+            imports: vec![],
+            funcs: vec![Func {
+                f_type: 0,
+                locals: vec![],
+                body: vec![F32Const(1.5), F64Const(-2.0), F32Sqrt, F64Max],
+            }],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![],
+            start: None,
+        };
+
+        let code = [
+            0x0a, // section code
+            0x14, // section size
+            0x01, // num functions
+            0x12, // func body size
+            0x00, // local decl count
+            0x43, 0x00, 0x00, 0xc0, 0x3f, // f32.const 1.5
+            0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc0, // f64.const -2
+            0x91, // f32.sqrt
+            0xa5, // f64.max
+            0x0b, // end
+        ];
+
+        assert_eq!(encode_code_section(&ast), code);
+    }
+
+    #[test]
+    fn encode_conversion_instrs() {
+        let ast = Module {
+            types: vec![(vec![], vec![I64])],
+            imports: vec![],
+            funcs: vec![Func {
+                f_type: 0,
+                locals: vec![],
+                body: vec![
+                    I32WrapI64,
+                    F64PromoteF32,
+                    I32TruncSatF32S,
+                    I64TruncSatF64U,
+                    I64Extend32S,
+                ],
+            }],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![],
+            start: None,
+        };
+
+        let code = [
+            0x0a, // section code
+            0x0b, // section size
+            0x01, // num functions
+            0x09, // func body size
+            0x00, // local decl count
+            0xa7, // i32.wrap_i64
+            0xbb, // f64.promote_f32
+            0xfc, 0x00, // i32.trunc_sat_f32_s
+            0xfc, 0x07, // i64.trunc_sat_f64_u
+            0xc4, // i64.extend32_s
+            0x0b, // end
+        ];
+
+        assert_eq!(encode_code_section(&ast), code);
+    }
+
+    #[test]
+    fn encode_control_instrs() {
+        let ast = Module {
+            types: vec![(vec![], vec![I32])],
+            imports: vec![],
+            funcs: vec![Func {
+                f_type: 0,
+                locals: vec![],
+                body: vec![Block(
+                    BlockType::Value(I32),
+                    vec![
+                        Loop(BlockType::Empty, vec![Br(1), BrIf(0)]),
+                        If(BlockType::Empty, vec![Return], vec![I32Const(1)]),
+                        BrTable(vec![0, 1], 0),
+                    ],
+                )],
+            }],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![],
+            start: None,
+        };
+
+        let code = [
+            0x0a, // section code
+            0x1a, // section size
+            0x01, // num functions
+            0x18, // func body size
+            0x00, // local decl count
+            0x02, 0x7f, // block (result i32)
+            0x03, 0x40, // loop
+            0x0c, 0x01, // br 1
+            0x0d, 0x00, // br_if 0
+            0x0b, // end
+            0x04, 0x40, // if
+            0x0f, // return
+            0x05, // else
+            0x41, 0x01, // i32.const 1
+            0x0b, // end
+            0x0e, 0x02, 0x00, 0x01, 0x00, // br_table 0 1 0
+            0x0b, // end
+            0x0b, // end
+        ];
+
+        assert_eq!(encode_code_section(&ast), code);
+
+        // Type indices are signed, so 64 needs a second byte.
+        assert_eq!(encode_block_type(&BlockType::TypeIdx(1)), [0x01]);
+        assert_eq!(encode_block_type(&BlockType::TypeIdx(64)), [0xc0, 0x00]);
+    }
+
+    #[test]
+    fn encode_parametric_instrs() {
+        assert_eq!(encode_instr(&Unreachable), [0x00]);
+        assert_eq!(encode_instr(&Nop), [0x01]);
+        assert_eq!(encode_instr(&Drop), [0x1a]);
+        assert_eq!(encode_instr(&Select(None)), [0x1b]);
+        assert_eq!(encode_instr(&Select(Some(F32))), [0x1c, 0x01, 0x7d]);
+    }
+
+    #[test]
+    fn encode_reference_instrs() {
+        assert_eq!(encode_instr(&RefNull(HeapType::Func)), [0xd0, 0x70]);
+        assert_eq!(encode_instr(&RefNull(HeapType::Extern)), [0xd0, 0x6f]);
+        assert_eq!(
+            encode_instr(&RefNull(HeapType::Type(64))),
+            [0xd0, 0xc0, 0x00]
+        );
+        assert_eq!(encode_instr(&RefIsNull), [0xd1]);
+        assert_eq!(encode_instr(&RefFunc(129)), [0xd2, 0x81, 0x01]);
+        assert_eq!(encode_instr(&Select(Some(ExternRef))), [0x1c, 0x01, 0x6f]);
+
+        assert_eq!(encode_instr(&TableGet(1)), [0x25, 0x01]);
+        assert_eq!(encode_instr(&TableSet(0)), [0x26, 0x00]);
+        assert_eq!(encode_instr(&TableInit(1, 2)), [0xfc, 0x0c, 0x02, 0x01]);
+        assert_eq!(encode_instr(&ElemDrop(2)), [0xfc, 0x0d, 0x02]);
+        assert_eq!(encode_instr(&TableCopy(1, 0)), [0xfc, 0x0e, 0x01, 0x00]);
+        assert_eq!(encode_instr(&TableGrow(0)), [0xfc, 0x0f, 0x00]);
+        assert_eq!(encode_instr(&TableSize(1)), [0xfc, 0x10, 0x01]);
+        assert_eq!(encode_instr(&TableFill(0)), [0xfc, 0x11, 0x00]);
+    }
+
+    #[test]
+    fn encode_call_instr() {
+        let ast = Module {
+            types: vec![(vec![I32], vec![I32])],
+            imports: vec![],
+            funcs: vec![Func {
+                f_type: 0,
+                locals: vec![],
+                body: vec![LocalGet(0), Call(0), Call(129)],
+            }],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![],
+            start: None,
+        };
+
+        let code = [
+            0x0a, // section code
+            0x0b, // section size
+            0x01, // num functions
+            0x09, // func body size
+            0x00, // local decl count
+            0x20, 0x00, // local.get 0
+            0x10, 0x00, // call 0
+            0x10, 0x81, 0x01, // call 129
+            0x0b, // end
+        ];
+
+        assert_eq!(encode_code_section(&ast), code);
+    }
+
+    #[test]
+    fn encode_locals() {
+        let ast = Module {
+            types: vec![(vec![], vec![])],
+            imports: vec![],
+            funcs: vec![Func {
+                f_type: 0,
+                locals: vec![I32, I32, I64, I32],
+                body: vec![
+                    I32Const(1),
+                    LocalSet(0),
+                    LocalGet(0),
+                    LocalTee(3),
+                    LocalSet(1),
+                ],
+            }],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![],
+            start: None,
+        };
+
+        let code = [
+            0x0a, // section code
+            0x14, // section size
+            0x01, // num functions
+            0x12, // func body size
+            0x03, // local decl count
+            0x02, 0x7f, // 2 x i32
+            0x01, 0x7e, // 1 x i64
+            0x01, 0x7f, // 1 x i32
+            0x41, 0x01, // i32.const 1
+            0x21, 0x00, // local.set 0
+            0x20, 0x00, // local.get 0
+            0x22, 0x03, // local.tee 3
+            0x21, 0x01, // local.set 1
+            0x0b, // end
+        ];
+
+        assert_eq!(encode_code_section(&ast), code);
+    }
+
+    #[test]
+    fn encode_global_section_and_exports() {
+        let ast = Module {
+            types: vec![],
+            imports: vec![],
+            funcs: vec![],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![
+                Global {
+                    g_type: GlobalType {
+                        value_type: I32,
+                        mutable: true,
+                    },
+                    init: vec![I32Const(-1)],
+                },
+                Global {
+                    g_type: GlobalType {
+                        value_type: F64,
+                        mutable: false,
+                    },
+                    init: vec![GlobalGet(0)],
+                },
+            ],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![
+                Export {
+                    name: "f".to_string(),
+                    e_desc: FuncExport(1),
+                },
+                Export {
+                    name: "g".to_string(),
+                    e_desc: EDesc::GlobalExport(1),
+                },
+            ],
+            start: None,
+        };
+
+        let globals = [
+            0x06, // section code
+            0x0b, // section size
+            0x02, // num globals
+            0x7f, 0x01, // mut i32
+            0x41, 0x7f, // i32.const -1
+            0x0b, // end
+            0x7c, 0x00, // f64
+            0x23, 0x00, // global.get 0
+            0x0b, // end
+        ];
+        let exports = [
+            0x07, // section code
+            0x09, // section size
+            0x02, // num exports
+            0x01, 0x66, // "f"
+            0x00, 0x01, // func 1
+            0x01, 0x67, // "g"
+            0x03, 0x01, // global 1
+        ];
+
+        assert_eq!(encode_global_section(&ast), globals);
+        assert_eq!(encode_export_section(&ast), exports);
+    }
+
+    #[test]
+    fn encode_memory() {
+        let ast = Module {
+            types: vec![(vec![], vec![])],
+            imports: vec![],
+            funcs: vec![Func {
+                f_type: 0,
+                locals: vec![],
+                body: vec![
+                    I32Load(MemArg {
+                        align: 2,
+                        offset: 4,
+                        memory: 0,
+                    }),
+                    I64Load8U(MemArg {
+                        align: 0,
+                        offset: 256,
+                        memory: 0,
+                    }),
+                    I32Store16(MemArg {
+                        align: 1,
+                        offset: 0,
+                        memory: 0,
+                    }),
+                    MemorySize(0),
+                    MemoryGrow(0),
+                ],
+            }],
+            tables: vec![],
+            memories: vec![
+                Memory {
+                    limits: Limits { min: 1, max: None },
+                    shared: false,
+                    memory64: false,
+                },
+                Memory {
+                    limits: Limits {
+                        min: 0,
+                        max: Some(0x80),
+                    },
+                    shared: false,
+                    memory64: false,
+                },
+            ],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![],
+            start: None,
+        };
+
+        let memories = [
+            0x05, // section code
+            0x07, // section size
+            0x02, // num memories
+            0x00, 0x01, // min 1
+            0x01, 0x00, 0x80, 0x01, // min 0, max 128
+        ];
+        let code = [
+            0x0a, // section code
+            0x12, // section size
+            0x01, // num functions
+            0x10, // func body size
+            0x00, // local decl count
+            0x28, 0x02, 0x04, // i32.load offset=4
+            0x31, 0x00, 0x80, 0x02, // i64.load8_u offset=256 align=1
+            0x3b, 0x01, 0x00, // i32.store16
+            0x3f, 0x00, // memory.size
+            0x40, 0x00, // memory.grow
+            0x0b, // end
+        ];
+
+        assert_eq!(encode_memory_section(&ast), memories);
+        assert_eq!(encode_code_section(&ast), code);
+    }
+
+    #[test]
+    fn encode_data() {
+        let ast = Module {
+            types: vec![(vec![], vec![])],
+            imports: vec![],
+            funcs: vec![Func {
+                f_type: 0,
+                locals: vec![],
+                body: vec![
+                    MemoryInit(0, 1),
+                    DataDrop(1),
+                    MemoryCopy(0, 0),
+                    MemoryFill(0),
+                ],
+            }],
+            tables: vec![],
+            memories: vec![Memory {
+                limits: Limits { min: 1, max: None },
+                shared: false,
+                memory64: false,
+            }],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![
+                Data {
+                    init: b"hi".to_vec(),
+                    mode: DataMode::Active {
+                        memory: 0,
+                        offset: vec![I32Const(8)],
+                    },
+                },
+                Data {
+                    init: vec![1, 2, 3],
+                    mode: DataMode::Passive,
+                },
+            ],
+            exports: vec![],
+            start: None,
+        };
+
+        let data_count = [0x0c, 0x01, 0x02];
+        let code = [
+            0x0a, // section code
+            0x12, // section size
+            0x01, // num functions
+            0x10, // func body size
+            0x00, // local decl count
+            0xfc, 0x08, 0x01, 0x00, // memory.init 1
+            0xfc, 0x09, 0x01, // data.drop 1
+            0xfc, 0x0a, 0x00, 0x00, // memory.copy
+            0xfc, 0x0b, 0x00, // memory.fill
+            0x0b, // end
+        ];
+        let datas = [
+            0x0b, // section code
+            0x0d, // section size
+            0x02, // num data segments
+            0x00, 0x41, 0x08, 0x0b, // active, offset i32.const 8
+            0x02, 0x68, 0x69, // "hi"
+            0x01, // passive
+            0x03, 0x01, 0x02, 0x03, // bytes
+        ];
+
+        assert_eq!(encode_data_count_section(&ast), data_count);
+        assert_eq!(encode_code_section(&ast), code);
+        assert_eq!(encode_data_section(&ast), datas);
+
+        let wasm = compile(&ast);
+        assert_eq!(wasm[wasm.len() - datas.len()..], datas);
+    }
+
+    #[test]
+    fn encode_tables() {
+        let ast = Module {
+            types: vec![(vec![], vec![])],
+            imports: vec![],
+            funcs: vec![Func {
+                f_type: 0,
+                locals: vec![],
+                body: vec![I32Const(0), CallIndirect(0, 0)],
+            }],
+            tables: vec![Table {
+                limits: Limits { min: 2, max: None },
+                ref_type: RefType::FuncRef,
+            }],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![
+                Elem {
+                    ref_type: RefType::FuncRef,
+                    init: vec![vec![RefFunc(0)], vec![RefFunc(0)]],
+                    mode: ElemMode::Active {
+                        table: 0,
+                        offset: vec![I32Const(0)],
+                    },
+                },
+                Elem {
+                    ref_type: RefType::FuncRef,
+                    init: vec![vec![RefFunc(0)]],
+                    mode: ElemMode::Passive,
+                },
+                Elem {
+                    ref_type: RefType::FuncRef,
+                    init: vec![vec![RefFunc(0)]],
+                    mode: ElemMode::Active {
+                        table: 1,
+                        offset: vec![I32Const(1)],
+                    },
+                },
+                Elem {
+                    ref_type: RefType::FuncRef,
+                    init: vec![vec![RefFunc(0)]],
+                    mode: ElemMode::Declarative,
+                },
+                Elem {
+                    ref_type: RefType::FuncRef,
+                    init: vec![vec![RefFunc(0)], vec![RefNull(HeapType::Func)]],
+                    mode: ElemMode::Active {
+                        table: 0,
+                        offset: vec![I32Const(1)],
+                    },
+                },
+                Elem {
+                    ref_type: RefType::ExternRef,
+                    init: vec![vec![RefNull(HeapType::Extern)]],
+                    mode: ElemMode::Passive,
+                },
+            ],
+            datas: vec![],
+            exports: vec![Export {
+                name: "t".to_string(),
+                e_desc: EDesc::TableExport(0),
+            }],
+            start: None,
+        };
+
+        let tables = [
+            0x04, // section code
+            0x04, // section size
+            0x01, // num tables
+            0x70, 0x00, 0x02, // funcref, min 2
+        ];
+        let exports = [0x07, 0x05, 0x01, 0x01, 0x74, 0x01, 0x00];
+        let elems = [
+            0x09, // section code
+            0x29, // section size
+            0x06, // num element segments
+            0x00, 0x41, 0x00, 0x0b, // active, offset i32.const 0
+            0x02, 0x00, 0x00, // funcs 0 0
+            0x01, 0x00, // passive funcref
+            0x01, 0x00, // func 0
+            0x02, 0x01, 0x41, 0x01, 0x0b, 0x00, // active in table 1, offset i32.const 1
+            0x01, 0x00, // func 0
+            0x03, 0x00, // declarative funcref
+            0x01, 0x00, // func 0
+            0x04, 0x41, 0x01, 0x0b, // active expressions, offset i32.const 1
+            0x02, 0xd2, 0x00, 0x0b, 0xd0, 0x70, 0x0b, // ref.func 0, ref.null func
+            0x05, 0x6f, // passive externref expressions
+            0x01, 0xd0, 0x6f, 0x0b, // ref.null extern
+        ];
+        let code = [
+            0x0a, // section code
+            0x09, // section size
+            0x01, // num functions
+            0x07, // func body size
+            0x00, // local decl count
+            0x41, 0x00, // i32.const 0
+            0x11, 0x00, 0x00, // call_indirect type 0, table 0
+            0x0b, // end
+        ];
+
+        assert_eq!(encode_table_section(&ast), tables);
+        assert_eq!(encode_export_section(&ast), exports);
+        assert_eq!(encode_elem_section(&ast), elems);
+        assert_eq!(encode_code_section(&ast), code);
+    }
+
+    #[test]
+    fn encode_imports() {
+        let ast = Module {
+            types: vec![(vec![I32], vec![])],
+            imports: vec![
+                Import {
+                    module: "env".to_string(),
+                    name: "f".to_string(),
+                    desc: ImportDesc::Func(0),
+                },
+                Import {
+                    module: "env".to_string(),
+                    name: "t".to_string(),
+                    desc: ImportDesc::Table(Table {
+                        limits: Limits { min: 1, max: None },
+                        ref_type: RefType::FuncRef,
+                    }),
+                },
+                Import {
+                    module: "env".to_string(),
+                    name: "m".to_string(),
+                    desc: ImportDesc::Memory(Memory {
+                        limits: Limits {
+                            min: 1,
+                            max: Some(2),
+                        },
+                        shared: false,
+                        memory64: false,
+                    }),
+                },
+                Import {
+                    module: "env".to_string(),
+                    name: "g".to_string(),
+                    desc: ImportDesc::Global(GlobalType {
+                        value_type: I64,
+                        mutable: true,
+                    }),
+                },
+            ],
+            funcs: vec![],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![],
+            start: None,
+        };
+
+        let imports = [
+            0x02, // section code
+            0x26, // section size
+            0x04, // num imports
+            0x03, 0x65, 0x6e, 0x76, 0x01, 0x66, // "env" "f"
+            0x00, 0x00, // func of type 0
+            0x03, 0x65, 0x6e, 0x76, 0x01, 0x74, // "env" "t"
+            0x01, 0x70, 0x00, 0x01, // funcref table, min 1
+            0x03, 0x65, 0x6e, 0x76, 0x01, 0x6d, // "env" "m"
+            0x02, 0x01, 0x01, 0x02, // memory, min 1, max 2
+            0x03, 0x65, 0x6e, 0x76, 0x01, 0x67, // "env" "g"
+            0x03, 0x7e, 0x01, // mutable i64 global
+        ];
+
+        assert_eq!(encode_import_section(&ast), imports);
+        assert_eq!(
+            compile(&ast)[8..],
+            [&[0x01, 0x05, 0x01, 0x60, 0x01, 0x7f, 0x00][..], &imports].concat()
+        );
+    }
+
+    #[test]
+    fn encode_simd() {
+        let body = vec![
+            V128Const(0x0f0e0d0c_0b0a0908_07060504_03020100),
+            V128Load(MemArg {
+                align: 4,
+                offset: 16,
+                memory: 0,
+            }),
+            V128Load8Lane(
+                MemArg {
+                    align: 0,
+                    offset: 0,
+                    memory: 0,
+                },
+                15,
+            ),
+            I8x16Shuffle([0, 16, 1, 17, 2, 18, 3, 19, 4, 20, 5, 21, 6, 22, 7, 23]),
+            I32x4ExtractLane(3),
+            Simd(SimdOp::I8x16Swizzle),
+            Simd(SimdOp::F64x2ConvertLowI32x4U),
+        ];
+        let code = [
+            vec![0xfd, 0x0c], // v128.const
+            (0..16).collect(),
+            vec![0xfd, 0x00, 0x04, 0x10],       // v128.load offset=16
+            vec![0xfd, 0x54, 0x00, 0x00, 0x0f], // v128.load8_lane 15
+            vec![0xfd, 0x0d],                   // i8x16.shuffle
+            vec![0, 16, 1, 17, 2, 18, 3, 19, 4, 20, 5, 21, 6, 22, 7, 23],
+            vec![0xfd, 0x1b, 0x03], // i32x4.extract_lane 3
+            vec![0xfd, 0x0e],       // i8x16.swizzle
+            vec![0xfd, 0xff, 0x01], // f64x2.convert_low_i32x4_u
+        ]
+        .concat();
+
+        assert_eq!(encode_instrs(&body), code);
+        assert_eq!(super::encode_locals(&[V128]), [0x01, 0x01, 0x7b]);
+    }
+
+    #[test]
+    fn encode_tail_calls() {
+        let body = vec![ReturnCall(130), ReturnCallIndirect(2, 1)];
+        let code = [
+            0x12, 0x82, 0x01, // return_call 130
+            0x13, 0x02, 0x01, // return_call_indirect 2 1
+        ];
+        assert_eq!(encode_instrs(&body), code);
+    }
+
+    #[test]
+    fn encode_func_refs() {
+        let body = vec![
+            CallRef(1),
+            ReturnCallRef(0),
+            RefAsNonNull,
+            BrOnNull(2),
+            BrOnNonNull(0),
+            Select(Some(Ref {
+                nullable: true,
+                type_idx: 1,
+            })),
+            Block(
+                BlockType::Value(Ref {
+                    nullable: false,
+                    type_idx: 64,
+                }),
+                vec![],
+            ),
+        ];
+        let code = [
+            0x14, 0x01, // call_ref 1
+            0x15, 0x00, // return_call_ref 0
+            0xd4, // ref.as_non_null
+            0xd5, 0x02, // br_on_null 2
+            0xd6, 0x00, // br_on_non_null 0
+            0x1c, 0x01, 0x63, 0x01, // select (result (ref null 1))
+            0x02, 0x64, 0xc0, 0x00, 0x0b, // block (result (ref 64)) end
+        ];
+        assert_eq!(encode_instrs(&body), code);
+    }
+
+    #[test]
+    fn encode_exceptions() {
+        let body = vec![
+            TryTable(
+                BlockType::Value(I32),
+                vec![Catch::Tag(1, 0), Catch::AllRef(2)],
+                vec![Throw(1)],
+            ),
+            ThrowRef,
+        ];
+        let code = [
+            0x1f, 0x7f, 0x02, // try_table (result i32) with two catch clauses
+            0x00, 0x01, 0x00, // catch 1 0
+            0x03, 0x02, // catch_all_ref 2
+            0x08, 0x01, // throw 1
+            0x0b, // end
+            0x0a, // throw_ref
+        ];
+        assert_eq!(encode_instrs(&body), code);
+
+        let mut ast = Module {
+            types: vec![(vec![I32], vec![])],
+            imports: vec![],
+            funcs: vec![],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![0],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![],
+            start: None,
+        };
+        ast.exports.push(Export {
+            name: "e".to_string(),
+            e_desc: EDesc::TagExport(0),
+        });
+        let tags = [
+            0x0d, // section code
+            0x03, // section size
+            0x01, // num tags
+            0x00, // exception
+            0x00, // type index
+        ];
+        assert_eq!(encode_tag_section(&ast), tags);
+        assert_eq!(compile(&ast)[15..20], tags);
+        assert_eq!(encode_export_section(&ast)[5..], [0x04, 0x00]);
+    }
+
+    #[test]
+    fn encode_memory64() {
+        let ast = Module {
+            types: vec![],
+            imports: vec![],
+            funcs: vec![],
+            tables: vec![],
+            memories: vec![Memory {
+                limits: Limits {
+                    min: 1,
+                    max: Some(1 << 32),
+                },
+                shared: false,
+                memory64: true,
+            }],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![],
+            start: None,
+        };
+        let memories = [
+            0x05, // section code
+            0x08, // section size
+            0x01, // num memories
+            0x05, // 64-bit, min and max
+            0x01, 0x80, 0x80, 0x80, 0x80, 0x10, // min 1, max 2^32
+        ];
+        assert_eq!(encode_memory_section(&ast), memories);
+
+        let load = I64Load(MemArg {
+            align: 3,
+            offset: 1 << 32,
+            memory: 0,
+        });
+        let code = [0x29, 0x03, 0x80, 0x80, 0x80, 0x80, 0x10];
+        assert_eq!(encode_instrs(&[load]), code);
+    }
+
+    #[test]
+    fn encode_multiple_memories() {
+        let memarg = MemArg {
+            align: 2,
+            offset: 4,
+            memory: 1,
+        };
+        assert_eq!(encode_instr(&I32Load(memarg)), [0x28, 0x42, 0x01, 0x04]);
+        assert_eq!(
+            encode_instr(&V128Load32Lane(memarg, 3)),
+            [0xfd, 0x56, 0x42, 0x01, 0x04, 0x03]
+        );
+        assert_eq!(
+            encode_instr(&AtomicLoad(AtomicWidth::I32, memarg)),
+            [0xfe, 0x10, 0x42, 0x01, 0x04]
+        );
+        assert_eq!(encode_instr(&MemorySize(1)), [0x3f, 0x01]);
+        assert_eq!(encode_instr(&MemoryGrow(2)), [0x40, 0x02]);
+        assert_eq!(encode_instr(&MemoryInit(1, 2)), [0xfc, 0x08, 0x02, 0x01]);
+        assert_eq!(encode_instr(&MemoryCopy(1, 0)), [0xfc, 0x0a, 0x01, 0x00]);
+        assert_eq!(encode_instr(&MemoryFill(1)), [0xfc, 0x0b, 0x01]);
+    }
+
+    #[test]
+    fn encode_atomics() {
+        let memarg = |align, offset| MemArg {
+            align,
+            offset,
+            memory: 0,
+        };
+        let body = vec![
+            MemoryAtomicWait64(memarg(3, 0)),
+            AtomicFence,
+            AtomicStore(AtomicWidth::I64U32, memarg(2, 4)),
+            AtomicRmw(AtomicRmwOp::Sub, AtomicWidth::I32U8, memarg(0, 0)),
+        ];
+        let code = [
+            vec![0xfe, 0x02, 0x03, 0x00], // memory.atomic.wait64
+            vec![0xfe, 0x03, 0x00],       // atomic.fence
+            vec![0xfe, 0x1d, 0x02, 0x04], // i64.atomic.store32 offset=4
+            vec![0xfe, 0x27, 0x00, 0x00], // i32.atomic.rmw8.sub_u
+        ]
+        .concat();
+        assert_eq!(encode_instrs(&body), code);
+
+        let memory = Memory {
+            limits: Limits {
+                min: 1,
+                max: Some(2),
+            },
+            shared: true,
+            memory64: false,
+        };
+        assert_eq!(super::encode_memory(&memory), [0x03, 0x01, 0x02]);
+    }
+
+    #[test]
+    fn encode_start() {
+        let mut ast = Module {
+            types: vec![(vec![], vec![])],
+            imports: vec![],
+            funcs: vec![Func {
+                f_type: 0,
+                locals: vec![],
+                body: vec![],
+            }],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![],
+            start: None,
+        };
+        assert_eq!(encode_start_section(&ast), []);
+
+        ast.start = Some(0);
+        let start = [
+            0x08, // section code
+            0x01, // section size
+            0x00, // start function
+        ];
+        assert_eq!(encode_start_section(&ast), start);
+        assert_eq!(compile(&ast)[18..21], start);
+    }
 }
@@ -0,0 +1,5 @@
+pub mod ast;
+pub mod compiler;
+mod op_codes;
+pub mod parser;
+pub mod runtime;
@@ -1,16 +1,11 @@
 use std::fs::{read_to_string, File};
 use std::io::{Read, Write};
-
-mod ast;
-mod compiler;
-mod op_codes;
-mod parser;
-mod runtime;
+use wasmc::{compiler, parser, runtime};
 
 fn main() {
     // Parse the "add.wat" file with the WASM text representation.
     let wat = read_to_string("./add.wat").expect("Failed to read wat file.");
-    let ast = parser::parse(&wat);
+    let ast = parser::parse(&wat).expect("Failed to parse wat file.");
 
     // Compile the WASM text representation to WASM binary code and save the
     // compiled module in the file "add.wasm"
@@ -24,7 +19,8 @@ fn main() {
         .unwrap()
         .read_to_end(&mut wasm)
         .unwrap();
-    let result = runtime::invoke_function(wasm, "add", &[5, 6]).unwrap();
+    let params = [runtime::Value::I32(5), runtime::Value::I32(6)];
+    let result = runtime::invoke_function(wasm, "add", &params).unwrap();
 
-    println!("5 + 6 = {}", result);
+    println!("5 + 6 = {}", result[0]);
 }
@@ -1,35 +1,663 @@
-use crate::ast::ValueType;
+use crate::ast::{RefType, SimdOp, ValueType};
 
 pub const MAGIC: &[u8] = &[0x00, 0x61, 0x73, 0x6d];
 pub const VERSION: &[u8] = &[0x01, 0x00, 0x00, 0x00];
 
+/// The byte that identifies `vt`. References to a function type are
+/// followed by the type index.
 pub fn val_type(vt: &ValueType) -> u8 {
     match vt {
         ValueType::I32 => 0x7f,
         ValueType::I64 => 0x7e,
+        ValueType::F32 => 0x7d,
+        ValueType::F64 => 0x7c,
+        ValueType::V128 => 0x7b,
+        ValueType::FuncRef => 0x70,
+        ValueType::ExternRef => 0x6f,
+        ValueType::ExnRef => 0x69,
+        ValueType::Ref { nullable: true, .. } => typed_ref::REF_NULL,
+        ValueType::Ref {
+            nullable: false, ..
+        } => typed_ref::REF,
     }
 }
 
+pub fn ref_type(rt: &RefType) -> u8 {
+    match rt {
+        RefType::FuncRef => 0x70,
+        RefType::ExternRef => 0x6f,
+        RefType::ExnRef => 0x69,
+    }
+}
+
+/// Prefixes of references to a function type.
+pub mod typed_ref {
+    pub const REF_NULL: u8 = 0x63;
+    pub const REF: u8 = 0x64;
+}
+
 pub mod section {
     pub const TYPE: u8 = 0x01;
+    pub const IMPORT: u8 = 0x02;
     pub const CODE: u8 = 0x0a;
+    pub const DATA: u8 = 0x0b;
+    pub const DATA_COUNT: u8 = 0x0c;
     pub const FUNC: u8 = 0x03;
+    pub const TABLE: u8 = 0x04;
+    pub const MEMORY: u8 = 0x05;
+    pub const GLOBAL: u8 = 0x06;
     pub const EXPORT: u8 = 0x07;
+    pub const START: u8 = 0x08;
+    pub const ELEM: u8 = 0x09;
+    /// Comes between the memory and the global section.
+    pub const TAG: u8 = 0x0d;
+}
+
+pub mod parametric {
+    pub const DROP: u8 = 0x1a;
+    pub const SELECT: u8 = 0x1b;
+    pub const SELECT_TYPED: u8 = 0x1c;
 }
 
 pub mod var_instr {
     pub const LOCAL_GET: u8 = 0x20;
+    pub const LOCAL_SET: u8 = 0x21;
+    pub const LOCAL_TEE: u8 = 0x22;
+    pub const GLOBAL_GET: u8 = 0x23;
+    pub const GLOBAL_SET: u8 = 0x24;
+}
+
+pub mod table_instr {
+    pub const TABLE_GET: u8 = 0x25;
+    pub const TABLE_SET: u8 = 0x26;
+}
+
+pub mod ref_instr {
+    pub const REF_NULL: u8 = 0xd0;
+    pub const REF_IS_NULL: u8 = 0xd1;
+    pub const REF_FUNC: u8 = 0xd2;
+    pub const REF_AS_NON_NULL: u8 = 0xd4;
+    pub const BR_ON_NULL: u8 = 0xd5;
+    pub const BR_ON_NON_NULL: u8 = 0xd6;
+}
+
+pub mod mem_instr {
+    pub const I32_LOAD: u8 = 0x28;
+    pub const I64_LOAD: u8 = 0x29;
+    pub const F32_LOAD: u8 = 0x2a;
+    pub const F64_LOAD: u8 = 0x2b;
+    pub const I32_LOAD8_S: u8 = 0x2c;
+    pub const I32_LOAD8_U: u8 = 0x2d;
+    pub const I32_LOAD16_S: u8 = 0x2e;
+    pub const I32_LOAD16_U: u8 = 0x2f;
+    pub const I64_LOAD8_S: u8 = 0x30;
+    pub const I64_LOAD8_U: u8 = 0x31;
+    pub const I64_LOAD16_S: u8 = 0x32;
+    pub const I64_LOAD16_U: u8 = 0x33;
+    pub const I64_LOAD32_S: u8 = 0x34;
+    pub const I64_LOAD32_U: u8 = 0x35;
+    pub const I32_STORE: u8 = 0x36;
+    pub const I64_STORE: u8 = 0x37;
+    pub const F32_STORE: u8 = 0x38;
+    pub const F64_STORE: u8 = 0x39;
+    pub const I32_STORE8: u8 = 0x3a;
+    pub const I32_STORE16: u8 = 0x3b;
+    pub const I64_STORE8: u8 = 0x3c;
+    pub const I64_STORE16: u8 = 0x3d;
+    pub const I64_STORE32: u8 = 0x3e;
+    pub const MEMORY_SIZE: u8 = 0x3f;
+    pub const MEMORY_GROW: u8 = 0x40;
 }
 
 pub mod num_instr {
+    pub const I32_CONST: u8 = 0x41;
+    pub const I64_CONST: u8 = 0x42;
+    pub const F32_CONST: u8 = 0x43;
+    pub const F64_CONST: u8 = 0x44;
+
+    pub const I32_EQZ: u8 = 0x45;
+    pub const I32_EQ: u8 = 0x46;
+    pub const I32_NE: u8 = 0x47;
+    pub const I32_LT_S: u8 = 0x48;
+    pub const I32_LT_U: u8 = 0x49;
+    pub const I32_GT_S: u8 = 0x4a;
+    pub const I32_GT_U: u8 = 0x4b;
+    pub const I32_LE_S: u8 = 0x4c;
+    pub const I32_LE_U: u8 = 0x4d;
+    pub const I32_GE_S: u8 = 0x4e;
+    pub const I32_GE_U: u8 = 0x4f;
+
+    pub const I32_CLZ: u8 = 0x67;
+    pub const I32_CTZ: u8 = 0x68;
+    pub const I32_POPCNT: u8 = 0x69;
     pub const I32_ADD: u8 = 0x6a;
+    pub const I32_SUB: u8 = 0x6b;
+    pub const I32_MUL: u8 = 0x6c;
+    pub const I32_DIV_S: u8 = 0x6d;
+    pub const I32_DIV_U: u8 = 0x6e;
+    pub const I32_REM_S: u8 = 0x6f;
+    pub const I32_REM_U: u8 = 0x70;
+    pub const I32_AND: u8 = 0x71;
+    pub const I32_OR: u8 = 0x72;
+    pub const I32_XOR: u8 = 0x73;
+    pub const I32_SHL: u8 = 0x74;
+    pub const I32_SHR_S: u8 = 0x75;
+    pub const I32_SHR_U: u8 = 0x76;
+    pub const I32_ROTL: u8 = 0x77;
+    pub const I32_ROTR: u8 = 0x78;
+
+    pub const I64_EQZ: u8 = 0x50;
+    pub const I64_EQ: u8 = 0x51;
+    pub const I64_NE: u8 = 0x52;
+    pub const I64_LT_S: u8 = 0x53;
+    pub const I64_LT_U: u8 = 0x54;
+    pub const I64_GT_S: u8 = 0x55;
+    pub const I64_GT_U: u8 = 0x56;
+    pub const I64_LE_S: u8 = 0x57;
+    pub const I64_LE_U: u8 = 0x58;
+    pub const I64_GE_S: u8 = 0x59;
+    pub const I64_GE_U: u8 = 0x5a;
+
+    pub const I64_CLZ: u8 = 0x79;
+    pub const I64_CTZ: u8 = 0x7a;
+    pub const I64_POPCNT: u8 = 0x7b;
+    pub const I64_ADD: u8 = 0x7c;
+    pub const I64_SUB: u8 = 0x7d;
+    pub const I64_MUL: u8 = 0x7e;
+    pub const I64_DIV_S: u8 = 0x7f;
+    pub const I64_DIV_U: u8 = 0x80;
+    pub const I64_REM_S: u8 = 0x81;
+    pub const I64_REM_U: u8 = 0x82;
+    pub const I64_AND: u8 = 0x83;
+    pub const I64_OR: u8 = 0x84;
+    pub const I64_XOR: u8 = 0x85;
+    pub const I64_SHL: u8 = 0x86;
+    pub const I64_SHR_S: u8 = 0x87;
+    pub const I64_SHR_U: u8 = 0x88;
+    pub const I64_ROTL: u8 = 0x89;
+    pub const I64_ROTR: u8 = 0x8a;
+
+    pub const F32_EQ: u8 = 0x5b;
+    pub const F32_NE: u8 = 0x5c;
+    pub const F32_LT: u8 = 0x5d;
+    pub const F32_GT: u8 = 0x5e;
+    pub const F32_LE: u8 = 0x5f;
+    pub const F32_GE: u8 = 0x60;
+
+    pub const F64_EQ: u8 = 0x61;
+    pub const F64_NE: u8 = 0x62;
+    pub const F64_LT: u8 = 0x63;
+    pub const F64_GT: u8 = 0x64;
+    pub const F64_LE: u8 = 0x65;
+    pub const F64_GE: u8 = 0x66;
+
+    pub const F32_ABS: u8 = 0x8b;
+    pub const F32_NEG: u8 = 0x8c;
+    pub const F32_CEIL: u8 = 0x8d;
+    pub const F32_FLOOR: u8 = 0x8e;
+    pub const F32_TRUNC: u8 = 0x8f;
+    pub const F32_NEAREST: u8 = 0x90;
+    pub const F32_SQRT: u8 = 0x91;
+    pub const F32_ADD: u8 = 0x92;
+    pub const F32_SUB: u8 = 0x93;
+    pub const F32_MUL: u8 = 0x94;
+    pub const F32_DIV: u8 = 0x95;
+    pub const F32_MIN: u8 = 0x96;
+    pub const F32_MAX: u8 = 0x97;
+    pub const F32_COPYSIGN: u8 = 0x98;
+
+    pub const F64_ABS: u8 = 0x99;
+    pub const F64_NEG: u8 = 0x9a;
+    pub const F64_CEIL: u8 = 0x9b;
+    pub const F64_FLOOR: u8 = 0x9c;
+    pub const F64_TRUNC: u8 = 0x9d;
+    pub const F64_NEAREST: u8 = 0x9e;
+    pub const F64_SQRT: u8 = 0x9f;
+    pub const F64_ADD: u8 = 0xa0;
+    pub const F64_SUB: u8 = 0xa1;
+    pub const F64_MUL: u8 = 0xa2;
+    pub const F64_DIV: u8 = 0xa3;
+    pub const F64_MIN: u8 = 0xa4;
+    pub const F64_MAX: u8 = 0xa5;
+    pub const F64_COPYSIGN: u8 = 0xa6;
+
+    pub const I32_WRAP_I64: u8 = 0xa7;
+    pub const I32_TRUNC_F32_S: u8 = 0xa8;
+    pub const I32_TRUNC_F32_U: u8 = 0xa9;
+    pub const I32_TRUNC_F64_S: u8 = 0xaa;
+    pub const I32_TRUNC_F64_U: u8 = 0xab;
+    pub const I64_EXTEND_I32_S: u8 = 0xac;
+    pub const I64_EXTEND_I32_U: u8 = 0xad;
+    pub const I64_TRUNC_F32_S: u8 = 0xae;
+    pub const I64_TRUNC_F32_U: u8 = 0xaf;
+    pub const I64_TRUNC_F64_S: u8 = 0xb0;
+    pub const I64_TRUNC_F64_U: u8 = 0xb1;
+    pub const F32_CONVERT_I32_S: u8 = 0xb2;
+    pub const F32_CONVERT_I32_U: u8 = 0xb3;
+    pub const F32_CONVERT_I64_S: u8 = 0xb4;
+    pub const F32_CONVERT_I64_U: u8 = 0xb5;
+    pub const F32_DEMOTE_F64: u8 = 0xb6;
+    pub const F64_CONVERT_I32_S: u8 = 0xb7;
+    pub const F64_CONVERT_I32_U: u8 = 0xb8;
+    pub const F64_CONVERT_I64_S: u8 = 0xb9;
+    pub const F64_CONVERT_I64_U: u8 = 0xba;
+    pub const F64_PROMOTE_F32: u8 = 0xbb;
+    pub const I32_REINTERPRET_F32: u8 = 0xbc;
+    pub const I64_REINTERPRET_F64: u8 = 0xbd;
+    pub const F32_REINTERPRET_I32: u8 = 0xbe;
+    pub const F64_REINTERPRET_I64: u8 = 0xbf;
+
+    pub const I32_EXTEND8_S: u8 = 0xc0;
+    pub const I32_EXTEND16_S: u8 = 0xc1;
+    pub const I64_EXTEND8_S: u8 = 0xc2;
+    pub const I64_EXTEND16_S: u8 = 0xc3;
+    pub const I64_EXTEND32_S: u8 = 0xc4;
+}
+
+pub mod prefix {
+    pub const MISC: u8 = 0xfc;
+    pub const SIMD: u8 = 0xfd;
+    pub const THREADS: u8 = 0xfe;
+}
+
+/// Opcodes after the threads prefix. Loads, stores and each read-modify-write
+/// operation form groups of consecutive opcodes, one for every width.
+pub mod atomic_instr {
+    use crate::ast::{AtomicRmwOp, AtomicWidth};
+
+    pub const MEMORY_ATOMIC_NOTIFY: u32 = 0x00;
+    pub const MEMORY_ATOMIC_WAIT32: u32 = 0x01;
+    pub const MEMORY_ATOMIC_WAIT64: u32 = 0x02;
+    pub const ATOMIC_FENCE: u32 = 0x03;
+    pub const ATOMIC_LOAD: u32 = 0x10;
+    pub const ATOMIC_STORE: u32 = 0x17;
+    pub const ATOMIC_RMW: u32 = 0x1e;
+    pub const ATOMIC_RMW_LAST: u32 = 0x4e;
+
+    /// Widths in the order of their opcodes within a group.
+    pub const WIDTHS: [AtomicWidth; 7] = [
+        AtomicWidth::I32,
+        AtomicWidth::I64,
+        AtomicWidth::I32U8,
+        AtomicWidth::I32U16,
+        AtomicWidth::I64U8,
+        AtomicWidth::I64U16,
+        AtomicWidth::I64U32,
+    ];
+
+    pub const RMW_OPS: [AtomicRmwOp; 7] = [
+        AtomicRmwOp::Add,
+        AtomicRmwOp::Sub,
+        AtomicRmwOp::And,
+        AtomicRmwOp::Or,
+        AtomicRmwOp::Xor,
+        AtomicRmwOp::Xchg,
+        AtomicRmwOp::Cmpxchg,
+    ];
+
+    pub fn width_idx(width: &AtomicWidth) -> u32 {
+        WIDTHS.iter().position(|w| w == width).unwrap() as u32
+    }
+
+    pub fn rmw_code(op: &AtomicRmwOp, width: &AtomicWidth) -> u32 {
+        let group = RMW_OPS.iter().position(|o| o == op).unwrap() as u32;
+        ATOMIC_RMW + group * WIDTHS.len() as u32 + width_idx(width)
+    }
+}
+
+/// Opcodes after the SIMD prefix of the instructions with immediates.
+pub mod simd_instr {
+    pub const V128_LOAD: u32 = 0x00;
+    pub const V128_LOAD8X8_S: u32 = 0x01;
+    pub const V128_LOAD8X8_U: u32 = 0x02;
+    pub const V128_LOAD16X4_S: u32 = 0x03;
+    pub const V128_LOAD16X4_U: u32 = 0x04;
+    pub const V128_LOAD32X2_S: u32 = 0x05;
+    pub const V128_LOAD32X2_U: u32 = 0x06;
+    pub const V128_LOAD8_SPLAT: u32 = 0x07;
+    pub const V128_LOAD16_SPLAT: u32 = 0x08;
+    pub const V128_LOAD32_SPLAT: u32 = 0x09;
+    pub const V128_LOAD64_SPLAT: u32 = 0x0a;
+    pub const V128_STORE: u32 = 0x0b;
+    pub const V128_CONST: u32 = 0x0c;
+    pub const I8X16_SHUFFLE: u32 = 0x0d;
+    pub const I8X16_EXTRACT_LANE_S: u32 = 0x15;
+    pub const I8X16_EXTRACT_LANE_U: u32 = 0x16;
+    pub const I8X16_REPLACE_LANE: u32 = 0x17;
+    pub const I16X8_EXTRACT_LANE_S: u32 = 0x18;
+    pub const I16X8_EXTRACT_LANE_U: u32 = 0x19;
+    pub const I16X8_REPLACE_LANE: u32 = 0x1a;
+    pub const I32X4_EXTRACT_LANE: u32 = 0x1b;
+    pub const I32X4_REPLACE_LANE: u32 = 0x1c;
+    pub const I64X2_EXTRACT_LANE: u32 = 0x1d;
+    pub const I64X2_REPLACE_LANE: u32 = 0x1e;
+    pub const F32X4_EXTRACT_LANE: u32 = 0x1f;
+    pub const F32X4_REPLACE_LANE: u32 = 0x20;
+    pub const F64X2_EXTRACT_LANE: u32 = 0x21;
+    pub const F64X2_REPLACE_LANE: u32 = 0x22;
+    pub const V128_LOAD8_LANE: u32 = 0x54;
+    pub const V128_LOAD16_LANE: u32 = 0x55;
+    pub const V128_LOAD32_LANE: u32 = 0x56;
+    pub const V128_LOAD64_LANE: u32 = 0x57;
+    pub const V128_STORE8_LANE: u32 = 0x58;
+    pub const V128_STORE16_LANE: u32 = 0x59;
+    pub const V128_STORE32_LANE: u32 = 0x5a;
+    pub const V128_STORE64_LANE: u32 = 0x5b;
+    pub const V128_LOAD32_ZERO: u32 = 0x5c;
+    pub const V128_LOAD64_ZERO: u32 = 0x5d;
+}
+
+/// Opcodes after the SIMD prefix of the instructions without immediates.
+const SIMD_OPS: &[(SimdOp, u32)] = &[
+    (SimdOp::I8x16Swizzle, 0x0e),
+    (SimdOp::I8x16Splat, 0x0f),
+    (SimdOp::I16x8Splat, 0x10),
+    (SimdOp::I32x4Splat, 0x11),
+    (SimdOp::I64x2Splat, 0x12),
+    (SimdOp::F32x4Splat, 0x13),
+    (SimdOp::F64x2Splat, 0x14),
+    (SimdOp::I8x16Eq, 0x23),
+    (SimdOp::I8x16Ne, 0x24),
+    (SimdOp::I8x16LtS, 0x25),
+    (SimdOp::I8x16LtU, 0x26),
+    (SimdOp::I8x16GtS, 0x27),
+    (SimdOp::I8x16GtU, 0x28),
+    (SimdOp::I8x16LeS, 0x29),
+    (SimdOp::I8x16LeU, 0x2a),
+    (SimdOp::I8x16GeS, 0x2b),
+    (SimdOp::I8x16GeU, 0x2c),
+    (SimdOp::I16x8Eq, 0x2d),
+    (SimdOp::I16x8Ne, 0x2e),
+    (SimdOp::I16x8LtS, 0x2f),
+    (SimdOp::I16x8LtU, 0x30),
+    (SimdOp::I16x8GtS, 0x31),
+    (SimdOp::I16x8GtU, 0x32),
+    (SimdOp::I16x8LeS, 0x33),
+    (SimdOp::I16x8LeU, 0x34),
+    (SimdOp::I16x8GeS, 0x35),
+    (SimdOp::I16x8GeU, 0x36),
+    (SimdOp::I32x4Eq, 0x37),
+    (SimdOp::I32x4Ne, 0x38),
+    (SimdOp::I32x4LtS, 0x39),
+    (SimdOp::I32x4LtU, 0x3a),
+    (SimdOp::I32x4GtS, 0x3b),
+    (SimdOp::I32x4GtU, 0x3c),
+    (SimdOp::I32x4LeS, 0x3d),
+    (SimdOp::I32x4LeU, 0x3e),
+    (SimdOp::I32x4GeS, 0x3f),
+    (SimdOp::I32x4GeU, 0x40),
+    (SimdOp::F32x4Eq, 0x41),
+    (SimdOp::F32x4Ne, 0x42),
+    (SimdOp::F32x4Lt, 0x43),
+    (SimdOp::F32x4Gt, 0x44),
+    (SimdOp::F32x4Le, 0x45),
+    (SimdOp::F32x4Ge, 0x46),
+    (SimdOp::F64x2Eq, 0x47),
+    (SimdOp::F64x2Ne, 0x48),
+    (SimdOp::F64x2Lt, 0x49),
+    (SimdOp::F64x2Gt, 0x4a),
+    (SimdOp::F64x2Le, 0x4b),
+    (SimdOp::F64x2Ge, 0x4c),
+    (SimdOp::V128Not, 0x4d),
+    (SimdOp::V128And, 0x4e),
+    (SimdOp::V128AndNot, 0x4f),
+    (SimdOp::V128Or, 0x50),
+    (SimdOp::V128Xor, 0x51),
+    (SimdOp::V128Bitselect, 0x52),
+    (SimdOp::V128AnyTrue, 0x53),
+    (SimdOp::F32x4DemoteF64x2Zero, 0x5e),
+    (SimdOp::F64x2PromoteLowF32x4, 0x5f),
+    (SimdOp::I8x16Abs, 0x60),
+    (SimdOp::I8x16Neg, 0x61),
+    (SimdOp::I8x16Popcnt, 0x62),
+    (SimdOp::I8x16AllTrue, 0x63),
+    (SimdOp::I8x16Bitmask, 0x64),
+    (SimdOp::I8x16NarrowI16x8S, 0x65),
+    (SimdOp::I8x16NarrowI16x8U, 0x66),
+    (SimdOp::F32x4Ceil, 0x67),
+    (SimdOp::F32x4Floor, 0x68),
+    (SimdOp::F32x4Trunc, 0x69),
+    (SimdOp::F32x4Nearest, 0x6a),
+    (SimdOp::I8x16Shl, 0x6b),
+    (SimdOp::I8x16ShrS, 0x6c),
+    (SimdOp::I8x16ShrU, 0x6d),
+    (SimdOp::I8x16Add, 0x6e),
+    (SimdOp::I8x16AddSatS, 0x6f),
+    (SimdOp::I8x16AddSatU, 0x70),
+    (SimdOp::I8x16Sub, 0x71),
+    (SimdOp::I8x16SubSatS, 0x72),
+    (SimdOp::I8x16SubSatU, 0x73),
+    (SimdOp::F64x2Ceil, 0x74),
+    (SimdOp::F64x2Floor, 0x75),
+    (SimdOp::I8x16MinS, 0x76),
+    (SimdOp::I8x16MinU, 0x77),
+    (SimdOp::I8x16MaxS, 0x78),
+    (SimdOp::I8x16MaxU, 0x79),
+    (SimdOp::F64x2Trunc, 0x7a),
+    (SimdOp::I8x16AvgrU, 0x7b),
+    (SimdOp::I16x8ExtaddPairwiseI8x16S, 0x7c),
+    (SimdOp::I16x8ExtaddPairwiseI8x16U, 0x7d),
+    (SimdOp::I32x4ExtaddPairwiseI16x8S, 0x7e),
+    (SimdOp::I32x4ExtaddPairwiseI16x8U, 0x7f),
+    (SimdOp::I16x8Abs, 0x80),
+    (SimdOp::I16x8Neg, 0x81),
+    (SimdOp::I16x8Q15mulrSatS, 0x82),
+    (SimdOp::I16x8AllTrue, 0x83),
+    (SimdOp::I16x8Bitmask, 0x84),
+    (SimdOp::I16x8NarrowI32x4S, 0x85),
+    (SimdOp::I16x8NarrowI32x4U, 0x86),
+    (SimdOp::I16x8ExtendLowI8x16S, 0x87),
+    (SimdOp::I16x8ExtendHighI8x16S, 0x88),
+    (SimdOp::I16x8ExtendLowI8x16U, 0x89),
+    (SimdOp::I16x8ExtendHighI8x16U, 0x8a),
+    (SimdOp::I16x8Shl, 0x8b),
+    (SimdOp::I16x8ShrS, 0x8c),
+    (SimdOp::I16x8ShrU, 0x8d),
+    (SimdOp::I16x8Add, 0x8e),
+    (SimdOp::I16x8AddSatS, 0x8f),
+    (SimdOp::I16x8AddSatU, 0x90),
+    (SimdOp::I16x8Sub, 0x91),
+    (SimdOp::I16x8SubSatS, 0x92),
+    (SimdOp::I16x8SubSatU, 0x93),
+    (SimdOp::F64x2Nearest, 0x94),
+    (SimdOp::I16x8Mul, 0x95),
+    (SimdOp::I16x8MinS, 0x96),
+    (SimdOp::I16x8MinU, 0x97),
+    (SimdOp::I16x8MaxS, 0x98),
+    (SimdOp::I16x8MaxU, 0x99),
+    (SimdOp::I16x8AvgrU, 0x9b),
+    (SimdOp::I16x8ExtmulLowI8x16S, 0x9c),
+    (SimdOp::I16x8ExtmulHighI8x16S, 0x9d),
+    (SimdOp::I16x8ExtmulLowI8x16U, 0x9e),
+    (SimdOp::I16x8ExtmulHighI8x16U, 0x9f),
+    (SimdOp::I32x4Abs, 0xa0),
+    (SimdOp::I32x4Neg, 0xa1),
+    (SimdOp::I32x4AllTrue, 0xa3),
+    (SimdOp::I32x4Bitmask, 0xa4),
+    (SimdOp::I32x4ExtendLowI16x8S, 0xa7),
+    (SimdOp::I32x4ExtendHighI16x8S, 0xa8),
+    (SimdOp::I32x4ExtendLowI16x8U, 0xa9),
+    (SimdOp::I32x4ExtendHighI16x8U, 0xaa),
+    (SimdOp::I32x4Shl, 0xab),
+    (SimdOp::I32x4ShrS, 0xac),
+    (SimdOp::I32x4ShrU, 0xad),
+    (SimdOp::I32x4Add, 0xae),
+    (SimdOp::I32x4Sub, 0xb1),
+    (SimdOp::I32x4Mul, 0xb5),
+    (SimdOp::I32x4MinS, 0xb6),
+    (SimdOp::I32x4MinU, 0xb7),
+    (SimdOp::I32x4MaxS, 0xb8),
+    (SimdOp::I32x4MaxU, 0xb9),
+    (SimdOp::I32x4DotI16x8S, 0xba),
+    (SimdOp::I32x4ExtmulLowI16x8S, 0xbc),
+    (SimdOp::I32x4ExtmulHighI16x8S, 0xbd),
+    (SimdOp::I32x4ExtmulLowI16x8U, 0xbe),
+    (SimdOp::I32x4ExtmulHighI16x8U, 0xbf),
+    (SimdOp::I64x2Abs, 0xc0),
+    (SimdOp::I64x2Neg, 0xc1),
+    (SimdOp::I64x2AllTrue, 0xc3),
+    (SimdOp::I64x2Bitmask, 0xc4),
+    (SimdOp::I64x2ExtendLowI32x4S, 0xc7),
+    (SimdOp::I64x2ExtendHighI32x4S, 0xc8),
+    (SimdOp::I64x2ExtendLowI32x4U, 0xc9),
+    (SimdOp::I64x2ExtendHighI32x4U, 0xca),
+    (SimdOp::I64x2Shl, 0xcb),
+    (SimdOp::I64x2ShrS, 0xcc),
+    (SimdOp::I64x2ShrU, 0xcd),
+    (SimdOp::I64x2Add, 0xce),
+    (SimdOp::I64x2Sub, 0xd1),
+    (SimdOp::I64x2Mul, 0xd5),
+    (SimdOp::I64x2Eq, 0xd6),
+    (SimdOp::I64x2Ne, 0xd7),
+    (SimdOp::I64x2LtS, 0xd8),
+    (SimdOp::I64x2GtS, 0xd9),
+    (SimdOp::I64x2LeS, 0xda),
+    (SimdOp::I64x2GeS, 0xdb),
+    (SimdOp::I64x2ExtmulLowI32x4S, 0xdc),
+    (SimdOp::I64x2ExtmulHighI32x4S, 0xdd),
+    (SimdOp::I64x2ExtmulLowI32x4U, 0xde),
+    (SimdOp::I64x2ExtmulHighI32x4U, 0xdf),
+    (SimdOp::F32x4Abs, 0xe0),
+    (SimdOp::F32x4Neg, 0xe1),
+    (SimdOp::F32x4Sqrt, 0xe3),
+    (SimdOp::F32x4Add, 0xe4),
+    (SimdOp::F32x4Sub, 0xe5),
+    (SimdOp::F32x4Mul, 0xe6),
+    (SimdOp::F32x4Div, 0xe7),
+    (SimdOp::F32x4Min, 0xe8),
+    (SimdOp::F32x4Max, 0xe9),
+    (SimdOp::F32x4Pmin, 0xea),
+    (SimdOp::F32x4Pmax, 0xeb),
+    (SimdOp::F64x2Abs, 0xec),
+    (SimdOp::F64x2Neg, 0xed),
+    (SimdOp::F64x2Sqrt, 0xef),
+    (SimdOp::F64x2Add, 0xf0),
+    (SimdOp::F64x2Sub, 0xf1),
+    (SimdOp::F64x2Mul, 0xf2),
+    (SimdOp::F64x2Div, 0xf3),
+    (SimdOp::F64x2Min, 0xf4),
+    (SimdOp::F64x2Max, 0xf5),
+    (SimdOp::F64x2Pmin, 0xf6),
+    (SimdOp::F64x2Pmax, 0xf7),
+    (SimdOp::I32x4TruncSatF32x4S, 0xf8),
+    (SimdOp::I32x4TruncSatF32x4U, 0xf9),
+    (SimdOp::F32x4ConvertI32x4S, 0xfa),
+    (SimdOp::F32x4ConvertI32x4U, 0xfb),
+    (SimdOp::I32x4TruncSatF64x2SZero, 0xfc),
+    (SimdOp::I32x4TruncSatF64x2UZero, 0xfd),
+    (SimdOp::F64x2ConvertLowI32x4S, 0xfe),
+    (SimdOp::F64x2ConvertLowI32x4U, 0xff),
+];
+
+pub fn simd_op_code(op: &SimdOp) -> u32 {
+    SIMD_OPS.iter().find(|(o, _)| o == op).unwrap().1
+}
+
+pub fn simd_op(code: u32) -> Option<SimdOp> {
+    SIMD_OPS.iter().find(|(_, c)| *c == code).map(|(op, _)| *op)
+}
+
+pub mod misc_instr {
+    pub const I32_TRUNC_SAT_F32_S: u32 = 0x00;
+    pub const I32_TRUNC_SAT_F32_U: u32 = 0x01;
+    pub const I32_TRUNC_SAT_F64_S: u32 = 0x02;
+    pub const I32_TRUNC_SAT_F64_U: u32 = 0x03;
+    pub const I64_TRUNC_SAT_F32_S: u32 = 0x04;
+    pub const I64_TRUNC_SAT_F32_U: u32 = 0x05;
+    pub const I64_TRUNC_SAT_F64_S: u32 = 0x06;
+    pub const I64_TRUNC_SAT_F64_U: u32 = 0x07;
+    pub const MEMORY_INIT: u32 = 0x08;
+    pub const DATA_DROP: u32 = 0x09;
+    pub const MEMORY_COPY: u32 = 0x0a;
+    pub const MEMORY_FILL: u32 = 0x0b;
+    pub const TABLE_INIT: u32 = 0x0c;
+    pub const ELEM_DROP: u32 = 0x0d;
+    pub const TABLE_COPY: u32 = 0x0e;
+    pub const TABLE_GROW: u32 = 0x0f;
+    pub const TABLE_SIZE: u32 = 0x10;
+    pub const TABLE_FILL: u32 = 0x11;
 }
 
 pub mod indices {
     pub const FUNC: u8 = 0x00;
+    pub const TABLE: u8 = 0x01;
+    pub const MEMORY: u8 = 0x02;
+    pub const GLOBAL: u8 = 0x03;
+    pub const TAG: u8 = 0x04;
+}
+
+pub mod limits {
+    pub const MIN: u8 = 0x00;
+    pub const MIN_MAX: u8 = 0x01;
+    /// Flag of the limits of a shared memory.
+    pub const SHARED: u8 = 0x02;
+    /// Flag of the limits of a 64-bit memory, whose limits are `u64`.
+    pub const MEMORY64: u8 = 0x04;
+}
+
+pub mod memarg {
+    /// Flag of the alignment of a memory argument that is followed by the
+    /// index of a memory other than 0.
+    pub const MEMORY_IDX: u32 = 0x40;
+}
+
+pub mod data_mode {
+    pub const ACTIVE: u32 = 0x00;
+    pub const PASSIVE: u32 = 0x01;
+    pub const ACTIVE_MEMORY: u32 = 0x02;
+}
+
+pub mod elem_mode {
+    pub const ACTIVE: u32 = 0x00;
+    pub const PASSIVE: u32 = 0x01;
+    pub const ACTIVE_TABLE: u32 = 0x02;
+    pub const DECLARATIVE: u32 = 0x03;
+    /// Flag of the modes whose elements are constant expressions, which are
+    /// preceded by a reference type instead of an element kind.
+    pub const EXPRS: u32 = 0x04;
+}
+
+pub mod elem_kind {
+    pub const FUNC_REF: u8 = 0x00;
+}
+
+/// Kinds of the catch clauses of a `try_table`.
+pub mod catch_kind {
+    pub const CATCH: u8 = 0x00;
+    pub const CATCH_REF: u8 = 0x01;
+    pub const CATCH_ALL: u8 = 0x02;
+    pub const CATCH_ALL_REF: u8 = 0x03;
+}
+
+/// Attribute of a tag that is an exception.
+pub const TAG_EXCEPTION: u8 = 0x00;
+
+pub mod mutability {
+    pub const CONST: u8 = 0x00;
+    pub const VAR: u8 = 0x01;
 }
 
 pub mod control_flow {
+    pub const UNREACHABLE: u8 = 0x00;
+    pub const NOP: u8 = 0x01;
     pub const FUNC: u8 = 0x60;
+    pub const EMPTY_BLOCK: u8 = 0x40;
+    pub const BLOCK: u8 = 0x02;
+    pub const LOOP: u8 = 0x03;
+    pub const IF: u8 = 0x04;
+    pub const ELSE: u8 = 0x05;
+    pub const THROW: u8 = 0x08;
+    pub const THROW_REF: u8 = 0x0a;
     pub const END: u8 = 0x0b;
+    pub const BR: u8 = 0x0c;
+    pub const BR_IF: u8 = 0x0d;
+    pub const BR_TABLE: u8 = 0x0e;
+    pub const RETURN: u8 = 0x0f;
+    pub const CALL: u8 = 0x10;
+    pub const CALL_INDIRECT: u8 = 0x11;
+    pub const RETURN_CALL: u8 = 0x12;
+    pub const RETURN_CALL_INDIRECT: u8 = 0x13;
+    pub const CALL_REF: u8 = 0x14;
+    pub const RETURN_CALL_REF: u8 = 0x15;
+    pub const TRY_TABLE: u8 = 0x1f;
 }
@@ -1,7 +1,9 @@
-use crate::ast::{Export, Func, FuncType, Type};
+use crate::ast::{
+    Data, Elem, Export, Func, FuncType, Global, Import, ImportDesc, Memory, Table, Type,
+};
 use crate::parser::types::Index;
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct Field<T> {
     pub ids: Vec<Option<String>>,
     pub list: Vec<T>,
@@ -29,60 +31,153 @@ impl<T> Field<T> {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+/// The index spaces of a module that ids are declared in.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Space {
+    Type,
+    Func,
+    Table,
+    Memory,
+    Tag,
+    Global,
+    Elem,
+    Data,
+}
+
+/// The index space that an import adds an entity to.
+fn import_space(desc: &ImportDesc) -> Space {
+    match desc {
+        ImportDesc::Func(_) => Space::Func,
+        ImportDesc::Table(_) => Space::Table,
+        ImportDesc::Memory(_) => Space::Memory,
+        ImportDesc::Global(_) => Space::Global,
+    }
+}
+
+/// Resolves an index, which fails if it is an id that isn't declared.
+fn resolve(ids: &[Option<String>], idx: &Index) -> Option<usize> {
+    match idx {
+        Index::Idx(i) => Some(*i),
+        Index::Id(id) => ids.iter().position(|i| i.as_deref() == Some(id)),
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
 pub struct Ctx {
     pub locals: Vec<Option<String>>,
+    pub labels: Vec<Option<String>>,
+    /// How deeply the instructions being parsed are nested in blocks and
+    /// folded operands.
+    pub depth: usize,
+    /// The ids of all index spaces below are declared before the module is
+    /// parsed, so that entities can be referenced before they are defined.
     pub types: Field<Type>,
+    /// Imports have no ids of their own, their ids are declared in the index
+    /// space of the imported entity instead.
+    pub imports: Field<Import>,
     pub funcs: Field<Func>,
+    pub tables: Field<Table>,
+    pub memories: Field<Memory>,
+    /// Type indices of the exception tags.
+    pub tags: Field<usize>,
+    pub globals: Field<Global>,
+    pub elems: Field<Elem>,
+    pub datas: Field<Data>,
     pub exports: Field<Export>,
+    pub start: Option<usize>,
 }
 
 impl Ctx {
     pub fn new() -> Self {
         Self {
             locals: Vec::new(),
+            labels: Vec::new(),
+            depth: 0,
             types: Field::new(),
+            imports: Field::new(),
             funcs: Field::new(),
+            tables: Field::new(),
+            memories: Field::new(),
+            tags: Field::new(),
+            globals: Field::new(),
+            elems: Field::new(),
+            datas: Field::new(),
             exports: Field::new(),
+            start: None,
         }
     }
 
-    pub fn get_func_idx(&self, idx: &Index) -> usize {
-        match idx {
-            Index::Idx(i) => *i,
-            Index::Id(id) => self
-                .funcs
-                .ids
-                .iter()
-                .position(|i| i == &Some(id.to_owned()))
-                .expect("Func id has to exists"),
+    pub fn declare_id(&mut self, space: Space, id: Option<String>) {
+        match space {
+            Space::Type => self.types.add_id(id),
+            Space::Func => self.funcs.add_id(id),
+            Space::Table => self.tables.add_id(id),
+            Space::Memory => self.memories.add_id(id),
+            Space::Tag => self.tags.add_id(id),
+            Space::Global => self.globals.add_id(id),
+            Space::Elem => self.elems.add_id(id),
+            Space::Data => self.datas.add_id(id),
         }
     }
 
-    pub fn insert_local_id(&mut self, id: &Option<String>) {
+    pub fn get_type_idx(&self, idx: &Index) -> Option<usize> {
+        resolve(&self.types.ids, idx)
+    }
+
+    pub fn get_func_idx(&self, idx: &Index) -> Option<usize> {
+        resolve(&self.funcs.ids, idx)
+    }
+
+    pub fn get_global_idx(&self, idx: &Index) -> Option<usize> {
+        resolve(&self.globals.ids, idx)
+    }
+
+    pub fn get_table_idx(&self, idx: &Index) -> Option<usize> {
+        resolve(&self.tables.ids, idx)
+    }
+
+    pub fn get_memory_idx(&self, idx: &Index) -> Option<usize> {
+        resolve(&self.memories.ids, idx)
+    }
+
+    pub fn get_tag_idx(&self, idx: &Index) -> Option<usize> {
+        resolve(&self.tags.ids, idx)
+    }
+
+    pub fn get_elem_idx(&self, idx: &Index) -> Option<usize> {
+        resolve(&self.elems.ids, idx)
+    }
+
+    pub fn get_data_idx(&self, idx: &Index) -> Option<usize> {
+        resolve(&self.datas.ids, idx)
+    }
+
+    /// Declares the next local, which fails if its id is already declared
+    /// since local ids have to be unique in the scope of a function.
+    pub fn insert_local_id(&mut self, id: &Option<String>) -> Option<()> {
         if id.is_some() && self.locals.contains(id) {
-            panic!("Local identifiers have to be unique in the scope of the function")
-        } else {
-            self.locals.push((*id).clone());
+            return None;
         }
+        self.locals.push((*id).clone());
+        Some(())
     }
 
-    pub fn get_local_idx(&self, index: &Index) -> usize {
+    pub fn get_local_idx(&self, index: &Index) -> Option<usize> {
+        resolve(&self.locals, index)
+    }
+
+    /// Resolves a label to its relative depth, the innermost label being 0.
+    pub fn get_label_idx(&self, index: &Index) -> Option<usize> {
         match index {
-            Index::Idx(i) => *i,
+            Index::Idx(i) => Some(*i),
             Index::Id(id) => self
-                .locals
+                .labels
                 .iter()
-                .position(|x| x == &Some(id.clone()))
-                .expect("Identifier not found"),
+                .rev()
+                .position(|l| l.as_deref() == Some(id)),
         }
     }
 
-    pub fn insert_func_id(&mut self, id: Option<String>) -> usize {
-        self.funcs.add_id(id);
-        self.funcs.ids.len() - 1
-    }
-
     pub fn insert_id_func_type(&mut self, id: Option<String>, t: &FuncType) {
         self.types.add(id, (*t).clone());
     }
@@ -103,10 +198,56 @@ impl Ctx {
         }
     }
 
+    pub fn insert_import(&mut self, import: &Import) {
+        self.imports.add_item((*import).clone());
+    }
+
+    /// The index of the next entity in `space`, after the imported and
+    /// defined ones.
+    pub fn next_idx(&self, space: Space) -> usize {
+        let imports = self.imports.list.iter();
+        let imported = imports.filter(|i| import_space(&i.desc) == space).count();
+        let defined = match space {
+            Space::Type => self.types.list.len(),
+            Space::Func => self.funcs.list.len(),
+            Space::Table => self.tables.list.len(),
+            Space::Memory => self.memories.list.len(),
+            Space::Tag => self.tags.list.len(),
+            Space::Global => self.globals.list.len(),
+            Space::Elem => self.elems.list.len(),
+            Space::Data => self.datas.list.len(),
+        };
+        imported + defined
+    }
+
     pub fn insert_func(&mut self, func: &Func) {
         self.funcs.add_item((*func).clone());
     }
 
+    pub fn insert_table(&mut self, table: &Table) {
+        self.tables.add_item(*table);
+    }
+
+    pub fn insert_memory(&mut self, memory: &Memory) {
+        self.memories.add_item(*memory);
+    }
+
+    pub fn insert_tag(&mut self, type_idx: usize) {
+        self.tags.add_item(type_idx);
+    }
+
+    pub fn insert_global(&mut self, global: &Global) {
+        self.globals.add_item((*global).clone());
+    }
+
+    pub fn insert_elem(&mut self, elem: &Elem) {
+        self.elems.add_item((*elem).clone());
+    }
+
+    pub fn insert_data(&mut self, data: &Data) {
+        self.datas.add_item((*data).clone());
+    }
+
     pub fn insert_export(&mut self, id: &Option<String>, export: &Export) {
         self.exports.add((*id).clone(), (*export).clone());
     }
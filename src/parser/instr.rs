@@ -1,56 +1,1521 @@
-use crate::ast::Instr;
-use crate::ast::Instr::I32Add;
+use crate::ast::Instr::*;
+use crate::ast::{AtomicRmwOp, AtomicWidth, Catch, HeapType, Instr, MemArg, SimdOp};
 use crate::parser::ctx::Ctx;
-use crate::parser::token::bws;
+use crate::parser::token::{bws, keyword, ws};
 use crate::parser::types::index;
+use crate::parser::{token, types, values};
 use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::combinator::map;
-use nom::multi::many1;
-use nom::sequence::preceded;
-use nom::IResult;
+use nom::combinator::{cond, map, map_opt, opt, value, verify};
+use nom::error::{Error, ErrorKind};
+use nom::multi::{count, many0, many1};
+use nom::sequence::{pair, preceded};
+use nom::{Err, IResult};
 use std::cell::RefCell;
+use std::convert::TryFrom;
 use std::rc::Rc;
 
-fn local_get<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, Instr> {
-    let local_get = bws(tag("local.get"));
-    let (input, i) = preceded(local_get, index)(input)?;
-    let i = ctx.borrow().get_local_idx(&i);
-    Ok((input, Instr::LocalGet(i)))
+/// The deepest that blocks and folded operands may be nested in a
+/// function. The parser recurses for every level, so deeper nesting would
+/// overflow the stack.
+const MAX_NESTING_DEPTH: usize = 200;
+
+/// Runs `parser` one level of nesting deeper. Nesting beyond
+/// `MAX_NESTING_DEPTH` is a failure, which isn't retried by alternatives.
+fn nested<'a, T>(
+    input: &'a str,
+    ctx: &Rc<RefCell<Ctx>>,
+    parser: impl FnOnce(&'a str) -> IResult<&'a str, T>,
+) -> IResult<&'a str, T> {
+    if ctx.borrow().depth >= MAX_NESTING_DEPTH {
+        return Err(Err::Failure(Error::new(input, ErrorKind::TooLarge)));
+    }
+    ctx.borrow_mut().depth += 1;
+    let result = parser(input);
+    ctx.borrow_mut().depth -= 1;
+    result
+}
+
+/// Parses the folded operands of a folded instruction, one level deeper.
+fn operands<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, Vec<Instr>> {
+    let folded = many0(bws(|i| folded_instr(i, &mut ctx.clone())));
+    let (input, operands) = nested(input, ctx, folded)?;
+    Ok((input, operands.concat()))
+}
+
+fn label<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, usize> {
+    map_opt(bws(index), |l| ctx.borrow().get_label_idx(&l))(input)
+}
+
+/// Parses the instructions of a block with `label` in scope, one level
+/// deeper.
+fn block_instrs<'a>(
+    input: &'a str,
+    ctx: &mut Rc<RefCell<Ctx>>,
+    label: Option<&str>,
+) -> IResult<&'a str, Vec<Instr>> {
+    ctx.borrow_mut().labels.push(label.map(|l| l.to_string()));
+    let result = nested(input, ctx, |i| instrs(i, &mut ctx.clone()));
+    ctx.borrow_mut().labels.pop();
+    result
+}
+
+/// Parses the `end` of a block with an optional repeated label.
+fn end(input: &str) -> IResult<&str, ()> {
+    map(preceded(keyword("end"), opt(values::id)), |_| ())(input)
+}
+
+/// Parses a block or loop, which ends with `end` unless it is folded like
+/// `(block (result i32) (i32.const 1))`.
+fn block<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>, folded: bool) -> IResult<&'a str, Instr> {
+    let (input, kind) = alt((keyword("block"), keyword("loop")))(input)?;
+    let (input, label) = opt(values::id)(input)?;
+    let (input, bt) = types::block_type(input, ctx)?;
+    let (input, body) = block_instrs(input, ctx, label)?;
+    let (input, _) = cond(!folded, end)(input)?;
+
+    match kind {
+        "block" => Ok((input, Block(bt, body))),
+        _ => Ok((input, Loop(bt, body))),
+    }
+}
+
+fn if_else<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, Instr> {
+    let (input, label) = preceded(keyword("if"), opt(values::id))(input)?;
+    let (input, bt) = types::block_type(input, ctx)?;
+    let (input, then) = block_instrs(input, ctx, label)?;
+    let (input, els) = opt(preceded(keyword("else"), opt(values::id)))(input)?;
+    let (input, els) = match els {
+        Some(_) => block_instrs(input, ctx, label)?,
+        None => (input, vec![]),
+    };
+    let (input, _) = end(input)?;
+
+    Ok((input, If(bt, then, els)))
+}
+
+/// Parses a folded `(if bt folded* (then instr*) (else instr*)?)`, whose
+/// folded instructions compute the condition.
+fn folded_if<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, Vec<Instr>> {
+    let (input, label) = preceded(keyword("if"), opt(values::id))(input)?;
+    let (input, bt) = types::block_type(input, ctx)?;
+    let (input, mut instrs) = operands(input, ctx)?;
+    let arm = |kw| {
+        let ctx = ctx.clone();
+        bws(token::pt(preceded(keyword(kw), move |i| {
+            block_instrs(i, &mut ctx.clone(), label)
+        })))
+    };
+    let (input, then) = arm("then")(input)?;
+    let (input, els) = opt(arm("else"))(input)?;
+
+    instrs.push(If(bt, then, els.unwrap_or_default()));
+    Ok((input, instrs))
+}
+
+/// Parses a catch clause of `try_table`, e.g. `(catch $e $l)`.
+fn catch<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, Catch> {
+    let tag = |i| map_opt(bws(index), |t| ctx.borrow().get_tag_idx(&t))(i);
+    let label = |i| label(i, ctx);
+    let clause = alt((
+        map(preceded(keyword("catch"), pair(tag, label)), |(t, l)| {
+            Catch::Tag(t, l)
+        }),
+        map(
+            preceded(keyword("catch_ref"), pair(tag, label)),
+            |(t, l)| Catch::TagRef(t, l),
+        ),
+        map(preceded(keyword("catch_all"), label), Catch::All),
+        map(preceded(keyword("catch_all_ref"), label), Catch::AllRef),
+    ));
+    bws(token::pt(clause))(input)
+}
+
+/// Parses `try_table`, whose catch clauses branch to labels outside of it.
+/// It ends with `end` unless it is folded.
+fn try_table<'a>(
+    input: &'a str,
+    ctx: &mut Rc<RefCell<Ctx>>,
+    folded: bool,
+) -> IResult<&'a str, Instr> {
+    let (input, label) = preceded(keyword("try_table"), opt(values::id))(input)?;
+    let (input, bt) = types::block_type(input, ctx)?;
+    let (input, catches) = many0(|i| catch(i, ctx))(input)?;
+    let (input, body) = block_instrs(input, ctx, label)?;
+    let (input, _) = cond(!folded, end)(input)?;
+
+    Ok((input, TryTable(bt, catches, body)))
+}
+
+fn throw<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, Instr> {
+    let tag = |i| map_opt(bws(index), |t| ctx.borrow().get_tag_idx(&t))(i);
+    alt((
+        map(preceded(keyword("throw"), tag), Throw),
+        map(keyword("throw_ref"), |_| ThrowRef),
+    ))(input)
+}
+
+fn branch<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, Instr> {
+    let label = |i| label(i, ctx);
+    alt((
+        map(preceded(keyword("br"), label), Br),
+        map(preceded(keyword("br_if"), label), BrIf),
+        map(preceded(keyword("br_on_null"), label), BrOnNull),
+        map(preceded(keyword("br_on_non_null"), label), BrOnNonNull),
+        map(preceded(keyword("br_table"), many1(label)), |mut labels| {
+            let default = labels.pop().unwrap();
+            BrTable(labels, default)
+        }),
+        map(keyword("return"), |_| Return),
+    ))(input)
+}
+
+fn call<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, Instr> {
+    let kind = alt((
+        value(false, keyword("call")),
+        value(true, keyword("return_call")),
+    ));
+    let func = map_opt(index, |f| ctx.borrow().get_func_idx(&f));
+    let (input, (tail, idx)) = pair(kind, func)(input)?;
+    Ok((input, if tail { ReturnCall(idx) } else { Call(idx) }))
+}
+
+/// Parses `call_ref` and `return_call_ref`, whose immediate is the type of
+/// the called function.
+fn call_ref<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, Instr> {
+    let kind = alt((
+        value(false, keyword("call_ref")),
+        value(true, keyword("return_call_ref")),
+    ));
+    let type_idx = map_opt(index, |t| ctx.borrow().get_type_idx(&t));
+    let (input, (tail, idx)) = pair(kind, type_idx)(input)?;
+    Ok((
+        input,
+        if tail {
+            ReturnCallRef(idx)
+        } else {
+            CallRef(idx)
+        },
+    ))
+}
+
+fn call_indirect<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, Instr> {
+    let kind = alt((
+        value(false, keyword("call_indirect")),
+        value(true, keyword("return_call_indirect")),
+    ));
+    let table = map_opt(index, |t| ctx.borrow().get_table_idx(&t));
+    let (input, (tail, table)) = pair(kind, opt(table))(input)?;
+    let table = table.unwrap_or(0);
+    let (input, type_idx) = types::inline_type_use(input, ctx)?;
+    let instr = if tail {
+        ReturnCallIndirect(type_idx, table)
+    } else {
+        CallIndirect(type_idx, table)
+    };
+    Ok((input, instr))
+}
+
+/// Parses `select` with an optional `(result t)` for typed select.
+fn select<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, Instr> {
+    let value_type = |i| types::value_type(i, ctx);
+    let result = bws(token::pt(preceded(token::result, value_type)));
+    map(preceded(keyword("select"), opt(result)), Select)(input)
+}
+
+fn reference<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, Instr> {
+    let heap_type = alt((
+        value(HeapType::Func, keyword("func")),
+        value(HeapType::Extern, keyword("extern")),
+        value(HeapType::Exn, keyword("exn")),
+        map_opt(bws(index), |idx| {
+            ctx.borrow().get_type_idx(&idx).map(HeapType::Type)
+        }),
+    ));
+    let func = |i| map_opt(bws(index), |f| ctx.borrow().get_func_idx(&f))(i);
+    alt((
+        map(preceded(keyword("ref.null"), heap_type), RefNull),
+        map(keyword("ref.is_null"), |_| RefIsNull),
+        map(keyword("ref.as_non_null"), |_| RefAsNonNull),
+        map(preceded(keyword("ref.func"), func), RefFunc),
+    ))(input)
+}
+
+/// Parses the table instructions, whose table index defaults to 0.
+fn table<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, Instr> {
+    let table = |i| map_opt(bws(index), |t| ctx.borrow().get_table_idx(&t))(i);
+    let elem = |i| map_opt(bws(index), |e| ctx.borrow().get_elem_idx(&e))(i);
+    let table_or_0 = move |i| map(opt(table), |t| t.unwrap_or(0))(i);
+
+    alt((
+        map(preceded(keyword("table.get"), table_or_0), TableGet),
+        map(preceded(keyword("table.set"), table_or_0), TableSet),
+        map(preceded(keyword("table.size"), table_or_0), TableSize),
+        map(preceded(keyword("table.grow"), table_or_0), TableGrow),
+        map(preceded(keyword("table.fill"), table_or_0), TableFill),
+        map(
+            preceded(keyword("table.copy"), opt(pair(table, table))),
+            |t| {
+                let (dst, src) = t.unwrap_or((0, 0));
+                TableCopy(dst, src)
+            },
+        ),
+        // A single index is the element index.
+        map_opt(
+            preceded(keyword("table.init"), pair(bws(index), opt(bws(index)))),
+            |(first, second)| {
+                let ctx = ctx.borrow();
+                match second {
+                    Some(elem) => Some(TableInit(
+                        ctx.get_table_idx(&first)?,
+                        ctx.get_elem_idx(&elem)?,
+                    )),
+                    None => Some(TableInit(0, ctx.get_elem_idx(&first)?)),
+                }
+            },
+        ),
+        map(preceded(keyword("elem.drop"), elem), ElemDrop),
+    ))(input)
+}
+
+fn global<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, Instr> {
+    let (input, instr) = alt((
+        map(keyword("global.get"), |_| GlobalGet as fn(usize) -> Instr),
+        map(keyword("global.set"), |_| GlobalSet as fn(usize) -> Instr),
+    ))(input)?;
+    let (input, i) = map_opt(index, |g| ctx.borrow().get_global_idx(&g))(input)?;
+    Ok((input, instr(i)))
+}
+
+/// Parses an optional memory index, which defaults to 0.
+fn memory_idx<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, usize> {
+    let memory = map_opt(bws(index), |m| ctx.borrow().get_memory_idx(&m));
+    map(opt(memory), |m| m.unwrap_or(0))(input)
+}
+
+/// Parses the memory instructions without a memory argument, whose memory
+/// index defaults to 0, and `data.drop`.
+fn memory<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, Instr> {
+    let memory = |i| map_opt(bws(index), |m| ctx.borrow().get_memory_idx(&m))(i);
+    let data = |i| map_opt(bws(index), |d| ctx.borrow().get_data_idx(&d))(i);
+    let memory_or_0 = |i| memory_idx(i, ctx);
+
+    alt((
+        map(preceded(keyword("memory.size"), memory_or_0), MemorySize),
+        map(preceded(keyword("memory.grow"), memory_or_0), MemoryGrow),
+        map(preceded(keyword("memory.fill"), memory_or_0), MemoryFill),
+        map(
+            preceded(keyword("memory.copy"), opt(pair(memory, memory))),
+            |m| {
+                let (dst, src) = m.unwrap_or((0, 0));
+                MemoryCopy(dst, src)
+            },
+        ),
+        // A single index is the data index.
+        map_opt(
+            preceded(keyword("memory.init"), pair(bws(index), opt(bws(index)))),
+            |(first, second)| {
+                let ctx = ctx.borrow();
+                match second {
+                    Some(data) => Some(MemoryInit(
+                        ctx.get_memory_idx(&first)?,
+                        ctx.get_data_idx(&data)?,
+                    )),
+                    None => Some(MemoryInit(0, ctx.get_data_idx(&first)?)),
+                }
+            },
+        ),
+        map(preceded(keyword("data.drop"), data), DataDrop),
+    ))(input)
+}
+
+fn local<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, Instr> {
+    let (input, instr) = alt((
+        map(bws(tag("local.get")), |_| LocalGet as fn(usize) -> Instr),
+        map(bws(tag("local.set")), |_| LocalSet as fn(usize) -> Instr),
+        map(bws(tag("local.tee")), |_| LocalTee as fn(usize) -> Instr),
+    ))(input)?;
+    let (input, i) = map_opt(index, |l| ctx.borrow().get_local_idx(&l))(input)?;
+    Ok((input, instr(i)))
+}
+
+fn i32_const(input: &str) -> IResult<&str, Instr> {
+    map(preceded(bws(tag("i32.const")), values::i32), I32Const)(input)
+}
+
+fn i64_const(input: &str) -> IResult<&str, Instr> {
+    map(preceded(bws(tag("i64.const")), values::i64), I64Const)(input)
+}
+
+fn f32_const(input: &str) -> IResult<&str, Instr> {
+    map(preceded(bws(tag("f32.const")), values::f32), F32Const)(input)
+}
+
+fn f64_const(input: &str) -> IResult<&str, Instr> {
+    map(preceded(bws(tag("f64.const")), values::f64), F64Const)(input)
+}
+
+type MemoryInstr = fn(MemArg) -> Instr;
+
+/// Memory instructions with the exponent of their natural alignment.
+const MEMORY_INSTRS: &[(&str, MemoryInstr, u32)] = &[
+    ("i32.load", I32Load, 2),
+    ("i64.load", I64Load, 3),
+    ("f32.load", F32Load, 2),
+    ("f64.load", F64Load, 3),
+    ("i32.load8_s", I32Load8S, 0),
+    ("i32.load8_u", I32Load8U, 0),
+    ("i32.load16_s", I32Load16S, 1),
+    ("i32.load16_u", I32Load16U, 1),
+    ("i64.load8_s", I64Load8S, 0),
+    ("i64.load8_u", I64Load8U, 0),
+    ("i64.load16_s", I64Load16S, 1),
+    ("i64.load16_u", I64Load16U, 1),
+    ("i64.load32_s", I64Load32S, 2),
+    ("i64.load32_u", I64Load32U, 2),
+    ("i32.store", I32Store, 2),
+    ("i64.store", I64Store, 3),
+    ("f32.store", F32Store, 2),
+    ("f64.store", F64Store, 3),
+    ("i32.store8", I32Store8, 0),
+    ("i32.store16", I32Store16, 1),
+    ("i64.store8", I64Store8, 0),
+    ("i64.store16", I64Store16, 1),
+    ("i64.store32", I64Store32, 2),
+    ("v128.load", V128Load, 4),
+    ("v128.load8x8_s", V128Load8x8S, 3),
+    ("v128.load8x8_u", V128Load8x8U, 3),
+    ("v128.load16x4_s", V128Load16x4S, 3),
+    ("v128.load16x4_u", V128Load16x4U, 3),
+    ("v128.load32x2_s", V128Load32x2S, 3),
+    ("v128.load32x2_u", V128Load32x2U, 3),
+    ("v128.load8_splat", V128Load8Splat, 0),
+    ("v128.load16_splat", V128Load16Splat, 1),
+    ("v128.load32_splat", V128Load32Splat, 2),
+    ("v128.load64_splat", V128Load64Splat, 3),
+    ("v128.load32_zero", V128Load32Zero, 2),
+    ("v128.load64_zero", V128Load64Zero, 3),
+    ("v128.store", V128Store, 4),
+    ("memory.atomic.notify", MemoryAtomicNotify, 2),
+    ("memory.atomic.wait32", MemoryAtomicWait32, 2),
+    ("memory.atomic.wait64", MemoryAtomicWait64, 3),
+    ("i32.atomic.load", |m| AtomicLoad(AtomicWidth::I32, m), 2),
+    ("i64.atomic.load", |m| AtomicLoad(AtomicWidth::I64, m), 3),
+    (
+        "i32.atomic.load8_u",
+        |m| AtomicLoad(AtomicWidth::I32U8, m),
+        0,
+    ),
+    (
+        "i32.atomic.load16_u",
+        |m| AtomicLoad(AtomicWidth::I32U16, m),
+        1,
+    ),
+    (
+        "i64.atomic.load8_u",
+        |m| AtomicLoad(AtomicWidth::I64U8, m),
+        0,
+    ),
+    (
+        "i64.atomic.load16_u",
+        |m| AtomicLoad(AtomicWidth::I64U16, m),
+        1,
+    ),
+    (
+        "i64.atomic.load32_u",
+        |m| AtomicLoad(AtomicWidth::I64U32, m),
+        2,
+    ),
+    ("i32.atomic.store", |m| AtomicStore(AtomicWidth::I32, m), 2),
+    ("i64.atomic.store", |m| AtomicStore(AtomicWidth::I64, m), 3),
+    (
+        "i32.atomic.store8",
+        |m| AtomicStore(AtomicWidth::I32U8, m),
+        0,
+    ),
+    (
+        "i32.atomic.store16",
+        |m| AtomicStore(AtomicWidth::I32U16, m),
+        1,
+    ),
+    (
+        "i64.atomic.store8",
+        |m| AtomicStore(AtomicWidth::I64U8, m),
+        0,
+    ),
+    (
+        "i64.atomic.store16",
+        |m| AtomicStore(AtomicWidth::I64U16, m),
+        1,
+    ),
+    (
+        "i64.atomic.store32",
+        |m| AtomicStore(AtomicWidth::I64U32, m),
+        2,
+    ),
+    (
+        "i32.atomic.rmw.add",
+        |m| AtomicRmw(AtomicRmwOp::Add, AtomicWidth::I32, m),
+        2,
+    ),
+    (
+        "i64.atomic.rmw.add",
+        |m| AtomicRmw(AtomicRmwOp::Add, AtomicWidth::I64, m),
+        3,
+    ),
+    (
+        "i32.atomic.rmw8.add_u",
+        |m| AtomicRmw(AtomicRmwOp::Add, AtomicWidth::I32U8, m),
+        0,
+    ),
+    (
+        "i32.atomic.rmw16.add_u",
+        |m| AtomicRmw(AtomicRmwOp::Add, AtomicWidth::I32U16, m),
+        1,
+    ),
+    (
+        "i64.atomic.rmw8.add_u",
+        |m| AtomicRmw(AtomicRmwOp::Add, AtomicWidth::I64U8, m),
+        0,
+    ),
+    (
+        "i64.atomic.rmw16.add_u",
+        |m| AtomicRmw(AtomicRmwOp::Add, AtomicWidth::I64U16, m),
+        1,
+    ),
+    (
+        "i64.atomic.rmw32.add_u",
+        |m| AtomicRmw(AtomicRmwOp::Add, AtomicWidth::I64U32, m),
+        2,
+    ),
+    (
+        "i32.atomic.rmw.sub",
+        |m| AtomicRmw(AtomicRmwOp::Sub, AtomicWidth::I32, m),
+        2,
+    ),
+    (
+        "i64.atomic.rmw.sub",
+        |m| AtomicRmw(AtomicRmwOp::Sub, AtomicWidth::I64, m),
+        3,
+    ),
+    (
+        "i32.atomic.rmw8.sub_u",
+        |m| AtomicRmw(AtomicRmwOp::Sub, AtomicWidth::I32U8, m),
+        0,
+    ),
+    (
+        "i32.atomic.rmw16.sub_u",
+        |m| AtomicRmw(AtomicRmwOp::Sub, AtomicWidth::I32U16, m),
+        1,
+    ),
+    (
+        "i64.atomic.rmw8.sub_u",
+        |m| AtomicRmw(AtomicRmwOp::Sub, AtomicWidth::I64U8, m),
+        0,
+    ),
+    (
+        "i64.atomic.rmw16.sub_u",
+        |m| AtomicRmw(AtomicRmwOp::Sub, AtomicWidth::I64U16, m),
+        1,
+    ),
+    (
+        "i64.atomic.rmw32.sub_u",
+        |m| AtomicRmw(AtomicRmwOp::Sub, AtomicWidth::I64U32, m),
+        2,
+    ),
+    (
+        "i32.atomic.rmw.and",
+        |m| AtomicRmw(AtomicRmwOp::And, AtomicWidth::I32, m),
+        2,
+    ),
+    (
+        "i64.atomic.rmw.and",
+        |m| AtomicRmw(AtomicRmwOp::And, AtomicWidth::I64, m),
+        3,
+    ),
+    (
+        "i32.atomic.rmw8.and_u",
+        |m| AtomicRmw(AtomicRmwOp::And, AtomicWidth::I32U8, m),
+        0,
+    ),
+    (
+        "i32.atomic.rmw16.and_u",
+        |m| AtomicRmw(AtomicRmwOp::And, AtomicWidth::I32U16, m),
+        1,
+    ),
+    (
+        "i64.atomic.rmw8.and_u",
+        |m| AtomicRmw(AtomicRmwOp::And, AtomicWidth::I64U8, m),
+        0,
+    ),
+    (
+        "i64.atomic.rmw16.and_u",
+        |m| AtomicRmw(AtomicRmwOp::And, AtomicWidth::I64U16, m),
+        1,
+    ),
+    (
+        "i64.atomic.rmw32.and_u",
+        |m| AtomicRmw(AtomicRmwOp::And, AtomicWidth::I64U32, m),
+        2,
+    ),
+    (
+        "i32.atomic.rmw.or",
+        |m| AtomicRmw(AtomicRmwOp::Or, AtomicWidth::I32, m),
+        2,
+    ),
+    (
+        "i64.atomic.rmw.or",
+        |m| AtomicRmw(AtomicRmwOp::Or, AtomicWidth::I64, m),
+        3,
+    ),
+    (
+        "i32.atomic.rmw8.or_u",
+        |m| AtomicRmw(AtomicRmwOp::Or, AtomicWidth::I32U8, m),
+        0,
+    ),
+    (
+        "i32.atomic.rmw16.or_u",
+        |m| AtomicRmw(AtomicRmwOp::Or, AtomicWidth::I32U16, m),
+        1,
+    ),
+    (
+        "i64.atomic.rmw8.or_u",
+        |m| AtomicRmw(AtomicRmwOp::Or, AtomicWidth::I64U8, m),
+        0,
+    ),
+    (
+        "i64.atomic.rmw16.or_u",
+        |m| AtomicRmw(AtomicRmwOp::Or, AtomicWidth::I64U16, m),
+        1,
+    ),
+    (
+        "i64.atomic.rmw32.or_u",
+        |m| AtomicRmw(AtomicRmwOp::Or, AtomicWidth::I64U32, m),
+        2,
+    ),
+    (
+        "i32.atomic.rmw.xor",
+        |m| AtomicRmw(AtomicRmwOp::Xor, AtomicWidth::I32, m),
+        2,
+    ),
+    (
+        "i64.atomic.rmw.xor",
+        |m| AtomicRmw(AtomicRmwOp::Xor, AtomicWidth::I64, m),
+        3,
+    ),
+    (
+        "i32.atomic.rmw8.xor_u",
+        |m| AtomicRmw(AtomicRmwOp::Xor, AtomicWidth::I32U8, m),
+        0,
+    ),
+    (
+        "i32.atomic.rmw16.xor_u",
+        |m| AtomicRmw(AtomicRmwOp::Xor, AtomicWidth::I32U16, m),
+        1,
+    ),
+    (
+        "i64.atomic.rmw8.xor_u",
+        |m| AtomicRmw(AtomicRmwOp::Xor, AtomicWidth::I64U8, m),
+        0,
+    ),
+    (
+        "i64.atomic.rmw16.xor_u",
+        |m| AtomicRmw(AtomicRmwOp::Xor, AtomicWidth::I64U16, m),
+        1,
+    ),
+    (
+        "i64.atomic.rmw32.xor_u",
+        |m| AtomicRmw(AtomicRmwOp::Xor, AtomicWidth::I64U32, m),
+        2,
+    ),
+    (
+        "i32.atomic.rmw.xchg",
+        |m| AtomicRmw(AtomicRmwOp::Xchg, AtomicWidth::I32, m),
+        2,
+    ),
+    (
+        "i64.atomic.rmw.xchg",
+        |m| AtomicRmw(AtomicRmwOp::Xchg, AtomicWidth::I64, m),
+        3,
+    ),
+    (
+        "i32.atomic.rmw8.xchg_u",
+        |m| AtomicRmw(AtomicRmwOp::Xchg, AtomicWidth::I32U8, m),
+        0,
+    ),
+    (
+        "i32.atomic.rmw16.xchg_u",
+        |m| AtomicRmw(AtomicRmwOp::Xchg, AtomicWidth::I32U16, m),
+        1,
+    ),
+    (
+        "i64.atomic.rmw8.xchg_u",
+        |m| AtomicRmw(AtomicRmwOp::Xchg, AtomicWidth::I64U8, m),
+        0,
+    ),
+    (
+        "i64.atomic.rmw16.xchg_u",
+        |m| AtomicRmw(AtomicRmwOp::Xchg, AtomicWidth::I64U16, m),
+        1,
+    ),
+    (
+        "i64.atomic.rmw32.xchg_u",
+        |m| AtomicRmw(AtomicRmwOp::Xchg, AtomicWidth::I64U32, m),
+        2,
+    ),
+    (
+        "i32.atomic.rmw.cmpxchg",
+        |m| AtomicRmw(AtomicRmwOp::Cmpxchg, AtomicWidth::I32, m),
+        2,
+    ),
+    (
+        "i64.atomic.rmw.cmpxchg",
+        |m| AtomicRmw(AtomicRmwOp::Cmpxchg, AtomicWidth::I64, m),
+        3,
+    ),
+    (
+        "i32.atomic.rmw8.cmpxchg_u",
+        |m| AtomicRmw(AtomicRmwOp::Cmpxchg, AtomicWidth::I32U8, m),
+        0,
+    ),
+    (
+        "i32.atomic.rmw16.cmpxchg_u",
+        |m| AtomicRmw(AtomicRmwOp::Cmpxchg, AtomicWidth::I32U16, m),
+        1,
+    ),
+    (
+        "i64.atomic.rmw8.cmpxchg_u",
+        |m| AtomicRmw(AtomicRmwOp::Cmpxchg, AtomicWidth::I64U8, m),
+        0,
+    ),
+    (
+        "i64.atomic.rmw16.cmpxchg_u",
+        |m| AtomicRmw(AtomicRmwOp::Cmpxchg, AtomicWidth::I64U16, m),
+        1,
+    ),
+    (
+        "i64.atomic.rmw32.cmpxchg_u",
+        |m| AtomicRmw(AtomicRmwOp::Cmpxchg, AtomicWidth::I64U32, m),
+        2,
+    ),
+];
+
+/// Parses `offset=n align=m` of an access to `memory`, where both are
+/// optional. The alignment has to be a power of two and defaults to the
+/// `natural` one.
+fn memarg(input: &str, natural: u32, memory: usize) -> IResult<&str, MemArg> {
+    let offset = preceded(tag("offset="), values::u64);
+    let align = verify(preceded(tag("align="), values::u32), |a: &u32| {
+        a.is_power_of_two()
+    });
+    let (input, offset) = bws(opt(offset))(input)?;
+    let (input, align) = bws(opt(align))(input)?;
+
+    let memarg = MemArg {
+        align: align.map_or(natural, |a| a.trailing_zeros()),
+        offset: offset.unwrap_or(0),
+        memory,
+    };
+    Ok((input, memarg))
+}
+
+/// Parses one lane shape of `v128.const` followed by its lanes, each given
+/// as the bits of the lane.
+fn v128_shape<'a>(
+    shape: &'static str,
+    lanes: usize,
+    lane: impl FnMut(&'a str) -> IResult<&'a str, u64>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, u128> {
+    let width = 128 / lanes;
+    let lanes = preceded(keyword(shape), count(bws(lane), lanes));
+    map(lanes, move |lanes| {
+        let bits = lanes.iter().enumerate();
+        bits.fold(0, |v, (i, lane)| v | (*lane as u128) << (i * width))
+    })
+}
+
+fn v128_const(input: &str) -> IResult<&str, Instr> {
+    let shape = alt((
+        v128_shape("i8x16", 16, map(values::i8, |i| i as u8 as u64)),
+        v128_shape("i16x8", 8, map(values::i16, |i| i as u16 as u64)),
+        v128_shape("i32x4", 4, map(values::i32, |i| i as u32 as u64)),
+        v128_shape("i64x2", 2, map(values::i64, |i| i as u64)),
+        v128_shape("f32x4", 4, map(values::f32, |f| f.to_bits() as u64)),
+        v128_shape("f64x2", 2, map(values::f64, f64::to_bits)),
+    ));
+    map(preceded(keyword("v128.const"), shape), V128Const)(input)
+}
+
+fn shuffle(input: &str) -> IResult<&str, Instr> {
+    let lanes = count(bws(values::u8), 16);
+    let lanes = map(lanes, |l| <[u8; 16]>::try_from(l).unwrap());
+    map(preceded(keyword("i8x16.shuffle"), lanes), I8x16Shuffle)(input)
+}
+
+type LaneInstr = fn(u8) -> Instr;
+
+/// Instructions with a lane index as their only immediate.
+const LANE_INSTRS: &[(&str, LaneInstr)] = &[
+    ("i8x16.extract_lane_s", I8x16ExtractLaneS),
+    ("i8x16.extract_lane_u", I8x16ExtractLaneU),
+    ("i8x16.replace_lane", I8x16ReplaceLane),
+    ("i16x8.extract_lane_s", I16x8ExtractLaneS),
+    ("i16x8.extract_lane_u", I16x8ExtractLaneU),
+    ("i16x8.replace_lane", I16x8ReplaceLane),
+    ("i32x4.extract_lane", I32x4ExtractLane),
+    ("i32x4.replace_lane", I32x4ReplaceLane),
+    ("i64x2.extract_lane", I64x2ExtractLane),
+    ("i64x2.replace_lane", I64x2ReplaceLane),
+    ("f32x4.extract_lane", F32x4ExtractLane),
+    ("f32x4.replace_lane", F32x4ReplaceLane),
+    ("f64x2.extract_lane", F64x2ExtractLane),
+    ("f64x2.replace_lane", F64x2ReplaceLane),
+];
+
+type LaneMemoryInstr = fn(MemArg, u8) -> Instr;
+
+/// Loads and stores of a single lane with the exponent of their natural
+/// alignment.
+const LANE_MEMORY_INSTRS: &[(&str, LaneMemoryInstr, u32)] = &[
+    ("v128.load8_lane", V128Load8Lane, 0),
+    ("v128.load16_lane", V128Load16Lane, 1),
+    ("v128.load32_lane", V128Load32Lane, 2),
+    ("v128.load64_lane", V128Load64Lane, 3),
+    ("v128.store8_lane", V128Store8Lane, 0),
+    ("v128.store16_lane", V128Store16Lane, 1),
+    ("v128.store32_lane", V128Store32Lane, 2),
+    ("v128.store64_lane", V128Store64Lane, 3),
+];
+
+/// Parses the SIMD instructions with a lane index. The memory index of lane
+/// loads and stores is optional, so a single index is the lane.
+fn lane_instr<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, Instr> {
+    for (name, instr) in LANE_INSTRS {
+        if let Ok((input, lane)) = preceded(keyword(name), bws(values::u8))(input) {
+            return Ok((input, instr(lane)));
+        }
+    }
+    for (name, instr, natural) in LANE_MEMORY_INSTRS {
+        if let Ok((input, _)) = keyword(name)(input) {
+            let with_memory = |i| {
+                let (i, memory) = memory_idx(i, ctx)?;
+                let (i, memarg) = memarg(i, *natural, memory)?;
+                bws(values::u8)(i).map(|(i, lane)| (i, (memarg, lane)))
+            };
+            let without_memory = |i| {
+                let (i, memarg) = memarg(i, *natural, 0)?;
+                bws(values::u8)(i).map(|(i, lane)| (i, (memarg, lane)))
+            };
+            let (input, (memarg, lane)) = alt((with_memory, without_memory))(input)?;
+            return Ok((input, instr(memarg, lane)));
+        }
+    }
+    Err(Err::Error(Error::new(input, ErrorKind::Tag)))
+}
+
+/// Parses the SIMD instructions with immediates other than a memory
+/// argument.
+fn simd<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, Instr> {
+    alt((v128_const, shuffle, |i| lane_instr(i, ctx)))(input)
 }
 
-fn i32_add(input: &str) -> IResult<&str, Instr> {
-    map(bws(tag("i32.add")), |_| I32Add)(input)
+/// Parses a load or store, optionally followed by a memory index. The
+/// longest matching name wins, so `i32.load8_s` is never mistaken for
+/// `i32.load`.
+fn memory_instr<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, Instr> {
+    let (input, _) = ws(input)?;
+    let found = MEMORY_INSTRS
+        .iter()
+        .filter(|(name, _, _)| input.starts_with(name))
+        .max_by_key(|(name, _, _)| name.len());
+
+    match found {
+        Some((name, instr, natural)) => {
+            let (input, memory) = memory_idx(&input[name.len()..], ctx)?;
+            let (input, memarg) = memarg(input, *natural, memory)?;
+            Ok((input, instr(memarg)))
+        }
+        None => Err(Err::Error(Error::new(input, ErrorKind::Tag))),
+    }
+}
+
+const NUMERIC_INSTRS: &[(&str, Instr)] = &[
+    ("unreachable", Unreachable),
+    ("nop", Nop),
+    ("drop", Drop),
+    ("atomic.fence", AtomicFence),
+    ("i32.eqz", I32Eqz),
+    ("i32.eq", I32Eq),
+    ("i32.ne", I32Ne),
+    ("i32.lt_s", I32LtS),
+    ("i32.lt_u", I32LtU),
+    ("i32.gt_s", I32GtS),
+    ("i32.gt_u", I32GtU),
+    ("i32.le_s", I32LeS),
+    ("i32.le_u", I32LeU),
+    ("i32.ge_s", I32GeS),
+    ("i32.ge_u", I32GeU),
+    ("i32.clz", I32Clz),
+    ("i32.ctz", I32Ctz),
+    ("i32.popcnt", I32Popcnt),
+    ("i32.add", I32Add),
+    ("i32.sub", I32Sub),
+    ("i32.mul", I32Mul),
+    ("i32.div_s", I32DivS),
+    ("i32.div_u", I32DivU),
+    ("i32.rem_s", I32RemS),
+    ("i32.rem_u", I32RemU),
+    ("i32.and", I32And),
+    ("i32.or", I32Or),
+    ("i32.xor", I32Xor),
+    ("i32.shl", I32Shl),
+    ("i32.shr_s", I32ShrS),
+    ("i32.shr_u", I32ShrU),
+    ("i32.rotl", I32Rotl),
+    ("i32.rotr", I32Rotr),
+    ("i64.eqz", I64Eqz),
+    ("i64.eq", I64Eq),
+    ("i64.ne", I64Ne),
+    ("i64.lt_s", I64LtS),
+    ("i64.lt_u", I64LtU),
+    ("i64.gt_s", I64GtS),
+    ("i64.gt_u", I64GtU),
+    ("i64.le_s", I64LeS),
+    ("i64.le_u", I64LeU),
+    ("i64.ge_s", I64GeS),
+    ("i64.ge_u", I64GeU),
+    ("i64.clz", I64Clz),
+    ("i64.ctz", I64Ctz),
+    ("i64.popcnt", I64Popcnt),
+    ("i64.add", I64Add),
+    ("i64.sub", I64Sub),
+    ("i64.mul", I64Mul),
+    ("i64.div_s", I64DivS),
+    ("i64.div_u", I64DivU),
+    ("i64.rem_s", I64RemS),
+    ("i64.rem_u", I64RemU),
+    ("i64.and", I64And),
+    ("i64.or", I64Or),
+    ("i64.xor", I64Xor),
+    ("i64.shl", I64Shl),
+    ("i64.shr_s", I64ShrS),
+    ("i64.shr_u", I64ShrU),
+    ("i64.rotl", I64Rotl),
+    ("i64.rotr", I64Rotr),
+    ("f32.eq", F32Eq),
+    ("f32.ne", F32Ne),
+    ("f32.lt", F32Lt),
+    ("f32.gt", F32Gt),
+    ("f32.le", F32Le),
+    ("f32.ge", F32Ge),
+    ("f64.eq", F64Eq),
+    ("f64.ne", F64Ne),
+    ("f64.lt", F64Lt),
+    ("f64.gt", F64Gt),
+    ("f64.le", F64Le),
+    ("f64.ge", F64Ge),
+    ("f32.abs", F32Abs),
+    ("f32.neg", F32Neg),
+    ("f32.ceil", F32Ceil),
+    ("f32.floor", F32Floor),
+    ("f32.trunc", F32Trunc),
+    ("f32.nearest", F32Nearest),
+    ("f32.sqrt", F32Sqrt),
+    ("f32.add", F32Add),
+    ("f32.sub", F32Sub),
+    ("f32.mul", F32Mul),
+    ("f32.div", F32Div),
+    ("f32.min", F32Min),
+    ("f32.max", F32Max),
+    ("f32.copysign", F32Copysign),
+    ("f64.abs", F64Abs),
+    ("f64.neg", F64Neg),
+    ("f64.ceil", F64Ceil),
+    ("f64.floor", F64Floor),
+    ("f64.trunc", F64Trunc),
+    ("f64.nearest", F64Nearest),
+    ("f64.sqrt", F64Sqrt),
+    ("f64.add", F64Add),
+    ("f64.sub", F64Sub),
+    ("f64.mul", F64Mul),
+    ("f64.div", F64Div),
+    ("f64.min", F64Min),
+    ("f64.max", F64Max),
+    ("f64.copysign", F64Copysign),
+    ("i32.wrap_i64", I32WrapI64),
+    ("i32.trunc_f32_s", I32TruncF32S),
+    ("i32.trunc_f32_u", I32TruncF32U),
+    ("i32.trunc_f64_s", I32TruncF64S),
+    ("i32.trunc_f64_u", I32TruncF64U),
+    ("i64.extend_i32_s", I64ExtendI32S),
+    ("i64.extend_i32_u", I64ExtendI32U),
+    ("i64.trunc_f32_s", I64TruncF32S),
+    ("i64.trunc_f32_u", I64TruncF32U),
+    ("i64.trunc_f64_s", I64TruncF64S),
+    ("i64.trunc_f64_u", I64TruncF64U),
+    ("f32.convert_i32_s", F32ConvertI32S),
+    ("f32.convert_i32_u", F32ConvertI32U),
+    ("f32.convert_i64_s", F32ConvertI64S),
+    ("f32.convert_i64_u", F32ConvertI64U),
+    ("f32.demote_f64", F32DemoteF64),
+    ("f64.convert_i32_s", F64ConvertI32S),
+    ("f64.convert_i32_u", F64ConvertI32U),
+    ("f64.convert_i64_s", F64ConvertI64S),
+    ("f64.convert_i64_u", F64ConvertI64U),
+    ("f64.promote_f32", F64PromoteF32),
+    ("i32.reinterpret_f32", I32ReinterpretF32),
+    ("i64.reinterpret_f64", I64ReinterpretF64),
+    ("f32.reinterpret_i32", F32ReinterpretI32),
+    ("f64.reinterpret_i64", F64ReinterpretI64),
+    ("i32.extend8_s", I32Extend8S),
+    ("i32.extend16_s", I32Extend16S),
+    ("i64.extend8_s", I64Extend8S),
+    ("i64.extend16_s", I64Extend16S),
+    ("i64.extend32_s", I64Extend32S),
+    ("i32.trunc_sat_f32_s", I32TruncSatF32S),
+    ("i32.trunc_sat_f32_u", I32TruncSatF32U),
+    ("i32.trunc_sat_f64_s", I32TruncSatF64S),
+    ("i32.trunc_sat_f64_u", I32TruncSatF64U),
+    ("i64.trunc_sat_f32_s", I64TruncSatF32S),
+    ("i64.trunc_sat_f32_u", I64TruncSatF32U),
+    ("i64.trunc_sat_f64_s", I64TruncSatF64S),
+    ("i64.trunc_sat_f64_u", I64TruncSatF64U),
+    ("i8x16.swizzle", Simd(SimdOp::I8x16Swizzle)),
+    ("i8x16.splat", Simd(SimdOp::I8x16Splat)),
+    ("i16x8.splat", Simd(SimdOp::I16x8Splat)),
+    ("i32x4.splat", Simd(SimdOp::I32x4Splat)),
+    ("i64x2.splat", Simd(SimdOp::I64x2Splat)),
+    ("f32x4.splat", Simd(SimdOp::F32x4Splat)),
+    ("f64x2.splat", Simd(SimdOp::F64x2Splat)),
+    ("i8x16.eq", Simd(SimdOp::I8x16Eq)),
+    ("i8x16.ne", Simd(SimdOp::I8x16Ne)),
+    ("i8x16.lt_s", Simd(SimdOp::I8x16LtS)),
+    ("i8x16.lt_u", Simd(SimdOp::I8x16LtU)),
+    ("i8x16.gt_s", Simd(SimdOp::I8x16GtS)),
+    ("i8x16.gt_u", Simd(SimdOp::I8x16GtU)),
+    ("i8x16.le_s", Simd(SimdOp::I8x16LeS)),
+    ("i8x16.le_u", Simd(SimdOp::I8x16LeU)),
+    ("i8x16.ge_s", Simd(SimdOp::I8x16GeS)),
+    ("i8x16.ge_u", Simd(SimdOp::I8x16GeU)),
+    ("i16x8.eq", Simd(SimdOp::I16x8Eq)),
+    ("i16x8.ne", Simd(SimdOp::I16x8Ne)),
+    ("i16x8.lt_s", Simd(SimdOp::I16x8LtS)),
+    ("i16x8.lt_u", Simd(SimdOp::I16x8LtU)),
+    ("i16x8.gt_s", Simd(SimdOp::I16x8GtS)),
+    ("i16x8.gt_u", Simd(SimdOp::I16x8GtU)),
+    ("i16x8.le_s", Simd(SimdOp::I16x8LeS)),
+    ("i16x8.le_u", Simd(SimdOp::I16x8LeU)),
+    ("i16x8.ge_s", Simd(SimdOp::I16x8GeS)),
+    ("i16x8.ge_u", Simd(SimdOp::I16x8GeU)),
+    ("i32x4.eq", Simd(SimdOp::I32x4Eq)),
+    ("i32x4.ne", Simd(SimdOp::I32x4Ne)),
+    ("i32x4.lt_s", Simd(SimdOp::I32x4LtS)),
+    ("i32x4.lt_u", Simd(SimdOp::I32x4LtU)),
+    ("i32x4.gt_s", Simd(SimdOp::I32x4GtS)),
+    ("i32x4.gt_u", Simd(SimdOp::I32x4GtU)),
+    ("i32x4.le_s", Simd(SimdOp::I32x4LeS)),
+    ("i32x4.le_u", Simd(SimdOp::I32x4LeU)),
+    ("i32x4.ge_s", Simd(SimdOp::I32x4GeS)),
+    ("i32x4.ge_u", Simd(SimdOp::I32x4GeU)),
+    ("f32x4.eq", Simd(SimdOp::F32x4Eq)),
+    ("f32x4.ne", Simd(SimdOp::F32x4Ne)),
+    ("f32x4.lt", Simd(SimdOp::F32x4Lt)),
+    ("f32x4.gt", Simd(SimdOp::F32x4Gt)),
+    ("f32x4.le", Simd(SimdOp::F32x4Le)),
+    ("f32x4.ge", Simd(SimdOp::F32x4Ge)),
+    ("f64x2.eq", Simd(SimdOp::F64x2Eq)),
+    ("f64x2.ne", Simd(SimdOp::F64x2Ne)),
+    ("f64x2.lt", Simd(SimdOp::F64x2Lt)),
+    ("f64x2.gt", Simd(SimdOp::F64x2Gt)),
+    ("f64x2.le", Simd(SimdOp::F64x2Le)),
+    ("f64x2.ge", Simd(SimdOp::F64x2Ge)),
+    ("v128.not", Simd(SimdOp::V128Not)),
+    ("v128.and", Simd(SimdOp::V128And)),
+    ("v128.andnot", Simd(SimdOp::V128AndNot)),
+    ("v128.or", Simd(SimdOp::V128Or)),
+    ("v128.xor", Simd(SimdOp::V128Xor)),
+    ("v128.bitselect", Simd(SimdOp::V128Bitselect)),
+    ("v128.any_true", Simd(SimdOp::V128AnyTrue)),
+    (
+        "f32x4.demote_f64x2_zero",
+        Simd(SimdOp::F32x4DemoteF64x2Zero),
+    ),
+    (
+        "f64x2.promote_low_f32x4",
+        Simd(SimdOp::F64x2PromoteLowF32x4),
+    ),
+    ("i8x16.abs", Simd(SimdOp::I8x16Abs)),
+    ("i8x16.neg", Simd(SimdOp::I8x16Neg)),
+    ("i8x16.popcnt", Simd(SimdOp::I8x16Popcnt)),
+    ("i8x16.all_true", Simd(SimdOp::I8x16AllTrue)),
+    ("i8x16.bitmask", Simd(SimdOp::I8x16Bitmask)),
+    ("i8x16.narrow_i16x8_s", Simd(SimdOp::I8x16NarrowI16x8S)),
+    ("i8x16.narrow_i16x8_u", Simd(SimdOp::I8x16NarrowI16x8U)),
+    ("f32x4.ceil", Simd(SimdOp::F32x4Ceil)),
+    ("f32x4.floor", Simd(SimdOp::F32x4Floor)),
+    ("f32x4.trunc", Simd(SimdOp::F32x4Trunc)),
+    ("f32x4.nearest", Simd(SimdOp::F32x4Nearest)),
+    ("i8x16.shl", Simd(SimdOp::I8x16Shl)),
+    ("i8x16.shr_s", Simd(SimdOp::I8x16ShrS)),
+    ("i8x16.shr_u", Simd(SimdOp::I8x16ShrU)),
+    ("i8x16.add", Simd(SimdOp::I8x16Add)),
+    ("i8x16.add_sat_s", Simd(SimdOp::I8x16AddSatS)),
+    ("i8x16.add_sat_u", Simd(SimdOp::I8x16AddSatU)),
+    ("i8x16.sub", Simd(SimdOp::I8x16Sub)),
+    ("i8x16.sub_sat_s", Simd(SimdOp::I8x16SubSatS)),
+    ("i8x16.sub_sat_u", Simd(SimdOp::I8x16SubSatU)),
+    ("f64x2.ceil", Simd(SimdOp::F64x2Ceil)),
+    ("f64x2.floor", Simd(SimdOp::F64x2Floor)),
+    ("i8x16.min_s", Simd(SimdOp::I8x16MinS)),
+    ("i8x16.min_u", Simd(SimdOp::I8x16MinU)),
+    ("i8x16.max_s", Simd(SimdOp::I8x16MaxS)),
+    ("i8x16.max_u", Simd(SimdOp::I8x16MaxU)),
+    ("f64x2.trunc", Simd(SimdOp::F64x2Trunc)),
+    ("i8x16.avgr_u", Simd(SimdOp::I8x16AvgrU)),
+    (
+        "i16x8.extadd_pairwise_i8x16_s",
+        Simd(SimdOp::I16x8ExtaddPairwiseI8x16S),
+    ),
+    (
+        "i16x8.extadd_pairwise_i8x16_u",
+        Simd(SimdOp::I16x8ExtaddPairwiseI8x16U),
+    ),
+    (
+        "i32x4.extadd_pairwise_i16x8_s",
+        Simd(SimdOp::I32x4ExtaddPairwiseI16x8S),
+    ),
+    (
+        "i32x4.extadd_pairwise_i16x8_u",
+        Simd(SimdOp::I32x4ExtaddPairwiseI16x8U),
+    ),
+    ("i16x8.abs", Simd(SimdOp::I16x8Abs)),
+    ("i16x8.neg", Simd(SimdOp::I16x8Neg)),
+    ("i16x8.q15mulr_sat_s", Simd(SimdOp::I16x8Q15mulrSatS)),
+    ("i16x8.all_true", Simd(SimdOp::I16x8AllTrue)),
+    ("i16x8.bitmask", Simd(SimdOp::I16x8Bitmask)),
+    ("i16x8.narrow_i32x4_s", Simd(SimdOp::I16x8NarrowI32x4S)),
+    ("i16x8.narrow_i32x4_u", Simd(SimdOp::I16x8NarrowI32x4U)),
+    (
+        "i16x8.extend_low_i8x16_s",
+        Simd(SimdOp::I16x8ExtendLowI8x16S),
+    ),
+    (
+        "i16x8.extend_high_i8x16_s",
+        Simd(SimdOp::I16x8ExtendHighI8x16S),
+    ),
+    (
+        "i16x8.extend_low_i8x16_u",
+        Simd(SimdOp::I16x8ExtendLowI8x16U),
+    ),
+    (
+        "i16x8.extend_high_i8x16_u",
+        Simd(SimdOp::I16x8ExtendHighI8x16U),
+    ),
+    ("i16x8.shl", Simd(SimdOp::I16x8Shl)),
+    ("i16x8.shr_s", Simd(SimdOp::I16x8ShrS)),
+    ("i16x8.shr_u", Simd(SimdOp::I16x8ShrU)),
+    ("i16x8.add", Simd(SimdOp::I16x8Add)),
+    ("i16x8.add_sat_s", Simd(SimdOp::I16x8AddSatS)),
+    ("i16x8.add_sat_u", Simd(SimdOp::I16x8AddSatU)),
+    ("i16x8.sub", Simd(SimdOp::I16x8Sub)),
+    ("i16x8.sub_sat_s", Simd(SimdOp::I16x8SubSatS)),
+    ("i16x8.sub_sat_u", Simd(SimdOp::I16x8SubSatU)),
+    ("f64x2.nearest", Simd(SimdOp::F64x2Nearest)),
+    ("i16x8.mul", Simd(SimdOp::I16x8Mul)),
+    ("i16x8.min_s", Simd(SimdOp::I16x8MinS)),
+    ("i16x8.min_u", Simd(SimdOp::I16x8MinU)),
+    ("i16x8.max_s", Simd(SimdOp::I16x8MaxS)),
+    ("i16x8.max_u", Simd(SimdOp::I16x8MaxU)),
+    ("i16x8.avgr_u", Simd(SimdOp::I16x8AvgrU)),
+    (
+        "i16x8.extmul_low_i8x16_s",
+        Simd(SimdOp::I16x8ExtmulLowI8x16S),
+    ),
+    (
+        "i16x8.extmul_high_i8x16_s",
+        Simd(SimdOp::I16x8ExtmulHighI8x16S),
+    ),
+    (
+        "i16x8.extmul_low_i8x16_u",
+        Simd(SimdOp::I16x8ExtmulLowI8x16U),
+    ),
+    (
+        "i16x8.extmul_high_i8x16_u",
+        Simd(SimdOp::I16x8ExtmulHighI8x16U),
+    ),
+    ("i32x4.abs", Simd(SimdOp::I32x4Abs)),
+    ("i32x4.neg", Simd(SimdOp::I32x4Neg)),
+    ("i32x4.all_true", Simd(SimdOp::I32x4AllTrue)),
+    ("i32x4.bitmask", Simd(SimdOp::I32x4Bitmask)),
+    (
+        "i32x4.extend_low_i16x8_s",
+        Simd(SimdOp::I32x4ExtendLowI16x8S),
+    ),
+    (
+        "i32x4.extend_high_i16x8_s",
+        Simd(SimdOp::I32x4ExtendHighI16x8S),
+    ),
+    (
+        "i32x4.extend_low_i16x8_u",
+        Simd(SimdOp::I32x4ExtendLowI16x8U),
+    ),
+    (
+        "i32x4.extend_high_i16x8_u",
+        Simd(SimdOp::I32x4ExtendHighI16x8U),
+    ),
+    ("i32x4.shl", Simd(SimdOp::I32x4Shl)),
+    ("i32x4.shr_s", Simd(SimdOp::I32x4ShrS)),
+    ("i32x4.shr_u", Simd(SimdOp::I32x4ShrU)),
+    ("i32x4.add", Simd(SimdOp::I32x4Add)),
+    ("i32x4.sub", Simd(SimdOp::I32x4Sub)),
+    ("i32x4.mul", Simd(SimdOp::I32x4Mul)),
+    ("i32x4.min_s", Simd(SimdOp::I32x4MinS)),
+    ("i32x4.min_u", Simd(SimdOp::I32x4MinU)),
+    ("i32x4.max_s", Simd(SimdOp::I32x4MaxS)),
+    ("i32x4.max_u", Simd(SimdOp::I32x4MaxU)),
+    ("i32x4.dot_i16x8_s", Simd(SimdOp::I32x4DotI16x8S)),
+    (
+        "i32x4.extmul_low_i16x8_s",
+        Simd(SimdOp::I32x4ExtmulLowI16x8S),
+    ),
+    (
+        "i32x4.extmul_high_i16x8_s",
+        Simd(SimdOp::I32x4ExtmulHighI16x8S),
+    ),
+    (
+        "i32x4.extmul_low_i16x8_u",
+        Simd(SimdOp::I32x4ExtmulLowI16x8U),
+    ),
+    (
+        "i32x4.extmul_high_i16x8_u",
+        Simd(SimdOp::I32x4ExtmulHighI16x8U),
+    ),
+    ("i64x2.abs", Simd(SimdOp::I64x2Abs)),
+    ("i64x2.neg", Simd(SimdOp::I64x2Neg)),
+    ("i64x2.all_true", Simd(SimdOp::I64x2AllTrue)),
+    ("i64x2.bitmask", Simd(SimdOp::I64x2Bitmask)),
+    (
+        "i64x2.extend_low_i32x4_s",
+        Simd(SimdOp::I64x2ExtendLowI32x4S),
+    ),
+    (
+        "i64x2.extend_high_i32x4_s",
+        Simd(SimdOp::I64x2ExtendHighI32x4S),
+    ),
+    (
+        "i64x2.extend_low_i32x4_u",
+        Simd(SimdOp::I64x2ExtendLowI32x4U),
+    ),
+    (
+        "i64x2.extend_high_i32x4_u",
+        Simd(SimdOp::I64x2ExtendHighI32x4U),
+    ),
+    ("i64x2.shl", Simd(SimdOp::I64x2Shl)),
+    ("i64x2.shr_s", Simd(SimdOp::I64x2ShrS)),
+    ("i64x2.shr_u", Simd(SimdOp::I64x2ShrU)),
+    ("i64x2.add", Simd(SimdOp::I64x2Add)),
+    ("i64x2.sub", Simd(SimdOp::I64x2Sub)),
+    ("i64x2.mul", Simd(SimdOp::I64x2Mul)),
+    ("i64x2.eq", Simd(SimdOp::I64x2Eq)),
+    ("i64x2.ne", Simd(SimdOp::I64x2Ne)),
+    ("i64x2.lt_s", Simd(SimdOp::I64x2LtS)),
+    ("i64x2.gt_s", Simd(SimdOp::I64x2GtS)),
+    ("i64x2.le_s", Simd(SimdOp::I64x2LeS)),
+    ("i64x2.ge_s", Simd(SimdOp::I64x2GeS)),
+    (
+        "i64x2.extmul_low_i32x4_s",
+        Simd(SimdOp::I64x2ExtmulLowI32x4S),
+    ),
+    (
+        "i64x2.extmul_high_i32x4_s",
+        Simd(SimdOp::I64x2ExtmulHighI32x4S),
+    ),
+    (
+        "i64x2.extmul_low_i32x4_u",
+        Simd(SimdOp::I64x2ExtmulLowI32x4U),
+    ),
+    (
+        "i64x2.extmul_high_i32x4_u",
+        Simd(SimdOp::I64x2ExtmulHighI32x4U),
+    ),
+    ("f32x4.abs", Simd(SimdOp::F32x4Abs)),
+    ("f32x4.neg", Simd(SimdOp::F32x4Neg)),
+    ("f32x4.sqrt", Simd(SimdOp::F32x4Sqrt)),
+    ("f32x4.add", Simd(SimdOp::F32x4Add)),
+    ("f32x4.sub", Simd(SimdOp::F32x4Sub)),
+    ("f32x4.mul", Simd(SimdOp::F32x4Mul)),
+    ("f32x4.div", Simd(SimdOp::F32x4Div)),
+    ("f32x4.min", Simd(SimdOp::F32x4Min)),
+    ("f32x4.max", Simd(SimdOp::F32x4Max)),
+    ("f32x4.pmin", Simd(SimdOp::F32x4Pmin)),
+    ("f32x4.pmax", Simd(SimdOp::F32x4Pmax)),
+    ("f64x2.abs", Simd(SimdOp::F64x2Abs)),
+    ("f64x2.neg", Simd(SimdOp::F64x2Neg)),
+    ("f64x2.sqrt", Simd(SimdOp::F64x2Sqrt)),
+    ("f64x2.add", Simd(SimdOp::F64x2Add)),
+    ("f64x2.sub", Simd(SimdOp::F64x2Sub)),
+    ("f64x2.mul", Simd(SimdOp::F64x2Mul)),
+    ("f64x2.div", Simd(SimdOp::F64x2Div)),
+    ("f64x2.min", Simd(SimdOp::F64x2Min)),
+    ("f64x2.max", Simd(SimdOp::F64x2Max)),
+    ("f64x2.pmin", Simd(SimdOp::F64x2Pmin)),
+    ("f64x2.pmax", Simd(SimdOp::F64x2Pmax)),
+    ("i32x4.trunc_sat_f32x4_s", Simd(SimdOp::I32x4TruncSatF32x4S)),
+    ("i32x4.trunc_sat_f32x4_u", Simd(SimdOp::I32x4TruncSatF32x4U)),
+    ("f32x4.convert_i32x4_s", Simd(SimdOp::F32x4ConvertI32x4S)),
+    ("f32x4.convert_i32x4_u", Simd(SimdOp::F32x4ConvertI32x4U)),
+    (
+        "i32x4.trunc_sat_f64x2_s_zero",
+        Simd(SimdOp::I32x4TruncSatF64x2SZero),
+    ),
+    (
+        "i32x4.trunc_sat_f64x2_u_zero",
+        Simd(SimdOp::I32x4TruncSatF64x2UZero),
+    ),
+    (
+        "f64x2.convert_low_i32x4_s",
+        Simd(SimdOp::F64x2ConvertLowI32x4S),
+    ),
+    (
+        "f64x2.convert_low_i32x4_u",
+        Simd(SimdOp::F64x2ConvertLowI32x4U),
+    ),
+];
+
+/// Parses an instruction without immediates. The longest matching name wins,
+/// so `i32.eqz` is never mistaken for `i32.eq`.
+fn numeric_instr(input: &str) -> IResult<&str, Instr> {
+    let (input, _) = ws(input)?;
+    let found = NUMERIC_INSTRS
+        .iter()
+        .filter(|(name, _)| input.starts_with(name))
+        .max_by_key(|(name, _)| name.len());
+
+    match found {
+        Some((name, instr)) => {
+            let (input, _) = ws(&input[name.len()..])?;
+            Ok((input, instr.clone()))
+        }
+        None => Err(Err::Error(Error::new(input, ErrorKind::Tag))),
+    }
+}
+
+/// Parses an instruction that isn't a block.
+fn plain_instr<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, Instr> {
+    let throw = |i| throw(i, ctx);
+    let branch = |i| branch(i, ctx);
+    let call = |i| call(i, ctx);
+    let call_indirect = |i| call_indirect(i, ctx);
+    let call_ref = |i| call_ref(i, ctx);
+    let select = |i| select(i, ctx);
+    let local = |i| local(i, ctx);
+    let global = |i| global(i, ctx);
+    let memory = |i| memory(i, ctx);
+    let simd = |i| simd(i, ctx);
+    let memory_instr = |i| memory_instr(i, ctx);
+    let reference = |i| reference(i, ctx);
+    let table = |i| table(i, ctx);
+    alt((
+        throw,
+        branch,
+        call,
+        call_indirect,
+        call_ref,
+        select,
+        reference,
+        local,
+        global,
+        table,
+        memory,
+        i32_const,
+        i64_const,
+        f32_const,
+        f64_const,
+        // Lane loads start with the name of `v128.load`.
+        simd,
+        memory_instr,
+        numeric_instr,
+    ))(input)
+}
+
+/// Parses a folded instruction like `(i32.add (local.get 0) (i32.const 1))`,
+/// which is unfolded into its operands followed by the instruction itself.
+pub fn folded_instr<'a>(
+    input: &'a str,
+    ctx: &mut Rc<RefCell<Ctx>>,
+) -> IResult<&'a str, Vec<Instr>> {
+    let block = map(|i| block(i, &mut ctx.clone(), true), |b| vec![b]);
+    let try_table = map(|i| try_table(i, &mut ctx.clone(), true), |t| vec![t]);
+    let if_else = |i| folded_if(i, &mut ctx.clone());
+    let plain = |i| {
+        let (i, instr) = plain_instr(i, ctx)?;
+        let (i, mut instrs) = operands(i, ctx)?;
+        instrs.push(instr);
+        Ok((i, instrs))
+    };
+    token::pt(alt((block, try_table, if_else, plain)))(input)
 }
 
 pub fn instrs<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, Vec<Instr>> {
-    let lg = |i| local_get(i, ctx);
-    let instruction = alt((lg, i32_add));
-    many1(bws(instruction))(input)
+    let block = |i| block(i, &mut ctx.clone(), false);
+    let if_else = |i| if_else(i, &mut ctx.clone());
+    let try_table = |i| try_table(i, &mut ctx.clone(), false);
+    let plain = |i| plain_instr(i, ctx);
+    let instruction = map(alt((block, if_else, try_table, plain)), |i| vec![i]);
+    let folded = |i| folded_instr(i, &mut ctx.clone());
+    let (input, instrs) = many0(bws(alt((instruction, folded))))(input)?;
+
+    Ok((input, instrs.concat()))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ast::Instr::LocalGet;
+    use crate::ast::{BlockType, ValueType};
 
     #[test]
-    fn local_get_parse() {
+    fn local_parse() {
         let ctx = Rc::new(RefCell::new(Ctx {
             locals: vec![Some("$lhs".to_string())],
             ..Ctx::new()
         }));
-        assert_eq!(local_get("local.get 1", &ctx), Ok(("", Instr::LocalGet(1))));
+        assert_eq!(local("local.get 1", &ctx), Ok(("", Instr::LocalGet(1))));
+        assert_eq!(local("local.get $lhs", &ctx), Ok(("", Instr::LocalGet(0))));
+        assert_eq!(local("local.set $lhs", &ctx), Ok(("", Instr::LocalSet(0))));
+        assert_eq!(local("local.tee 2", &ctx), Ok(("", Instr::LocalTee(2))));
+    }
+
+    #[test]
+    fn i32_add_parse() {
+        assert_eq!(numeric_instr(" i32.add "), Ok(("", I32Add)));
+        assert!(numeric_instr("local.get").is_err());
+    }
+
+    #[test]
+    fn i32_numeric_parse() {
+        assert_eq!(numeric_instr("i32.eqz"), Ok(("", I32Eqz)));
+        assert_eq!(numeric_instr("i32.eq"), Ok(("", I32Eq)));
+        assert_eq!(numeric_instr("i32.shr_u\n"), Ok(("", I32ShrU)));
         assert_eq!(
-            local_get("local.get $lhs", &ctx),
-            Ok(("", Instr::LocalGet(0)))
+            numeric_instr("i32.popcnt i32.add"),
+            Ok(("i32.add", I32Popcnt))
         );
+        assert!(numeric_instr("i32").is_err());
     }
 
     #[test]
-    fn i32_add_parse() {
-        assert_eq!(i32_add(" i32.add "), Ok(("", I32Add)));
-        assert!(i32_add("local.get").is_err());
+    fn i64_numeric_parse() {
+        assert_eq!(numeric_instr("i64.eqz"), Ok(("", I64Eqz)));
+        assert_eq!(numeric_instr("i64.eq"), Ok(("", I64Eq)));
+        assert_eq!(numeric_instr("i64.rem_u "), Ok(("", I64RemU)));
+        assert_eq!(numeric_instr("i64.rotl"), Ok(("", I64Rotl)));
+    }
+
+    #[test]
+    fn float_numeric_parse() {
+        assert_eq!(numeric_instr("f32.ne"), Ok(("", F32Ne)));
+        assert_eq!(numeric_instr("f32.nearest"), Ok(("", F32Nearest)));
+        assert_eq!(numeric_instr("f64.copysign"), Ok(("", F64Copysign)));
+        assert_eq!(numeric_instr("f64.ge"), Ok(("", F64Ge)));
+    }
+
+    #[test]
+    fn conversion_parse() {
+        assert_eq!(numeric_instr("i32.trunc_f32_s"), Ok(("", I32TruncF32S)));
+        assert_eq!(
+            numeric_instr("i32.trunc_sat_f64_u"),
+            Ok(("", I32TruncSatF64U))
+        );
+        assert_eq!(numeric_instr("f32.trunc"), Ok(("", F32Trunc)));
+        assert_eq!(numeric_instr("f32.demote_f64"), Ok(("", F32DemoteF64)));
+        assert_eq!(
+            numeric_instr("i64.reinterpret_f64"),
+            Ok(("", I64ReinterpretF64))
+        );
+    }
+
+    #[test]
+    fn sign_extension_parse() {
+        assert_eq!(numeric_instr("i32.extend8_s"), Ok(("", I32Extend8S)));
+        assert_eq!(numeric_instr("i32.extend16_s"), Ok(("", I32Extend16S)));
+        assert_eq!(numeric_instr("i64.extend8_s"), Ok(("", I64Extend8S)));
+        assert_eq!(numeric_instr("i64.extend16_s"), Ok(("", I64Extend16S)));
+        assert_eq!(numeric_instr("i64.extend32_s"), Ok(("", I64Extend32S)));
+        assert_eq!(numeric_instr("i64.extend_i32_s"), Ok(("", I64ExtendI32S)));
+    }
+
+    #[test]
+    fn const_parse() {
+        assert_eq!(i32_const("i32.const -1"), Ok(("", I32Const(-1))));
+        assert_eq!(i32_const("i32.const 0xffffffff"), Ok(("", I32Const(-1))));
+        assert_eq!(i64_const("i64.const 0x10"), Ok(("", I64Const(16))));
+        assert!(i32_const("i32.const 0x1_0000_0000").is_err());
+        assert!(i64_const("i32.const 1").is_err());
+        assert_eq!(f32_const("f32.const -0x1p-1"), Ok(("", F32Const(-0.5))));
+        assert_eq!(f64_const("f64.const 1e10"), Ok(("", F64Const(1e10))));
+        assert_eq!(
+            f64_const("f64.const inf"),
+            Ok(("", F64Const(f64::INFINITY)))
+        );
+    }
+
+    #[test]
+    fn simd_parse() {
+        let ctx = Rc::new(RefCell::new(Ctx::new()));
+        assert_eq!(
+            simd("v128.const i32x4 0x03020100 0x07060504 0x0b0a0908 -1", &ctx),
+            Ok(("", V128Const(0xffffffff_0b0a0908_07060504_03020100)))
+        );
+        assert_eq!(
+            simd(
+                "v128.const i8x16 -1 255 2 3 4 5 6 7 8 9 10 11 12 13 14 0",
+                &ctx
+            ),
+            Ok(("", V128Const(0x000e0d0c_0b0a0908_07060504_0302ffff)))
+        );
+        assert_eq!(
+            simd("v128.const f64x2 1.0 -0x0p+0", &ctx),
+            Ok(("", V128Const(0x80000000_00000000_3ff00000_00000000)))
+        );
+        assert!(simd("v128.const i16x8 1 2 3", &ctx).is_err());
+        assert!(simd("v128.const i8x16 256 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0", &ctx).is_err());
+        assert_eq!(
+            simd("i8x16.shuffle 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 31", &ctx),
+            Ok((
+                "",
+                I8x16Shuffle([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 31])
+            ))
+        );
+        assert_eq!(
+            simd("i16x8.extract_lane_u 7", &ctx),
+            Ok(("", I16x8ExtractLaneU(7)))
+        );
+        let memarg = |align, offset| MemArg {
+            align,
+            offset,
+            memory: 0,
+        };
+        assert_eq!(
+            simd("v128.load16_lane offset=2 1", &ctx),
+            Ok(("", V128Load16Lane(memarg(1, 2), 1)))
+        );
+
+        let mut ctx = Rc::new(RefCell::new(Ctx::new()));
+        assert_eq!(
+            instrs(
+                "v128.load8_lane 3 v128.load align=8 v128.load8x8_s i8x16.add_sat_u",
+                &mut ctx
+            ),
+            Ok((
+                "",
+                vec![
+                    V128Load8Lane(memarg(0, 0), 3),
+                    V128Load(memarg(3, 0)),
+                    V128Load8x8S(memarg(3, 0)),
+                    Simd(SimdOp::I8x16AddSatU),
+                ]
+            ))
+        );
     }
 
     #[test]
@@ -66,4 +1531,458 @@ mod tests {
             Ok(("", vec![LocalGet(1), I32Add, LocalGet(2)]))
         );
     }
+
+    #[test]
+    fn folded_parse() {
+        let mut ctx = Rc::new(RefCell::new(Ctx::new()));
+        assert_eq!(
+            instrs(
+                "(i32.add (local.get 0) (i32.mul (local.get 1) (i32.const 2)))
+                 (drop (block (result i32) (i32.const 1)))
+                 (loop $l (br_if $l (local.get 0)))",
+                &mut ctx
+            ),
+            Ok((
+                "",
+                vec![
+                    LocalGet(0),
+                    LocalGet(1),
+                    I32Const(2),
+                    I32Mul,
+                    I32Add,
+                    Block(BlockType::Value(ValueType::I32), vec![I32Const(1)]),
+                    Drop,
+                    Loop(BlockType::Empty, vec![LocalGet(0), BrIf(0)]),
+                ]
+            ))
+        );
+        assert_eq!(
+            instrs(
+                "(if $a (result i32) (local.get 0)
+                   (then (i32.const 1))
+                   (else (br $a (i32.const 2))))
+                 (if (i32.const 0) (then nop))",
+                &mut ctx
+            ),
+            Ok((
+                "",
+                vec![
+                    LocalGet(0),
+                    If(
+                        BlockType::Value(ValueType::I32),
+                        vec![I32Const(1)],
+                        vec![I32Const(2), Br(0)]
+                    ),
+                    I32Const(0),
+                    If(BlockType::Empty, vec![Nop], vec![]),
+                ]
+            ))
+        );
+        assert!(ctx.borrow().labels.is_empty());
+        assert_eq!(
+            instrs("(if (i32.const 0) nop)", &mut ctx),
+            Ok(("(if (i32.const 0) nop)", vec![]))
+        );
+    }
+
+    #[test]
+    fn parametric_parse() {
+        let mut ctx = Rc::new(RefCell::new(Ctx::new()));
+        assert_eq!(
+            instrs(
+                "nop drop select
+                 select (result f64) unreachable",
+                &mut ctx
+            ),
+            Ok((
+                "",
+                vec![
+                    Nop,
+                    Drop,
+                    Select(None),
+                    Select(Some(ValueType::F64)),
+                    Unreachable
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn block_parse() {
+        let mut ctx = Rc::new(RefCell::new(Ctx::new()));
+        assert_eq!(
+            block(
+                "block $outer (result i32) loop $inner br $outer br 0 end end",
+                &mut ctx,
+                false
+            ),
+            Ok((
+                "",
+                Block(
+                    BlockType::Value(ValueType::I32),
+                    vec![Loop(BlockType::Empty, vec![Br(1), Br(0)])]
+                )
+            ))
+        );
+        assert!(ctx.borrow().labels.is_empty());
+    }
+
+    #[test]
+    fn nesting_depth_parse() {
+        let mut ctx = Rc::new(RefCell::new(Ctx::new()));
+        // Whether parsing fails, or how much input is left.
+        let mut parse = |wat: String| match instrs(&wat, &mut ctx) {
+            Ok((rest, _)) => Ok(rest.len()),
+            Err(e) => Err(matches!(e, Err::Failure(_))),
+        };
+        let blocks = |n| "block ".repeat(n) + &"end ".repeat(n);
+        let folded = |n| "(i32.eqz ".repeat(n) + "(i32.const 0)" + &")".repeat(n);
+
+        assert_eq!(Ok(0), parse(blocks(MAX_NESTING_DEPTH)));
+        assert_eq!(Ok(0), parse(folded(MAX_NESTING_DEPTH - 1)));
+        assert_eq!(Err(true), parse(blocks(MAX_NESTING_DEPTH + 1)));
+        assert_eq!(Err(true), parse(folded(MAX_NESTING_DEPTH)));
+        assert_eq!(Err(true), parse(blocks(100_000)));
+        assert_eq!(0, ctx.borrow().depth);
+    }
+
+    #[test]
+    fn if_else_parse() {
+        let mut ctx = Rc::new(RefCell::new(Ctx::new()));
+        assert_eq!(
+            if_else(
+                "if $l (result i64) i64.const 1 else br_if $l end $l",
+                &mut ctx
+            ),
+            Ok((
+                "",
+                If(
+                    BlockType::Value(ValueType::I64),
+                    vec![I64Const(1)],
+                    vec![BrIf(0)]
+                )
+            ))
+        );
+        assert_eq!(
+            if_else("if return end", &mut ctx),
+            Ok(("", If(BlockType::Empty, vec![Return], vec![])))
+        );
+    }
+
+    #[test]
+    fn br_table_parse() {
+        let mut ctx = Rc::new(RefCell::new(Ctx::new()));
+        assert_eq!(
+            instrs("block $a block $b br_table $a $b 0 end end", &mut ctx),
+            Ok((
+                "",
+                vec![Block(
+                    BlockType::Empty,
+                    vec![Block(BlockType::Empty, vec![BrTable(vec![1, 0], 0)])]
+                )]
+            ))
+        );
+    }
+
+    #[test]
+    fn memory_instr_parse() {
+        let ctx = Rc::new(RefCell::new(Ctx::new()));
+        let memarg = |align, offset| MemArg {
+            align,
+            offset,
+            memory: 0,
+        };
+        assert_eq!(
+            memory_instr("i32.load", &ctx),
+            Ok(("", I32Load(memarg(2, 0))))
+        );
+        assert_eq!(
+            memory_instr("i64.load8_u offset=4 align=1", &ctx),
+            Ok(("", I64Load8U(memarg(0, 4))))
+        );
+        assert_eq!(
+            memory_instr("f64.store align=8 ", &ctx),
+            Ok(("", F64Store(memarg(3, 0))))
+        );
+        assert_eq!(
+            memory_instr("i32.store16 offset=0x10", &ctx),
+            Ok(("", I32Store16(memarg(1, 16))))
+        );
+        assert_eq!(
+            memory_instr("i32.load align=3", &ctx),
+            Ok(("align=3", I32Load(memarg(2, 0))))
+        );
+
+        ctx.borrow_mut().memories.add_id(None);
+        ctx.borrow_mut().memories.add_id(Some("$m".to_string()));
+        assert_eq!(
+            memory_instr("i64.store $m offset=8", &ctx),
+            Ok((
+                "",
+                I64Store(MemArg {
+                    memory: 1,
+                    ..memarg(3, 8)
+                })
+            ))
+        );
+        assert_eq!(
+            memory_instr("i32.load8_u 1", &ctx),
+            Ok((
+                "",
+                I32Load8U(MemArg {
+                    memory: 1,
+                    ..memarg(0, 0)
+                })
+            ))
+        );
+        assert_eq!(memory("memory.grow", &ctx), Ok(("", MemoryGrow(0))));
+        assert_eq!(memory("memory.size $m", &ctx), Ok(("", MemorySize(1))));
+    }
+
+    #[test]
+    fn atomic_instr_parse() {
+        let ctx = Rc::new(RefCell::new(Ctx::new()));
+        let memarg = |align, offset| MemArg {
+            align,
+            offset,
+            memory: 0,
+        };
+        assert_eq!(
+            memory_instr("memory.atomic.wait64 offset=8", &ctx),
+            Ok(("", MemoryAtomicWait64(memarg(3, 8))))
+        );
+        assert_eq!(
+            memory_instr("i32.atomic.load8_u", &ctx),
+            Ok(("", AtomicLoad(AtomicWidth::I32U8, memarg(0, 0))))
+        );
+        assert_eq!(
+            memory_instr("i64.atomic.store32 align=4", &ctx),
+            Ok(("", AtomicStore(AtomicWidth::I64U32, memarg(2, 0))))
+        );
+        assert_eq!(
+            memory_instr("i32.atomic.rmw16.cmpxchg_u", &ctx),
+            Ok((
+                "",
+                AtomicRmw(AtomicRmwOp::Cmpxchg, AtomicWidth::I32U16, memarg(1, 0))
+            ))
+        );
+        assert_eq!(
+            memory_instr("i64.atomic.rmw.add", &ctx),
+            Ok((
+                "",
+                AtomicRmw(AtomicRmwOp::Add, AtomicWidth::I64, memarg(3, 0))
+            ))
+        );
+        assert_eq!(numeric_instr("atomic.fence"), Ok(("", AtomicFence)));
+    }
+
+    #[test]
+    fn bulk_memory_parse() {
+        let mut ctx = Rc::new(RefCell::new(Ctx::new()));
+        ctx.borrow_mut().datas.add_id(Some("$d".to_string()));
+        assert_eq!(
+            instrs(
+                "memory.init $d data.drop 0 memory.copy memory.fill",
+                &mut ctx
+            ),
+            Ok((
+                "",
+                vec![
+                    MemoryInit(0, 0),
+                    DataDrop(0),
+                    MemoryCopy(0, 0),
+                    MemoryFill(0)
+                ]
+            ))
+        );
+
+        ctx.borrow_mut().memories.add_id(None);
+        ctx.borrow_mut().memories.add_id(Some("$m".to_string()));
+        assert_eq!(
+            instrs(
+                "memory.init $m $d memory.copy 0 $m memory.fill $m",
+                &mut ctx
+            ),
+            Ok(("", vec![MemoryInit(1, 0), MemoryCopy(0, 1), MemoryFill(1)]))
+        );
+    }
+
+    #[test]
+    fn reference_parse() {
+        let mut ctx = Rc::new(RefCell::new(Ctx::new()));
+        ctx.borrow_mut().funcs.add_id(Some("$f".to_string()));
+        ctx.borrow_mut().types.add_id(Some("$t".to_string()));
+        assert_eq!(
+            instrs(
+                "ref.null func ref.null extern ref.null $t ref.is_null ref.func $f ref.func 3",
+                &mut ctx
+            ),
+            Ok((
+                "",
+                vec![
+                    RefNull(HeapType::Func),
+                    RefNull(HeapType::Extern),
+                    RefNull(HeapType::Type(0)),
+                    RefIsNull,
+                    RefFunc(0),
+                    RefFunc(3)
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn table_instr_parse() {
+        let mut ctx = Rc::new(RefCell::new(Ctx::new()));
+        ctx.borrow_mut().tables.add_id(None);
+        ctx.borrow_mut().tables.add_id(Some("$t".to_string()));
+        ctx.borrow_mut().elems.add_id(Some("$e".to_string()));
+        assert_eq!(
+            instrs(
+                "table.get table.set $t table.size 1 table.grow table.fill $t
+                 table.copy table.copy $t 0 table.init $e table.init $t 0 elem.drop $e",
+                &mut ctx
+            ),
+            Ok((
+                "",
+                vec![
+                    TableGet(0),
+                    TableSet(1),
+                    TableSize(1),
+                    TableGrow(0),
+                    TableFill(1),
+                    TableCopy(0, 0),
+                    TableCopy(1, 0),
+                    TableInit(0, 0),
+                    TableInit(1, 0),
+                    ElemDrop(0)
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn tail_call_parse() {
+        let mut ctx = Rc::new(RefCell::new(Ctx::new()));
+        ctx.borrow_mut().funcs.add_id(Some("$f".to_string()));
+        ctx.borrow_mut().tables.add_id(Some("$t".to_string()));
+        assert_eq!(
+            instrs(
+                "return_call $f call 0 return_call_indirect $t (result i32) return",
+                &mut ctx
+            ),
+            Ok((
+                "",
+                vec![ReturnCall(0), Call(0), ReturnCallIndirect(0, 0), Return]
+            ))
+        );
+    }
+
+    #[test]
+    fn func_ref_parse() {
+        let mut ctx = Rc::new(RefCell::new(Ctx::new()));
+        ctx.borrow_mut().labels.push(Some("$l".to_string()));
+        ctx.borrow_mut()
+            .insert_id_func_type(Some("$t".to_string()), &(vec![], vec![]));
+        assert_eq!(
+            instrs(
+                "call_ref $t return_call_ref 0 ref.as_non_null br_on_null $l br_on_non_null 0
+                select (result (ref null $t))",
+                &mut ctx
+            ),
+            Ok((
+                "",
+                vec![
+                    CallRef(0),
+                    ReturnCallRef(0),
+                    RefAsNonNull,
+                    BrOnNull(0),
+                    BrOnNonNull(0),
+                    Select(Some(ValueType::Ref {
+                        nullable: true,
+                        type_idx: 0
+                    }))
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn exception_parse() {
+        let mut ctx = Rc::new(RefCell::new(Ctx::new()));
+        ctx.borrow_mut().tags.add_id(None);
+        ctx.borrow_mut().tags.add_id(Some("$e".to_string()));
+        assert_eq!(
+            instrs(
+                "block $outer (result exnref)
+                   try_table $try (result i32) (catch $e $outer) (catch_ref 0 0)
+                     (catch_all 1) (catch_all_ref $outer)
+                     i32.const 1 throw $e
+                   end
+                   ref.null exn
+                 end
+                 throw_ref",
+                &mut ctx
+            ),
+            Ok((
+                "",
+                vec![
+                    Block(
+                        BlockType::Value(ValueType::ExnRef),
+                        vec![
+                            TryTable(
+                                BlockType::Value(ValueType::I32),
+                                vec![
+                                    Catch::Tag(1, 0),
+                                    Catch::TagRef(0, 0),
+                                    Catch::All(1),
+                                    Catch::AllRef(0),
+                                ],
+                                vec![I32Const(1), Throw(1)],
+                            ),
+                            RefNull(HeapType::Exn),
+                        ],
+                    ),
+                    ThrowRef,
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn call_indirect_parse() {
+        let mut ctx = Rc::new(RefCell::new(Ctx::new()));
+        ctx.borrow_mut().tables.add_id(None);
+        ctx.borrow_mut().tables.add_id(Some("$t".to_string()));
+        assert_eq!(
+            instrs(
+                "call_indirect (param i32 i64) (param f32) (result i32)
+                 call_indirect $t
+                 call_indirect 1 (result i32)
+                 call_indirect $t (type 0)",
+                &mut ctx
+            ),
+            Ok((
+                "",
+                vec![
+                    CallIndirect(0, 0),
+                    CallIndirect(1, 1),
+                    CallIndirect(2, 1),
+                    CallIndirect(0, 1)
+                ]
+            ))
+        );
+        assert_eq!(
+            vec![
+                (
+                    vec![ValueType::I32, ValueType::I64, ValueType::F32],
+                    vec![ValueType::I32]
+                ),
+                (vec![], vec![]),
+                (vec![], vec![ValueType::I32])
+            ],
+            ctx.borrow().types.list
+        );
+        assert!(ctx.borrow().locals.is_empty());
+    }
 }
@@ -7,7 +7,50 @@ mod token;
 mod types;
 mod values;
 
-pub fn parse(wat: &str) -> Module {
-    let (_, ast) = module::module(wat).expect("Ups, something went wrong!");
-    ast
+/// The text isn't a valid module. The position of the first field that
+/// can't be parsed is given by its line and column, both counted from 1.
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl ParseError {
+    /// Locates the error at the start of `rest`, the unparsed end of `wat`.
+    fn at(wat: &str, rest: &str) -> Self {
+        let parsed = &wat[..wat.len() - rest.len()];
+        let line_start = parsed.rfind('\n').map_or(0, |i| i + 1);
+        ParseError {
+            line: parsed.matches('\n').count() + 1,
+            column: parsed[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+pub fn parse(wat: &str) -> Result<Module, ParseError> {
+    match module::module(wat) {
+        Ok((_, ast)) => Ok(ast),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => Err(ParseError::at(wat, e.input)),
+        Err(nom::Err::Incomplete(_)) => Err(ParseError::at(wat, "")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_error() {
+        let wat = "(module\n  (func $f i32.const 1)\n  (func $g i32.bogus))";
+        assert_eq!(Err(ParseError { line: 3, column: 3 }), parse(wat));
+        assert_eq!(Err(ParseError { line: 1, column: 2 }), parse("(func)"));
+        assert!(parse("(module (func))").is_ok());
+    }
+
+    #[test]
+    fn parse_deeply_nested() {
+        let n = 100_000;
+        let wat = format!("(module (func {}{}))", "(block ".repeat(n), ")".repeat(n));
+        assert!(parse(&wat).is_err());
+    }
 }
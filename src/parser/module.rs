@@ -1,47 +1,447 @@
-use crate::ast::EDesc::FuncExport;
+use crate::ast::EDesc::{FuncExport, GlobalExport, MemoryExport, TableExport, TagExport};
 use crate::ast::*;
-use crate::parser::ctx::Ctx;
-use crate::parser::token::{bws, ws};
+use crate::parser::ctx::{Ctx, Space};
+use crate::parser::token::{bws, keyword, ws};
 use crate::parser::{instr, token, types, values};
 use nom::branch::alt;
-use nom::combinator::map;
+use nom::bytes::complete::is_not;
+use nom::character::complete::char;
+use nom::combinator::{map, map_opt, opt, value};
+use nom::error::{make_error, ErrorKind};
 use nom::multi::many0;
-use nom::sequence::{preceded, tuple};
+use nom::sequence::{pair, preceded, terminated, tuple};
 use nom::IResult;
 use std::cell::RefCell;
 use std::rc::Rc;
 
+/// The size of a memory page in bytes.
+const PAGE_SIZE: u64 = 65536;
+
+/// Parses `(local $id t)` or `(local t*)` and registers the local ids.
+fn local<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, Vec<ValueType>> {
+    let value_type = |i| types::value_type(i, ctx);
+    let named = map(tuple((values::id, value_type)), |(id, vt)| {
+        (Some(id.to_string()), vec![vt])
+    });
+    let anonymous = map(many0(value_type), |vts| (None, vts));
+    let (input, (id, locals)) =
+        bws(token::pt(preceded(token::local, alt((named, anonymous)))))(input)?;
+
+    for _ in &locals {
+        if ctx.borrow_mut().insert_local_id(&id).is_none() {
+            return Err(nom::Err::Error(make_error(input, ErrorKind::Verify)));
+        }
+    }
+
+    Ok((input, locals))
+}
+
+/// Parses `(type $id? (func (param t*)* (result t*)*))`, which defines a
+/// function type that references like `(ref $id)` can refer to.
+fn type_def<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, FuncType> {
+    fn inner<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, FuncType> {
+        let (input, _) = preceded(keyword("type"), opt(values::id))(input)?;
+        let func_type = |i| types::anonymous_func_type(i, ctx);
+        let (input, ft) = bws(token::pt(preceded(token::func, func_type)))(input)?;
+        ctx.borrow_mut().types.add_item(ft.clone());
+
+        Ok((input, ft))
+    }
+
+    token::pt(|i| inner(i, ctx))(input)
+}
+
+/// Parses `(export "name")*`, the inline exports of a function, table,
+/// memory, tag or global.
+fn inline_exports(input: &str) -> IResult<&str, Vec<String>> {
+    many0(bws(token::pt(preceded(token::export, values::literal))))(input)
+}
+
+/// Adds an export field for each of the inline `names` of an entity.
+fn insert_exports(ctx: &Rc<RefCell<Ctx>>, names: Vec<String>, e_desc: EDesc) {
+    for name in names {
+        let export = Export {
+            name: name.clone(),
+            e_desc: e_desc.clone(),
+        };
+        ctx.borrow_mut().insert_export(&Some(name), &export);
+    }
+}
+
+/// Parses `(func $id? (export "name")* typeuse local* instr*)`.
 fn func<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, Func> {
-    fn inner<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, Func> {
-        let (input, id) = preceded(token::func, values::id)(input)?;
-        ctx.borrow_mut().insert_func_id(Some(id.to_string()));
+    fn inner<'a>(
+        input: &'a str,
+        ctx: &mut Rc<RefCell<Ctx>>,
+    ) -> IResult<&'a str, (Vec<String>, Func)> {
+        let (input, _) = preceded(token::func, opt(values::id))(input)?;
+        let (input, exports) = inline_exports(input)?;
+        ctx.borrow_mut().locals.clear();
         let (input, f_type) = types::type_use(input, ctx)?;
+        let (input, locals) = many0(|i| local(i, &mut ctx.clone()))(input)?;
         let (input, instrs) = instr::instrs(input, ctx)?;
 
         let f = Func {
             f_type: f_type as i32,
-            locals: vec![],
+            locals: locals.concat(),
             body: instrs,
         };
 
-        Ok((input, f))
+        Ok((input, (exports, f)))
     }
 
     let in_pt = |i| inner(i, ctx);
-    let (input, func) = token::pt(in_pt)(input)?;
+    let (input, (exports, func)) = token::pt(in_pt)(input)?;
+    let idx = ctx.borrow().next_idx(Space::Func);
+    insert_exports(ctx, exports, FuncExport(idx));
     ctx.borrow_mut().insert_func(&func);
 
     Ok((input, func))
 }
 
+/// Parses the keyword and optional id of an entity that can be imported.
+fn import_kind(input: &str) -> IResult<&str, Space> {
+    let kind = alt((
+        value(Space::Func, token::func),
+        value(Space::Table, token::table),
+        value(Space::Memory, token::memory),
+        value(Space::Global, token::global),
+    ));
+    terminated(kind, opt(values::id))(input)
+}
+
+/// Parses the type of an imported entity of the given kind.
+fn import_desc<'a>(
+    input: &'a str,
+    kind: Space,
+    ctx: &mut Rc<RefCell<Ctx>>,
+) -> IResult<&'a str, ImportDesc> {
+    match kind {
+        Space::Func => {
+            ctx.borrow_mut().locals.clear();
+            map(|i| types::type_use(i, ctx), ImportDesc::Func)(input)
+        }
+        Space::Table => map(
+            tuple((types::limits, types::ref_type)),
+            |(limits, ref_type)| ImportDesc::Table(Table { limits, ref_type }),
+        )(input),
+        Space::Memory => map(types::memory_type, ImportDesc::Memory)(input),
+        Space::Global => map(|i| types::global_type(i, ctx), ImportDesc::Global)(input),
+        _ => Err(nom::Err::Error(make_error(input, ErrorKind::Alt))),
+    }
+}
+
+/// Parses `(import "module" "name" (kind $id? desc))` or the abbreviation
+/// `(kind $id? (export "name")* (import "module" "name") desc)`, where `kind`
+/// is a function, table, memory or global and `desc` is its type.
+fn import<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, Import> {
+    fn explicit<'a>(
+        input: &'a str,
+        ctx: &mut Rc<RefCell<Ctx>>,
+    ) -> IResult<&'a str, (Vec<String>, Import)> {
+        let names = tuple((values::literal, values::literal));
+        let (input, (module, name)) = preceded(token::import, names)(input)?;
+        let desc = |i| {
+            let (i, kind) = import_kind(i)?;
+            import_desc(i, kind, &mut ctx.clone())
+        };
+        let (input, desc) = bws(token::pt(desc))(input)?;
+
+        Ok((input, (vec![], Import { module, name, desc })))
+    }
+
+    fn inline<'a>(
+        input: &'a str,
+        ctx: &mut Rc<RefCell<Ctx>>,
+    ) -> IResult<&'a str, (Vec<String>, Import)> {
+        let (input, kind) = import_kind(input)?;
+        let (input, exports) = inline_exports(input)?;
+        let names = tuple((values::literal, values::literal));
+        let (input, (module, name)) = bws(token::pt(preceded(token::import, names)))(input)?;
+        let (input, desc) = import_desc(input, kind, ctx)?;
+
+        Ok((input, (exports, Import { module, name, desc })))
+    }
+
+    let explicit = |i| explicit(i, &mut ctx.clone());
+    let inline = |i| inline(i, &mut ctx.clone());
+    let (input, (exports, import)) = token::pt(alt((explicit, inline)))(input)?;
+
+    let (space, e_desc): (_, fn(usize) -> EDesc) = match import.desc {
+        ImportDesc::Func(_) => (Space::Func, FuncExport),
+        ImportDesc::Table(_) => (Space::Table, TableExport),
+        ImportDesc::Memory(_) => (Space::Memory, MemoryExport),
+        ImportDesc::Global(_) => (Space::Global, GlobalExport),
+    };
+    let idx = ctx.borrow().next_idx(space);
+    insert_exports(ctx, exports, e_desc(idx));
+    ctx.borrow_mut().insert_import(&import);
+
+    Ok((input, import))
+}
+
+/// Parses function indices as `ref.func` expressions of a segment.
+fn func_refs<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, Vec<Vec<Instr>>> {
+    map_opt(many0(types::index), |funcs| {
+        let ctx = ctx.borrow();
+        funcs
+            .iter()
+            .map(|f| Some(vec![Instr::RefFunc(ctx.get_func_idx(f)?)]))
+            .collect()
+    })(input)
+}
+
+/// Parses the expressions of a segment, each either `(item instr*)` or a
+/// single folded instruction like `(ref.func $f)`.
+fn elem_exprs<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, Vec<Vec<Instr>>> {
+    let item = token::pt(preceded(keyword("item"), |i| {
+        instr::instrs(i, &mut ctx.clone())
+    }));
+    let single = |i| instr::folded_instr(i, &mut ctx.clone());
+    many0(bws(alt((item, single))))(input)
+}
+
+/// Parses the elements of a segment, either `func? idx*` or
+/// `reftype elemexpr*`.
+fn elem_list<'a>(
+    input: &'a str,
+    ctx: &Rc<RefCell<Ctx>>,
+) -> IResult<&'a str, (RefType, Vec<Vec<Instr>>)> {
+    let exprs = pair(types::ref_type, |i| elem_exprs(i, ctx));
+    let funcs = preceded(opt(keyword("func")), |i| func_refs(i, ctx));
+    alt((exprs, map(funcs, |init| (RefType::FuncRef, init))))(input)
+}
+
+/// Parses `(table $id? (export "name")* limits reftype)` or the abbreviation
+/// `(table $id? (export "name")* reftype (elem idx* | elemexpr*))`, which
+/// sizes the table to fit the elements and adds an active element segment
+/// for them.
+fn table<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, Table> {
+    let funcs = token::pt(preceded(token::elem, |i| func_refs(i, ctx)));
+    let exprs = token::pt(preceded(token::elem, |i| elem_exprs(i, ctx)));
+    let elems = alt((funcs, exprs));
+    let explicit = map(tuple((types::limits, types::ref_type)), |(limits, rt)| {
+        (limits, rt, None)
+    });
+    let inline = map(tuple((types::ref_type, elems)), |(rt, init)| {
+        let len = init.len() as u64;
+        let limits = Limits {
+            min: len,
+            max: Some(len),
+        };
+        (limits, rt, Some(init))
+    });
+    let table = preceded(
+        token::table,
+        tuple((opt(values::id), inline_exports, alt((explicit, inline)))),
+    );
+    let (input, (_, exports, (limits, ref_type, elem))) = token::pt(table)(input)?;
+
+    let table = Table { limits, ref_type };
+    let idx = ctx.borrow().next_idx(Space::Table);
+    insert_exports(ctx, exports, TableExport(idx));
+    ctx.borrow_mut().insert_table(&table);
+
+    if let Some(init) = elem {
+        let elem = Elem {
+            ref_type,
+            init,
+            mode: ElemMode::Active {
+                table: idx,
+                offset: vec![Instr::I32Const(0)],
+            },
+        };
+        ctx.borrow_mut().insert_elem(&elem);
+    }
+
+    Ok((input, table))
+}
+
+/// Parses `(memory $id? (export "name")* memtype)` or the abbreviation
+/// `(memory $id? (export "name")* (data string*))`, which sizes the memory to
+/// fit the data and adds an active data segment for it.
+fn memory<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, Memory> {
+    let explicit = map(types::memory_type, |memory| (memory, None));
+    let data = token::pt(preceded(token::data, many0(bws(values::string))));
+    let inline = map(bws(data), |strings| {
+        let init = strings.concat();
+        let pages = (init.len() as u64).div_ceil(PAGE_SIZE);
+        let memory = Memory {
+            limits: Limits {
+                min: pages,
+                max: Some(pages),
+            },
+            shared: false,
+            memory64: false,
+        };
+        (memory, Some(init))
+    });
+    let memory = preceded(
+        token::memory,
+        tuple((opt(values::id), inline_exports, alt((explicit, inline)))),
+    );
+    let (input, (_, exports, (memory, init))) = token::pt(memory)(input)?;
+
+    let idx = ctx.borrow().next_idx(Space::Memory);
+    insert_exports(ctx, exports, MemoryExport(idx));
+    ctx.borrow_mut().insert_memory(&memory);
+
+    if let Some(init) = init {
+        let data = Data {
+            init,
+            mode: DataMode::Active {
+                memory: idx,
+                offset: vec![Instr::I32Const(0)],
+            },
+        };
+        ctx.borrow_mut().insert_data(&data);
+    }
+
+    Ok((input, memory))
+}
+
+/// Parses `(tag $id? (export "name")* (param t*)*)`, whose parameters are the
+/// payload of the exceptions with the tag.
+fn tag<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, usize> {
+    fn inner<'a>(
+        input: &'a str,
+        ctx: &mut Rc<RefCell<Ctx>>,
+    ) -> IResult<&'a str, (Vec<String>, usize)> {
+        let (input, _) = preceded(keyword("tag"), opt(values::id))(input)?;
+        let (input, exports) = inline_exports(input)?;
+        let (input, type_idx) = types::inline_type_use(input, ctx)?;
+
+        Ok((input, (exports, type_idx)))
+    }
+
+    let (input, (exports, type_idx)) = token::pt(|i| inner(i, ctx))(input)?;
+    let idx = ctx.borrow().next_idx(Space::Tag);
+    insert_exports(ctx, exports, TagExport(idx));
+    ctx.borrow_mut().insert_tag(type_idx);
+
+    Ok((input, type_idx))
+}
+
+/// Parses `(global $id? (export "name")* globaltype expr)`.
+fn global<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, Global> {
+    fn inner<'a>(
+        input: &'a str,
+        ctx: &mut Rc<RefCell<Ctx>>,
+    ) -> IResult<&'a str, (Vec<String>, Global)> {
+        let (input, _) = preceded(token::global, opt(values::id))(input)?;
+        let (input, exports) = inline_exports(input)?;
+        let (input, g_type) = types::global_type(input, ctx)?;
+        let (input, init) = bws(|i| instr::instrs(i, &mut ctx.clone()))(input)?;
+
+        Ok((input, (exports, Global { g_type, init })))
+    }
+
+    let (input, (exports, global)) = token::pt(|i| inner(i, ctx))(input)?;
+    let idx = ctx.borrow().next_idx(Space::Global);
+    insert_exports(ctx, exports, GlobalExport(idx));
+    ctx.borrow_mut().insert_global(&global);
+
+    Ok((input, global))
+}
+
+/// Parses an active segment `(data (memory idx)? (offset expr) string*)`, where
+/// the offset may also be a single folded instruction, or a passive segment
+/// `(data string*)`.
+fn data<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, Data> {
+    fn inner<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, Data> {
+        let (input, _) = preceded(token::data, opt(values::id))(input)?;
+        let memory = map_opt(token::pt(preceded(token::memory, types::index)), |idx| {
+            ctx.borrow().get_memory_idx(&idx)
+        });
+        let (input, memory) = opt(memory)(input)?;
+        let offset = token::pt(preceded(token::offset, |i| {
+            instr::instrs(i, &mut ctx.clone())
+        }));
+        let single = |i| instr::folded_instr(i, &mut ctx.clone());
+        let (input, offset) = opt(bws(alt((offset, single))))(input)?;
+        let (input, strings) = many0(values::string)(input)?;
+
+        let mode = match offset {
+            Some(offset) => DataMode::Active {
+                memory: memory.unwrap_or(0),
+                offset,
+            },
+            None => DataMode::Passive,
+        };
+        let data = Data {
+            init: strings.concat(),
+            mode,
+        };
+        ctx.borrow_mut().insert_data(&data);
+
+        Ok((input, data))
+    }
+
+    token::pt(|i| inner(i, ctx))(input)
+}
+
+/// Parses an active segment `(elem (table idx)? (offset expr) elemlist)`,
+/// where the offset may also be a single folded instruction, a declarative
+/// segment `(elem declare elemlist)` or a passive segment `(elem elemlist)`.
+fn elem<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, Elem> {
+    fn inner<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, Elem> {
+        let (input, _) = preceded(token::elem, opt(values::id))(input)?;
+        let table = map_opt(token::pt(preceded(token::table, types::index)), |idx| {
+            ctx.borrow().get_table_idx(&idx)
+        });
+        let (input, table) = opt(table)(input)?;
+        let offset = token::pt(preceded(token::offset, |i| {
+            instr::instrs(i, &mut ctx.clone())
+        }));
+        let single = |i| instr::folded_instr(i, &mut ctx.clone());
+        let (input, offset) = opt(bws(alt((offset, single))))(input)?;
+        let (input, declare) = opt(keyword("declare"))(input)?;
+        let (input, (ref_type, init)) = elem_list(input, ctx)?;
+
+        let mode = match (offset, declare) {
+            (Some(offset), _) => ElemMode::Active {
+                table: table.unwrap_or(0),
+                offset,
+            },
+            (None, Some(_)) => ElemMode::Declarative,
+            (None, None) => ElemMode::Passive,
+        };
+        let elem = Elem {
+            ref_type,
+            init,
+            mode,
+        };
+        ctx.borrow_mut().insert_elem(&elem);
+
+        Ok((input, elem))
+    }
+
+    token::pt(|i| inner(i, ctx))(input)
+}
+
 fn export<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, Export> {
-    let index = token::pt(preceded(token::func, types::index));
-    let mut exp = token::pt(preceded(token::export, tuple((values::literal, index))));
-    let (input, (lit, idx)) = exp(input)?;
+    let func = map_opt(preceded(token::func, types::index), |idx| {
+        ctx.borrow().get_func_idx(&idx).map(FuncExport)
+    });
+    let table = map_opt(preceded(token::table, types::index), |idx| {
+        ctx.borrow().get_table_idx(&idx).map(TableExport)
+    });
+    let memory = map_opt(preceded(token::memory, types::index), |idx| {
+        ctx.borrow().get_memory_idx(&idx).map(MemoryExport)
+    });
+    let global = map_opt(preceded(token::global, types::index), |idx| {
+        ctx.borrow().get_global_idx(&idx).map(GlobalExport)
+    });
+    let tag = map_opt(preceded(keyword("tag"), types::index), |idx| {
+        ctx.borrow().get_tag_idx(&idx).map(TagExport)
+    });
+    let desc = token::pt(alt((func, table, memory, global, tag)));
+    let mut exp = token::pt(preceded(token::export, tuple((values::literal, desc))));
+    let (input, (lit, e_desc)) = exp(input)?;
 
     let export = Export {
         name: lit.clone(),
-        e_desc: FuncExport(ctx.borrow().get_func_idx(&idx)),
+        e_desc,
     };
 
     ctx.borrow_mut().insert_export(&Some(lit), &export);
@@ -49,20 +449,138 @@ fn export<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, Ex
     Ok((input, export))
 }
 
+fn start<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, usize> {
+    let func = map_opt(types::index, |idx| ctx.borrow().get_func_idx(&idx));
+    let (input, idx) = token::pt(preceded(token::start, func))(input)?;
+    ctx.borrow_mut().start = Some(idx);
+
+    Ok((input, idx))
+}
+
+/// Skips a token, a string or a parenthesized list of them. Lists are
+/// matched by counting parentheses rather than recursively, so that deeply
+/// nested fields don't overflow the stack before they are parsed.
+fn skip(input: &str) -> IResult<&str, ()> {
+    let open = |i| value(1, char('('))(i);
+    let close = |i| value(-1, char(')'))(i);
+    let string = |i| value(0, values::string)(i);
+    let atom = |i| value(0, is_not("() \t\r\n\""))(i);
+    let (mut input, mut depth) = alt((open, string, atom))(input)?;
+    while depth > 0 {
+        let (rest, delta) = preceded(ws, alt((open, close, string, atom)))(input)?;
+        input = rest;
+        depth += delta;
+    }
+    Ok((input, ()))
+}
+
+/// Matches a parenthesized list that starts with the keyword `kw`.
+fn list<'a>(kw: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, ()> {
+    map(token::pt(preceded(keyword(kw), many0(bws(skip)))), |_| ())
+}
+
+/// Collects the ids that a module field declares, together with the index
+/// spaces they are declared in. A table with inline elements or a memory
+/// with inline data also declares a segment without an id.
+fn field_ids(input: &str) -> IResult<&str, Vec<(Space, Option<String>)>> {
+    let id = || map(opt(values::id), |id| id.map(String::from));
+    let space = || {
+        alt((
+            value(Space::Type, keyword("type")),
+            value(Space::Func, keyword("func")),
+            value(Space::Table, keyword("table")),
+            value(Space::Memory, keyword("memory")),
+            value(Space::Tag, keyword("tag")),
+            value(Space::Global, keyword("global")),
+            value(Space::Elem, keyword("elem")),
+            value(Space::Data, keyword("data")),
+        ))
+    };
+    let names = tuple((keyword("import"), values::string, values::string));
+    let desc = token::pt(terminated(tuple((space(), id())), many0(bws(skip))));
+    let import = map(preceded(names, bws(desc)), |decl| vec![decl]);
+    let item = alt((
+        value(Some(Space::Elem), list("elem")),
+        value(Some(Space::Data), list("data")),
+        value(None, skip),
+    ));
+    let definition = map(
+        tuple((space(), id(), many0(bws(item)))),
+        |(space, id, items)| {
+            let segment = match space {
+                Space::Table => Some(Space::Elem),
+                Space::Memory => Some(Space::Data),
+                _ => None,
+            };
+            let mut ids = vec![(space, id)];
+            if let Some(segment) = segment.filter(|s| items.contains(&Some(*s))) {
+                ids.push((segment, None));
+            }
+            ids
+        },
+    );
+    let other = map(many0(bws(skip)), |_| vec![]);
+    token::pt(alt((import, definition, other)))(input)
+}
+
+/// Collects the ids of all module fields in index order, so that entities
+/// can be referenced before they are defined.
+fn module_ids(input: &str) -> IResult<&str, Vec<(Space, Option<String>)>> {
+    let fields = preceded(token::module, many0(bws(field_ids)));
+    let (input, ids) = preceded(ws, token::pt(fields))(input)?;
+
+    Ok((input, ids.concat()))
+}
+
 pub fn module(input: &str) -> IResult<&str, Module> {
     let ctx = Rc::new(RefCell::new(Ctx::new()));
+    for (space, id) in module_ids(input)?.1 {
+        ctx.borrow_mut().declare_id(space, id);
+    }
+
+    // Type definitions are parsed first, so that type uses in other fields
+    // find every defined type before adding types of their own.
+    let type_ctx = |i| type_def(i, &mut ctx.clone());
+    let types = many0(bws(alt((map(type_ctx, |_| ()), skip))));
+    let _ = preceded(ws, token::pt(preceded(token::module, types)))(input)?;
+
+    let import_ctx = |i| import(i, &mut ctx.clone());
     let func_ctx = |i| func(i, &mut ctx.clone());
+    let table_ctx = |i| table(i, &mut ctx.clone());
+    let memory_ctx = |i| memory(i, &mut ctx.clone());
+    let tag_ctx = |i| tag(i, &mut ctx.clone());
+    let global_ctx = |i| global(i, &mut ctx.clone());
+    let elem_ctx = |i| elem(i, &mut ctx.clone());
+    let data_ctx = |i| data(i, &mut ctx.clone());
     let export_ctx = |i| export(i, &mut ctx.clone());
+    let start_ctx = |i| start(i, &mut ctx.clone());
     let mod_field = bws(many0(bws(alt((
+        list("type"),
+        map(import_ctx, |_| ()),
         map(func_ctx, |_| ()),
+        map(table_ctx, |_| ()),
+        map(memory_ctx, |_| ()),
+        map(tag_ctx, |_| ()),
+        map(global_ctx, |_| ()),
+        map(elem_ctx, |_| ()),
+        map(data_ctx, |_| ()),
         map(export_ctx, |_| ()),
+        map(start_ctx, |_| ()),
     )))));
     let _ = preceded(ws, token::pt(preceded(token::module, mod_field)))(input)?;
 
     let module = Module {
         types: ctx.borrow().types.list.clone(),
+        imports: ctx.borrow().imports.list.clone(),
         funcs: ctx.borrow().funcs.list.clone(),
+        tables: ctx.borrow().tables.list.clone(),
+        memories: ctx.borrow().memories.list.clone(),
+        tags: ctx.borrow().tags.list.clone(),
+        globals: ctx.borrow().globals.list.clone(),
+        elems: ctx.borrow().elems.list.clone(),
+        datas: ctx.borrow().datas.list.clone(),
         exports: ctx.borrow().exports.list.clone(),
+        start: ctx.borrow().start,
     };
 
     Ok(("", module))
@@ -72,7 +590,7 @@ pub fn module(input: &str) -> IResult<&str, Module> {
 mod tests {
     use super::*;
     use crate::ast::Instr::*;
-    use crate::ast::ValueType::I32;
+    use crate::ast::ValueType::{F32, I32, I64};
     use crate::parser::ctx::Field;
 
     #[test]
@@ -94,15 +612,25 @@ mod tests {
             ctx,
             Rc::new(RefCell::new(Ctx {
                 locals: vec![Some("$lhs".to_string()), Some("$rhs".to_string())],
+                labels: vec![],
+                depth: 0,
                 types: Field {
                     ids: vec![None],
                     list: vec![(vec![I32, I32], vec![I32])],
                 },
+                imports: Field::new(),
                 funcs: Field {
-                    ids: vec![Some("$add".to_string())],
+                    ids: vec![],
                     list: vec![expected]
                 },
-                exports: Field::new()
+                tables: Field::new(),
+                memories: Field::new(),
+                tags: Field::new(),
+                globals: Field::new(),
+                elems: Field::new(),
+                datas: Field::new(),
+                exports: Field::new(),
+                start: None
             }))
         )
     }
@@ -128,18 +656,28 @@ mod tests {
             ctx,
             Rc::new(RefCell::new(Ctx {
                 locals: vec![],
+                labels: vec![],
+                depth: 0,
                 types: Field::new(),
+                imports: Field::new(),
                 funcs: Field {
                     ids: vec![Some("$add".to_string())],
                     list: vec![]
                 },
+                tables: Field::new(),
+                memories: Field::new(),
+                tags: Field::new(),
+                globals: Field::new(),
+                elems: Field::new(),
+                datas: Field::new(),
                 exports: Field {
                     ids: vec![Some("add".to_string())],
                     list: vec![Export {
                         name: "add".to_string(),
                         e_desc: EDesc::FuncExport(0)
                     }]
-                }
+                },
+                start: None
             }))
         )
     }
@@ -156,17 +694,836 @@ mod tests {
 
         let expected = Module {
             types: vec![(vec![I32, I32], vec![I32])],
+            imports: vec![],
             funcs: vec![Func {
                 f_type: 0,
                 locals: vec![],
                 body: vec![LocalGet(0), LocalGet(1), I32Add],
             }],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
             exports: vec![Export {
                 name: "add".to_string(),
                 e_desc: FuncExport(0),
             }],
+            start: None,
         };
 
         assert_eq!(module(wat), Ok(("", expected)));
     }
+
+    #[test]
+    fn module_call_parse() {
+        let wat = "(module
+                (func $double (param $x i32) (result i32)
+                  local.get $x
+                  local.get $x
+                  i32.add)
+                (func $quadruple (param $y i32) (result i32)
+                  local.get $y
+                  call $double
+                  call 0)
+            )";
+
+        let expected = Module {
+            types: vec![(vec![I32], vec![I32])],
+            imports: vec![],
+            funcs: vec![
+                Func {
+                    f_type: 0,
+                    locals: vec![],
+                    body: vec![LocalGet(0), LocalGet(0), I32Add],
+                },
+                Func {
+                    f_type: 0,
+                    locals: vec![],
+                    body: vec![LocalGet(0), Call(0), Call(0)],
+                },
+            ],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![],
+            start: None,
+        };
+
+        assert_eq!(module(wat), Ok(("", expected)));
+    }
+
+    #[test]
+    fn module_forward_call_parse() {
+        let wat = "(module
+                (import \"env\" \"log\" (func $log (param i32)))
+                (func $quadruple (param $y i32) (result i32)
+                  local.get $y
+                  call $double
+                  call $double)
+                (func $double (param $x i32) (result i32)
+                  local.get $x
+                  call $log
+                  local.get $x
+                  local.get $x
+                  i32.add)
+                (export \"quadruple\" (func $quadruple))
+            )";
+
+        let (_, module) = module(wat).unwrap();
+        assert_eq!(module.funcs[0].body, vec![LocalGet(0), Call(2), Call(2)]);
+        assert_eq!(module.funcs[1].body[1], Call(0));
+        assert_eq!(module.exports[0].e_desc, FuncExport(1));
+    }
+
+    #[test]
+    fn module_unknown_id_parse() {
+        let call = "(module (func $f call $g))";
+        let local = "(module (func $f local.get $x drop))";
+        let export = "(module (func $f) (export \"g\" (func $g)))";
+
+        assert!(module(call).is_err());
+        assert!(module(local).is_err());
+        assert!(module(export).is_err());
+    }
+
+    #[test]
+    fn func_locals_parse() {
+        let mut ctx = Rc::new(RefCell::new(Ctx::new()));
+        let wat = "(func $f (param $n i32) (result i32)
+              (local $sum i32) (local i64 f32) (local)
+              local.get $n
+              local.tee $sum
+              local.set 3
+              local.get $sum)";
+
+        let expected = Func {
+            f_type: 0,
+            locals: vec![I32, I64, F32],
+            body: vec![LocalGet(0), LocalTee(1), LocalSet(3), LocalGet(1)],
+        };
+
+        assert_eq!(func(wat, &mut ctx), Ok(("", expected)));
+        assert_eq!(
+            ctx.borrow().locals,
+            vec![Some("$n".to_string()), Some("$sum".to_string()), None, None]
+        );
+    }
+
+    #[test]
+    fn module_globals_parse() {
+        let wat = "(module
+                (global $limit i64 (i64.const 10))
+                (global $count (mut i32) i32.const 0)
+                (func $inc (result i32)
+                  global.get $count
+                  i32.const 1
+                  i32.add
+                  global.set $count
+                  global.get 1)
+                (export \"count\" (global $count))
+                (export \"inc\" (func $inc))
+            )";
+
+        let expected = Module {
+            types: vec![(vec![], vec![I32])],
+            imports: vec![],
+            funcs: vec![Func {
+                f_type: 0,
+                locals: vec![],
+                body: vec![
+                    GlobalGet(1),
+                    I32Const(1),
+                    I32Add,
+                    GlobalSet(1),
+                    GlobalGet(1),
+                ],
+            }],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![
+                Global {
+                    g_type: GlobalType {
+                        value_type: I64,
+                        mutable: false,
+                    },
+                    init: vec![I64Const(10)],
+                },
+                Global {
+                    g_type: GlobalType {
+                        value_type: I32,
+                        mutable: true,
+                    },
+                    init: vec![I32Const(0)],
+                },
+            ],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![
+                Export {
+                    name: "count".to_string(),
+                    e_desc: GlobalExport(1),
+                },
+                Export {
+                    name: "inc".to_string(),
+                    e_desc: FuncExport(0),
+                },
+            ],
+            start: None,
+        };
+
+        assert_eq!(module(wat), Ok(("", expected)));
+    }
+
+    #[test]
+    fn module_memory_parse() {
+        let wat = "(module
+                (memory $mem 1 2)
+                (func $load (param i32) (result i32)
+                  local.get 0
+                  i32.load offset=4
+                  memory.size
+                  i32.add)
+            )";
+
+        let (_, module) = module(wat).unwrap();
+
+        assert_eq!(
+            vec![Memory {
+                limits: Limits {
+                    min: 1,
+                    max: Some(2)
+                },
+                shared: false,
+                memory64: false,
+            }],
+            module.memories
+        );
+        assert_eq!(
+            vec![
+                LocalGet(0),
+                I32Load(MemArg {
+                    align: 2,
+                    offset: 4,
+                    memory: 0,
+                }),
+                MemorySize(0),
+                I32Add
+            ],
+            module.funcs[0].body
+        );
+
+        let wat = "(module
+                (memory i64 1)
+                (func $load (result i64)
+                  i64.const 0
+                  i64.load offset=0x1_0000_0000))";
+        let (_, memory64) = super::module(wat).unwrap();
+        assert!(memory64.memories[0].memory64);
+        assert_eq!(
+            I64Load(MemArg {
+                align: 3,
+                offset: 1 << 32,
+                memory: 0,
+            }),
+            memory64.funcs[0].body[1]
+        );
+    }
+
+    #[test]
+    fn module_data_parse() {
+        let wat = r#"(module
+                (memory $mem 1)
+                (data (i32.const 8) "hi" "\00")
+                (data $bytes "\01\02")
+                (data (memory $mem) (offset i32.const 1 i32.const 2 i32.add))
+                (func $init
+                  i32.const 0
+                  i32.const 0
+                  i32.const 2
+                  memory.init $bytes
+                  data.drop $bytes)
+            )"#;
+
+        let (_, module) = module(wat).unwrap();
+
+        assert_eq!(
+            vec![
+                Data {
+                    init: vec![b'h', b'i', 0],
+                    mode: DataMode::Active {
+                        memory: 0,
+                        offset: vec![I32Const(8)]
+                    }
+                },
+                Data {
+                    init: vec![1, 2],
+                    mode: DataMode::Passive
+                },
+                Data {
+                    init: vec![],
+                    mode: DataMode::Active {
+                        memory: 0,
+                        offset: vec![I32Const(1), I32Const(2), I32Add]
+                    }
+                }
+            ],
+            module.datas
+        );
+        assert_eq!(
+            vec![
+                I32Const(0),
+                I32Const(0),
+                I32Const(2),
+                MemoryInit(0, 1),
+                DataDrop(1)
+            ],
+            module.funcs[0].body
+        );
+    }
+
+    #[test]
+    fn module_folded_parse() {
+        let wat = r#"(module
+                (memory 1)
+                (table $a 1 funcref)
+                (table $b 2 funcref)
+                (global $g i32 (i32.add (i32.const 1) (i32.const 2)))
+                (data (offset (i32.const 0)) "a")
+                (data (i32.add (global.get $g) (i32.const 1)) "b")
+                (elem (table $b) (offset (i32.const 0)) func $f)
+                (elem (table $b) (i32.const 1) func $f)
+                (func $f (param i32) (result i32)
+                  (if (result i32) (local.get 0)
+                    (then (call $f (i32.sub (local.get 0) (i32.const 1))))
+                    (else (i32.const 0))))
+            )"#;
+
+        let (_, module) = module(wat).unwrap();
+        assert_eq!(
+            vec![I32Const(1), I32Const(2), I32Add],
+            module.globals[0].init
+        );
+        let offsets: Vec<_> = module.datas.iter().map(|d| &d.mode).collect();
+        assert_eq!(
+            vec![
+                &DataMode::Active {
+                    memory: 0,
+                    offset: vec![I32Const(0)]
+                },
+                &DataMode::Active {
+                    memory: 0,
+                    offset: vec![GlobalGet(0), I32Const(1), I32Add]
+                }
+            ],
+            offsets
+        );
+        assert_eq!(
+            ElemMode::Active {
+                table: 1,
+                offset: vec![I32Const(0)]
+            },
+            module.elems[0].mode
+        );
+        assert_eq!(
+            ElemMode::Active {
+                table: 1,
+                offset: vec![I32Const(1)]
+            },
+            module.elems[1].mode
+        );
+        assert_eq!(
+            vec![
+                LocalGet(0),
+                If(
+                    BlockType::Value(I32),
+                    vec![LocalGet(0), I32Const(1), I32Sub, Call(0)],
+                    vec![I32Const(0)]
+                )
+            ],
+            module.funcs[0].body
+        );
+    }
+
+    #[test]
+    fn module_duplicate_local_parse() {
+        let params = "(module (func $f (param $x i32) (param $x i32)))";
+        let locals = "(module (func $f (param $x i32) (local $x i32)))";
+
+        assert!(module(params).is_err());
+        assert!(module(locals).is_err());
+    }
+
+    #[test]
+    fn module_table_parse() {
+        let wat = "(module
+                (func $f (result i32) i32.const 1)
+                (func $g (result i32) i32.const 2)
+                (table $fns funcref (elem $f $g))
+                (table $t 4 funcref)
+                (elem (table $t) (offset i32.const 1) func $g)
+                (elem (i32.const 0) $f)
+                (elem $later func $f $g)
+                (elem declare func $g)
+                (func $call (param i32) (result i32)
+                  local.get 0
+                  call_indirect $t (result i32))
+                (export \"fns\" (table $fns))
+            )";
+
+        let (_, module) = module(wat).unwrap();
+        let elem = |funcs: Vec<usize>, mode| Elem {
+            ref_type: RefType::FuncRef,
+            init: funcs.into_iter().map(|f| vec![RefFunc(f)]).collect(),
+            mode,
+        };
+        let active = |table, offset| ElemMode::Active {
+            table,
+            offset: vec![I32Const(offset)],
+        };
+
+        assert_eq!(
+            vec![
+                Table {
+                    limits: Limits {
+                        min: 2,
+                        max: Some(2)
+                    },
+                    ref_type: RefType::FuncRef
+                },
+                Table {
+                    limits: Limits { min: 4, max: None },
+                    ref_type: RefType::FuncRef
+                }
+            ],
+            module.tables
+        );
+        assert_eq!(
+            vec![
+                elem(vec![0, 1], active(0, 0)),
+                elem(vec![1], active(1, 1)),
+                elem(vec![0], active(0, 0)),
+                elem(vec![0, 1], ElemMode::Passive),
+                elem(vec![1], ElemMode::Declarative)
+            ],
+            module.elems
+        );
+        assert_eq!(vec![LocalGet(0), CallIndirect(0, 1)], module.funcs[2].body);
+        assert_eq!(TableExport(0), module.exports[0].e_desc);
+    }
+
+    #[test]
+    fn module_table_before_funcs_parse() {
+        let wat = "(module
+                (table funcref (elem $f $g))
+                (func $f (result i32) i32.const 1)
+                (func $g (result i32) i32.const 2)
+            )";
+        let unknown = "(module
+                (table funcref (elem $f $h))
+                (func $f (result i32) i32.const 1)
+            )";
+
+        assert!(module(unknown).is_err());
+        let (_, module) = module(wat).unwrap();
+        assert_eq!(
+            vec![vec![RefFunc(0)], vec![RefFunc(1)]],
+            module.elems[0].init
+        );
+    }
+
+    #[test]
+    fn module_elem_exprs_parse() {
+        let wat = "(module
+                (func $f)
+                (table $t 2 externref)
+                (table funcref (elem (ref.func $f) (item ref.null func)))
+                (elem (table $t) (i32.const 0) externref (ref.null extern))
+                (elem funcref (item (ref.func $f)) (ref.null func))
+                (elem declare funcref (ref.func 0))
+            )";
+
+        let (_, module) = module(wat).unwrap();
+        let elem = |ref_type, init, mode| Elem {
+            ref_type,
+            init,
+            mode,
+        };
+        let active = |table| ElemMode::Active {
+            table,
+            offset: vec![I32Const(0)],
+        };
+        let func = || vec![RefFunc(0)];
+        let null = |ht| vec![RefNull(ht)];
+
+        assert_eq!(
+            vec![
+                elem(
+                    RefType::FuncRef,
+                    vec![func(), null(HeapType::Func)],
+                    active(1)
+                ),
+                elem(RefType::ExternRef, vec![null(HeapType::Extern)], active(0)),
+                elem(
+                    RefType::FuncRef,
+                    vec![func(), null(HeapType::Func)],
+                    ElemMode::Passive
+                ),
+                elem(RefType::FuncRef, vec![func()], ElemMode::Declarative),
+            ],
+            module.elems
+        );
+        assert_eq!(2, module.tables[1].limits.min);
+    }
+
+    #[test]
+    fn module_forward_reference_parse() {
+        let wat = "(module
+                (func $f (param $r (ref $t)) (result i32)
+                  global.get $g
+                  memory.size $m
+                  table.size $tab
+                  elem.drop $e
+                  data.drop $d
+                  local.get $r
+                  call_ref $t
+                  throw $x)
+                (type $t (func (result i32)))
+                (global $g i32 (i32.const 0))
+                (memory 1)
+                (memory $m 1)
+                (table 1 funcref)
+                (table $tab 1 funcref)
+                (tag $x)
+                (elem $e func $f)
+                (data $d \"a\")
+            )";
+
+        let (_, module) = module(wat).unwrap();
+        assert_eq!(
+            vec![
+                GlobalGet(0),
+                MemorySize(1),
+                TableSize(1),
+                ElemDrop(0),
+                DataDrop(0),
+                LocalGet(0),
+                CallRef(0),
+                Throw(0)
+            ],
+            module.funcs[0].body
+        );
+        assert_eq!((vec![], vec![I32]), module.types[0]);
+    }
+
+    #[test]
+    fn module_forward_reference_after_inline_parse() {
+        let wat = "(module
+                (table funcref (elem $f))
+                (func $f (result i32) i32.const 1)
+                (func $g (result i64) i64.const 1)
+                (type $u (func (result i64)))
+                (elem $e func $g)
+                (func $h elem.drop $e global.get $y)
+                (global $x i32 (i32.const 0))
+                (global $y i32 (i32.const 1))
+            )";
+
+        let (_, module) = module(wat).unwrap();
+        assert_eq!(vec![ElemDrop(1), GlobalGet(1)], module.funcs[2].body);
+        assert_eq!(0, module.funcs[1].f_type);
+        assert_eq!((vec![], vec![I64]), module.types[0]);
+    }
+
+    #[test]
+    fn module_import_parse() {
+        let wat = r#"(module
+                (import "env" "log" (func $log (param $x i32)))
+                (import "env" "table" (table $t 1 funcref))
+                (import "env" "mem" (memory $mem 1 2))
+                (import "env" "base" (global $base i32))
+                (global $next (mut i32) (global.get $base))
+                (func $main (param i32)
+                  local.get 0
+                  call $log
+                  global.get $next
+                  call $log)
+                (export "main" (func $main))
+            )"#;
+
+        let (_, module) = module(wat).unwrap();
+        let import = |name: &str, desc| Import {
+            module: "env".to_string(),
+            name: name.to_string(),
+            desc,
+        };
+
+        assert_eq!(
+            vec![
+                import("log", ImportDesc::Func(0)),
+                import(
+                    "table",
+                    ImportDesc::Table(Table {
+                        limits: Limits { min: 1, max: None },
+                        ref_type: RefType::FuncRef
+                    })
+                ),
+                import(
+                    "mem",
+                    ImportDesc::Memory(Memory {
+                        limits: Limits {
+                            min: 1,
+                            max: Some(2)
+                        },
+                        shared: false,
+                        memory64: false,
+                    })
+                ),
+                import(
+                    "base",
+                    ImportDesc::Global(GlobalType {
+                        value_type: I32,
+                        mutable: false
+                    })
+                )
+            ],
+            module.imports
+        );
+        assert_eq!(vec![GlobalGet(0)], module.globals[0].init);
+        assert_eq!(
+            vec![LocalGet(0), Call(0), GlobalGet(1), Call(0)],
+            module.funcs[0].body
+        );
+        assert_eq!(FuncExport(1), module.exports[0].e_desc);
+    }
+
+    #[test]
+    fn module_inline_import_parse() {
+        let wat = r#"(module
+                (func $log (import "env" "log") (param i32))
+                (global $base (export "base") (import "env" "base") i32)
+                (memory $mem (import "env" "mem") 1)
+                (table (import "env" "table") 1 funcref)
+                (func $f (export "f") (export "g") (import "env" "f"))
+                (func $main call $f call $log)
+            )"#;
+
+        let (_, module) = module(wat).unwrap();
+        let names: Vec<_> = module.imports.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(vec!["log", "base", "mem", "table", "f"], names);
+        assert_eq!(
+            ImportDesc::Global(GlobalType {
+                value_type: I32,
+                mutable: false
+            }),
+            module.imports[1].desc
+        );
+        assert_eq!(vec![Call(1), Call(0)], module.funcs[0].body);
+        let exports: Vec<_> = module.exports.iter().map(|e| &e.e_desc).collect();
+        assert_eq!(
+            vec![&GlobalExport(0), &FuncExport(1), &FuncExport(1)],
+            exports
+        );
+    }
+
+    #[test]
+    fn module_inline_export_parse() {
+        let wat = r#"(module
+                (func $f (export "f") (result i32) i32.const 1)
+                (func)
+                (global (export "g") (mut i32) (i32.const 0))
+                (memory $m (export "mem") 1)
+                (table (export "t") (export "u") 1 funcref)
+                (tag (export "e") (param i32))
+                (func (export "h"))
+            )"#;
+
+        let (_, module) = module(wat).unwrap();
+        let export = |name: &str, e_desc| Export {
+            name: name.to_string(),
+            e_desc,
+        };
+        assert_eq!(
+            vec![
+                export("f", FuncExport(0)),
+                export("g", GlobalExport(0)),
+                export("mem", MemoryExport(0)),
+                export("t", TableExport(0)),
+                export("u", TableExport(0)),
+                export("e", TagExport(0)),
+                export("h", FuncExport(2)),
+            ],
+            module.exports
+        );
+        assert_eq!(3, module.funcs.len());
+        assert_eq!(vec![I32Const(1)], module.funcs[0].body);
+    }
+
+    #[test]
+    fn module_inline_data_parse() {
+        let wat = r#"(module
+                (memory $m (export "mem") (data "ab" "c"))
+                (memory $n (data))
+                (data $d "x")
+                (func $f data.drop $d)
+            )"#;
+
+        let (_, module) = module(wat).unwrap();
+        let memory = |pages| Memory {
+            limits: Limits {
+                min: pages,
+                max: Some(pages),
+            },
+            shared: false,
+            memory64: false,
+        };
+        assert_eq!(vec![memory(1), memory(0)], module.memories);
+        assert_eq!(b"abc".to_vec(), module.datas[0].init);
+        assert_eq!(
+            DataMode::Active {
+                memory: 1,
+                offset: vec![I32Const(0)]
+            },
+            module.datas[1].mode
+        );
+        assert_eq!(vec![DataDrop(2)], module.funcs[0].body);
+    }
+
+    #[test]
+    fn module_start_parse() {
+        let wat = "(module
+                (global $g (mut i32) (i32.const 0))
+                (func $helper)
+                (func $init i32.const 1 global.set $g)
+                (start $init)
+            )";
+
+        let (_, module) = module(wat).unwrap();
+        assert_eq!(Some(1), module.start);
+    }
+
+    #[test]
+    fn module_reference_parse() {
+        let wat = "(module
+                (func $f)
+                (table $funcs 1 funcref)
+                (table $objects 2 externref)
+                (global $g funcref (ref.func $f))
+                (elem $e func $f)
+                (func $keep (param $x externref)
+                  i32.const 0
+                  local.get $x
+                  table.set $objects
+                  i32.const 0
+                  i32.const 0
+                  i32.const 1
+                  table.init $funcs $e
+                  elem.drop $e)
+            )";
+
+        let (_, module) = module(wat).unwrap();
+        assert_eq!(RefType::ExternRef, module.tables[1].ref_type);
+        assert_eq!(ValueType::FuncRef, module.globals[0].g_type.value_type);
+        assert_eq!(vec![RefFunc(0)], module.globals[0].init);
+        assert_eq!(vec![ValueType::ExternRef], module.types[1].0);
+        assert_eq!(
+            vec![
+                I32Const(0),
+                LocalGet(0),
+                TableSet(1),
+                I32Const(0),
+                I32Const(0),
+                I32Const(1),
+                TableInit(0, 0),
+                ElemDrop(0)
+            ],
+            module.funcs[1].body
+        );
+    }
+
+    #[test]
+    fn module_func_ref_parse() {
+        let wat = "(module
+                (type $unary (func (param i32) (result i32)))
+                (func $inc (param $x i32) (result i32)
+                  local.get $x
+                  i32.const 1
+                  i32.add)
+                (global $g (ref $unary) (ref.func $inc))
+                (func $apply (param $f (ref null $unary)) (param $x i32) (result i32)
+                  (local $g (ref $unary))
+                  block $null
+                    local.get $f
+                    br_on_null $null
+                    local.set $g
+                    local.get $x
+                    local.get $g
+                    call_ref $unary
+                    return
+                  end
+                  unreachable)
+            )";
+
+        let (_, module) = module(wat).unwrap();
+        let unary = ValueType::Ref {
+            nullable: false,
+            type_idx: 0,
+        };
+        assert_eq!((vec![I32], vec![I32]), module.types[0]);
+        assert_eq!(unary, module.globals[0].g_type.value_type);
+        assert_eq!(vec![unary], module.funcs[1].locals);
+        assert_eq!(
+            vec![
+                ValueType::Ref {
+                    nullable: true,
+                    type_idx: 0
+                },
+                I32
+            ],
+            module.types[module.funcs[1].f_type as usize].0
+        );
+        assert_eq!(
+            vec![
+                LocalGet(0),
+                BrOnNull(0),
+                LocalSet(2),
+                LocalGet(1),
+                LocalGet(2),
+                CallRef(0),
+                Return
+            ],
+            match &module.funcs[1].body[0] {
+                Block(_, body) => body.clone(),
+                _ => unreachable!(),
+            }
+        );
+    }
+    #[test]
+    fn module_type_use_parse() {
+        let wat = "(module
+                (type $p (func (param i32) (result i32)))
+                (func $double (type $p)
+                  local.get 0
+                  block (type $p)
+                    i32.const 2
+                    i32.mul
+                  end)
+            )";
+
+        let (_, module) = module(wat).unwrap();
+        assert_eq!(1, module.types.len());
+        assert_eq!(0, module.funcs[0].f_type);
+        assert_eq!(
+            vec![
+                LocalGet(0),
+                Block(BlockType::TypeIdx(0), vec![I32Const(2), I32Mul])
+            ],
+            module.funcs[0].body
+        );
+    }
 }
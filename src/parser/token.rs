@@ -1,7 +1,7 @@
 use nom::error::ParseError;
 use nom::{
-    bytes::complete::*, character::complete::*, sequence::delimited, AsChar, IResult, InputIter,
-    InputTakeAtPosition, Parser, Slice,
+    bytes::complete::*, character::complete::*, combinator::not, sequence::delimited,
+    sequence::terminated, AsChar, IResult, InputIter, InputTakeAtPosition, Parser, Slice,
 };
 use std::ops::RangeFrom;
 
@@ -23,6 +23,13 @@ where
     delimited(multispace0, inner, multispace0)
 }
 
+/// Matches the keyword `kw` unless it is only the prefix of a longer keyword,
+/// e.g. `br` in `br_if`.
+pub fn keyword<'a>(kw: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    let id_char = |c: char| c.is_ascii_alphanumeric() || "!#$%&′∗+−./:<=>?@∖^_`|~".contains(c);
+    bws(terminated(tag(kw), not(satisfy(id_char))))
+}
+
 pub fn ws(input: &str) -> IResult<&str, &str> {
     multispace0(input)
 }
@@ -35,10 +42,46 @@ pub fn param(input: &str) -> IResult<&str, &str> {
     bws(tag("param"))(input)
 }
 
+pub fn table(input: &str) -> IResult<&str, &str> {
+    bws(tag("table"))(input)
+}
+
+pub fn elem(input: &str) -> IResult<&str, &str> {
+    bws(tag("elem"))(input)
+}
+
+pub fn memory(input: &str) -> IResult<&str, &str> {
+    bws(tag("memory"))(input)
+}
+
+pub fn global(input: &str) -> IResult<&str, &str> {
+    bws(tag("global"))(input)
+}
+
+pub fn data(input: &str) -> IResult<&str, &str> {
+    bws(tag("data"))(input)
+}
+
+pub fn offset(input: &str) -> IResult<&str, &str> {
+    bws(tag("offset"))(input)
+}
+
+pub fn local(input: &str) -> IResult<&str, &str> {
+    bws(tag("local"))(input)
+}
+
 pub fn result(input: &str) -> IResult<&str, &str> {
     bws(tag("result"))(input)
 }
 
+pub fn start(input: &str) -> IResult<&str, &str> {
+    bws(tag("start"))(input)
+}
+
+pub fn import(input: &str) -> IResult<&str, &str> {
+    bws(tag("import"))(input)
+}
+
 pub fn export(input: &str) -> IResult<&str, &str> {
     bws(tag("export"))(input)
 }
@@ -64,6 +107,14 @@ mod tests {
         assert!(func("notfunc").is_err());
     }
 
+    #[test]
+    fn keyword_parse() {
+        assert_eq!(keyword("br")(" br 0"), Ok(("0", "br")));
+        assert_eq!(keyword("br")("br)"), Ok((")", "br")));
+        assert!(keyword("br")("br_if 0").is_err());
+        assert!(keyword("end")("end$label").is_err());
+    }
+
     #[test]
     fn ws_parse() {
         assert_eq!(ws("  foo"), Ok(("foo", "  ")));
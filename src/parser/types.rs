@@ -1,11 +1,12 @@
 use crate::ast::ValueType::*;
-use crate::ast::{FuncType, ValueType};
+use crate::ast::{BlockType, FuncType, GlobalType, Limits, Memory, RefType, ValueType};
 use crate::parser::ctx::Ctx;
-use crate::parser::token::{bws, ws};
+use crate::parser::token::{bws, keyword, ws};
 use crate::parser::*;
 use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::combinator::{map, opt, value};
+use nom::combinator::{map, map_opt, opt, value};
+use nom::error::{Error, ErrorKind};
 use nom::multi::many0;
 use nom::sequence::{preceded, tuple};
 use nom::IResult;
@@ -19,21 +20,27 @@ pub fn func_type<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a
         P(ValueType, Option<String>),
     }
 
-    let p = map(
-        preceded(
-            ws,
-            token::pt(tuple((token::param, opt(values::id), types::value_type))),
-        ),
-        |p| PR::P(p.2, p.1.map(|id| id.to_string())),
+    // A named parameter declares a single value, an anonymous one several.
+    let value_type = |i| value_type(i, ctx);
+    let named = map(tuple((values::id, value_type)), |(id, vt)| {
+        vec![PR::P(vt, Some(id.to_string()))]
+    });
+    let anonymous = map(many0(value_type), |vts| {
+        vts.into_iter().map(|vt| PR::P(vt, None)).collect()
+    });
+    let p = preceded(
+        ws,
+        token::pt(preceded(token::param, alt((named, anonymous)))),
     );
 
     let r = map(
-        preceded(ws, token::pt(preceded(token::result, types::value_type))),
-        PR::R,
+        preceded(ws, token::pt(preceded(token::result, many0(value_type)))),
+        |vts| vts.into_iter().map(PR::R).collect(),
     );
 
     let t = alt((p, r));
     let (input, many_t) = many0(t)(input)?;
+    let many_t: Vec<PR> = many_t.concat();
 
     let results = many_t
         .iter()
@@ -43,31 +50,178 @@ pub fn func_type<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a
         })
         .collect::<Vec<ValueType>>();
 
-    let params = many_t
-        .iter()
-        .filter_map(|t| match t {
-            PR::R(_) => None,
-            PR::P(p, id) => {
-                ctx.borrow_mut().insert_local_id(id);
-                Some(*p)
+    let mut params = vec![];
+    for t in &many_t {
+        if let PR::P(p, id) = t {
+            if ctx.borrow_mut().insert_local_id(id).is_none() {
+                return Err(nom::Err::Error(Error::new(input, ErrorKind::Verify)));
             }
-        })
-        .collect::<Vec<ValueType>>();
+            params.push(*p);
+        }
+    }
 
     let ft = (params, results);
     Ok((input, ft))
 }
 
-pub fn value_type(input: &str) -> IResult<&str, ValueType> {
-    let types = alt((value(I32, tag("i32")), value(I64, tag("i64"))));
+/// Parses a value type. References to a function type are written as
+/// `(ref null? $t)`, where `(ref null func)` is the same as `funcref`.
+pub fn value_type<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, ValueType> {
+    let types = alt((
+        value(I32, tag("i32")),
+        value(I64, tag("i64")),
+        value(F32, tag("f32")),
+        value(F64, tag("f64")),
+        value(V128, tag("v128")),
+        value(FuncRef, tag("funcref")),
+        value(ExternRef, tag("externref")),
+        value(ExnRef, tag("exnref")),
+        |i| reference(i, ctx),
+    ));
+    bws(types)(input)
+}
+
+fn reference<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, ValueType> {
+    let abstract_type = alt((
+        value(FuncRef, keyword("func")),
+        value(ExternRef, keyword("extern")),
+        value(ExnRef, keyword("exn")),
+    ));
+    let concrete = |nullable| {
+        map_opt(bws(index), move |idx| {
+            let type_idx = ctx.borrow().get_type_idx(&idx)?;
+            Some(Ref { nullable, type_idx })
+        })
+    };
+    let nullable = preceded(keyword("null"), alt((abstract_type, concrete(true))));
+    token::pt(preceded(keyword("ref"), alt((nullable, concrete(false)))))(input)
+}
+
+pub fn ref_type(input: &str) -> IResult<&str, RefType> {
+    let types = alt((
+        value(RefType::FuncRef, tag("funcref")),
+        value(RefType::ExternRef, tag("externref")),
+        value(RefType::ExnRef, tag("exnref")),
+    ));
     bws(types)(input)
 }
 
+pub fn limits(input: &str) -> IResult<&str, Limits> {
+    let (input, min) = bws(values::u64)(input)?;
+    let (input, max) = opt(bws(values::u64))(input)?;
+    Ok((input, Limits { min, max }))
+}
+
+/// Parses the limits of a memory, which are preceded by its address type
+/// `i32` or `i64` and followed by `shared` if the memory is shared.
+pub fn memory_type(input: &str) -> IResult<&str, Memory> {
+    let address_type = alt((value(false, keyword("i32")), value(true, keyword("i64"))));
+    let (input, memory64) = opt(address_type)(input)?;
+    let (input, limits) = limits(input)?;
+    let (input, shared) = opt(keyword("shared"))(input)?;
+    let memory = Memory {
+        limits,
+        shared: shared.is_some(),
+        memory64: memory64.unwrap_or(false),
+    };
+    Ok((input, memory))
+}
+
+pub fn global_type<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, GlobalType> {
+    let value_type = |i| value_type(i, ctx);
+    let var = map(
+        bws(token::pt(preceded(bws(tag("mut")), value_type))),
+        |vt| GlobalType {
+            value_type: vt,
+            mutable: true,
+        },
+    );
+    let constant = map(value_type, |vt| GlobalType {
+        value_type: vt,
+        mutable: false,
+    });
+    alt((var, constant))(input)
+}
+
+/// Parses the signature of a block. Blocks with parameters or several
+/// results refer to a function type.
+pub fn block_type<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, BlockType> {
+    let (input, type_idx) = opt(|i| type_ref(i, ctx))(input)?;
+    let (rest, ft) = anonymous_func_type(input, ctx)?;
+    let bt = match (type_idx, ft.0.as_slice(), ft.1.as_slice()) {
+        (Some(_), _, _) => BlockType::TypeIdx(resolve_type_use(input, ctx, type_idx, &ft)?),
+        (None, [], []) => BlockType::Empty,
+        (None, [], [vt]) => BlockType::Value(*vt),
+        (None, _, _) => BlockType::TypeIdx(ctx.borrow_mut().upsert_func_type(&ft)),
+    };
+    Ok((rest, bt))
+}
+
+/// Parses the type of a function, whose parameters may have ids. A type
+/// use without inline parameters declares the referenced type's
+/// parameters as locals without ids.
 pub fn type_use<'a>(input: &'a str, ctx: &mut Rc<RefCell<Ctx>>) -> IResult<&'a str, usize> {
-    let mut ft = |i| func_type(i, ctx);
-    let (input, ft) = ft(input)?;
-    let index = ctx.borrow_mut().upsert_func_type(&ft);
-    Ok((input, index))
+    let (input, type_idx) = opt(|i| type_ref(i, ctx))(input)?;
+    let (rest, ft) = func_type(input, ctx)?;
+    if let (Some(type_idx), ([], [])) = (type_idx, (ft.0.as_slice(), ft.1.as_slice())) {
+        let params = ctx
+            .borrow()
+            .types
+            .list
+            .get(type_idx)
+            .map_or(0, |t| t.0.len());
+        for _ in 0..params {
+            ctx.borrow_mut().insert_local_id(&None);
+        }
+    }
+    let index = resolve_type_use(input, ctx, type_idx, &ft)?;
+    Ok((rest, index))
+}
+
+/// Parses the signature of `call_indirect`, whose parameters have no ids,
+/// and returns the index of its type.
+pub fn inline_type_use<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, usize> {
+    let (input, type_idx) = opt(|i| type_ref(i, ctx))(input)?;
+    let (rest, ft) = anonymous_func_type(input, ctx)?;
+    let index = resolve_type_use(input, ctx, type_idx, &ft)?;
+    Ok((rest, index))
+}
+
+/// Parses `(type idx)`, which refers to a function type.
+fn type_ref<'a>(input: &'a str, ctx: &Rc<RefCell<Ctx>>) -> IResult<&'a str, usize> {
+    let type_idx = map_opt(bws(index), |t| ctx.borrow().get_type_idx(&t));
+    bws(token::pt(preceded(keyword("type"), type_idx)))(input)
+}
+
+/// Returns the index of the type `ft` parsed from `input`, which follows
+/// the reference to `type_idx` if there is one. The inline parameters and
+/// results then have to be the referenced type's, if there are any.
+/// Otherwise, `ft` is added to the types unless it is there already.
+fn resolve_type_use<'a>(
+    input: &'a str,
+    ctx: &Rc<RefCell<Ctx>>,
+    type_idx: Option<usize>,
+    ft: &FuncType,
+) -> Result<usize, nom::Err<Error<&'a str>>> {
+    let ctx = &mut ctx.borrow_mut();
+    match type_idx {
+        None => Ok(ctx.upsert_func_type(ft)),
+        Some(type_idx) if ft.0.is_empty() && ft.1.is_empty() => Ok(type_idx),
+        Some(type_idx) if ctx.types.list.get(type_idx) == Some(ft) => Ok(type_idx),
+        Some(_) => Err(nom::Err::Error(Error::new(input, ErrorKind::Verify))),
+    }
+}
+
+/// Parses `(param vt*)* (result vt*)*` without ids.
+pub fn anonymous_func_type<'a>(
+    input: &'a str,
+    ctx: &Rc<RefCell<Ctx>>,
+) -> IResult<&'a str, FuncType> {
+    let value_type = |i| value_type(i, ctx);
+    let params = many0(bws(token::pt(preceded(token::param, many0(value_type)))));
+    let results = many0(bws(token::pt(preceded(token::result, many0(value_type)))));
+    let (input, (params, results)) = tuple((params, results))(input)?;
+    Ok((input, (params.concat(), results.concat())))
 }
 
 pub enum Index {
@@ -170,10 +324,199 @@ mod tests {
         );
     }
 
+    #[test]
+    fn block_type_parse() {
+        let ctx = Rc::new(RefCell::new(Ctx::new()));
+        assert_eq!(
+            block_type(" (result i64) ", &ctx),
+            Ok(("", BlockType::Value(I64)))
+        );
+        assert_eq!(
+            block_type("i32.const 1", &ctx),
+            Ok(("i32.const 1", BlockType::Empty))
+        );
+        assert_eq!(
+            block_type("(param i32) (result i32)", &ctx),
+            Ok(("", BlockType::TypeIdx(0)))
+        );
+        assert_eq!(
+            block_type("(result i32 i64) (result f32)", &ctx),
+            Ok(("", BlockType::TypeIdx(1)))
+        );
+        assert_eq!(
+            block_type("(param i32) (result i32)", &ctx),
+            Ok(("", BlockType::TypeIdx(0)))
+        );
+        assert_eq!(vec![I32, I64, F32], ctx.borrow().types.list[1].1);
+
+        // A reference to a type can repeat its parameters and results.
+        ctx.borrow_mut()
+            .insert_id_func_type(Some("$p".to_string()), &(vec![I32], vec![]));
+        assert_eq!(
+            block_type("(type $p) i32.const 1", &ctx),
+            Ok(("i32.const 1", BlockType::TypeIdx(2)))
+        );
+        assert_eq!(
+            block_type("(type 0) (param i32) (result i32)", &ctx),
+            Ok(("", BlockType::TypeIdx(0)))
+        );
+        assert!(block_type("(type $p) (result i32)", &ctx).is_err());
+    }
+
+    #[test]
+    fn type_use_parse() {
+        let mut ctx = Rc::new(RefCell::new(Ctx::new()));
+        let ft = (vec![I32, I64], vec![I32]);
+        ctx.borrow_mut()
+            .insert_id_func_type(Some("$t".to_string()), &ft);
+        assert_eq!(
+            type_use("(type $t) i32.const 0", &mut ctx),
+            Ok(("i32.const 0", 0))
+        );
+        // The parameters of the referenced type have no ids.
+        assert_eq!(vec![None, None], ctx.borrow().locals);
+
+        ctx.borrow_mut().locals.clear();
+        assert_eq!(
+            type_use("(type 0) (param $x i32) (param i64) (result i32)", &mut ctx),
+            Ok(("", 0))
+        );
+        assert_eq!(vec![Some("$x".to_string()), None], ctx.borrow().locals);
+        assert!(type_use("(type 0) (param i32)", &mut ctx).is_err());
+    }
+
+    #[test]
+    fn func_type_multi_value_parse() {
+        let mut ctx = Rc::new(RefCell::new(Ctx::new()));
+        assert_eq!(
+            func_type("(param i32 i64) (param $x f32) (result f64 i32)", &mut ctx),
+            Ok(("", (vec![I32, I64, F32], vec![F64, I32])))
+        );
+        assert_eq!(
+            vec![None, None, Some("$x".to_string())],
+            ctx.borrow().locals
+        );
+    }
+
     #[test]
     fn value_type_parse() {
+        let ctx = Rc::new(RefCell::new(Ctx::new()));
+        let value_type = |i| value_type(i, &ctx);
         assert_eq!(value_type("i32"), Ok(("", I32)));
         assert_eq!(value_type("i64"), Ok(("", I64)));
+        assert_eq!(value_type("f32"), Ok(("", F32)));
+        assert_eq!(value_type("f64"), Ok(("", F64)));
+        assert_eq!(value_type("v128"), Ok(("", V128)));
+        assert_eq!(value_type("funcref"), Ok(("", FuncRef)));
+        assert_eq!(value_type("externref"), Ok(("", ExternRef)));
         assert!(value_type("x32").is_err());
+        assert_eq!(ref_type(" externref "), Ok(("", RefType::ExternRef)));
+        assert_eq!(ref_type("exnref"), Ok(("", RefType::ExnRef)));
+        assert!(ref_type("i32").is_err());
+    }
+
+    #[test]
+    fn ref_value_type_parse() {
+        let ctx = Rc::new(RefCell::new(Ctx::new()));
+        ctx.borrow_mut()
+            .insert_id_func_type(None, &(vec![], vec![]));
+        ctx.borrow_mut()
+            .insert_id_func_type(Some("$t".to_string()), &(vec![I32], vec![]));
+        let value_type = |i| value_type(i, &ctx);
+        assert_eq!(
+            value_type("(ref $t)"),
+            Ok((
+                "",
+                Ref {
+                    nullable: false,
+                    type_idx: 1
+                }
+            ))
+        );
+        assert_eq!(
+            value_type(" ( ref null 0 ) "),
+            Ok((
+                "",
+                Ref {
+                    nullable: true,
+                    type_idx: 0
+                }
+            ))
+        );
+        assert_eq!(value_type("(ref null func)"), Ok(("", FuncRef)));
+        assert_eq!(value_type("(ref null extern)"), Ok(("", ExternRef)));
+        assert!(value_type("(ref func)").is_err());
+    }
+
+    #[test]
+    fn global_type_parse() {
+        let ctx = Rc::new(RefCell::new(Ctx::new()));
+        let global_type = |i| global_type(i, &ctx);
+        assert_eq!(
+            global_type("f32"),
+            Ok((
+                "",
+                GlobalType {
+                    value_type: F32,
+                    mutable: false
+                }
+            ))
+        );
+        assert_eq!(
+            global_type("( mut i64 ) "),
+            Ok((
+                "",
+                GlobalType {
+                    value_type: I64,
+                    mutable: true
+                }
+            ))
+        );
+        assert!(global_type("(mut)").is_err());
+    }
+
+    #[test]
+    fn limits_parse() {
+        assert_eq!(limits("1"), Ok(("", Limits { min: 1, max: None })));
+        assert_eq!(
+            limits(" 0 0x10 "),
+            Ok((
+                "",
+                Limits {
+                    min: 0,
+                    max: Some(16)
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn memory_type_parse() {
+        let memory = |max, shared| Memory {
+            limits: Limits { min: 1, max },
+            shared,
+            memory64: false,
+        };
+        assert_eq!(memory_type("1"), Ok(("", memory(None, false))));
+        assert_eq!(memory_type(" 1 2 shared "), Ok(("", memory(Some(2), true))));
+        assert_eq!(
+            memory_type("1 shared_x"),
+            Ok(("shared_x", memory(None, false)))
+        );
+        assert_eq!(memory_type("i32 1"), Ok(("", memory(None, false))));
+        assert_eq!(
+            memory_type("i64 0x1_0000_0000"),
+            Ok((
+                "",
+                Memory {
+                    limits: Limits {
+                        min: 1 << 32,
+                        max: None
+                    },
+                    shared: false,
+                    memory64: true,
+                }
+            ))
+        );
     }
 }
@@ -6,6 +6,7 @@ use nom::combinator::*;
 use nom::multi::*;
 use nom::sequence::*;
 use nom::IResult;
+use std::convert::TryFrom;
 
 pub fn id(input: &str) -> IResult<&str, &str> {
     let additional_chars = "!#$%&′∗+−./:<=>?@∖^_`|~";
@@ -15,18 +16,273 @@ pub fn id(input: &str) -> IResult<&str, &str> {
 }
 
 pub fn u32(input: &str) -> IResult<&str, u32> {
-    map(digit1, |d: &str| {
-        d.parse().expect("Integer format not supported")
-    })(input)
+    map_opt(unsigned, |u| u32::try_from(u).ok())(input)
 }
 
-pub fn literal(input: &str) -> IResult<&str, String> {
+pub fn u64(input: &str) -> IResult<&str, u64> {
+    map_opt(unsigned, |u| u64::try_from(u).ok())(input)
+}
+
+pub fn u8(input: &str) -> IResult<&str, u8> {
+    map_opt(unsigned, |u| u8::try_from(u).ok())(input)
+}
+
+fn digits<'a>(
+    digit: fn(&'a str) -> IResult<&'a str, &'a str>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    recognize(pair(digit, many0(pair(opt(char('_')), digit))))
+}
+
+/// Parses an integer literal without sign in decimal or `0x` hexadecimal
+/// notation, with optional `_` separators between digits.
+fn unsigned(input: &str) -> IResult<&str, u128> {
+    let hex = map_opt(preceded(tag("0x"), digits(hex_digit1)), |d: &str| {
+        u128::from_str_radix(&d.replace('_', ""), 16).ok()
+    });
+    let dec = map_opt(digits(digit1), |d: &str| {
+        d.replace('_', "").parse::<u128>().ok()
+    });
+    alt((hex, dec))(input)
+}
+
+/// Parses a signed or unsigned integer literal.
+fn integer(input: &str) -> IResult<&str, i128> {
+    let (input, sign) = opt(one_of("+-"))(input)?;
+    let (input, magnitude) = map_opt(unsigned, |m| i128::try_from(m).ok())(input)?;
+
+    match sign {
+        Some('-') => Ok((input, -magnitude)),
+        _ => Ok((input, magnitude)),
+    }
+}
+
+/// Integer literals of `i8` may be given in the signed or the unsigned range.
+pub fn i8(input: &str) -> IResult<&str, i8> {
+    let in_range = |i: &i128| (i8::MIN as i128..=u8::MAX as i128).contains(i);
+    map(verify(integer, in_range), |i| i as i8)(input)
+}
+
+/// Integer literals of `i16` may be given in the signed or the unsigned range.
+pub fn i16(input: &str) -> IResult<&str, i16> {
+    let in_range = |i: &i128| (i16::MIN as i128..=u16::MAX as i128).contains(i);
+    map(verify(integer, in_range), |i| i as i16)(input)
+}
+
+/// Integer literals of `i32` may be given in the signed or the unsigned range.
+pub fn i32(input: &str) -> IResult<&str, i32> {
+    let in_range = |i: &i128| (i32::MIN as i128..=u32::MAX as i128).contains(i);
+    map(verify(integer, in_range), |i| i as i32)(input)
+}
+
+/// Integer literals of `i64` may be given in the signed or the unsigned range.
+pub fn i64(input: &str) -> IResult<&str, i64> {
+    let in_range = |i: &i128| (i64::MIN as i128..=u64::MAX as i128).contains(i);
+    map(verify(integer, in_range), |i| i as i64)(input)
+}
+
+/// Bit layout of an IEEE-754 binary floating-point format.
+struct FloatFormat {
+    mantissa_bits: u32,
+    exponent_bits: u32,
+    decimal: fn(&str) -> Option<u64>,
+}
+
+const F32_FORMAT: FloatFormat = FloatFormat {
+    mantissa_bits: 23,
+    exponent_bits: 8,
+    decimal: |d| d.parse::<f32>().ok().map(|f| f.to_bits() as u64),
+};
+
+const F64_FORMAT: FloatFormat = FloatFormat {
+    mantissa_bits: 52,
+    exponent_bits: 11,
+    decimal: |d| d.parse::<f64>().ok().map(f64::to_bits),
+};
+
+impl FloatFormat {
+    fn infinity(&self) -> u64 {
+        ((1 << self.exponent_bits) - 1) << self.mantissa_bits
+    }
+
+    /// Rounds `mantissa * 2^exponent` to the nearest representable value, ties
+    /// to even. `sticky` marks non-zero digits that did not fit into
+    /// `mantissa`. Returns `None` if the value is too large for the format.
+    fn round(&self, mantissa: u128, exponent: i64, sticky: bool) -> Option<u64> {
+        if mantissa == 0 {
+            return Some(0);
+        }
+
+        let precision = self.mantissa_bits as i64 + 1;
+        let bias = (1i64 << (self.exponent_bits - 1)) - 1;
+        let min_exponent = 1 - bias;
+
+        let top_exponent = exponent + 127 - mantissa.leading_zeros() as i64;
+        if top_exponent > bias {
+            return None;
+        }
+
+        let mut lsb_exponent = top_exponent.max(min_exponent) - (precision - 1);
+        let shift = lsb_exponent - exponent;
+
+        let mut bits = if shift <= 0 {
+            (mantissa << -shift) as u64
+        } else if shift > 128 {
+            0
+        } else {
+            let (kept, rest) = match shift {
+                128 => (0, mantissa),
+                _ => (mantissa >> shift, mantissa & ((1 << shift) - 1)),
+            };
+            let half = 1u128 << (shift - 1);
+            let round_up = rest > half || (rest == half && (sticky || kept & 1 == 1));
+            kept as u64 + round_up as u64
+        };
+
+        if bits == 1 << precision {
+            bits >>= 1;
+            lsb_exponent += 1;
+        }
+
+        if bits >> (precision - 1) == 0 {
+            return Some(bits);
+        }
+
+        let biased = lsb_exponent + precision - 1 + bias;
+        if biased >= (1 << self.exponent_bits) - 1 {
+            None
+        } else {
+            let mantissa_mask = (1 << self.mantissa_bits) - 1;
+            Some((biased as u64) << self.mantissa_bits | (bits & mantissa_mask))
+        }
+    }
+}
+
+/// Parses a float literal into the bit pattern of the given format.
+fn float<'a>(input: &'a str, format: &FloatFormat) -> IResult<&'a str, u64> {
+    let nan_payload = map_opt(digits(hex_digit1), |p: &str| {
+        let payload = u64::from_str_radix(&p.replace('_', ""), 16).ok()?;
+        let valid = payload != 0 && payload >> format.mantissa_bits == 0;
+        valid.then(|| format.infinity() | payload)
+    });
+    let nan_payload = preceded(tag("nan:0x"), cut(nan_payload));
+    let nan = value(
+        format.infinity() | 1 << (format.mantissa_bits - 1),
+        tag("nan"),
+    );
+    let inf = value(format.infinity(), tag("inf"));
+
+    // A malformed hexadecimal float must not fall back to the decimal `0`.
+    let hex_body = map_opt(
+        tuple((
+            digits(hex_digit1),
+            opt(preceded(char('.'), opt(digits(hex_digit1)))),
+            opt(preceded(
+                one_of("pP"),
+                pair(opt(one_of("+-")), digits(digit1)),
+            )),
+        )),
+        |(int, frac, exp): (&str, Option<Option<&str>>, _)| {
+            let mut mantissa = 0u128;
+            let mut exponent = 0i64;
+            let mut sticky = false;
+
+            let int = int.chars().filter(|c| *c != '_');
+            let frac = frac.flatten().unwrap_or("").chars().filter(|c| *c != '_');
+            for (c, is_frac) in int.map(|c| (c, false)).chain(frac.map(|c| (c, true))) {
+                let d = c.to_digit(16).unwrap() as u128;
+                if mantissa >> 120 == 0 {
+                    mantissa = mantissa << 4 | d;
+                    exponent -= 4 * is_frac as i64;
+                } else {
+                    sticky |= d != 0;
+                    exponent += 4 * !is_frac as i64;
+                }
+            }
+
+            if let Some((sign, e)) = exp {
+                let e = e.replace('_', "").parse::<i64>().unwrap_or(i64::MAX / 2);
+                let e = e.min(i64::MAX / 4);
+                exponent += if sign == Some('-') { -e } else { e };
+            }
+
+            format.round(mantissa, exponent, sticky)
+        },
+    );
+    let hex = preceded(tag("0x"), cut(hex_body));
+
+    let dec = map_opt(
+        recognize(tuple((
+            digits(digit1),
+            opt(preceded(char('.'), opt(digits(digit1)))),
+            opt(tuple((one_of("eE"), opt(one_of("+-")), digits(digit1)))),
+        ))),
+        |d: &str| {
+            let bits = (format.decimal)(&d.replace('_', ""))?;
+            (bits != format.infinity()).then_some(bits)
+        },
+    );
+
+    let (input, sign) = opt(one_of("+-"))(input)?;
+    let (input, bits) = alt((nan_payload, nan, inf, hex, dec))(input)?;
+    let sign_bit = 1 << (format.mantissa_bits + format.exponent_bits);
+
+    match sign {
+        Some('-') => Ok((input, bits | sign_bit)),
+        _ => Ok((input, bits)),
+    }
+}
+
+pub fn f32(input: &str) -> IResult<&str, f32> {
     map(
-        bws(delimited(char('"'), is_not("\""), char('"'))),
-        |s: &str| s.to_string(),
+        |i| float(i, &F32_FORMAT),
+        |bits| f32::from_bits(bits as u32),
     )(input)
 }
 
+pub fn f64(input: &str) -> IResult<&str, f64> {
+    map(|i| float(i, &F64_FORMAT), f64::from_bits)(input)
+}
+
+/// Parses a string literal into its bytes. Besides plain text, a string may
+/// contain the escapes `\t`, `\n`, `\r`, `\"`, `\'`, `\\`, `\hh` for a raw
+/// byte and `\u{hhhh}` for a UTF-8 encoded character.
+pub fn string(input: &str) -> IResult<&str, Vec<u8>> {
+    let byte = map_res(take_while_m_n(2, 2, |c: char| c.is_ascii_hexdigit()), |h| {
+        u8::from_str_radix(h, 16)
+    });
+    let unicode = map_opt(
+        delimited(tag("u{"), digits(hex_digit1), char('}')),
+        |h: &str| {
+            let c = char::from_u32(u32::from_str_radix(&h.replace('_', ""), 16).ok()?)?;
+            Some(c.to_string().into_bytes())
+        },
+    );
+    let escape = preceded(
+        char('\\'),
+        alt((
+            value(vec![b'\t'], char('t')),
+            value(vec![b'\n'], char('n')),
+            value(vec![b'\r'], char('r')),
+            value(vec![b'"'], char('"')),
+            value(vec![b'\''], char('\'')),
+            value(vec![b'\\'], char('\\')),
+            map(byte, |b| vec![b]),
+            unicode,
+        )),
+    );
+    let text = map(is_not("\"\\"), |s: &str| s.as_bytes().to_vec());
+    let chars = fold_many0(alt((text, escape)), Vec::new(), |mut acc, bytes| {
+        acc.extend(bytes);
+        acc
+    });
+    bws(delimited(char('"'), chars, char('"')))(input)
+}
+
+/// Parses a string literal that has to be valid UTF-8, e.g. a name.
+pub fn literal(input: &str) -> IResult<&str, String> {
+    map_res(string, String::from_utf8)(input)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -41,6 +297,84 @@ mod tests {
     #[test]
     fn u32_parse() {
         assert_eq!(u32("12"), Ok(("", 12)));
+        assert_eq!(u32("0xffff_ffff"), Ok(("", u32::MAX)));
+        assert!(u32("4294967296").is_err());
+        assert!(u32("-1").is_err());
+    }
+
+    #[test]
+    fn u64_parse() {
+        assert_eq!(u64("4294967296"), Ok(("", 1 << 32)));
+        assert_eq!(u64("0xffff_ffff_ffff_ffff"), Ok(("", u64::MAX)));
+        assert!(u64("18446744073709551616").is_err());
+    }
+
+    #[test]
+    fn i32_parse() {
+        assert_eq!(i32("0"), Ok(("", 0)));
+        assert_eq!(i32("-42 "), Ok((" ", -42)));
+        assert_eq!(i32("+42"), Ok(("", 42)));
+        assert_eq!(i32("1_000_000"), Ok(("", 1_000_000)));
+        assert_eq!(i32("0x7fff_ffff"), Ok(("", i32::MAX)));
+        assert_eq!(i32("-0x8000_0000"), Ok(("", i32::MIN)));
+        assert_eq!(i32("0xffffffff"), Ok(("", -1)));
+        assert_eq!(i32("4294967295"), Ok(("", -1)));
+        assert!(i32("4294967296").is_err());
+        assert!(i32("-2147483649").is_err());
+        assert!(i32("$x").is_err());
+    }
+
+    #[test]
+    fn i64_parse() {
+        assert_eq!(i64("-9223372036854775808"), Ok(("", i64::MIN)));
+        assert_eq!(i64("0xffff_ffff_ffff_ffff"), Ok(("", -1)));
+        assert_eq!(i64("4294967296"), Ok(("", 1 << 32)));
+        assert!(i64("18446744073709551616").is_err());
+    }
+
+    #[test]
+    fn f32_parse() {
+        assert_eq!(f32("1.5"), Ok(("", 1.5)));
+        assert_eq!(f32("-1.5e2 "), Ok((" ", -150.0)));
+        assert_eq!(f32("1."), Ok(("", 1.0)));
+        assert_eq!(f32("1_000"), Ok(("", 1000.0)));
+        assert_eq!(f32("0x1p-1"), Ok(("", 0.5)));
+        assert_eq!(f32("0x1.8p1"), Ok(("", 3.0)));
+        assert_eq!(f32("0x1p-149"), Ok(("", f32::from_bits(1))));
+        assert_eq!(f32("0x1p-150"), Ok(("", 0.0)));
+        assert_eq!(f32("0x1.8p-149"), Ok(("", f32::from_bits(2))));
+        assert_eq!(f32("0x1.fffffep127"), Ok(("", f32::MAX)));
+        assert_eq!(f32("0x1.fffffefp127"), Ok(("", f32::MAX)));
+        assert!(f32("0x1.ffffffp127").is_err());
+        assert!(f32("1e39").is_err());
+        assert_eq!(f32("-inf"), Ok(("", f32::NEG_INFINITY)));
+        assert_eq!(f32("nan").map(|(_, f)| f.to_bits()), Ok(0x7fc0_0000));
+        assert_eq!(f32("-nan:0x1").map(|(_, f)| f.to_bits()), Ok(0xff80_0001));
+        assert!(f32("nan:0x0").is_err());
+        assert!(f32("nan:0x800000").is_err());
+        assert!(f32("-0").unwrap().1.is_sign_negative());
+    }
+
+    #[test]
+    fn f64_parse() {
+        assert_eq!(f64("0.1"), Ok(("", 0.1)));
+        assert_eq!(f64("1e308"), Ok(("", 1e308)));
+        assert_eq!(f64("0x1.fffffffffffffp1023"), Ok(("", f64::MAX)));
+        assert_eq!(f64("0x1p-1074"), Ok(("", f64::from_bits(1))));
+        assert_eq!(f64("0x1.00000000000008p0"), Ok(("", 1.0)));
+        assert_eq!(
+            f64("0x1.00000000000018p0"),
+            Ok(("", 1.0 + 2.0 * f64::EPSILON))
+        );
+        assert_eq!(
+            f64("0x1.000000000000080000001p0"),
+            Ok(("", 1.0 + f64::EPSILON))
+        );
+        assert_eq!(
+            f64("nan:0xf_ffff_ffff_ffff").map(|(_, f)| f.to_bits()),
+            Ok(u64::MAX >> 1)
+        );
+        assert!(f64("1e309").is_err());
     }
 
     #[test]
@@ -51,5 +385,21 @@ mod tests {
         );
 
         assert!(literal("invalid").is_err());
+        assert!(literal("\"\\ff\"").is_err());
+    }
+
+    #[test]
+    fn string_parse() {
+        assert_eq!(Ok(("", vec![])), string("\"\""));
+        assert_eq!(
+            Ok(("", b"a\tb\n\"'\\\x00\xff".to_vec())),
+            string(r#""a\tb\n\"\'\\\00\FF""#)
+        );
+        assert_eq!(
+            Ok(("", "λ€".as_bytes().to_vec())),
+            string(r#""\u{3bb}\u{20AC}""#)
+        );
+        assert!(string(r#""\u{d800}""#).is_err());
+        assert!(string(r#""\q""#).is_err());
     }
 }
@@ -2,6 +2,8 @@ use crate::ast::*;
 use crate::op_codes::*;
 use crate::runtime::error::RuntimeError;
 use crate::runtime::reader::Reader;
+use crate::runtime::validator::MAX_NESTING_DEPTH;
+use std::convert::TryFrom;
 
 fn check_header(wasm: &Reader) -> Result<(), RuntimeError> {
     if wasm.len() < 8 {
@@ -19,181 +21,1916 @@ fn check_header(wasm: &Reader) -> Result<(), RuntimeError> {
     Ok(())
 }
 
+fn parse_valuetype(wasm: &Reader) -> Result<ValueType, RuntimeError> {
+    match wasm.byte() {
+        0x7f => Ok(ValueType::I32),
+        0x7e => Ok(ValueType::I64),
+        0x7d => Ok(ValueType::F32),
+        0x7c => Ok(ValueType::F64),
+        0x7b => Ok(ValueType::V128),
+        0x70 => Ok(ValueType::FuncRef),
+        0x6f => Ok(ValueType::ExternRef),
+        0x69 => Ok(ValueType::ExnRef),
+        typed_ref::REF_NULL => parse_ref(wasm, true),
+        typed_ref::REF => parse_ref(wasm, false),
+        _ => Err(RuntimeError::InvalidValueType),
+    }
+}
+
+/// Parses the heap type of `(ref null? ht)`. Nullable references to the
+/// abstract heap types are the same as their shorthands, e.g. `funcref`.
+fn parse_ref(wasm: &Reader, nullable: bool) -> Result<ValueType, RuntimeError> {
+    match parse_heap_type(wasm)? {
+        HeapType::Type(type_idx) => Ok(ValueType::Ref { nullable, type_idx }),
+        heap_type if nullable => Ok(heap_type.into()),
+        _ => Err(RuntimeError::InvalidValueType),
+    }
+}
+
+/// Parses an abstract heap type or a type index, which is encoded as a
+/// positive signed 33 bit integer.
+fn parse_heap_type(wasm: &Reader) -> Result<HeapType, RuntimeError> {
+    match wasm.byte() {
+        0x70 => Ok(HeapType::Func),
+        0x6f => Ok(HeapType::Extern),
+        0x69 => Ok(HeapType::Exn),
+        _ => {
+            wasm.back();
+            let type_idx =
+                u32::try_from(wasm.leb_i64()?).map_err(|_| RuntimeError::InvalidRefType)? as usize;
+            Ok(HeapType::Type(type_idx))
+        }
+    }
+}
+
 fn parse_type_section(wasm: &Reader) -> Result<Vec<Type>, RuntimeError> {
     if wasm.byte() != section::TYPE {
         return Err(RuntimeError::InvalidSectionCode);
     }
-    let _size = wasm.byte();
-    let num_types = wasm.byte();
-    let mut types = vec![];
+    let _size = wasm.leb_u32()?;
+    let num_types = wasm.leb_u32()?;
+    let mut types = vec![];
+
+    for _ in 0..num_types {
+        let _func = wasm.byte();
+
+        // parse params
+        let mut params = vec![];
+        for _ in 0..wasm.leb_u32()? {
+            params.push(parse_valuetype(wasm)?);
+        }
+
+        // parse results
+        let mut results = vec![];
+        for _ in 0..wasm.leb_u32()? {
+            results.push(parse_valuetype(wasm)?);
+        }
+
+        types.push((params, results));
+    }
+
+    Ok(types)
+}
+
+fn parse_func_section(wasm: &Reader) -> Result<Vec<i32>, RuntimeError> {
+    if wasm.byte() != section::FUNC {
+        return Err(RuntimeError::InvalidSectionCode);
+    }
+
+    let _size = wasm.leb_u32()?;
+    let num = wasm.leb_u32()?;
+    let mut f_types = vec![];
+
+    for _ in 0..num {
+        f_types.push(wasm.leb_u32()? as i32)
+    }
+
+    Ok(f_types)
+}
+
+fn parse_ref_type(wasm: &Reader) -> Result<RefType, RuntimeError> {
+    match wasm.byte() {
+        0x70 => Ok(RefType::FuncRef),
+        0x6f => Ok(RefType::ExternRef),
+        0x69 => Ok(RefType::ExnRef),
+        _ => Err(RuntimeError::InvalidRefType),
+    }
+}
+
+fn parse_limits(wasm: &Reader) -> Result<Limits, RuntimeError> {
+    match wasm.byte() {
+        flags @ (limits::MIN | limits::MIN_MAX) => parse_bounds(wasm, flags),
+        _ => Err(RuntimeError::InvalidLimits),
+    }
+}
+
+/// Parses the minimum and, if `flags` say so, the maximum of limits.
+fn parse_bounds(wasm: &Reader, flags: u8) -> Result<Limits, RuntimeError> {
+    // Only the limits of 64-bit memories are encoded as `u64`.
+    let bound = || match flags & limits::MEMORY64 {
+        0 => wasm.leb_u32().map(u64::from),
+        _ => wasm.leb_u64(),
+    };
+    let min = bound()?;
+    let max = match flags & limits::MIN_MAX {
+        0 => None,
+        _ => Some(bound()?),
+    };
+    Ok(Limits { min, max })
+}
+
+/// Parses the limits of a memory, which may be flagged as shared or 64-bit.
+fn parse_memory(wasm: &Reader) -> Result<Memory, RuntimeError> {
+    let flags = wasm.byte();
+    if flags & !(limits::MIN_MAX | limits::SHARED | limits::MEMORY64) != 0 {
+        return Err(RuntimeError::InvalidLimits);
+    }
+    Ok(Memory {
+        limits: parse_bounds(wasm, flags)?,
+        shared: flags & limits::SHARED != 0,
+        memory64: flags & limits::MEMORY64 != 0,
+    })
+}
+
+fn parse_table(wasm: &Reader) -> Result<Table, RuntimeError> {
+    let ref_type = parse_ref_type(wasm)?;
+    Ok(Table {
+        limits: parse_limits(wasm)?,
+        ref_type,
+    })
+}
+
+fn parse_table_section(wasm: &Reader) -> Result<Vec<Table>, RuntimeError> {
+    if wasm.byte() != section::TABLE {
+        return Err(RuntimeError::InvalidSectionCode);
+    }
+
+    let _size = wasm.leb_u32()?;
+    let num = wasm.leb_u32()?;
+    let mut tables = vec![];
+
+    for _ in 0..num {
+        tables.push(parse_table(wasm)?);
+    }
+
+    Ok(tables)
+}
+
+fn parse_memory_section(wasm: &Reader) -> Result<Vec<Memory>, RuntimeError> {
+    if wasm.byte() != section::MEMORY {
+        return Err(RuntimeError::InvalidSectionCode);
+    }
+
+    let _size = wasm.leb_u32()?;
+    let num = wasm.leb_u32()?;
+    let mut memories = vec![];
+
+    for _ in 0..num {
+        memories.push(parse_memory(wasm)?);
+    }
+
+    Ok(memories)
+}
+
+/// Parses the tag section into the type indices of the tags.
+fn parse_tag_section(wasm: &Reader) -> Result<Vec<usize>, RuntimeError> {
+    if wasm.byte() != section::TAG {
+        return Err(RuntimeError::InvalidSectionCode);
+    }
+
+    let _size = wasm.leb_u32()?;
+    let num = wasm.leb_u32()?;
+    let mut tags = vec![];
+
+    for _ in 0..num {
+        if wasm.byte() != TAG_EXCEPTION {
+            return Err(RuntimeError::InvalidTagType);
+        }
+        tags.push(wasm.leb_u32()? as usize);
+    }
+
+    Ok(tags)
+}
+
+fn parse_global_type(wasm: &Reader) -> Result<GlobalType, RuntimeError> {
+    let value_type = parse_valuetype(wasm)?;
+    let mutable = match wasm.byte() {
+        mutability::CONST => false,
+        mutability::VAR => true,
+        _ => return Err(RuntimeError::InvalidMutability),
+    };
+
+    Ok(GlobalType {
+        value_type,
+        mutable,
+    })
+}
+
+fn parse_global_section(wasm: &Reader) -> Result<Vec<Global>, RuntimeError> {
+    if wasm.byte() != section::GLOBAL {
+        return Err(RuntimeError::InvalidSectionCode);
+    }
+
+    let _size = wasm.leb_u32()?;
+    let num = wasm.leb_u32()?;
+    let mut globals = vec![];
+
+    for _ in 0..num {
+        let g_type = parse_global_type(wasm)?;
+        let init = parse_block(wasm, 1)?;
+
+        globals.push(Global { g_type, init })
+    }
+
+    Ok(globals)
+}
+
+/// Parses a length-prefixed UTF-8 name, failing with `invalid` otherwise.
+fn parse_name(wasm: &Reader, invalid: RuntimeError) -> Result<String, RuntimeError> {
+    let length = wasm.leb_u32()?;
+    match std::str::from_utf8(wasm.bytes(length as usize)) {
+        Ok(name) => Ok(name.to_string()),
+        Err(_) => Err(invalid),
+    }
+}
+
+fn parse_import_section(wasm: &Reader) -> Result<Vec<Import>, RuntimeError> {
+    if wasm.byte() != section::IMPORT {
+        return Err(RuntimeError::InvalidSectionCode);
+    }
+
+    let _size = wasm.leb_u32()?;
+    let num = wasm.leb_u32()?;
+    let mut imports = vec![];
+
+    for _ in 0..num {
+        let module = parse_name(wasm, RuntimeError::InvalidImportName)?;
+        let name = parse_name(wasm, RuntimeError::InvalidImportName)?;
+        let desc = match wasm.byte() {
+            indices::FUNC => ImportDesc::Func(wasm.leb_u32()? as usize),
+            indices::TABLE => ImportDesc::Table(parse_table(wasm)?),
+            indices::MEMORY => ImportDesc::Memory(parse_memory(wasm)?),
+            indices::GLOBAL => ImportDesc::Global(parse_global_type(wasm)?),
+            _ => return Err(RuntimeError::InvalidImportType),
+        };
+
+        imports.push(Import { module, name, desc })
+    }
+
+    Ok(imports)
+}
+
+fn parse_export_section(wasm: &Reader) -> Result<Vec<Export>, RuntimeError> {
+    if wasm.byte() != section::EXPORT {
+        return Err(RuntimeError::InvalidSectionCode);
+    }
+
+    let _size = wasm.leb_u32()?;
+    let num = wasm.leb_u32()?;
+    let mut exports = vec![];
+
+    for _ in 0..num {
+        let name = parse_name(wasm, RuntimeError::InvalidExportName)?;
+        let kind = wasm.byte();
+        let idx = wasm.leb_u32()? as usize;
+        let e_desc = match kind {
+            indices::FUNC => EDesc::FuncExport(idx),
+            indices::TABLE => EDesc::TableExport(idx),
+            indices::MEMORY => EDesc::MemoryExport(idx),
+            indices::GLOBAL => EDesc::GlobalExport(idx),
+            indices::TAG => EDesc::TagExport(idx),
+            _ => return Err(RuntimeError::InvalidExportType),
+        };
+
+        exports.push(Export { name, e_desc })
+    }
+
+    Ok(exports)
+}
+
+/// Parses a block type, which is either empty, a value type or a type index
+/// encoded as a positive signed 33 bit integer. Value types are the negative
+/// integers of a single byte.
+fn parse_block_type(wasm: &Reader) -> Result<BlockType, RuntimeError> {
+    match wasm.byte() {
+        control_flow::EMPTY_BLOCK => Ok(BlockType::Empty),
+        byte if byte & 0xc0 == 0x40 => {
+            wasm.back();
+            Ok(BlockType::Value(parse_valuetype(wasm)?))
+        }
+        _ => {
+            wasm.back();
+            let idx = u32::try_from(wasm.leb_i64()?).map_err(|_| RuntimeError::IntegerTooLarge)?;
+            Ok(BlockType::TypeIdx(idx as usize))
+        }
+    }
+}
+
+fn parse_catch(wasm: &Reader) -> Result<Catch, RuntimeError> {
+    Ok(match wasm.byte() {
+        catch_kind::CATCH => {
+            let tag = wasm.leb_u32()? as usize;
+            Catch::Tag(tag, wasm.leb_u32()? as usize)
+        }
+        catch_kind::CATCH_REF => {
+            let tag = wasm.leb_u32()? as usize;
+            Catch::TagRef(tag, wasm.leb_u32()? as usize)
+        }
+        catch_kind::CATCH_ALL => Catch::All(wasm.leb_u32()? as usize),
+        catch_kind::CATCH_ALL_REF => Catch::AllRef(wasm.leb_u32()? as usize),
+        _ => return Err(RuntimeError::InvalidInstruction),
+    })
+}
+
+/// Parses a memory argument, whose alignment is flagged if the index of the
+/// memory follows.
+fn parse_memarg(wasm: &Reader) -> Result<MemArg, RuntimeError> {
+    let align = wasm.leb_u32()?;
+    let memory = match align & memarg::MEMORY_IDX {
+        0 => 0,
+        _ => wasm.leb_u32()? as usize,
+    };
+    Ok(MemArg {
+        align: align & !memarg::MEMORY_IDX,
+        offset: wasm.leb_u64()?,
+        memory,
+    })
+}
+
+/// Parses instructions up to and including the next `end` or `else` opcode
+/// and returns them together with that terminating opcode. `depth` counts
+/// the blocks they are nested in, including the function body.
+fn parse_instrs(wasm: &Reader, depth: usize) -> Result<(Vec<Instr>, u8), RuntimeError> {
+    if depth > MAX_NESTING_DEPTH {
+        return Err(RuntimeError::NestingTooDeep);
+    }
+    let mut instrs = vec![];
+
+    loop {
+        let instr = match wasm.byte() {
+            control_flow::BLOCK => {
+                let bt = parse_block_type(wasm)?;
+                Instr::Block(bt, parse_block(wasm, depth + 1)?)
+            }
+            control_flow::LOOP => {
+                let bt = parse_block_type(wasm)?;
+                Instr::Loop(bt, parse_block(wasm, depth + 1)?)
+            }
+            control_flow::IF => {
+                let bt = parse_block_type(wasm)?;
+                match parse_instrs(wasm, depth + 1)? {
+                    (then, control_flow::ELSE) => {
+                        Instr::If(bt, then, parse_block(wasm, depth + 1)?)
+                    }
+                    (then, _) => Instr::If(bt, then, vec![]),
+                }
+            }
+            control_flow::TRY_TABLE => {
+                let bt = parse_block_type(wasm)?;
+                let mut catches = vec![];
+                for _ in 0..wasm.leb_u32()? {
+                    catches.push(parse_catch(wasm)?);
+                }
+                Instr::TryTable(bt, catches, parse_block(wasm, depth + 1)?)
+            }
+            terminator @ (control_flow::END | control_flow::ELSE) => {
+                return Ok((instrs, terminator))
+            }
+            op_code => parse_plain_instr(op_code, wasm)?,
+        };
+
+        instrs.push(instr);
+    }
+}
+
+/// Parses an instruction without a body. `parse_instrs` recurses for every
+/// block, so this large match is kept out of it to keep the frames small.
+#[inline(never)]
+fn parse_plain_instr(op_code: u8, wasm: &Reader) -> Result<Instr, RuntimeError> {
+    let instr = match op_code {
+        control_flow::UNREACHABLE => Instr::Unreachable,
+        control_flow::NOP => Instr::Nop,
+        control_flow::BR => Instr::Br(wasm.leb_u32()? as usize),
+        control_flow::BR_IF => Instr::BrIf(wasm.leb_u32()? as usize),
+        control_flow::BR_TABLE => {
+            let mut labels = vec![];
+            for _ in 0..wasm.leb_u32()? {
+                labels.push(wasm.leb_u32()? as usize);
+            }
+            Instr::BrTable(labels, wasm.leb_u32()? as usize)
+        }
+        control_flow::RETURN => Instr::Return,
+        control_flow::CALL => Instr::Call(wasm.leb_u32()? as usize),
+        control_flow::CALL_INDIRECT => {
+            let type_idx = wasm.leb_u32()? as usize;
+            Instr::CallIndirect(type_idx, wasm.leb_u32()? as usize)
+        }
+        control_flow::RETURN_CALL => Instr::ReturnCall(wasm.leb_u32()? as usize),
+        control_flow::RETURN_CALL_INDIRECT => {
+            let type_idx = wasm.leb_u32()? as usize;
+            Instr::ReturnCallIndirect(type_idx, wasm.leb_u32()? as usize)
+        }
+        control_flow::CALL_REF => Instr::CallRef(wasm.leb_u32()? as usize),
+        control_flow::RETURN_CALL_REF => Instr::ReturnCallRef(wasm.leb_u32()? as usize),
+        control_flow::THROW => Instr::Throw(wasm.leb_u32()? as usize),
+        control_flow::THROW_REF => Instr::ThrowRef,
+        parametric::DROP => Instr::Drop,
+        parametric::SELECT => Instr::Select(None),
+        // Typed select has a vector of exactly one value type.
+        parametric::SELECT_TYPED => match wasm.leb_u32()? {
+            1 => Instr::Select(Some(parse_valuetype(wasm)?)),
+            _ => return Err(RuntimeError::InvalidInstruction),
+        },
+        ref_instr::REF_NULL => Instr::RefNull(parse_heap_type(wasm)?),
+        ref_instr::REF_IS_NULL => Instr::RefIsNull,
+        ref_instr::REF_FUNC => Instr::RefFunc(wasm.leb_u32()? as usize),
+        ref_instr::REF_AS_NON_NULL => Instr::RefAsNonNull,
+        ref_instr::BR_ON_NULL => Instr::BrOnNull(wasm.leb_u32()? as usize),
+        ref_instr::BR_ON_NON_NULL => Instr::BrOnNonNull(wasm.leb_u32()? as usize),
+        var_instr::LOCAL_GET => Instr::LocalGet(wasm.leb_u32()? as usize),
+        var_instr::LOCAL_SET => Instr::LocalSet(wasm.leb_u32()? as usize),
+        var_instr::LOCAL_TEE => Instr::LocalTee(wasm.leb_u32()? as usize),
+        var_instr::GLOBAL_GET => Instr::GlobalGet(wasm.leb_u32()? as usize),
+        var_instr::GLOBAL_SET => Instr::GlobalSet(wasm.leb_u32()? as usize),
+        table_instr::TABLE_GET => Instr::TableGet(wasm.leb_u32()? as usize),
+        table_instr::TABLE_SET => Instr::TableSet(wasm.leb_u32()? as usize),
+        mem_instr::I32_LOAD => Instr::I32Load(parse_memarg(wasm)?),
+        mem_instr::I64_LOAD => Instr::I64Load(parse_memarg(wasm)?),
+        mem_instr::F32_LOAD => Instr::F32Load(parse_memarg(wasm)?),
+        mem_instr::F64_LOAD => Instr::F64Load(parse_memarg(wasm)?),
+        mem_instr::I32_LOAD8_S => Instr::I32Load8S(parse_memarg(wasm)?),
+        mem_instr::I32_LOAD8_U => Instr::I32Load8U(parse_memarg(wasm)?),
+        mem_instr::I32_LOAD16_S => Instr::I32Load16S(parse_memarg(wasm)?),
+        mem_instr::I32_LOAD16_U => Instr::I32Load16U(parse_memarg(wasm)?),
+        mem_instr::I64_LOAD8_S => Instr::I64Load8S(parse_memarg(wasm)?),
+        mem_instr::I64_LOAD8_U => Instr::I64Load8U(parse_memarg(wasm)?),
+        mem_instr::I64_LOAD16_S => Instr::I64Load16S(parse_memarg(wasm)?),
+        mem_instr::I64_LOAD16_U => Instr::I64Load16U(parse_memarg(wasm)?),
+        mem_instr::I64_LOAD32_S => Instr::I64Load32S(parse_memarg(wasm)?),
+        mem_instr::I64_LOAD32_U => Instr::I64Load32U(parse_memarg(wasm)?),
+        mem_instr::I32_STORE => Instr::I32Store(parse_memarg(wasm)?),
+        mem_instr::I64_STORE => Instr::I64Store(parse_memarg(wasm)?),
+        mem_instr::F32_STORE => Instr::F32Store(parse_memarg(wasm)?),
+        mem_instr::F64_STORE => Instr::F64Store(parse_memarg(wasm)?),
+        mem_instr::I32_STORE8 => Instr::I32Store8(parse_memarg(wasm)?),
+        mem_instr::I32_STORE16 => Instr::I32Store16(parse_memarg(wasm)?),
+        mem_instr::I64_STORE8 => Instr::I64Store8(parse_memarg(wasm)?),
+        mem_instr::I64_STORE16 => Instr::I64Store16(parse_memarg(wasm)?),
+        mem_instr::I64_STORE32 => Instr::I64Store32(parse_memarg(wasm)?),
+        mem_instr::MEMORY_SIZE => Instr::MemorySize(wasm.leb_u32()? as usize),
+        mem_instr::MEMORY_GROW => Instr::MemoryGrow(wasm.leb_u32()? as usize),
+        num_instr::I32_CONST => Instr::I32Const(wasm.leb_i32()?),
+        num_instr::I64_CONST => Instr::I64Const(wasm.leb_i64()?),
+        num_instr::F32_CONST => Instr::F32Const(wasm.f32()),
+        num_instr::F64_CONST => Instr::F64Const(wasm.f64()),
+        num_instr::I32_EQZ => Instr::I32Eqz,
+        num_instr::I32_EQ => Instr::I32Eq,
+        num_instr::I32_NE => Instr::I32Ne,
+        num_instr::I32_LT_S => Instr::I32LtS,
+        num_instr::I32_LT_U => Instr::I32LtU,
+        num_instr::I32_GT_S => Instr::I32GtS,
+        num_instr::I32_GT_U => Instr::I32GtU,
+        num_instr::I32_LE_S => Instr::I32LeS,
+        num_instr::I32_LE_U => Instr::I32LeU,
+        num_instr::I32_GE_S => Instr::I32GeS,
+        num_instr::I32_GE_U => Instr::I32GeU,
+        num_instr::I32_CLZ => Instr::I32Clz,
+        num_instr::I32_CTZ => Instr::I32Ctz,
+        num_instr::I32_POPCNT => Instr::I32Popcnt,
+        num_instr::I32_ADD => Instr::I32Add,
+        num_instr::I32_SUB => Instr::I32Sub,
+        num_instr::I32_MUL => Instr::I32Mul,
+        num_instr::I32_DIV_S => Instr::I32DivS,
+        num_instr::I32_DIV_U => Instr::I32DivU,
+        num_instr::I32_REM_S => Instr::I32RemS,
+        num_instr::I32_REM_U => Instr::I32RemU,
+        num_instr::I32_AND => Instr::I32And,
+        num_instr::I32_OR => Instr::I32Or,
+        num_instr::I32_XOR => Instr::I32Xor,
+        num_instr::I32_SHL => Instr::I32Shl,
+        num_instr::I32_SHR_S => Instr::I32ShrS,
+        num_instr::I32_SHR_U => Instr::I32ShrU,
+        num_instr::I32_ROTL => Instr::I32Rotl,
+        num_instr::I32_ROTR => Instr::I32Rotr,
+        num_instr::I64_EQZ => Instr::I64Eqz,
+        num_instr::I64_EQ => Instr::I64Eq,
+        num_instr::I64_NE => Instr::I64Ne,
+        num_instr::I64_LT_S => Instr::I64LtS,
+        num_instr::I64_LT_U => Instr::I64LtU,
+        num_instr::I64_GT_S => Instr::I64GtS,
+        num_instr::I64_GT_U => Instr::I64GtU,
+        num_instr::I64_LE_S => Instr::I64LeS,
+        num_instr::I64_LE_U => Instr::I64LeU,
+        num_instr::I64_GE_S => Instr::I64GeS,
+        num_instr::I64_GE_U => Instr::I64GeU,
+        num_instr::I64_CLZ => Instr::I64Clz,
+        num_instr::I64_CTZ => Instr::I64Ctz,
+        num_instr::I64_POPCNT => Instr::I64Popcnt,
+        num_instr::I64_ADD => Instr::I64Add,
+        num_instr::I64_SUB => Instr::I64Sub,
+        num_instr::I64_MUL => Instr::I64Mul,
+        num_instr::I64_DIV_S => Instr::I64DivS,
+        num_instr::I64_DIV_U => Instr::I64DivU,
+        num_instr::I64_REM_S => Instr::I64RemS,
+        num_instr::I64_REM_U => Instr::I64RemU,
+        num_instr::I64_AND => Instr::I64And,
+        num_instr::I64_OR => Instr::I64Or,
+        num_instr::I64_XOR => Instr::I64Xor,
+        num_instr::I64_SHL => Instr::I64Shl,
+        num_instr::I64_SHR_S => Instr::I64ShrS,
+        num_instr::I64_SHR_U => Instr::I64ShrU,
+        num_instr::I64_ROTL => Instr::I64Rotl,
+        num_instr::I64_ROTR => Instr::I64Rotr,
+        num_instr::F32_EQ => Instr::F32Eq,
+        num_instr::F32_NE => Instr::F32Ne,
+        num_instr::F32_LT => Instr::F32Lt,
+        num_instr::F32_GT => Instr::F32Gt,
+        num_instr::F32_LE => Instr::F32Le,
+        num_instr::F32_GE => Instr::F32Ge,
+        num_instr::F64_EQ => Instr::F64Eq,
+        num_instr::F64_NE => Instr::F64Ne,
+        num_instr::F64_LT => Instr::F64Lt,
+        num_instr::F64_GT => Instr::F64Gt,
+        num_instr::F64_LE => Instr::F64Le,
+        num_instr::F64_GE => Instr::F64Ge,
+        num_instr::F32_ABS => Instr::F32Abs,
+        num_instr::F32_NEG => Instr::F32Neg,
+        num_instr::F32_CEIL => Instr::F32Ceil,
+        num_instr::F32_FLOOR => Instr::F32Floor,
+        num_instr::F32_TRUNC => Instr::F32Trunc,
+        num_instr::F32_NEAREST => Instr::F32Nearest,
+        num_instr::F32_SQRT => Instr::F32Sqrt,
+        num_instr::F32_ADD => Instr::F32Add,
+        num_instr::F32_SUB => Instr::F32Sub,
+        num_instr::F32_MUL => Instr::F32Mul,
+        num_instr::F32_DIV => Instr::F32Div,
+        num_instr::F32_MIN => Instr::F32Min,
+        num_instr::F32_MAX => Instr::F32Max,
+        num_instr::F32_COPYSIGN => Instr::F32Copysign,
+        num_instr::F64_ABS => Instr::F64Abs,
+        num_instr::F64_NEG => Instr::F64Neg,
+        num_instr::F64_CEIL => Instr::F64Ceil,
+        num_instr::F64_FLOOR => Instr::F64Floor,
+        num_instr::F64_TRUNC => Instr::F64Trunc,
+        num_instr::F64_NEAREST => Instr::F64Nearest,
+        num_instr::F64_SQRT => Instr::F64Sqrt,
+        num_instr::F64_ADD => Instr::F64Add,
+        num_instr::F64_SUB => Instr::F64Sub,
+        num_instr::F64_MUL => Instr::F64Mul,
+        num_instr::F64_DIV => Instr::F64Div,
+        num_instr::F64_MIN => Instr::F64Min,
+        num_instr::F64_MAX => Instr::F64Max,
+        num_instr::F64_COPYSIGN => Instr::F64Copysign,
+        num_instr::I32_WRAP_I64 => Instr::I32WrapI64,
+        num_instr::I32_TRUNC_F32_S => Instr::I32TruncF32S,
+        num_instr::I32_TRUNC_F32_U => Instr::I32TruncF32U,
+        num_instr::I32_TRUNC_F64_S => Instr::I32TruncF64S,
+        num_instr::I32_TRUNC_F64_U => Instr::I32TruncF64U,
+        num_instr::I64_EXTEND_I32_S => Instr::I64ExtendI32S,
+        num_instr::I64_EXTEND_I32_U => Instr::I64ExtendI32U,
+        num_instr::I64_TRUNC_F32_S => Instr::I64TruncF32S,
+        num_instr::I64_TRUNC_F32_U => Instr::I64TruncF32U,
+        num_instr::I64_TRUNC_F64_S => Instr::I64TruncF64S,
+        num_instr::I64_TRUNC_F64_U => Instr::I64TruncF64U,
+        num_instr::F32_CONVERT_I32_S => Instr::F32ConvertI32S,
+        num_instr::F32_CONVERT_I32_U => Instr::F32ConvertI32U,
+        num_instr::F32_CONVERT_I64_S => Instr::F32ConvertI64S,
+        num_instr::F32_CONVERT_I64_U => Instr::F32ConvertI64U,
+        num_instr::F32_DEMOTE_F64 => Instr::F32DemoteF64,
+        num_instr::F64_CONVERT_I32_S => Instr::F64ConvertI32S,
+        num_instr::F64_CONVERT_I32_U => Instr::F64ConvertI32U,
+        num_instr::F64_CONVERT_I64_S => Instr::F64ConvertI64S,
+        num_instr::F64_CONVERT_I64_U => Instr::F64ConvertI64U,
+        num_instr::F64_PROMOTE_F32 => Instr::F64PromoteF32,
+        num_instr::I32_REINTERPRET_F32 => Instr::I32ReinterpretF32,
+        num_instr::I64_REINTERPRET_F64 => Instr::I64ReinterpretF64,
+        num_instr::F32_REINTERPRET_I32 => Instr::F32ReinterpretI32,
+        num_instr::F64_REINTERPRET_I64 => Instr::F64ReinterpretI64,
+        num_instr::I32_EXTEND8_S => Instr::I32Extend8S,
+        num_instr::I32_EXTEND16_S => Instr::I32Extend16S,
+        num_instr::I64_EXTEND8_S => Instr::I64Extend8S,
+        num_instr::I64_EXTEND16_S => Instr::I64Extend16S,
+        num_instr::I64_EXTEND32_S => Instr::I64Extend32S,
+        prefix::MISC => match wasm.leb_u32()? {
+            misc_instr::I32_TRUNC_SAT_F32_S => Instr::I32TruncSatF32S,
+            misc_instr::I32_TRUNC_SAT_F32_U => Instr::I32TruncSatF32U,
+            misc_instr::I32_TRUNC_SAT_F64_S => Instr::I32TruncSatF64S,
+            misc_instr::I32_TRUNC_SAT_F64_U => Instr::I32TruncSatF64U,
+            misc_instr::I64_TRUNC_SAT_F32_S => Instr::I64TruncSatF32S,
+            misc_instr::I64_TRUNC_SAT_F32_U => Instr::I64TruncSatF32U,
+            misc_instr::I64_TRUNC_SAT_F64_S => Instr::I64TruncSatF64S,
+            misc_instr::I64_TRUNC_SAT_F64_U => Instr::I64TruncSatF64U,
+            misc_instr::MEMORY_INIT => {
+                let data = wasm.leb_u32()? as usize;
+                Instr::MemoryInit(wasm.leb_u32()? as usize, data)
+            }
+            misc_instr::DATA_DROP => Instr::DataDrop(wasm.leb_u32()? as usize),
+            misc_instr::MEMORY_COPY => {
+                let dst = wasm.leb_u32()? as usize;
+                Instr::MemoryCopy(dst, wasm.leb_u32()? as usize)
+            }
+            misc_instr::MEMORY_FILL => Instr::MemoryFill(wasm.leb_u32()? as usize),
+            misc_instr::TABLE_INIT => {
+                let elem = wasm.leb_u32()? as usize;
+                Instr::TableInit(wasm.leb_u32()? as usize, elem)
+            }
+            misc_instr::ELEM_DROP => Instr::ElemDrop(wasm.leb_u32()? as usize),
+            misc_instr::TABLE_COPY => {
+                let dst = wasm.leb_u32()? as usize;
+                Instr::TableCopy(dst, wasm.leb_u32()? as usize)
+            }
+            misc_instr::TABLE_GROW => Instr::TableGrow(wasm.leb_u32()? as usize),
+            misc_instr::TABLE_SIZE => Instr::TableSize(wasm.leb_u32()? as usize),
+            misc_instr::TABLE_FILL => Instr::TableFill(wasm.leb_u32()? as usize),
+            _ => return Err(RuntimeError::InvalidInstruction),
+        },
+        prefix::SIMD => parse_simd_instr(wasm)?,
+        prefix::THREADS => parse_atomic_instr(wasm)?,
+        _ => return Err(RuntimeError::InvalidInstruction),
+    };
+    Ok(instr)
+}
+
+/// Parses an instruction following the SIMD prefix.
+fn parse_simd_instr(wasm: &Reader) -> Result<Instr, RuntimeError> {
+    let instr = match wasm.leb_u32()? {
+        simd_instr::V128_LOAD => Instr::V128Load(parse_memarg(wasm)?),
+        simd_instr::V128_LOAD8X8_S => Instr::V128Load8x8S(parse_memarg(wasm)?),
+        simd_instr::V128_LOAD8X8_U => Instr::V128Load8x8U(parse_memarg(wasm)?),
+        simd_instr::V128_LOAD16X4_S => Instr::V128Load16x4S(parse_memarg(wasm)?),
+        simd_instr::V128_LOAD16X4_U => Instr::V128Load16x4U(parse_memarg(wasm)?),
+        simd_instr::V128_LOAD32X2_S => Instr::V128Load32x2S(parse_memarg(wasm)?),
+        simd_instr::V128_LOAD32X2_U => Instr::V128Load32x2U(parse_memarg(wasm)?),
+        simd_instr::V128_LOAD8_SPLAT => Instr::V128Load8Splat(parse_memarg(wasm)?),
+        simd_instr::V128_LOAD16_SPLAT => Instr::V128Load16Splat(parse_memarg(wasm)?),
+        simd_instr::V128_LOAD32_SPLAT => Instr::V128Load32Splat(parse_memarg(wasm)?),
+        simd_instr::V128_LOAD64_SPLAT => Instr::V128Load64Splat(parse_memarg(wasm)?),
+        simd_instr::V128_LOAD32_ZERO => Instr::V128Load32Zero(parse_memarg(wasm)?),
+        simd_instr::V128_LOAD64_ZERO => Instr::V128Load64Zero(parse_memarg(wasm)?),
+        simd_instr::V128_STORE => Instr::V128Store(parse_memarg(wasm)?),
+        simd_instr::V128_LOAD8_LANE => Instr::V128Load8Lane(parse_memarg(wasm)?, wasm.byte()),
+        simd_instr::V128_LOAD16_LANE => Instr::V128Load16Lane(parse_memarg(wasm)?, wasm.byte()),
+        simd_instr::V128_LOAD32_LANE => Instr::V128Load32Lane(parse_memarg(wasm)?, wasm.byte()),
+        simd_instr::V128_LOAD64_LANE => Instr::V128Load64Lane(parse_memarg(wasm)?, wasm.byte()),
+        simd_instr::V128_STORE8_LANE => Instr::V128Store8Lane(parse_memarg(wasm)?, wasm.byte()),
+        simd_instr::V128_STORE16_LANE => Instr::V128Store16Lane(parse_memarg(wasm)?, wasm.byte()),
+        simd_instr::V128_STORE32_LANE => Instr::V128Store32Lane(parse_memarg(wasm)?, wasm.byte()),
+        simd_instr::V128_STORE64_LANE => Instr::V128Store64Lane(parse_memarg(wasm)?, wasm.byte()),
+        simd_instr::V128_CONST => {
+            let bytes = <[u8; 16]>::try_from(wasm.bytes(16)).unwrap();
+            Instr::V128Const(u128::from_le_bytes(bytes))
+        }
+        simd_instr::I8X16_SHUFFLE => {
+            Instr::I8x16Shuffle(<[u8; 16]>::try_from(wasm.bytes(16)).unwrap())
+        }
+        simd_instr::I8X16_EXTRACT_LANE_S => Instr::I8x16ExtractLaneS(wasm.byte()),
+        simd_instr::I8X16_EXTRACT_LANE_U => Instr::I8x16ExtractLaneU(wasm.byte()),
+        simd_instr::I8X16_REPLACE_LANE => Instr::I8x16ReplaceLane(wasm.byte()),
+        simd_instr::I16X8_EXTRACT_LANE_S => Instr::I16x8ExtractLaneS(wasm.byte()),
+        simd_instr::I16X8_EXTRACT_LANE_U => Instr::I16x8ExtractLaneU(wasm.byte()),
+        simd_instr::I16X8_REPLACE_LANE => Instr::I16x8ReplaceLane(wasm.byte()),
+        simd_instr::I32X4_EXTRACT_LANE => Instr::I32x4ExtractLane(wasm.byte()),
+        simd_instr::I32X4_REPLACE_LANE => Instr::I32x4ReplaceLane(wasm.byte()),
+        simd_instr::I64X2_EXTRACT_LANE => Instr::I64x2ExtractLane(wasm.byte()),
+        simd_instr::I64X2_REPLACE_LANE => Instr::I64x2ReplaceLane(wasm.byte()),
+        simd_instr::F32X4_EXTRACT_LANE => Instr::F32x4ExtractLane(wasm.byte()),
+        simd_instr::F32X4_REPLACE_LANE => Instr::F32x4ReplaceLane(wasm.byte()),
+        simd_instr::F64X2_EXTRACT_LANE => Instr::F64x2ExtractLane(wasm.byte()),
+        simd_instr::F64X2_REPLACE_LANE => Instr::F64x2ReplaceLane(wasm.byte()),
+        code => Instr::Simd(simd_op(code).ok_or(RuntimeError::InvalidInstruction)?),
+    };
+    Ok(instr)
+}
+
+/// Parses an instruction following the threads prefix.
+fn parse_atomic_instr(wasm: &Reader) -> Result<Instr, RuntimeError> {
+    let instr = match wasm.leb_u32()? {
+        atomic_instr::MEMORY_ATOMIC_NOTIFY => Instr::MemoryAtomicNotify(parse_memarg(wasm)?),
+        atomic_instr::MEMORY_ATOMIC_WAIT32 => Instr::MemoryAtomicWait32(parse_memarg(wasm)?),
+        atomic_instr::MEMORY_ATOMIC_WAIT64 => Instr::MemoryAtomicWait64(parse_memarg(wasm)?),
+        atomic_instr::ATOMIC_FENCE => match wasm.byte() {
+            0x00 => Instr::AtomicFence,
+            _ => return Err(RuntimeError::InvalidInstruction),
+        },
+        code @ atomic_instr::ATOMIC_LOAD..=atomic_instr::ATOMIC_RMW_LAST => {
+            let widths = atomic_instr::WIDTHS.len() as u32;
+            let width =
+                atomic_instr::WIDTHS[((code - atomic_instr::ATOMIC_LOAD) % widths) as usize];
+            let memarg = parse_memarg(wasm)?;
+            if code < atomic_instr::ATOMIC_STORE {
+                Instr::AtomicLoad(width, memarg)
+            } else if code < atomic_instr::ATOMIC_RMW {
+                Instr::AtomicStore(width, memarg)
+            } else {
+                let op =
+                    atomic_instr::RMW_OPS[((code - atomic_instr::ATOMIC_RMW) / widths) as usize];
+                Instr::AtomicRmw(op, width, memarg)
+            }
+        }
+        _ => return Err(RuntimeError::InvalidInstruction),
+    };
+    Ok(instr)
+}
+
+/// Parses a block body at `depth`, which may only be terminated by `end`.
+fn parse_block(wasm: &Reader, depth: usize) -> Result<Vec<Instr>, RuntimeError> {
+    match parse_instrs(wasm, depth)? {
+        (instrs, control_flow::END) => Ok(instrs),
+        _ => Err(RuntimeError::InvalidInstruction),
+    }
+}
+
+pub fn parse_code_section(wasm: &Reader) -> Result<Vec<(StackType, Vec<Instr>)>, RuntimeError> {
+    if wasm.byte() != section::CODE {
+        return Err(RuntimeError::InvalidSectionCode);
+    };
+
+    let _size = wasm.leb_u32()?;
+    let num = wasm.leb_u32()?;
+    let mut code = vec![];
+
+    for _ in 0..num {
+        let _size = wasm.leb_u32()?;
+        let num_groups = wasm.leb_u32()?;
+        let mut groups = vec![];
+
+        for _ in 0..num_groups {
+            groups.push((wasm.leb_u32()?, parse_valuetype(wasm)?));
+        }
+
+        let num_locals = groups.iter().map(|(count, _)| *count as u64).sum::<u64>();
+        if num_locals > u32::MAX as u64 {
+            return Err(RuntimeError::TooManyLocals);
+        }
+
+        let locals = groups
+            .into_iter()
+            .flat_map(|(count, value_type)| std::iter::repeat_n(value_type, count as usize))
+            .collect();
+
+        let instrs = parse_block(wasm, 1)?;
+        code.push((locals, instrs));
+    }
+
+    Ok(code)
+}
+
+fn parse_elem_section(wasm: &Reader) -> Result<Vec<Elem>, RuntimeError> {
+    fn parse_elem_kind(wasm: &Reader) -> Result<RefType, RuntimeError> {
+        match wasm.byte() {
+            elem_kind::FUNC_REF => Ok(RefType::FuncRef),
+            _ => Err(RuntimeError::InvalidRefType),
+        }
+    }
+
+    if wasm.byte() != section::ELEM {
+        return Err(RuntimeError::InvalidSectionCode);
+    }
+
+    let _size = wasm.leb_u32()?;
+    let num = wasm.leb_u32()?;
+    let mut elems = vec![];
+
+    for _ in 0..num {
+        let flags = wasm.leb_u32()?;
+        if flags > (elem_mode::DECLARATIVE | elem_mode::EXPRS) {
+            return Err(RuntimeError::InvalidElemMode);
+        }
+        // Segments of expressions have a reference type instead of a kind.
+        let exprs = flags & elem_mode::EXPRS != 0;
+        let ref_type = |wasm| match exprs {
+            true => parse_ref_type(wasm),
+            false => parse_elem_kind(wasm),
+        };
+        let (ref_type, mode) = match flags & !elem_mode::EXPRS {
+            elem_mode::ACTIVE => {
+                let offset = parse_block(wasm, 1)?;
+                (RefType::FuncRef, ElemMode::Active { table: 0, offset })
+            }
+            elem_mode::PASSIVE => (ref_type(wasm)?, ElemMode::Passive),
+            elem_mode::ACTIVE_TABLE => {
+                let table = wasm.leb_u32()? as usize;
+                let offset = parse_block(wasm, 1)?;
+                (ref_type(wasm)?, ElemMode::Active { table, offset })
+            }
+            _ => (ref_type(wasm)?, ElemMode::Declarative),
+        };
+        let length = wasm.leb_u32()?;
+        let mut init = vec![];
+        for _ in 0..length {
+            init.push(match exprs {
+                true => parse_block(wasm, 1)?,
+                false => vec![Instr::RefFunc(wasm.leb_u32()? as usize)],
+            });
+        }
+
+        elems.push(Elem {
+            ref_type,
+            init,
+            mode,
+        })
+    }
+
+    Ok(elems)
+}
+
+fn parse_start_section(wasm: &Reader) -> Result<usize, RuntimeError> {
+    if wasm.byte() != section::START {
+        return Err(RuntimeError::InvalidSectionCode);
+    }
+
+    let _size = wasm.leb_u32()?;
+    Ok(wasm.leb_u32()? as usize)
+}
+
+fn parse_data_count_section(wasm: &Reader) -> Result<u32, RuntimeError> {
+    if wasm.byte() != section::DATA_COUNT {
+        return Err(RuntimeError::InvalidSectionCode);
+    }
+
+    let _size = wasm.leb_u32()?;
+    wasm.leb_u32()
+}
+
+fn parse_data_section(wasm: &Reader) -> Result<Vec<Data>, RuntimeError> {
+    if wasm.byte() != section::DATA {
+        return Err(RuntimeError::InvalidSectionCode);
+    }
+
+    let _size = wasm.leb_u32()?;
+    let num = wasm.leb_u32()?;
+    let mut datas = vec![];
+
+    for _ in 0..num {
+        let mode = match wasm.leb_u32()? {
+            data_mode::ACTIVE => DataMode::Active {
+                memory: 0,
+                offset: parse_block(wasm, 1)?,
+            },
+            data_mode::PASSIVE => DataMode::Passive,
+            data_mode::ACTIVE_MEMORY => DataMode::Active {
+                memory: wasm.leb_u32()? as usize,
+                offset: parse_block(wasm, 1)?,
+            },
+            _ => return Err(RuntimeError::InvalidDataMode),
+        };
+        let length = wasm.leb_u32()? as usize;
+        let init = wasm.bytes(length).to_vec();
+
+        datas.push(Data { init, mode })
+    }
+
+    Ok(datas)
+}
+
+/// Position of a section in a module. The data count section has to precede
+/// the code section and the tag section the global section although their
+/// codes are higher.
+fn section_order(section_code: u8) -> u8 {
+    match section_code {
+        section::DATA_COUNT => 2 * section::CODE - 1,
+        section::TAG => 2 * section::MEMORY + 1,
+        code => 2 * code,
+    }
+}
+
+pub fn parse_wasm(wasm: &Reader) -> Result<Module, RuntimeError> {
+    check_header(wasm)?;
+
+    let mut types = vec![];
+    let mut imports = vec![];
+    let mut funcs = vec![];
+    let mut tables = vec![];
+    let mut memories = vec![];
+    let mut tags = vec![];
+    let mut globals = vec![];
+    let mut exports = vec![];
+    let mut start = None;
+    let mut elems = vec![];
+    let mut data_count = None;
+    let mut code = vec![];
+    let mut datas = vec![];
+    let mut last_section = 0;
+
+    // Sections are optional, but have to appear in a fixed order.
+    while !wasm.is_at_end() {
+        let section_code = wasm.byte();
+        wasm.back();
+
+        if section_order(section_code) <= last_section {
+            return Err(RuntimeError::InvalidSectionCode);
+        }
+        last_section = section_order(section_code);
+
+        match section_code {
+            section::TYPE => types = parse_type_section(wasm)?,
+            section::IMPORT => imports = parse_import_section(wasm)?,
+            section::FUNC => funcs = parse_func_section(wasm)?,
+            section::TABLE => tables = parse_table_section(wasm)?,
+            section::MEMORY => memories = parse_memory_section(wasm)?,
+            section::TAG => tags = parse_tag_section(wasm)?,
+            section::GLOBAL => globals = parse_global_section(wasm)?,
+            section::EXPORT => exports = parse_export_section(wasm)?,
+            section::START => start = Some(parse_start_section(wasm)?),
+            section::ELEM => elems = parse_elem_section(wasm)?,
+            section::DATA_COUNT => data_count = Some(parse_data_count_section(wasm)?),
+            section::CODE => code = parse_code_section(wasm)?,
+            section::DATA => datas = parse_data_section(wasm)?,
+            _ => return Err(RuntimeError::InvalidSectionCode),
+        }
+    }
+
+    if data_count.is_some_and(|count| count as usize != datas.len()) {
+        return Err(RuntimeError::DataCountMismatch);
+    }
+
+    if funcs.len() != code.len() {
+        return Err(RuntimeError::FuncCodeMismatch);
+    }
+
+    let funcs = funcs
+        .into_iter()
+        .zip(code)
+        .map(|(f_type, (locals, body))| Func {
+            f_type,
+            locals,
+            body,
+        })
+        .collect();
+
+    Ok(Module {
+        types,
+        imports,
+        funcs,
+        tables,
+        memories,
+        tags,
+        globals,
+        elems,
+        datas,
+        exports,
+        start,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_code_section_test() {
+        let wasm = vec![
+            0x0a, // section code
+            0x09, // section size
+            0x01, // num function
+            // function body 0
+            0x07, // func body size
+            0x00, // local decl count
+            0x20, // local.get
+            0x00, // local index
+            0x20, // local.get
+            0x01, // local index
+            0x6a, // i32.add
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
+
+        let (locals, instructions) = parse_code_section(&reader).unwrap()[0].clone();
+
+        assert_eq!(Vec::<ValueType>::new(), locals);
+        assert_eq!(
+            vec![Instr::LocalGet(0), Instr::LocalGet(1), Instr::I32Add],
+            instructions
+        );
+    }
+
+    #[test]
+    fn parse_code_section_i32_numeric_test() {
+        let wasm = vec![
+            0x0a, // section code
+            0x0a, // section size
+            0x01, // num function
+            0x08, // func body size
+            0x00, // local decl count
+            0x46, // i32.eq
+            0x49, // i32.lt_u
+            0x4f, // i32.ge_u
+            0x67, // i32.clz
+            0x6d, // i32.div_s
+            0x70, // i32.rem_u
+            0x75, // i32.shr_s
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
+
+        let (_, instructions) = parse_code_section(&reader).unwrap()[0].clone();
+
+        assert_eq!(
+            vec![
+                Instr::I32Eq,
+                Instr::I32LtU,
+                Instr::I32GeU,
+                Instr::I32Clz,
+                Instr::I32DivS,
+                Instr::I32RemU,
+                Instr::I32ShrS
+            ],
+            instructions
+        );
+    }
+
+    #[test]
+    fn parse_code_section_i64_numeric_test() {
+        let wasm = vec![
+            0x0a, // section code
+            0x08, // section size
+            0x01, // num function
+            0x06, // func body size
+            0x00, // local decl count
+            0x50, // i64.eqz
+            0x5a, // i64.ge_u
+            0x7f, // i64.div_s
+            0x8a, // i64.rotr
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
+
+        let (_, instructions) = parse_code_section(&reader).unwrap()[0].clone();
+
+        assert_eq!(
+            vec![Instr::I64Eqz, Instr::I64GeU, Instr::I64DivS, Instr::I64Rotr],
+            instructions
+        );
+    }
+
+    #[test]
+    fn parse_code_section_const_test() {
+        let wasm = vec![
+            0x0a, // section code
+            0x12, // section size
+            0x01, // num function
+            0x10, // func body size
+            0x00, // local decl count
+            0x41, 0x7f, // i32.const -1
+            0x42, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80,
+            0x7f, // i64.const i64::MIN
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
+
+        let (_, instructions) = parse_code_section(&reader).unwrap()[0].clone();
+
+        assert_eq!(
+            vec![Instr::I32Const(-1), Instr::I64Const(i64::MIN)],
+            instructions
+        );
+    }
+
+    #[test]
+    fn parse_code_section_float_test() {
+        let wasm = vec![
+            0x0a, // section code
+            0x15, // section size
+            0x01, // num function
+            0x13, // func body size
+            0x01, // local decl count
+            0x01, 0x7c, // 1 x f64
+            0x43, 0x00, 0x00, 0xc0, 0x7f, // f32.const nan
+            0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x3f, // f64.const 1
+            0x5b, // f32.eq
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
+
+        let (locals, instructions) = parse_code_section(&reader).unwrap()[0].clone();
+
+        assert_eq!(vec![ValueType::F64], locals);
+        assert!(matches!(instructions[0], Instr::F32Const(v) if v.is_nan()));
+        assert_eq!(vec![Instr::F64Const(1.0), Instr::F32Eq], instructions[1..]);
+    }
+
+    #[test]
+    fn parse_code_section_conversion_test() {
+        let wasm = vec![
+            0x0a, // section code
+            0x0b, // section size
+            0x01, // num function
+            0x09, // func body size
+            0x00, // local decl count
+            0xac, // i64.extend_i32_s
+            0xbf, // f64.reinterpret_i64
+            0xfc, 0x03, // i32.trunc_sat_f64_u
+            0xfc, 0x84, 0x00, // i64.trunc_sat_f32_s, padded sub opcode
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
+
+        let (_, instructions) = parse_code_section(&reader).unwrap()[0].clone();
+
+        assert_eq!(
+            vec![
+                Instr::I64ExtendI32S,
+                Instr::F64ReinterpretI64,
+                Instr::I32TruncSatF64U,
+                Instr::I64TruncSatF32S
+            ],
+            instructions
+        );
+
+        let wasm = vec![0x0a, 0x06, 0x01, 0x04, 0x00, 0xfc, 0x7f, 0x0b];
+        let reader = Reader::new(wasm);
+        assert_eq!(
+            Err(RuntimeError::InvalidInstruction),
+            parse_code_section(&reader)
+        );
+    }
+
+    #[test]
+    fn parse_code_section_sign_extension_test() {
+        let wasm = vec![
+            0x0a, // section code
+            0x09, // section size
+            0x01, // num function
+            0x07, // func body size
+            0x00, // local decl count
+            0xc0, // i32.extend8_s
+            0xc1, // i32.extend16_s
+            0xc2, // i64.extend8_s
+            0xc3, // i64.extend16_s
+            0xc4, // i64.extend32_s
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
+
+        let (_, instructions) = parse_code_section(&reader).unwrap()[0].clone();
+
+        assert_eq!(
+            vec![
+                Instr::I32Extend8S,
+                Instr::I32Extend16S,
+                Instr::I64Extend8S,
+                Instr::I64Extend16S,
+                Instr::I64Extend32S
+            ],
+            instructions
+        );
+    }
+
+    #[test]
+    fn parse_code_section_control_test() {
+        let wasm = vec![
+            0x0a, // section code
+            0x1a, // section size
+            0x01, // num function
+            0x18, // func body size
+            0x00, // local decl count
+            0x02, 0x7f, // block (result i32)
+            0x03, 0x40, // loop
+            0x0c, 0x01, // br 1
+            0x0d, 0x00, // br_if 0
+            0x0b, // end
+            0x04, 0x40, // if
+            0x0f, // return
+            0x05, // else
+            0x41, 0x01, // i32.const 1
+            0x0b, // end
+            0x0e, 0x02, 0x00, 0x01, 0x00, // br_table 0 1 0
+            0x0b, // end
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
+
+        let (_, instructions) = parse_code_section(&reader).unwrap()[0].clone();
+
+        assert_eq!(
+            vec![Instr::Block(
+                BlockType::Value(ValueType::I32),
+                vec![
+                    Instr::Loop(BlockType::Empty, vec![Instr::Br(1), Instr::BrIf(0)]),
+                    Instr::If(
+                        BlockType::Empty,
+                        vec![Instr::Return],
+                        vec![Instr::I32Const(1)]
+                    ),
+                    Instr::BrTable(vec![0, 1], 0),
+                ],
+            )],
+            instructions
+        );
+
+        let wasm = vec![0x0a, 0x07, 0x01, 0x05, 0x00, 0x02, 0x40, 0x05, 0x0b];
+        let reader = Reader::new(wasm);
+        assert_eq!(
+            Err(RuntimeError::InvalidInstruction),
+            parse_code_section(&reader)
+        );
+    }
+
+    #[test]
+    fn parse_block_type_test() {
+        let parse = |bytes: &[u8]| parse_block_type(&Reader::new(bytes.to_vec()));
+        assert_eq!(Ok(BlockType::Empty), parse(&[0x40]));
+        assert_eq!(Ok(BlockType::Value(ValueType::F64)), parse(&[0x7c]));
+        assert_eq!(Ok(BlockType::TypeIdx(1)), parse(&[0x01]));
+        assert_eq!(Ok(BlockType::TypeIdx(64)), parse(&[0xc0, 0x00]));
+        assert_eq!(Err(RuntimeError::InvalidValueType), parse(&[0x7a]));
+        assert_eq!(Err(RuntimeError::IntegerTooLarge), parse(&[0x80, 0x7f]));
+    }
+
+    #[test]
+    fn parse_nested_blocks_test() {
+        // `n` nested empty blocks in a function body.
+        let parse = |n: usize| {
+            let wasm = [[0x02, 0x40].repeat(n), vec![0x0b; n + 1]].concat();
+            parse_block(&Reader::new(wasm), 1).map(|_| ())
+        };
+        assert_eq!(Ok(()), parse(MAX_NESTING_DEPTH - 1));
+        assert_eq!(Err(RuntimeError::NestingTooDeep), parse(MAX_NESTING_DEPTH));
+        assert_eq!(Err(RuntimeError::NestingTooDeep), parse(100_000));
+    }
+
+    #[test]
+    fn parse_code_section_call_test() {
+        let wasm = vec![
+            0x0a, // section code
+            0x0e, // section size
+            0x01, // num function
+            0x0c, // func body size
+            0x00, // local decl count
+            0x20, 0x00, // local.get 0
+            0x10, 0x00, // call 0
+            0x10, 0x81, 0x01, // call 129
+            0x11, 0x02, 0x00, // call_indirect type 2, table 0
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
+
+        let (_, instructions) = parse_code_section(&reader).unwrap()[0].clone();
+
+        assert_eq!(
+            vec![
+                Instr::LocalGet(0),
+                Instr::Call(0),
+                Instr::Call(129),
+                Instr::CallIndirect(2, 0)
+            ],
+            instructions
+        );
+    }
+
+    #[test]
+    fn parse_code_section_parametric_test() {
+        let wasm = vec![
+            0x0a, // section code
+            0x0b, // section size
+            0x01, // num function
+            0x09, // func body size
+            0x00, // local decl count
+            0x01, // nop
+            0x1a, // drop
+            0x1b, // select
+            0x1c, 0x01, 0x7e, // select (result i64)
+            0x00, // unreachable
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
+
+        let (_, instructions) = parse_code_section(&reader).unwrap()[0].clone();
+
+        assert_eq!(
+            vec![
+                Instr::Nop,
+                Instr::Drop,
+                Instr::Select(None),
+                Instr::Select(Some(ValueType::I64)),
+                Instr::Unreachable
+            ],
+            instructions
+        );
+
+        let wasm = vec![0x0a, 0x07, 0x01, 0x05, 0x00, 0x1c, 0x02, 0x7f, 0x7f, 0x0b];
+        let reader = Reader::new(wasm);
+        assert_eq!(
+            Err(RuntimeError::InvalidInstruction),
+            parse_code_section(&reader)
+        );
+    }
+
+    #[test]
+    fn parse_code_section_reference_test() {
+        let wasm = vec![
+            0x0a, // section code
+            0x23, // section size
+            0x01, // num function
+            0x21, // func body size
+            0x00, // local decl count
+            0xd0, 0x6f, // ref.null extern
+            0xd0, 0x02, // ref.null 2
+            0xd1, // ref.is_null
+            0xd2, 0x03, // ref.func 3
+            0x25, 0x00, // table.get 0
+            0x26, 0x01, // table.set 1
+            0xfc, 0x0c, 0x02, 0x01, // table.init 1 2
+            0xfc, 0x0d, 0x02, // elem.drop 2
+            0xfc, 0x0e, 0x01, 0x00, // table.copy 1 0
+            0xfc, 0x0f, 0x00, // table.grow 0
+            0xfc, 0x10, 0x01, // table.size 1
+            0xfc, 0x11, 0x00, // table.fill 0
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
+
+        let (_, instructions) = parse_code_section(&reader).unwrap()[0].clone();
+
+        assert_eq!(
+            vec![
+                Instr::RefNull(HeapType::Extern),
+                Instr::RefNull(HeapType::Type(2)),
+                Instr::RefIsNull,
+                Instr::RefFunc(3),
+                Instr::TableGet(0),
+                Instr::TableSet(1),
+                Instr::TableInit(1, 2),
+                Instr::ElemDrop(2),
+                Instr::TableCopy(1, 0),
+                Instr::TableGrow(0),
+                Instr::TableSize(1),
+                Instr::TableFill(0),
+            ],
+            instructions
+        );
+
+        let wasm = vec![0x0a, 0x06, 0x01, 0x04, 0x00, 0xd0, 0x7f, 0x0b];
+        let reader = Reader::new(wasm);
+        assert_eq!(
+            Err(RuntimeError::InvalidRefType),
+            parse_code_section(&reader)
+        );
+    }
+
+    #[test]
+    fn parse_code_section_locals_test() {
+        let wasm = vec![
+            0x0a, // section code
+            0x0e, // section size
+            0x01, // num function
+            0x0c, // func body size
+            0x03, // local decl count
+            0x02, 0x7f, // 2 x i32
+            0x00, 0x7c, // 0 x f64
+            0x01, 0x7e, // 1 x i64
+            0x21, 0x00, // local.set 0
+            0x22, 0x02, // local.tee 2
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
+
+        let (locals, instructions) = parse_code_section(&reader).unwrap()[0].clone();
+
+        assert_eq!(vec![ValueType::I32, ValueType::I32, ValueType::I64], locals);
+        assert_eq!(vec![Instr::LocalSet(0), Instr::LocalTee(2)], instructions);
+
+        let wasm = vec![
+            0x0a, 0x10, 0x01, 0x0e, 0x02, // two local groups
+            0xff, 0xff, 0xff, 0xff, 0x0f, 0x7f, // u32::MAX x i32
+            0x01, 0x7f, // 1 x i32
+            0x0b,
+        ];
+        let reader = Reader::new(wasm);
+        assert_eq!(
+            Err(RuntimeError::TooManyLocals),
+            parse_code_section(&reader)
+        );
+    }
+
+    #[test]
+    fn parse_code_section_memory_test() {
+        let wasm = vec![
+            0x0a, // section code
+            0x12, // section size
+            0x01, // num function
+            0x10, // func body size
+            0x00, // local decl count
+            0x28, 0x02, 0x04, // i32.load offset=4
+            0x31, 0x00, 0x80, 0x02, // i64.load8_u offset=256 align=1
+            0x3b, 0x01, 0x00, // i32.store16
+            0x3f, 0x00, // memory.size
+            0x40, 0x00, // memory.grow
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
+
+        let (_, instructions) = parse_code_section(&reader).unwrap()[0].clone();
+
+        assert_eq!(
+            vec![
+                Instr::I32Load(MemArg {
+                    align: 2,
+                    offset: 4,
+                    memory: 0,
+                }),
+                Instr::I64Load8U(MemArg {
+                    align: 0,
+                    offset: 256,
+                    memory: 0,
+                }),
+                Instr::I32Store16(MemArg {
+                    align: 1,
+                    offset: 0,
+                    memory: 0,
+                }),
+                Instr::MemorySize(0),
+                Instr::MemoryGrow(0)
+            ],
+            instructions
+        );
+
+        // The alignment of accesses to other memories than 0 is flagged.
+        let wasm = vec![
+            0x0a, 0x0c, 0x01, 0x0a, 0x00, // code section with one function
+            0x3f, 0x01, // memory.size 1
+            0x28, 0x42, 0x02, 0x04, // i32.load 2 offset=4
+            0xfc, 0x0b, 0x01, // memory.fill 1
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
+        let (_, instructions) = parse_code_section(&reader).unwrap()[0].clone();
+        assert_eq!(
+            vec![
+                Instr::MemorySize(1),
+                Instr::I32Load(MemArg {
+                    align: 2,
+                    offset: 4,
+                    memory: 2,
+                }),
+                Instr::MemoryFill(1)
+            ],
+            instructions
+        );
+
+        // Offsets of 64-bit memories may exceed `u32`.
+        let wasm = vec![
+            0x0a, 0x0b, 0x01, 0x09, 0x00, // code section with one function
+            0x29, 0x03, 0x80, 0x80, 0x80, 0x80, 0x10, // i64.load offset=2^32
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
+        let (_, instructions) = parse_code_section(&reader).unwrap()[0].clone();
+        let memarg = MemArg {
+            align: 3,
+            offset: 1 << 32,
+            memory: 0,
+        };
+        assert_eq!(vec![Instr::I64Load(memarg)], instructions);
+    }
 
-    fn parse_valuetype(wasm: &Reader) -> Result<ValueType, RuntimeError> {
-        match wasm.byte() {
-            0x7f => Ok(ValueType::I32),
-            0x7e => Ok(ValueType::I64),
-            _ => Err(RuntimeError::InvalidValueType),
-        }
+    #[test]
+    fn parse_code_section_bulk_memory_test() {
+        let wasm = vec![
+            0x0a, 0x12, 0x01, 0x10, 0x00, // code section with one function
+            0xfc, 0x08, 0x01, 0x00, // memory.init 1
+            0xfc, 0x09, 0x01, // data.drop 1
+            0xfc, 0x0a, 0x00, 0x00, // memory.copy
+            0xfc, 0x0b, 0x00, // memory.fill
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
+
+        let (_, instructions) = parse_code_section(&reader).unwrap()[0].clone();
+
+        assert_eq!(
+            vec![
+                Instr::MemoryInit(0, 1),
+                Instr::DataDrop(1),
+                Instr::MemoryCopy(0, 0),
+                Instr::MemoryFill(0)
+            ],
+            instructions
+        );
     }
 
-    for _ in 0..num_types {
-        let _func = wasm.byte();
+    #[test]
+    fn parse_code_section_simd_test() {
+        let wasm = [
+            vec![0x0a, 0x27, 0x01, 0x25, 0x01, 0x01, 0x7b], // one v128 local
+            vec![0xfd, 0x0c],                               // v128.const
+            (0..16).collect(),
+            vec![0xfd, 0x00, 0x04, 0x10],       // v128.load offset=16
+            vec![0xfd, 0x58, 0x00, 0x00, 0x0f], // v128.store8_lane 15
+            vec![0xfd, 0x17, 0x03],             // i8x16.replace_lane 3
+            vec![0xfd, 0xba, 0x01],             // i32x4.dot_i16x8_s
+            vec![0x0b],                         // end
+        ]
+        .concat();
+        let reader = Reader::new(wasm);
 
-        // parse params
-        let mut params = vec![];
-        for _ in 0..wasm.byte() {
-            params.push(parse_valuetype(wasm)?);
-        }
+        let (locals, instructions) = parse_code_section(&reader).unwrap()[0].clone();
 
-        // parse results
-        let mut results = vec![];
-        for _ in 0..wasm.byte() {
-            results.push(parse_valuetype(wasm)?);
-        }
+        let memarg = |align, offset| MemArg {
+            align,
+            offset,
+            memory: 0,
+        };
+        assert_eq!(vec![ValueType::V128], locals);
+        assert_eq!(
+            vec![
+                Instr::V128Const(0x0f0e0d0c_0b0a0908_07060504_03020100),
+                Instr::V128Load(memarg(4, 16)),
+                Instr::V128Store8Lane(memarg(0, 0), 15),
+                Instr::I8x16ReplaceLane(3),
+                Instr::Simd(SimdOp::I32x4DotI16x8S),
+            ],
+            instructions
+        );
 
-        types.push((params, results));
+        let reader = Reader::new(vec![0x0a, 0x06, 0x01, 0x04, 0x00, 0xfd, 0x9a, 0x01]);
+        assert_eq!(
+            Err(RuntimeError::InvalidInstruction),
+            parse_code_section(&reader)
+        );
     }
 
-    Ok(types)
-}
+    #[test]
+    fn parse_code_section_tail_call_test() {
+        let wasm = vec![
+            0x0a, 0x0a, 0x01, 0x08, 0x00, // one function without locals
+            0x12, 0x82, 0x01, // return_call 130
+            0x13, 0x02, 0x01, // return_call_indirect 2 1
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
+        assert_eq!(
+            vec![Instr::ReturnCall(130), Instr::ReturnCallIndirect(2, 1)],
+            parse_code_section(&reader).unwrap()[0].1
+        );
+    }
 
-fn parse_func_section(wasm: &Reader) -> Result<Vec<i32>, RuntimeError> {
-    if wasm.byte() != section::FUNC {
-        return Err(RuntimeError::InvalidSectionCode);
+    #[test]
+    fn parse_code_section_func_ref_test() {
+        let wasm = vec![
+            0x0a, 0x11, 0x01, 0x0f, 0x00, // one function without locals
+            0x14, 0x01, // call_ref 1
+            0x15, 0x00, // return_call_ref 0
+            0xd4, // ref.as_non_null
+            0xd5, 0x02, // br_on_null 2
+            0xd6, 0x00, // br_on_non_null 0
+            0x1c, 0x01, 0x64, 0x01, // select (result (ref 1))
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
+        let select = Instr::Select(Some(ValueType::Ref {
+            nullable: false,
+            type_idx: 1,
+        }));
+        assert_eq!(
+            vec![
+                Instr::CallRef(1),
+                Instr::ReturnCallRef(0),
+                Instr::RefAsNonNull,
+                Instr::BrOnNull(2),
+                Instr::BrOnNonNull(0),
+                select
+            ],
+            parse_code_section(&reader).unwrap()[0].1
+        );
     }
 
-    let _size = wasm.byte();
-    let num = wasm.byte();
-    let mut f_types = vec![];
+    #[test]
+    fn parse_code_section_exception_test() {
+        let wasm = vec![
+            0x0a, 0x13, 0x01, 0x11, 0x00, // one function without locals
+            0x1f, 0x40, 0x04, // try_table with four catch clauses
+            0x00, 0x01, 0x00, // catch 1 0
+            0x01, 0x02, 0x01, // catch_ref 2 1
+            0x02, 0x02, // catch_all 2
+            0x03, 0x03, // catch_all_ref 3
+            0x08, 0x01, // throw 1
+            0x0b, // end
+            0x0a, // throw_ref
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
+        let catches = vec![
+            Catch::Tag(1, 0),
+            Catch::TagRef(2, 1),
+            Catch::All(2),
+            Catch::AllRef(3),
+        ];
+        assert_eq!(
+            vec![
+                Instr::TryTable(BlockType::Empty, catches, vec![Instr::Throw(1)]),
+                Instr::ThrowRef
+            ],
+            parse_code_section(&reader).unwrap()[0].1
+        );
 
-    for _ in 0..num {
-        f_types.push(wasm.byte() as i32)
+        let wasm = vec![0x0a, 0x07, 0x01, 0x05, 0x00, 0x1f, 0x40, 0x01, 0x04];
+        let reader = Reader::new(wasm);
+        assert_eq!(
+            Err(RuntimeError::InvalidInstruction),
+            parse_code_section(&reader)
+        );
     }
 
-    Ok(f_types)
-}
+    #[test]
+    fn parse_code_section_atomic_test() {
+        let wasm = vec![
+            0x0a, 0x13, 0x01, 0x11, 0x00, // one function without locals
+            0xfe, 0x00, 0x02, 0x00, // memory.atomic.notify
+            0xfe, 0x03, 0x00, // atomic.fence
+            0xfe, 0x12, 0x00, 0x04, // i32.atomic.load8_u offset=4
+            0xfe, 0x4d, 0x01, 0x08, // i64.atomic.rmw16.cmpxchg_u offset=8
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
 
-fn parse_export_section(wasm: &Reader) -> Result<Vec<Export>, RuntimeError> {
-    if wasm.byte() != section::EXPORT {
-        return Err(RuntimeError::InvalidSectionCode);
+        let memarg = |align, offset| MemArg {
+            align,
+            offset,
+            memory: 0,
+        };
+        assert_eq!(
+            vec![
+                Instr::MemoryAtomicNotify(memarg(2, 0)),
+                Instr::AtomicFence,
+                Instr::AtomicLoad(AtomicWidth::I32U8, memarg(0, 4)),
+                Instr::AtomicRmw(AtomicRmwOp::Cmpxchg, AtomicWidth::I64U16, memarg(1, 8)),
+            ],
+            parse_code_section(&reader).unwrap()[0].1
+        );
+
+        for code in [vec![0xfe, 0x03, 0x01], vec![0xfe, 0x4f, 0x00, 0x00]] {
+            let size = code.len() as u8 + 2;
+            let wasm = [vec![0x0a, size + 2, 0x01, size, 0x00], code, vec![0x0b]].concat();
+            assert_eq!(
+                Err(RuntimeError::InvalidInstruction),
+                parse_code_section(&Reader::new(wasm))
+            );
+        }
     }
 
-    let _size = wasm.byte();
-    let num = wasm.byte();
-    let mut exports = vec![];
+    #[test]
+    fn parse_data_section_test() {
+        let wasm = vec![
+            0x0b, // section code
+            0x11, // section size
+            0x03, // num data segments
+            0x00, 0x41, 0x08, 0x0b, // active, offset i32.const 8
+            0x02, 0x68, 0x69, // "hi"
+            0x01, // passive
+            0x01, 0xff, // bytes
+            0x02, 0x00, 0x41, 0x00, 0x0b, // active in memory 0, offset i32.const 0
+            0x00, // no bytes
+        ];
+        let reader = Reader::new(wasm);
 
-    for _ in 0..num {
-        let length = wasm.byte();
-        let name = match std::str::from_utf8(wasm.bytes(length.into())) {
-            Ok(n) => n.to_string(),
-            Err(_) => return Err(RuntimeError::InvalidExportName),
-        };
-        let _zero = wasm.byte();
-        let e_desc = match wasm.byte() {
-            0x00 => EDesc::FuncExport(0),
-            _ => return Err(RuntimeError::InvalidExportType),
-        };
+        assert_eq!(
+            Ok(vec![
+                Data {
+                    init: b"hi".to_vec(),
+                    mode: DataMode::Active {
+                        memory: 0,
+                        offset: vec![Instr::I32Const(8)]
+                    }
+                },
+                Data {
+                    init: vec![0xff],
+                    mode: DataMode::Passive
+                },
+                Data {
+                    init: vec![],
+                    mode: DataMode::Active {
+                        memory: 0,
+                        offset: vec![Instr::I32Const(0)]
+                    }
+                }
+            ]),
+            parse_data_section(&reader)
+        );
 
-        exports.push(Export { name, e_desc })
+        let reader = Reader::new(vec![0x0b, 0x02, 0x01, 0x03]);
+        assert_eq!(
+            Err(RuntimeError::InvalidDataMode),
+            parse_data_section(&reader)
+        );
     }
 
-    Ok(exports)
-}
+    #[test]
+    fn parse_table_section_test() {
+        let wasm = vec![0x04, 0x05, 0x01, 0x70, 0x01, 0x01, 0x02];
+        let reader = Reader::new(wasm);
 
-pub fn parse_code_section(wasm: &Reader) -> Result<Vec<(StackType, Vec<Instr>)>, RuntimeError> {
-    if wasm.byte() != section::CODE {
-        return Err(RuntimeError::InvalidSectionCode);
-    };
+        assert_eq!(
+            Ok(vec![Table {
+                limits: Limits {
+                    min: 1,
+                    max: Some(2)
+                },
+                ref_type: RefType::FuncRef
+            }]),
+            parse_table_section(&reader)
+        );
 
-    let _size = wasm.byte();
-    let num = wasm.byte();
-    let mut code = vec![];
+        let reader = Reader::new(vec![0x04, 0x04, 0x01, 0x7f, 0x00, 0x01]);
+        assert_eq!(
+            Err(RuntimeError::InvalidRefType),
+            parse_table_section(&reader)
+        );
+    }
 
-    for _ in 0..num {
-        let _size = wasm.byte();
-        let num_locals = wasm.byte() as i32;
-        let mut locals = vec![];
-        let mut instrs = vec![];
-
-        for _ in 0..num_locals {
-            let vt = match wasm.byte() {
-                0x7f => ValueType::I32,
-                0x7e => ValueType::I64,
-                _ => return Err(RuntimeError::InvalidValueType),
-            };
-            locals.push(vt);
-        }
+    #[test]
+    fn parse_elem_section_test() {
+        let wasm = vec![
+            0x09, // section code
+            0x17, // section size
+            0x04, // num element segments
+            0x00, 0x41, 0x00, 0x0b, // active, offset i32.const 0
+            0x02, 0x00, 0x81, 0x01, // funcs 0 129
+            0x01, 0x00, 0x00, // passive funcref without funcs
+            0x02, 0x01, 0x41, 0x01, 0x0b, 0x00, // active in table 1, offset i32.const 1
+            0x01, 0x00, // func 0
+            0x03, 0x00, 0x00, // declarative funcref without funcs
+        ];
+        let reader = Reader::new(wasm);
+        let elem = |funcs: Vec<usize>, mode| Elem {
+            ref_type: RefType::FuncRef,
+            init: funcs.into_iter().map(|f| vec![Instr::RefFunc(f)]).collect(),
+            mode,
+        };
 
-        loop {
-            let instr = match wasm.byte() {
-                0x20 => Instr::LocalGet(wasm.byte() as usize),
-                0x6a => Instr::I32Add,
-                0x0b => break,
-                _ => return Err(RuntimeError::InvalidInstruction),
-            };
+        assert_eq!(
+            Ok(vec![
+                elem(
+                    vec![0, 129],
+                    ElemMode::Active {
+                        table: 0,
+                        offset: vec![Instr::I32Const(0)]
+                    }
+                ),
+                elem(vec![], ElemMode::Passive),
+                elem(
+                    vec![0],
+                    ElemMode::Active {
+                        table: 1,
+                        offset: vec![Instr::I32Const(1)]
+                    }
+                ),
+                elem(vec![], ElemMode::Declarative)
+            ]),
+            parse_elem_section(&reader)
+        );
 
-            instrs.push(instr);
-        }
+        let reader = Reader::new(vec![0x09, 0x03, 0x01, 0x01, 0x01]);
+        assert_eq!(
+            Err(RuntimeError::InvalidRefType),
+            parse_elem_section(&reader)
+        );
+        let reader = Reader::new(vec![0x09, 0x02, 0x01, 0x08]);
+        assert_eq!(
+            Err(RuntimeError::InvalidElemMode),
+            parse_elem_section(&reader)
+        );
+    }
 
-        code.push((locals, instrs));
+    #[test]
+    fn parse_elem_exprs_test() {
+        let wasm = vec![
+            0x09, // section code
+            0x1f, // section size
+            0x04, // num element segments
+            0x04, 0x41, 0x00, 0x0b, // active, offset i32.const 0
+            0x01, 0xd2, 0x02, 0x0b, // ref.func 2
+            0x05, 0x6f, // passive externref
+            0x01, 0xd0, 0x6f, 0x0b, // ref.null extern
+            0x06, 0x01, 0x41, 0x01, 0x0b, 0x70, // active in table 1, funcref
+            0x02, 0xd0, 0x70, 0x0b, 0x23, 0x00, 0x0b, // ref.null func, global.get 0
+            0x07, 0x70, 0x00, // declarative funcref without expressions
+        ];
+        let reader = Reader::new(wasm);
+        let active = |table, offset| ElemMode::Active {
+            table,
+            offset: vec![Instr::I32Const(offset)],
+        };
+
+        assert_eq!(
+            Ok(vec![
+                Elem {
+                    ref_type: RefType::FuncRef,
+                    init: vec![vec![Instr::RefFunc(2)]],
+                    mode: active(0, 0),
+                },
+                Elem {
+                    ref_type: RefType::ExternRef,
+                    init: vec![vec![Instr::RefNull(HeapType::Extern)]],
+                    mode: ElemMode::Passive,
+                },
+                Elem {
+                    ref_type: RefType::FuncRef,
+                    init: vec![
+                        vec![Instr::RefNull(HeapType::Func)],
+                        vec![Instr::GlobalGet(0)]
+                    ],
+                    mode: active(1, 1),
+                },
+                Elem {
+                    ref_type: RefType::FuncRef,
+                    init: vec![],
+                    mode: ElemMode::Declarative,
+                },
+            ]),
+            parse_elem_section(&reader)
+        );
     }
 
-    Ok(code)
-}
+    #[test]
+    fn parse_memory_section_test() {
+        let wasm = vec![
+            0x05, // section memory
+            0x07, // section size
+            0x02, // num memories
+            0x00, 0x01, // min 1
+            0x01, 0x00, 0x80, 0x01, // min 0, max 128
+        ];
+        let reader = Reader::new(wasm);
 
-pub fn parse_wasm(wasm: &Reader) -> Result<Module, RuntimeError> {
-    check_header(wasm)?;
-    let types = parse_type_section(wasm)?;
-    let funcs = parse_func_section(wasm)?;
-    let exports = parse_export_section(wasm)?;
-    let code = parse_code_section(wasm)?;
-
-    let join_code_func = || {
-        funcs
-            .iter()
-            .enumerate()
-            .map(|(i, f)| Func {
-                f_type: *f,
-                locals: code[i].0.clone(),
-                body: code[i].1.clone(),
-            })
-            .collect::<Vec<Func>>()
-    };
+        assert_eq!(
+            vec![
+                Memory {
+                    limits: Limits { min: 1, max: None },
+                    shared: false,
+                    memory64: false,
+                },
+                Memory {
+                    limits: Limits {
+                        min: 0,
+                        max: Some(128)
+                    },
+                    shared: false,
+                    memory64: false,
+                }
+            ],
+            parse_memory_section(&reader).unwrap()
+        );
 
-    Ok(Module {
-        types,
-        exports,
-        funcs: join_code_func(),
-    })
-}
+        let wasm = vec![0x05, 0x04, 0x01, 0x03, 0x01, 0x02];
+        let reader = Reader::new(wasm);
+        assert_eq!(
+            vec![Memory {
+                limits: Limits {
+                    min: 1,
+                    max: Some(2)
+                },
+                shared: true,
+                memory64: false,
+            }],
+            parse_memory_section(&reader).unwrap()
+        );
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        // The limits of 64-bit memories may exceed `u32`.
+        let wasm = vec![0x05, 0x07, 0x01, 0x04, 0x80, 0x80, 0x80, 0x80, 0x10];
+        let reader = Reader::new(wasm);
+        assert_eq!(
+            vec![Memory {
+                limits: Limits {
+                    min: 1 << 32,
+                    max: None
+                },
+                shared: false,
+                memory64: true,
+            }],
+            parse_memory_section(&reader).unwrap()
+        );
+        let wasm = vec![0x05, 0x07, 0x01, 0x00, 0x80, 0x80, 0x80, 0x80, 0x10];
+        let reader = Reader::new(wasm);
+        assert_eq!(
+            Err(RuntimeError::IntegerTooLarge),
+            parse_memory_section(&reader)
+        );
+
+        let wasm = vec![0x05, 0x03, 0x01, 0x08, 0x01];
+        let reader = Reader::new(wasm);
+        assert_eq!(
+            Err(RuntimeError::InvalidLimits),
+            parse_memory_section(&reader)
+        );
+    }
 
     #[test]
-    fn parse_code_section_test() {
+    fn parse_code_section_overlong_const_test() {
         let wasm = vec![
             0x0a, // section code
-            0x09, // section size
+            0x0a, // section size
             0x01, // num function
-            // function body 0
-            0x07, // func body size
+            0x08, // func body size
             0x00, // local decl count
-            0x20, // local.get
-            0x00, // local index
-            0x20, // local.get
-            0x01, // local index
-            0x6a, // i32.add
+            0x41, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00, // i32.const with 6 bytes
             0x0b, // end
         ];
         let reader = Reader::new(wasm);
 
-        let (locals, instructions) = parse_code_section(&reader).unwrap()[0].clone();
-
-        assert_eq!(Vec::<ValueType>::new(), locals);
         assert_eq!(
-            vec![Instr::LocalGet(0), Instr::LocalGet(1), Instr::I32Add],
-            instructions
+            Err(RuntimeError::IntegerRepresentationTooLong),
+            parse_code_section(&reader)
         );
     }
 
@@ -208,9 +1945,8 @@ mod tests {
             0x61, // a
             0x64, // d
             0x64, // d
-            0x00, // 0
-            // export kind
-            0x00, // export func index
+            0x00, // export kind func
+            0x00, // func index
         ];
         let reader = Reader::new(wasm);
 
@@ -223,6 +1959,138 @@ mod tests {
             }],
             result
         );
+
+        let wasm = vec![
+            0x07, // section export
+            0x0e, // section size
+            0x03, // num exports
+            0x01, 0x66, // "f"
+            0x00, 0x81, 0x01, // func 129
+            0x01, 0x67, // "g"
+            0x03, 0x02, // global 2
+            0x01, 0x74, // "t"
+            0x01, 0x00, // table 0
+        ];
+        let reader = Reader::new(wasm);
+
+        assert_eq!(
+            vec![
+                Export {
+                    name: "f".to_string(),
+                    e_desc: EDesc::FuncExport(129)
+                },
+                Export {
+                    name: "g".to_string(),
+                    e_desc: EDesc::GlobalExport(2)
+                },
+                Export {
+                    name: "t".to_string(),
+                    e_desc: EDesc::TableExport(0)
+                }
+            ],
+            parse_export_section(&reader).unwrap()
+        );
+
+        let wasm = vec![0x07, 0x05, 0x01, 0x01, 0x66, 0x09, 0x00];
+        let reader = Reader::new(wasm);
+        assert_eq!(
+            Err(RuntimeError::InvalidExportType),
+            parse_export_section(&reader)
+        );
+    }
+
+    #[test]
+    fn parse_import_section_test() {
+        let wasm = vec![
+            0x02, // section import
+            0x14, // section size
+            0x03, // num imports
+            0x01, 0x6d, 0x01, 0x66, // "m" "f"
+            0x00, 0x01, // func of type 1
+            0x01, 0x6d, 0x01, 0x6d, // "m" "m"
+            0x02, 0x00, 0x01, // memory, min 1
+            0x01, 0x6d, 0x01, 0x67, // "m" "g"
+            0x03, 0x7f, 0x00, // immutable i32 global
+        ];
+        let reader = Reader::new(wasm);
+        let import = |name: &str, desc| Import {
+            module: "m".to_string(),
+            name: name.to_string(),
+            desc,
+        };
+
+        assert_eq!(
+            Ok(vec![
+                import("f", ImportDesc::Func(1)),
+                import(
+                    "m",
+                    ImportDesc::Memory(Memory {
+                        limits: Limits { min: 1, max: None },
+                        shared: false,
+                        memory64: false,
+                    })
+                ),
+                import(
+                    "g",
+                    ImportDesc::Global(GlobalType {
+                        value_type: ValueType::I32,
+                        mutable: false
+                    })
+                )
+            ]),
+            parse_import_section(&reader)
+        );
+
+        let wasm = vec![0x02, 0x07, 0x01, 0x01, 0x6d, 0x01, 0x66, 0x04, 0x00];
+        let reader = Reader::new(wasm);
+        assert_eq!(
+            Err(RuntimeError::InvalidImportType),
+            parse_import_section(&reader)
+        );
+    }
+
+    #[test]
+    fn parse_global_section_test() {
+        let wasm = vec![
+            0x06, // section global
+            0x0d, // section size
+            0x02, // num globals
+            0x7f, 0x01, // mut i32
+            0x41, 0x7f, // i32.const -1
+            0x0b, // end
+            0x7c, 0x00, // f64
+            0x23, 0x00, // global.get 0
+            0x24, 0x00, // global.set 0
+            0x0b, // end
+        ];
+        let reader = Reader::new(wasm);
+
+        assert_eq!(
+            vec![
+                Global {
+                    g_type: GlobalType {
+                        value_type: ValueType::I32,
+                        mutable: true
+                    },
+                    init: vec![Instr::I32Const(-1)]
+                },
+                Global {
+                    g_type: GlobalType {
+                        value_type: ValueType::F64,
+                        mutable: false
+                    },
+                    init: vec![Instr::GlobalGet(0), Instr::GlobalSet(0)]
+                }
+            ],
+            parse_global_section(&reader).unwrap()
+        );
+
+        let wasm = vec![0x06, 0x05, 0x01, 0x7f, 0x02, 0x41, 0x00, 0x0b];
+        let reader = Reader::new(wasm);
+        assert_eq!(
+            Err(RuntimeError::InvalidMutability),
+            parse_global_section(&reader)
+        );
     }
 
     #[test]
@@ -302,20 +2170,81 @@ mod tests {
         assert_eq!(
             Module {
                 types: vec![(vec![ValueType::I32, ValueType::I32], vec![ValueType::I32])],
+                imports: vec![],
                 funcs: vec![Func {
                     f_type: 0,
                     locals: vec![],
                     body: vec![Instr::LocalGet(0), Instr::LocalGet(1), Instr::I32Add],
                 }],
+                tables: vec![],
+                memories: vec![],
+                tags: vec![],
+                globals: vec![],
+                elems: vec![],
+                datas: vec![],
                 exports: vec![Export {
                     name: "add".to_string(),
                     e_desc: EDesc::FuncExport(0),
                 }],
+                start: None,
             },
             result
         );
     }
 
+    #[test]
+    fn parse_wasm_optional_sections_test() {
+        let header = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        let globals = [0x06, 0x06, 0x01, 0x7e, 0x00, 0x42, 0x07, 0x0b];
+        let exports = [0x07, 0x05, 0x01, 0x01, 0x67, 0x03, 0x00];
+
+        let reader = Reader::new([&header[..], &globals, &exports].concat());
+        let module = parse_wasm(&reader).unwrap();
+        assert!(module.funcs.is_empty());
+        assert_eq!(vec![Instr::I64Const(7)], module.globals[0].init);
+        assert_eq!(EDesc::GlobalExport(0), module.exports[0].e_desc);
+
+        let reader = Reader::new([&header[..], &exports, &globals].concat());
+        assert_eq!(Err(RuntimeError::InvalidSectionCode), parse_wasm(&reader));
+
+        let funcs = [0x03, 0x02, 0x01, 0x00];
+        let reader = Reader::new([&header[..], &funcs].concat());
+        assert_eq!(Err(RuntimeError::FuncCodeMismatch), parse_wasm(&reader));
+
+        // The data count section precedes the code section.
+        let data_count = [0x0c, 0x01, 0x01];
+        let code = [0x0a, 0x01, 0x00];
+        let datas = [0x0b, 0x04, 0x01, 0x01, 0x01, 0x2a];
+        let reader = Reader::new([&header[..], &data_count, &code, &datas].concat());
+        assert_eq!(vec![42], parse_wasm(&reader).unwrap().datas[0].init);
+
+        let reader = Reader::new([&header[..], &code, &data_count, &datas].concat());
+        assert_eq!(Err(RuntimeError::InvalidSectionCode), parse_wasm(&reader));
+
+        let reader = Reader::new([&header[..], &data_count].concat());
+        assert_eq!(Err(RuntimeError::DataCountMismatch), parse_wasm(&reader));
+
+        // The start section follows the export section.
+        let start = [0x08, 0x01, 0x02];
+        let reader = Reader::new([&header[..], &exports, &start].concat());
+        assert_eq!(Some(2), parse_wasm(&reader).unwrap().start);
+
+        let reader = Reader::new([&header[..], &start, &exports].concat());
+        assert_eq!(Err(RuntimeError::InvalidSectionCode), parse_wasm(&reader));
+
+        // The tag section precedes the global section.
+        let tags = [0x0d, 0x03, 0x01, 0x00, 0x02];
+        let reader = Reader::new([&header[..], &tags, &globals].concat());
+        assert_eq!(vec![2], parse_wasm(&reader).unwrap().tags);
+
+        let reader = Reader::new([&header[..], &globals, &tags].concat());
+        assert_eq!(Err(RuntimeError::InvalidSectionCode), parse_wasm(&reader));
+
+        let tags = [0x0d, 0x03, 0x01, 0x01, 0x02];
+        let reader = Reader::new([&header[..], &tags].concat());
+        assert_eq!(Err(RuntimeError::InvalidTagType), parse_wasm(&reader));
+    }
+
     #[test]
     fn check_header_test() {
         let wasm = vec![
@@ -359,4 +2288,27 @@ mod tests {
             vec![(vec![ValueType::I32, ValueType::I32], vec![ValueType::I32])]
         );
     }
+
+    #[test]
+    fn parse_ref_value_type_test() {
+        let parse = |bytes: &[u8]| parse_valuetype(&Reader::new(bytes.to_vec()));
+        assert_eq!(
+            Ok(ValueType::Ref {
+                nullable: true,
+                type_idx: 64
+            }),
+            parse(&[0x63, 0xc0, 0x00])
+        );
+        assert_eq!(
+            Ok(ValueType::Ref {
+                nullable: false,
+                type_idx: 0
+            }),
+            parse(&[0x64, 0x00])
+        );
+        // `(ref null func)` is the same as `funcref`.
+        assert_eq!(Ok(ValueType::FuncRef), parse(&[0x63, 0x70]));
+        assert_eq!(Ok(ValueType::ExternRef), parse(&[0x63, 0x6f]));
+        assert_eq!(Err(RuntimeError::InvalidValueType), parse(&[0x64, 0x70]));
+    }
 }
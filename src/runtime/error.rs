@@ -1,4 +1,7 @@
-#[derive(Debug, PartialEq, Eq)]
+use crate::runtime::value::Exception;
+use std::sync::Arc;
+
+#[derive(Debug, PartialEq)]
 pub enum RuntimeError {
     ModuleToShort,
     WrongMagicHeader,
@@ -10,4 +13,67 @@ pub enum RuntimeError {
     InvalidInstruction,
     ExportNotFound,
     InvalidArgNumber,
+    InvalidArgType,
+    IntegerTooLarge,
+    IntegerRepresentationTooLong,
+    IntegerDivideByZero,
+    IntegerOverflow,
+    InvalidConversionToInteger,
+    CallStackExhausted,
+    TooManyLocals,
+    FuncCodeMismatch,
+    InvalidMutability,
+    UnknownGlobal,
+    UnknownLocal,
+    UnknownLabel,
+    ImmutableGlobal,
+    InvalidConstExpr,
+    InvalidLimits,
+    UnknownMemory,
+    InvalidAlignment,
+    OffsetTooLarge,
+    MemoryOutOfBounds,
+    /// The host can't allocate the initial pages of a memory.
+    MemoryTooLarge,
+    InvalidDataMode,
+    DataCountMismatch,
+    UnknownData,
+    InvalidRefType,
+    InvalidElemMode,
+    UnknownTable,
+    UnknownType,
+    UnknownFunc,
+    TableOutOfBounds,
+    /// A table is created with more than `MAX_ELEMENTS` elements.
+    TableTooLarge,
+    UndefinedElement,
+    UninitializedElement,
+    IndirectCallTypeMismatch,
+    InvalidImportName,
+    InvalidImportType,
+    UnknownImport,
+    IncompatibleImportType,
+    InvalidHostResult,
+    InvalidStartFunction,
+    Unreachable,
+    UnknownElem,
+    UndeclaredFuncRef,
+    RefTypeMismatch,
+    InvalidLaneIndex,
+    UnalignedAtomic,
+    ExpectedSharedMemory,
+    UnboundedSharedMemory,
+    TailCallTypeMismatch,
+    UnknownTag,
+    InvalidTagType,
+    NullReference,
+    /// An instruction finds operands of the wrong type or number on the
+    /// stack, or a block doesn't end with its results.
+    TypeMismatch,
+    /// Blocks are nested deeper than `MAX_NESTING_DEPTH`.
+    NestingTooDeep,
+    /// A local of a non-nullable reference type is read before it is set.
+    UninitializedLocal,
+    /// An exception that no `try_table` caught, with its payload.
+    UncaughtException(Arc<Exception>),
 }
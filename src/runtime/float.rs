@@ -0,0 +1,118 @@
+//! Floating-point operations whose IEEE-754 semantics, as required by
+//! WebAssembly, differ from the corresponding Rust standard library functions.
+
+use crate::runtime::error::RuntimeError;
+
+/// `f32::min` returns the other operand if one of them is NaN and does not
+/// order `-0.0` below `0.0`. WebAssembly propagates NaN and does both.
+pub fn f32_min(a: f32, b: f32) -> f32 {
+    if a.is_nan() || b.is_nan() {
+        a + b
+    } else if a == 0.0 && b == 0.0 {
+        f32::from_bits(a.to_bits() | b.to_bits())
+    } else {
+        a.min(b)
+    }
+}
+
+pub fn f32_max(a: f32, b: f32) -> f32 {
+    if a.is_nan() || b.is_nan() {
+        a + b
+    } else if a == 0.0 && b == 0.0 {
+        f32::from_bits(a.to_bits() & b.to_bits())
+    } else {
+        a.max(b)
+    }
+}
+
+pub fn f64_min(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        a + b
+    } else if a == 0.0 && b == 0.0 {
+        f64::from_bits(a.to_bits() | b.to_bits())
+    } else {
+        a.min(b)
+    }
+}
+
+pub fn f64_max(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        a + b
+    } else if a == 0.0 && b == 0.0 {
+        f64::from_bits(a.to_bits() & b.to_bits())
+    } else {
+        a.max(b)
+    }
+}
+
+/// Rounds to the nearest integer, ties to even.
+pub fn f32_nearest(a: f32) -> f32 {
+    a.round_ties_even()
+}
+
+pub fn f64_nearest(a: f64) -> f64 {
+    a.round_ties_even()
+}
+
+/// Truncates towards zero and traps unless the result lies strictly between
+/// `lower` and `upper`. Unlike Rust's saturating `as` casts, NaN traps as well.
+pub fn trunc(a: f64, lower: f64, upper: f64) -> Result<f64, RuntimeError> {
+    if a.is_nan() {
+        Err(RuntimeError::InvalidConversionToInteger)
+    } else if a <= lower || a >= upper {
+        Err(RuntimeError::IntegerOverflow)
+    } else {
+        Ok(a.trunc())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn min_max() {
+        assert_eq!(1.0, f32_min(1.0, 2.0));
+        assert_eq!(2.0, f64_max(1.0, 2.0));
+        assert!(f32_min(f32::NAN, 1.0).is_nan());
+        assert!(f32_max(1.0, f32::NAN).is_nan());
+        assert!(f64_min(1.0, f64::NAN).is_nan());
+        assert!(f64_max(f64::NAN, 1.0).is_nan());
+        assert!(f32_min(0.0, -0.0).is_sign_negative());
+        assert!(f32_min(-0.0, 0.0).is_sign_negative());
+        assert!(f32_max(-0.0, 0.0).is_sign_positive());
+        assert!(f64_min(0.0, -0.0).is_sign_negative());
+        assert!(f64_max(0.0, -0.0).is_sign_positive());
+        assert_eq!(f64::NEG_INFINITY, f64_min(f64::NEG_INFINITY, 0.0));
+    }
+
+    #[test]
+    fn trunc_bounds() {
+        let lower = -2147483649.0;
+        let upper = 2147483648.0;
+        assert_eq!(Ok(-2147483648.0), trunc(-2147483648.9, lower, upper));
+        assert_eq!(Ok(2147483647.0), trunc(2147483647.9, lower, upper));
+        assert_eq!(
+            Err(RuntimeError::IntegerOverflow),
+            trunc(lower, lower, upper)
+        );
+        assert_eq!(
+            Err(RuntimeError::IntegerOverflow),
+            trunc(upper, lower, upper)
+        );
+        assert_eq!(
+            Err(RuntimeError::InvalidConversionToInteger),
+            trunc(f64::NAN, lower, upper)
+        );
+    }
+
+    #[test]
+    fn nearest() {
+        assert_eq!(2.0, f32_nearest(2.5));
+        assert_eq!(4.0, f32_nearest(3.5));
+        assert_eq!(-2.0, f64_nearest(-2.5));
+        assert!(f64_nearest(-0.5).is_sign_negative());
+        assert_eq!(0.0, f64_nearest(0.49999999999999994));
+        assert_eq!(4503599627370497.0, f64_nearest(4503599627370497.0));
+    }
+}
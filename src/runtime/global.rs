@@ -0,0 +1,78 @@
+use crate::ast::{GlobalType, ValueType};
+use crate::runtime::error::RuntimeError;
+use crate::runtime::error::RuntimeError::{ImmutableGlobal, InvalidArgType};
+use crate::runtime::shared::Shared;
+use crate::runtime::value::Value;
+
+/// A global variable. Clones refer to the same variable, so the host sees
+/// what the instances importing it set and vice versa.
+#[derive(Clone)]
+pub struct Global {
+    value: Shared<Value>,
+    g_type: GlobalType,
+}
+
+impl Global {
+    /// Creates a global of the type of `value`, which can only be set if it
+    /// is `mutable`.
+    pub fn new(value: Value, mutable: bool) -> Self {
+        let g_type = GlobalType {
+            value_type: value.value_type(),
+            mutable,
+        };
+        Self::with_type(value, g_type)
+    }
+
+    /// Creates a global of type `g_type`, e.g. a typed function reference,
+    /// which `value` has to match.
+    pub(crate) fn with_type(value: Value, g_type: GlobalType) -> Self {
+        Self {
+            value: Shared::new(value),
+            g_type,
+        }
+    }
+
+    pub fn global_type(&self) -> GlobalType {
+        self.g_type
+    }
+
+    /// Returns the current value.
+    pub fn get(&mut self) -> Value {
+        self.value.access().clone()
+    }
+
+    /// Sets the value of a mutable global, which has to keep its type.
+    pub fn set(&mut self, value: Value) -> Result<(), RuntimeError> {
+        if !self.g_type.mutable {
+            return Err(ImmutableGlobal);
+        }
+        // Typed function references are function references at runtime.
+        let value_type = match self.g_type.value_type {
+            ValueType::Ref { .. } => ValueType::FuncRef,
+            value_type => value_type,
+        };
+        if value.value_type() != value_type {
+            return Err(InvalidArgType);
+        }
+        *self.value.access() = value;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_share_the_value() {
+        let mut global = Global::new(Value::I32(1), true);
+        let mut clone = global.clone();
+        clone.set(Value::I32(2)).unwrap();
+        assert_eq!(Value::I32(2), global.get());
+        assert_eq!(Err(InvalidArgType), global.set(Value::I64(3)));
+
+        let mut constant = Global::new(Value::I32(1), false);
+        assert_eq!(Err(ImmutableGlobal), constant.set(Value::I32(2)));
+        assert_eq!(Value::I32(1), constant.get());
+    }
+}
@@ -0,0 +1,734 @@
+use crate::ast::{DataMode, EDesc, ElemMode, Instr, Module, ValueType};
+use crate::runtime::error::RuntimeError;
+use crate::runtime::global::Global;
+use crate::runtime::interpreter;
+use crate::runtime::linker::{HostFunc, Linker};
+use crate::runtime::memory::Memory;
+use crate::runtime::processor::Processor;
+use crate::runtime::table::Table;
+use crate::runtime::validator;
+use crate::runtime::value::{Exception, ExternRef, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// The mutable state of an instance. Imported entities come first in their
+/// index space.
+pub struct Store<T> {
+    /// The imported functions, which are all implemented by the host.
+    pub host_funcs: Vec<HostFunc<T>>,
+    pub tables: Vec<Table>,
+    pub memories: Vec<Memory>,
+    pub globals: Vec<Global>,
+    /// References of the element segments. Dropped segments are empty.
+    pub elems: Vec<Vec<Value>>,
+    /// Bytes of the data segments. Dropped segments are empty.
+    pub datas: Vec<Vec<u8>>,
+    /// Host objects that were passed into the instance. Extern references
+    /// are stored as handles of these.
+    externs: Handles<ExternRef>,
+    /// Caught exceptions, which exception references are handles of.
+    exceptions: Handles<Arc<Exception>>,
+    /// User data of the host.
+    pub data: T,
+}
+
+impl<T> Store<T> {
+    pub fn new(data: T) -> Self {
+        Self {
+            host_funcs: vec![],
+            tables: vec![],
+            memories: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            externs: Handles::new(),
+            exceptions: Handles::new(),
+            data,
+        }
+    }
+
+    /// Returns the handle of `object` and adds it if necessary.
+    pub fn extern_handle(&mut self, object: &ExternRef) -> usize {
+        self.externs.handle(object, object.as_ptr() as usize)
+    }
+
+    /// Returns the host object with the given handle.
+    pub fn extern_ref(&self, handle: usize) -> ExternRef {
+        self.externs.get(handle)
+    }
+
+    /// Returns the handle of `exception` and adds it if necessary.
+    pub fn exception_handle(&mut self, exception: &Arc<Exception>) -> usize {
+        self.exceptions
+            .handle(exception, Arc::as_ptr(exception) as usize)
+    }
+
+    /// Returns the exception with the given handle.
+    pub fn exception(&self, handle: usize) -> Arc<Exception> {
+        self.exceptions.get(handle)
+    }
+
+    /// Whether a handle table is full, so that the unreachable handles
+    /// should be freed before adding another one.
+    pub fn handles_full(&self) -> bool {
+        self.externs.is_full() || self.exceptions.is_full()
+    }
+
+    /// Frees all handles that aren't `reachable`, so that their slots are
+    /// reused.
+    pub fn retain_handles(&mut self, reachable: &HashSet<usize>) {
+        self.externs.retain(reachable);
+        self.exceptions.retain(reachable);
+    }
+
+    /// Frees all handles, so that their slots are reused. Only the operand
+    /// stack holds handles, which are gone between calls.
+    pub fn free_handles(&mut self) {
+        self.externs.clear();
+        self.exceptions.clear();
+    }
+}
+
+/// Number of handles of a kind before unreachable ones are freed.
+const MIN_HANDLES: usize = 1024;
+
+/// Objects that wasm code refers to by handle, which are indices into a
+/// list. An object keeps its handle until it is freed.
+struct Handles<T> {
+    /// Objects by handle. Freed slots are empty.
+    objects: Vec<Option<T>>,
+    /// Handles by the address of the object.
+    handles: HashMap<usize, usize>,
+    /// Freed slots of `objects`, which new handles reuse first.
+    free: Vec<usize>,
+    /// Number of slots above which unreachable handles are freed. It grows
+    /// with the number of reachable ones, so that every object is only
+    /// scanned for a few times on average.
+    limit: usize,
+}
+
+impl<T: Clone> Handles<T> {
+    fn new() -> Self {
+        Self {
+            objects: vec![],
+            handles: HashMap::new(),
+            free: vec![],
+            limit: MIN_HANDLES,
+        }
+    }
+
+    /// Returns the handle of `object` at the address `addr`, which is added
+    /// if necessary.
+    fn handle(&mut self, object: &T, addr: usize) -> usize {
+        let objects = &mut self.objects;
+        let free = &mut self.free;
+        *self
+            .handles
+            .entry(addr)
+            .or_insert_with(|| match free.pop() {
+                Some(handle) => {
+                    objects[handle] = Some(object.clone());
+                    handle
+                }
+                None => {
+                    objects.push(Some(object.clone()));
+                    objects.len() - 1
+                }
+            })
+    }
+
+    fn get(&self, handle: usize) -> T {
+        self.objects[handle]
+            .clone()
+            .expect("reachable handles are kept")
+    }
+
+    /// Whether the next handle would exceed the limit.
+    fn is_full(&self) -> bool {
+        self.free.is_empty() && self.objects.len() >= self.limit
+    }
+
+    /// Frees the objects whose handles aren't `reachable`.
+    fn retain(&mut self, reachable: &HashSet<usize>) {
+        for (handle, object) in self.objects.iter_mut().enumerate() {
+            if object.is_some() && !reachable.contains(&handle) {
+                *object = None;
+                self.free.push(handle);
+            }
+        }
+        self.handles.retain(|_, handle| reachable.contains(handle));
+        let live = self.objects.len() - self.free.len();
+        self.limit = MIN_HANDLES.max(2 * live);
+    }
+
+    /// Frees all objects.
+    fn clear(&mut self) {
+        self.objects.clear();
+        self.handles.clear();
+        self.free.clear();
+        self.limit = MIN_HANDLES;
+    }
+}
+
+/// Evaluates the offset expression of an active segment, which is an `i64`
+/// for segments of 64-bit memories.
+fn eval_offset<T>(
+    module: &Module,
+    store: &mut Store<T>,
+    offset: &[Instr],
+    value_type: ValueType,
+    max_call_depth: usize,
+) -> Result<u64, RuntimeError> {
+    let mut processor = Processor::new(module, store, max_call_depth);
+    match processor.eval_const(offset, &value_type)? {
+        Value::I32(offset) => Ok(offset as u32 as u64),
+        Value::I64(offset) => Ok(offset as u64),
+        _ => Err(RuntimeError::InvalidConstExpr),
+    }
+}
+
+/// A module together with its state, which lives as long as the instance.
+pub struct Instance<T = ()> {
+    module: Module,
+    store: Store<T>,
+    max_call_depth: usize,
+}
+
+impl Instance {
+    /// Instantiates a module without imports.
+    pub fn new(module: Module) -> Result<Self, RuntimeError> {
+        Self::with_linker(module, &Linker::new(), ())
+    }
+}
+
+impl<T> Instance<T> {
+    /// Instantiates `module` with the imports defined by `linker`. Host
+    /// functions can access `data` while they are called.
+    pub fn with_linker(module: Module, linker: &Linker<T>, data: T) -> Result<Self, RuntimeError> {
+        validator::validate(&module)?;
+
+        let mut store = Store::new(data);
+        linker.link(&module, &mut store)?;
+        for table in &module.tables {
+            store
+                .tables
+                .push(Table::new(&table.limits, table.ref_type)?);
+        }
+        for memory in &module.memories {
+            store.memories.push(Memory::with_type(memory)?);
+        }
+
+        for global in &module.globals {
+            let mut processor = Processor::new(&module, &mut store, linker.max_call_depth);
+            let value = processor.eval_const(&global.init, &global.g_type.value_type)?;
+            store.globals.push(Global::with_type(value, global.g_type));
+        }
+
+        for elem in &module.elems {
+            let mut processor = Processor::new(&module, &mut store, linker.max_call_depth);
+            let ref_type = elem.ref_type.into();
+            let elements = elem
+                .init
+                .iter()
+                .map(|init| processor.eval_const(init, &ref_type))
+                .collect::<Result<_, _>>()?;
+            store.elems.push(elements);
+        }
+        for data in &module.datas {
+            store.datas.push(data.init.clone());
+        }
+
+        // Active segments are copied into their table. Like declarative
+        // segments, they are dropped afterwards.
+        for (i, elem) in module.elems.iter().enumerate() {
+            match &elem.mode {
+                ElemMode::Active { table, offset } => {
+                    let offset = eval_offset(
+                        &module,
+                        &mut store,
+                        offset,
+                        ValueType::I32,
+                        linker.max_call_depth,
+                    )?;
+                    let elements = std::mem::take(&mut store.elems[i]);
+                    store.tables[*table].write(offset as u32, &elements)?;
+                }
+                ElemMode::Declarative => store.elems[i].clear(),
+                ElemMode::Passive => {}
+            }
+        }
+
+        // Active segments are copied into memory and dropped afterwards.
+        for (i, data) in module.datas.iter().enumerate() {
+            if let DataMode::Active { memory, offset } = &data.mode {
+                let value_type = match store.memories[*memory].is_64() {
+                    true => ValueType::I64,
+                    false => ValueType::I32,
+                };
+                let offset = eval_offset(
+                    &module,
+                    &mut store,
+                    offset,
+                    value_type,
+                    linker.max_call_depth,
+                )?;
+                store.memories[*memory].store(offset, 0, &data.init)?;
+                store.datas[i].clear();
+            }
+        }
+
+        // A trap in the start function fails the instantiation.
+        if let Some(start) = module.start {
+            let mut processor = Processor::new(&module, &mut store, linker.max_call_depth);
+            processor.execute_func(start, &[])?;
+            store.free_handles();
+        }
+
+        Ok(Self {
+            module,
+            store,
+            max_call_depth: linker.max_call_depth,
+        })
+    }
+
+    /// Calls the exported function `func` and returns all of its results.
+    pub fn invoke(&mut self, func: &str, params: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+        let results = interpreter::invoke_function(
+            &self.module,
+            &mut self.store,
+            func,
+            params,
+            self.max_call_depth,
+        );
+        self.store.free_handles();
+        results
+    }
+
+    /// Returns the current value of the exported global `name`.
+    pub fn global(&self, name: &str) -> Option<Value> {
+        self.module.exports.iter().find_map(|e| match e.e_desc {
+            EDesc::GlobalExport(idx) if e.name == name => {
+                Some(self.store.globals[idx].clone().get())
+            }
+            _ => None,
+        })
+    }
+
+    /// Returns the exported memory `name`. Shared memories can be cloned to
+    /// import them into other instances.
+    pub fn memory(&self, name: &str) -> Option<&Memory> {
+        self.module.exports.iter().find_map(|e| match e.e_desc {
+            EDesc::MemoryExport(idx) if e.name == name => Some(&self.store.memories[idx]),
+            _ => None,
+        })
+    }
+
+    /// Returns all exported memories together with their names, so memories
+    /// of the guest can be told apart from buffers shared with the host.
+    pub fn memories(&self) -> impl Iterator<Item = (&str, &Memory)> {
+        self.module
+            .exports
+            .iter()
+            .filter_map(move |e| match e.e_desc {
+                EDesc::MemoryExport(idx) => Some((e.name.as_str(), &self.store.memories[idx])),
+                _ => None,
+            })
+    }
+
+    pub fn data(&self) -> &T {
+        &self.store.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut T {
+        &mut self.store.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast;
+    use crate::ast::*;
+
+    fn counter() -> Module {
+        Module {
+            types: vec![(vec![], vec![ValueType::I32])],
+            imports: vec![],
+            funcs: vec![Func {
+                f_type: 0,
+                locals: vec![],
+                body: vec![
+                    Instr::GlobalGet(1),
+                    Instr::GlobalGet(0),
+                    Instr::I32Add,
+                    Instr::GlobalSet(1),
+                    Instr::GlobalGet(1),
+                ],
+            }],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![
+                ast::Global {
+                    g_type: GlobalType {
+                        value_type: ValueType::I32,
+                        mutable: false,
+                    },
+                    init: vec![Instr::I32Const(2)],
+                },
+                ast::Global {
+                    g_type: GlobalType {
+                        value_type: ValueType::I32,
+                        mutable: true,
+                    },
+                    init: vec![Instr::GlobalGet(0)],
+                },
+            ],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![
+                Export {
+                    name: "next".to_string(),
+                    e_desc: EDesc::FuncExport(0),
+                },
+                Export {
+                    name: "count".to_string(),
+                    e_desc: EDesc::GlobalExport(1),
+                },
+            ],
+            start: None,
+        }
+    }
+
+    #[test]
+    fn globals_live_as_long_as_the_instance() {
+        let mut instance = Instance::new(counter()).unwrap();
+        assert_eq!(Some(Value::I32(2)), instance.global("count"));
+        assert_eq!(Ok(vec![Value::I32(4)]), instance.invoke("next", &[]));
+        assert_eq!(Ok(vec![Value::I32(6)]), instance.invoke("next", &[]));
+        assert_eq!(Some(Value::I32(6)), instance.global("count"));
+        assert_eq!(None, instance.global("next"));
+        assert_eq!(
+            Err(RuntimeError::ExportNotFound),
+            instance.invoke("count", &[])
+        );
+    }
+
+    #[test]
+    fn instantiate_invalid_module() {
+        let mut module = counter();
+        module.globals[1].g_type.mutable = false;
+        assert_eq!(
+            Err(RuntimeError::ImmutableGlobal),
+            Instance::new(module).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn active_data_is_copied_and_dropped() {
+        let load = Instr::I32Load(MemArg {
+            align: 2,
+            offset: 0,
+            memory: 0,
+        });
+        let module = |offset| {
+            let mut module = counter();
+            module.memories = vec![ast::Memory {
+                limits: Limits { min: 1, max: None },
+                shared: false,
+                memory64: false,
+            }];
+            module.funcs[0].body = vec![Instr::I32Const(2), load.clone()];
+            module.datas = vec![
+                Data {
+                    init: vec![1, 2],
+                    mode: DataMode::Active {
+                        memory: 0,
+                        offset: vec![offset],
+                    },
+                },
+                Data {
+                    init: vec![3],
+                    mode: DataMode::Passive,
+                },
+            ];
+            module
+        };
+
+        let mut instance = Instance::new(module(Instr::GlobalGet(0))).unwrap();
+        assert_eq!(vec![Vec::<u8>::new(), vec![3]], instance.store.datas);
+        assert_eq!(Ok(vec![Value::I32(0x0201)]), instance.invoke("next", &[]));
+
+        assert_eq!(
+            Err(RuntimeError::MemoryOutOfBounds),
+            Instance::new(module(Instr::I32Const(65535))).map(|_| ())
+        );
+
+        // Segments of 64-bit memories have `i64` offsets.
+        let mut memory64 = module(Instr::I64Const(2));
+        memory64.memories[0].memory64 = true;
+        memory64.funcs[0].body = vec![Instr::I64Const(2), load.clone()];
+        let mut instance = Instance::new(memory64).unwrap();
+        assert_eq!(Ok(vec![Value::I32(0x0201)]), instance.invoke("next", &[]));
+
+        let mut huge = module(Instr::I64Const(0));
+        huge.memories[0].memory64 = true;
+        huge.memories[0].limits.min = 1 << 32;
+        huge.funcs[0].body = vec![Instr::I64Const(2), load];
+        assert_eq!(
+            Err(RuntimeError::MemoryTooLarge),
+            Instance::new(huge).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn active_elems_are_copied_into_tables() {
+        let module = |offset| {
+            let mut module = counter();
+            module
+                .types
+                .push((vec![ValueType::I32], vec![ValueType::I32]));
+            module.funcs.push(Func {
+                f_type: 1,
+                locals: vec![],
+                body: vec![Instr::LocalGet(0), Instr::CallIndirect(0, 0)],
+            });
+            module.tables = vec![ast::Table {
+                limits: Limits { min: 2, max: None },
+                ref_type: RefType::FuncRef,
+            }];
+            module.elems = vec![
+                Elem {
+                    ref_type: RefType::FuncRef,
+                    init: vec![vec![Instr::RefFunc(0)]],
+                    mode: ElemMode::Active {
+                        table: 0,
+                        offset: vec![Instr::I32Const(offset)],
+                    },
+                },
+                Elem {
+                    ref_type: RefType::FuncRef,
+                    init: vec![
+                        vec![Instr::RefFunc(1)],
+                        vec![Instr::RefNull(HeapType::Func)],
+                    ],
+                    mode: ElemMode::Passive,
+                },
+                Elem {
+                    ref_type: RefType::FuncRef,
+                    init: vec![vec![Instr::RefFunc(0)]],
+                    mode: ElemMode::Declarative,
+                },
+            ];
+            module.exports.push(Export {
+                name: "dispatch".to_string(),
+                e_desc: EDesc::TableExport(0),
+            });
+            module.exports.push(Export {
+                name: "call".to_string(),
+                e_desc: EDesc::FuncExport(1),
+            });
+            module
+        };
+
+        let mut instance = Instance::new(module(1)).unwrap();
+        assert_eq!(
+            vec![
+                vec![],
+                vec![Value::FuncRef(Some(1)), Value::FuncRef(None)],
+                vec![]
+            ],
+            instance.store.elems
+        );
+        assert_eq!(
+            Ok(vec![Value::I32(4)]),
+            instance.invoke("call", &[Value::I32(1)])
+        );
+        assert_eq!(
+            Err(RuntimeError::UninitializedElement),
+            instance.invoke("call", &[Value::I32(0)])
+        );
+        assert_eq!(
+            Err(RuntimeError::ExportNotFound),
+            instance.invoke("dispatch", &[])
+        );
+
+        assert_eq!(
+            Err(RuntimeError::TableOutOfBounds),
+            Instance::new(module(2)).map(|_| ())
+        );
+
+        let mut huge = module(1);
+        huge.tables[0].limits.min = u32::MAX as u64;
+        assert_eq!(
+            Err(RuntimeError::TableTooLarge),
+            Instance::new(huge).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn start_runs_at_instantiation() {
+        let mut module = counter();
+        module.types.push((vec![], vec![]));
+        module.funcs.push(Func {
+            f_type: 1,
+            locals: vec![],
+            body: vec![Instr::Call(0), Instr::GlobalSet(1)],
+        });
+        module.start = Some(1);
+        let instance = Instance::new(module).unwrap();
+        assert_eq!(Some(Value::I32(4)), instance.global("count"));
+
+        let mut module = counter();
+        module.types.push((vec![], vec![]));
+        module.funcs.push(Func {
+            f_type: 1,
+            locals: vec![],
+            body: vec![
+                Instr::I32Const(1),
+                Instr::I32Const(0),
+                Instr::I32DivS,
+                Instr::GlobalSet(1),
+            ],
+        });
+        module.start = Some(1);
+        assert_eq!(
+            Err(RuntimeError::IntegerDivideByZero),
+            Instance::new(module).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn free_handles_between_calls() {
+        use ValueType::{ExternRef as Extern, I32};
+        let mut module = counter();
+        module.types = vec![
+            (vec![Extern], vec![Extern]),
+            (vec![I32], vec![Extern]),
+            (vec![], vec![]),
+        ];
+        module.globals = vec![];
+        module.exports = vec![];
+        module.tables = vec![ast::Table {
+            limits: Limits { min: 1, max: None },
+            ref_type: RefType::ExternRef,
+        }];
+        module.tags = vec![2];
+        let body = |f_type, body| Func {
+            f_type,
+            locals: vec![],
+            body,
+        };
+        let keep = vec![
+            Instr::I32Const(0),
+            Instr::LocalGet(0),
+            Instr::TableSet(0),
+            Instr::LocalGet(0),
+        ];
+        let catch = vec![
+            Instr::Block(
+                BlockType::Value(ValueType::ExnRef),
+                vec![
+                    Instr::TryTable(
+                        BlockType::Empty,
+                        vec![Catch::AllRef(0)],
+                        vec![Instr::Throw(0)],
+                    ),
+                    Instr::Unreachable,
+                ],
+            ),
+            Instr::Drop,
+        ];
+        module.funcs = vec![
+            body(0, keep),
+            body(0, vec![Instr::LocalGet(0)]),
+            body(1, vec![Instr::LocalGet(0), Instr::TableGet(0)]),
+            body(2, catch),
+        ];
+        let names = [("keep", 0), ("pass", 1), ("get", 2), ("catch", 3)];
+        for (name, func) in &names {
+            module.exports.push(Export {
+                name: name.to_string(),
+                e_desc: EDesc::FuncExport(*func),
+            });
+        }
+        let mut instance = Instance::new(module).unwrap();
+
+        let kept = ExternRef::new("kept");
+        instance
+            .invoke("keep", &[Value::ExternRef(Some(kept.clone()))])
+            .unwrap();
+        for _ in 0..10 {
+            let object = Value::ExternRef(Some(ExternRef::new(0)));
+            assert_eq!(Ok(vec![object.clone()]), instance.invoke("pass", &[object]));
+            instance.invoke("catch", &[]).unwrap();
+        }
+
+        // The table holds the kept object itself, so no handle outlives a
+        // call.
+        assert_eq!(0, instance.store.externs.objects.len());
+        assert_eq!(0, instance.store.exceptions.objects.len());
+        assert_eq!(
+            Ok(vec![Value::ExternRef(Some(kept))]),
+            instance.invoke("get", &[Value::I32(0)])
+        );
+    }
+
+    #[test]
+    fn free_unreachable_handles_during_calls() {
+        use crate::runtime::processor::MAX_CALL_DEPTH;
+        let mut module = counter();
+        module.types = vec![
+            (vec![ValueType::I32], vec![ValueType::ExnRef]),
+            (vec![ValueType::I32], vec![]),
+        ];
+        module.globals = vec![];
+        module.exports = vec![];
+        module.tags = vec![1];
+        // Catches an exception with the payload `code` by reference.
+        let catch = |code| {
+            Instr::Block(
+                BlockType::Value(ValueType::ExnRef),
+                vec![
+                    Instr::TryTable(
+                        BlockType::Empty,
+                        vec![Catch::AllRef(0)],
+                        vec![Instr::I32Const(code), Instr::Throw(0)],
+                    ),
+                    Instr::Unreachable,
+                ],
+            )
+        };
+        // Keeps the first exception on the stack while dropping the others.
+        let body = vec![
+            catch(1),
+            Instr::Loop(
+                BlockType::Empty,
+                vec![
+                    catch(2),
+                    Instr::Drop,
+                    Instr::LocalGet(0),
+                    Instr::I32Const(1),
+                    Instr::I32Sub,
+                    Instr::LocalTee(0),
+                    Instr::BrIf(0),
+                ],
+            ),
+        ];
+        module.funcs = vec![Func {
+            f_type: 0,
+            locals: vec![],
+            body,
+        }];
+
+        let mut store = Store::new(());
+        let mut processor = Processor::new(&module, &mut store, MAX_CALL_DEPTH);
+        processor.execute_func(0, &[Value::I32(10_000)]).unwrap();
+        let kept = match processor.pop_values(&[ValueType::ExnRef])[..] {
+            [Value::ExnRef(Some(ref exn))] => exn.values.clone(),
+            _ => unreachable!(),
+        };
+        assert_eq!(vec![Value::I32(1)], kept);
+        assert!(store.exceptions.objects.len() <= MIN_HANDLES);
+    }
+}
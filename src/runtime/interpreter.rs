@@ -1,49 +1,117 @@
 use crate::ast::*;
 use crate::runtime::error::RuntimeError;
 use crate::runtime::error::RuntimeError::ExportNotFound;
+use crate::runtime::instance::Store;
 use crate::runtime::processor::Processor;
+use crate::runtime::value::Value;
 
-pub fn invoke_function(ast: &Module, func: &str, params: &[i32]) -> Result<i32, RuntimeError> {
-    let export = match ast.exports.iter().find(|e| e.name == func) {
-        None => return Err(ExportNotFound),
-        Some(e) => e,
+pub fn invoke_function<T>(
+    ast: &Module,
+    store: &mut Store<T>,
+    func: &str,
+    params: &[Value],
+    max_call_depth: usize,
+) -> Result<Vec<Value>, RuntimeError> {
+    let export = ast.exports.iter().find(|e| e.name == func);
+    let f_index = match export.map(|e| &e.e_desc) {
+        Some(EDesc::FuncExport(idx)) => *idx,
+        _ => return Err(ExportNotFound),
     };
-
-    let EDesc::FuncExport(f_index) = export.e_desc;
-    let func = &ast.funcs[f_index];
-    let f_type = &ast.types[func.f_type as usize];
+    let f_type = &ast.types[ast.func_types()[f_index]];
 
     if f_type.0.len() != params.len() {
         return Err(RuntimeError::InvalidArgNumber);
     };
 
-    let mut processor = Processor::new();
-    processor.execute_func(&func, params);
+    if f_type.0.iter().zip(params).any(|(t, p)| !p.matches(t, ast)) {
+        return Err(RuntimeError::InvalidArgType);
+    }
+
+    let mut processor = Processor::new(ast, store, max_call_depth);
+    processor.execute_func(f_index, params)?;
 
-    Ok(processor.get_result())
+    Ok(processor.pop_values(&f_type.1))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::runtime::processor::MAX_CALL_DEPTH;
 
     #[test]
     fn invoke_function_test() {
         let ast = Module {
             types: vec![(vec![ValueType::I32, ValueType::I32], vec![ValueType::I32])],
+            imports: vec![],
             funcs: vec![Func {
                 f_type: 0,
                 locals: vec![],
                 body: vec![Instr::LocalGet(0), Instr::LocalGet(1), Instr::I32Add],
             }],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
             exports: vec![Export {
                 name: "add".to_string(),
                 e_desc: EDesc::FuncExport(0),
             }],
+            start: None,
+        };
+
+        let result = invoke_function(
+            &ast,
+            &mut Store::new(()),
+            "add",
+            &[Value::I32(5), Value::I32(6)],
+            MAX_CALL_DEPTH,
+        )
+        .unwrap();
+
+        assert_eq!(vec![Value::I32(11)], result);
+    }
+
+    #[test]
+    fn invoke_i64_function_test() {
+        let ast = Module {
+            types: vec![(vec![ValueType::I64, ValueType::I64], vec![ValueType::I64])],
+            imports: vec![],
+            funcs: vec![Func {
+                f_type: 0,
+                locals: vec![],
+                body: vec![Instr::LocalGet(0), Instr::LocalGet(1), Instr::I64Mul],
+            }],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![Export {
+                name: "mul".to_string(),
+                e_desc: EDesc::FuncExport(0),
+            }],
+            start: None,
         };
 
-        let result = invoke_function(&ast, "add", &[5, 6]).unwrap();
+        let result = invoke_function(
+            &ast,
+            &mut Store::new(()),
+            "mul",
+            &[Value::I64(1 << 31), Value::I64(6)],
+            MAX_CALL_DEPTH,
+        );
+        assert_eq!(Ok(vec![Value::I64(6 << 31)]), result);
 
-        assert_eq!(11, result);
+        let result = invoke_function(
+            &ast,
+            &mut Store::new(()),
+            "mul",
+            &[Value::I32(1), Value::I64(6)],
+            MAX_CALL_DEPTH,
+        );
+        assert_eq!(Err(RuntimeError::InvalidArgType), result);
     }
 }
@@ -0,0 +1,893 @@
+use crate::ast::{FuncType, ImportDesc, Limits, Module};
+use crate::runtime::disassembler::parse_wasm;
+use crate::runtime::error::RuntimeError;
+use crate::runtime::error::RuntimeError::{IncompatibleImportType, UnknownImport};
+use crate::runtime::global::Global;
+use crate::runtime::instance::{Instance, Store};
+use crate::runtime::memory::Memory;
+use crate::runtime::processor::MAX_CALL_DEPTH;
+use crate::runtime::reader::Reader;
+use crate::runtime::table::Table;
+use crate::runtime::value::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+type HostClosure<T> =
+    dyn Fn(&mut Caller<T>, &[Value]) -> Result<Vec<Value>, RuntimeError> + Send + Sync;
+
+/// A function implemented by the host.
+pub struct HostFunc<T> {
+    pub f_type: FuncType,
+    pub func: Arc<HostClosure<T>>,
+}
+
+impl<T> Clone for HostFunc<T> {
+    fn clone(&self) -> Self {
+        Self {
+            f_type: self.f_type.clone(),
+            func: self.func.clone(),
+        }
+    }
+}
+
+/// The state of the instance that calls a host function.
+pub struct Caller<'a, T> {
+    store: &'a mut Store<T>,
+}
+
+impl<'a, T> Caller<'a, T> {
+    pub(crate) fn new(store: &'a mut Store<T>) -> Self {
+        Self { store }
+    }
+
+    pub fn data(&self) -> &T {
+        &self.store.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut T {
+        &mut self.store.data
+    }
+
+    /// The first memory of the calling instance, if it has one.
+    pub fn memory(&mut self) -> Option<&mut Memory> {
+        self.store.memories.first_mut()
+    }
+
+    /// The memory `idx` of the calling instance, counting imported ones.
+    pub fn memory_at(&mut self, idx: usize) -> Option<&mut Memory> {
+        self.store.memories.get_mut(idx)
+    }
+
+    /// The table `idx` of the calling instance, counting imported ones.
+    pub fn table(&mut self, idx: usize) -> Option<&mut Table> {
+        self.store.tables.get_mut(idx)
+    }
+
+    /// The global `idx` of the calling instance, counting imported ones.
+    pub fn global(&mut self, idx: usize) -> Option<&mut Global> {
+        self.store.globals.get_mut(idx)
+    }
+}
+
+enum Extern<T> {
+    Func(HostFunc<T>),
+    Table(Table),
+    Memory(Memory),
+    Global(Global),
+}
+
+/// Definitions of the host, which satisfy the imports of a module by their
+/// module and field name.
+pub struct Linker<T> {
+    externs: HashMap<(String, String), Extern<T>>,
+    /// Number of active calls before execution traps in the instances of
+    /// this linker.
+    pub(crate) max_call_depth: usize,
+}
+
+impl<T> Linker<T> {
+    pub fn new() -> Self {
+        Self {
+            externs: HashMap::new(),
+            max_call_depth: MAX_CALL_DEPTH,
+        }
+    }
+
+    /// Sets the number of calls that may be active at once before execution
+    /// traps with `CallStackExhausted`, which defaults to `MAX_CALL_DEPTH`.
+    /// Every call takes up to 1.5 KiB of the host stack in debug builds, so
+    /// a deeper limit needs a larger stack.
+    pub fn max_call_depth(&mut self, depth: usize) -> &mut Self {
+        self.max_call_depth = depth;
+        self
+    }
+
+    fn define(&mut self, module: &str, name: &str, ext: Extern<T>) -> &mut Self {
+        self.externs
+            .insert((module.to_string(), name.to_string()), ext);
+        self
+    }
+
+    /// Defines a function of type `f_type`, which has to return values of
+    /// the declared result types.
+    pub fn func(
+        &mut self,
+        module: &str,
+        name: &str,
+        f_type: FuncType,
+        func: impl Fn(&mut Caller<T>, &[Value]) -> Result<Vec<Value>, RuntimeError>
+            + Send
+            + Sync
+            + 'static,
+    ) -> &mut Self {
+        let func = HostFunc {
+            f_type,
+            func: Arc::new(func),
+        };
+        self.define(module, name, Extern::Func(func))
+    }
+
+    /// Defines a table. Every instance importing it accesses `table` itself,
+    /// so the host sees the elements they set and vice versa.
+    pub fn table(&mut self, module: &str, name: &str, table: &Table) -> &mut Self {
+        self.define(module, name, Extern::Table(table.clone()))
+    }
+
+    /// Defines a memory. Every instance importing it accesses `memory`
+    /// itself, so the host sees the bytes they store and vice versa. Only
+    /// shared memories can be accessed from other threads at the same time.
+    pub fn memory(&mut self, module: &str, name: &str, memory: &Memory) -> &mut Self {
+        self.define(module, name, Extern::Memory(memory.clone()))
+    }
+
+    /// Defines a global. Every instance importing it accesses `global`
+    /// itself, so the host sees what they set and vice versa.
+    pub fn global(&mut self, module: &str, name: &str, global: &Global) -> &mut Self {
+        self.define(module, name, Extern::Global(global.clone()))
+    }
+
+    /// Decodes and instantiates the module `wasm`.
+    pub fn instantiate(&self, wasm: Vec<u8>, data: T) -> Result<Instance<T>, RuntimeError> {
+        let reader = Reader::new(wasm);
+        Instance::with_linker(parse_wasm(&reader)?, self, data)
+    }
+
+    /// Adds the definitions for the imports of `module` to `store`, which
+    /// have to match the imported types.
+    pub(crate) fn link(&self, module: &Module, store: &mut Store<T>) -> Result<(), RuntimeError> {
+        for import in &module.imports {
+            let key = (import.module.clone(), import.name.clone());
+            let ext = self.externs.get(&key).ok_or(UnknownImport)?;
+
+            match (&import.desc, ext) {
+                (ImportDesc::Func(type_idx), Extern::Func(func))
+                    if module.types[*type_idx] == func.f_type =>
+                {
+                    store.host_funcs.push(func.clone())
+                }
+                (ImportDesc::Table(imported), Extern::Table(table))
+                    if imported.ref_type == table.ref_type()
+                        && limits_match(&table.limits(), &imported.limits) =>
+                {
+                    store.tables.push(table.clone())
+                }
+                (ImportDesc::Memory(imported), Extern::Memory(memory))
+                    if imported.shared == memory.is_shared()
+                        && imported.memory64 == memory.is_64()
+                        && limits_match(&memory.limits(), &imported.limits) =>
+                {
+                    store.memories.push(memory.clone())
+                }
+                (ImportDesc::Global(imported), Extern::Global(global))
+                    if imported.mutable == global.global_type().mutable
+                        && global.clone().get().matches(&imported.value_type, module) =>
+                {
+                    store.globals.push(global.clone())
+                }
+                _ => return Err(IncompatibleImportType),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> Default for Linker<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether `actual` limits satisfy the `expected` ones of an import: they
+/// have to be at least as large and may not grow beyond the expected maximum.
+fn limits_match(actual: &Limits, expected: &Limits) -> bool {
+    let max_matches = match (actual.max, expected.max) {
+        (_, None) => true,
+        (Some(actual), Some(expected)) => actual <= expected,
+        (None, Some(_)) => false,
+    };
+    actual.min >= expected.min && max_matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::ValueType::{FuncRef, I32};
+    use crate::ast::{self, RefType, ValueType};
+    use crate::runtime::value::{Exception, ExternRef};
+    use crate::{compiler, parser};
+
+    fn compile(wat: &str) -> Vec<u8> {
+        compiler::compile(&parser::parse(wat).unwrap())
+    }
+
+    const MODULE: &str = r#"(module
+            (import "env" "sum" (func $sum (param i32) (param i32) (result i32)))
+            (import "env" "table" (table $t 2 funcref))
+            (import "env" "mem" (memory 1))
+            (import "env" "base" (global $base i32))
+            (data (i32.const 0) "\01\02\03")
+            (elem (i32.const 1) $sum)
+            (func $main (result i32)
+              i32.const 0
+              i32.const 3
+              call $sum
+              i32.const 1
+              i32.const 2
+              i32.const 1
+              call_indirect $t (param i32) (param i32) (result i32)
+              i32.add
+              global.get $base
+              i32.add)
+            (export "main" (func $main))
+            (export "sum" (func $sum))
+        )"#;
+
+    /// Defines the imports of `MODULE`. `sum` adds up `len` bytes of memory
+    /// starting at `addr` and counts its calls.
+    fn env_linker() -> Linker<u32> {
+        let mut linker = Linker::new();
+        linker
+            .func("env", "sum", (vec![I32, I32], vec![I32]), |caller, args| {
+                *caller.data_mut() += 1;
+                let (addr, len) = match args {
+                    [Value::I32(addr), Value::I32(len)] => {
+                        (*addr as u32 as u64, *len as u32 as u64)
+                    }
+                    _ => unreachable!(),
+                };
+                let memory = caller.memory().expect("memory is imported");
+                let mut sum = 0;
+                for offset in 0..len {
+                    sum += memory.load::<1>(addr, offset)?[0] as i32;
+                }
+                Ok(vec![Value::I32(sum)])
+            })
+            .table("env", "table", &table(2, RefType::FuncRef))
+            .memory("env", "mem", &memory(1))
+            .global("env", "base", &Global::new(Value::I32(10), false));
+        linker
+    }
+
+    fn table(min: u64, ref_type: RefType) -> Table {
+        Table::new(&Limits { min, max: None }, ref_type).unwrap()
+    }
+
+    fn memory(min: u64) -> Memory {
+        Memory::new(&Limits { min, max: None }).unwrap()
+    }
+
+    #[test]
+    fn call_host_funcs() {
+        let mut instance = env_linker().instantiate(compile(MODULE), 0).unwrap();
+        assert_eq!(Ok(vec![Value::I32(21)]), instance.invoke("main", &[]));
+        assert_eq!(2, *instance.data());
+
+        assert_eq!(
+            Ok(vec![Value::I32(2)]),
+            instance.invoke("sum", &[Value::I32(1), Value::I32(1)])
+        );
+        assert_eq!(
+            Err(RuntimeError::MemoryOutOfBounds),
+            instance.invoke("sum", &[Value::I32(65535), Value::I32(2)])
+        );
+        assert_eq!(4, *instance.data());
+    }
+
+    #[test]
+    fn host_funcs_return_declared_results() {
+        let mut linker = env_linker();
+        linker.func("env", "sum", (vec![I32, I32], vec![I32]), |_, _| {
+            Ok(vec![Value::I64(0)])
+        });
+        let mut instance = linker.instantiate(compile(MODULE), 0).unwrap();
+        assert_eq!(
+            Err(RuntimeError::InvalidHostResult),
+            instance.invoke("main", &[])
+        );
+    }
+
+    #[test]
+    fn imports_have_to_be_defined() {
+        let mut linker = env_linker();
+        linker
+            .externs
+            .remove(&("env".to_string(), "base".to_string()));
+        assert_eq!(
+            Err(RuntimeError::UnknownImport),
+            linker.instantiate(compile(MODULE), 0).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn imports_have_to_match() {
+        let instantiate = |linker: &Linker<u32>| linker.instantiate(compile(MODULE), 0).map(|_| ());
+
+        let mut linker = env_linker();
+        linker.global("env", "base", &Global::new(Value::I32(10), true));
+        assert_eq!(Err(IncompatibleImportType), instantiate(&linker));
+
+        let mut linker = env_linker();
+        linker.global("env", "base", &Global::new(Value::I64(10), false));
+        assert_eq!(Err(IncompatibleImportType), instantiate(&linker));
+
+        let mut linker = env_linker();
+        linker.memory("env", "mem", &memory(0));
+        assert_eq!(Err(IncompatibleImportType), instantiate(&linker));
+
+        let memory64 = Memory::with_type(&ast::Memory {
+            limits: Limits { min: 1, max: None },
+            shared: false,
+            memory64: true,
+        });
+        let mut linker = env_linker();
+        linker.memory("env", "mem", &memory64.unwrap());
+        assert_eq!(Err(IncompatibleImportType), instantiate(&linker));
+
+        let mut linker = env_linker();
+        linker.table("env", "table", &table(2, RefType::ExternRef));
+        assert_eq!(Err(IncompatibleImportType), instantiate(&linker));
+
+        let mut linker = env_linker();
+        linker.func("env", "sum", (vec![I32], vec![I32]), |_, _| Ok(vec![]));
+        assert_eq!(Err(IncompatibleImportType), instantiate(&linker));
+
+        let mut linker = env_linker();
+        linker.global("env", "table", &Global::new(Value::I32(0), false));
+        assert_eq!(Err(IncompatibleImportType), instantiate(&linker));
+    }
+
+    #[test]
+    fn pass_host_objects() {
+        let wat = r#"(module
+                (import "env" "objects" (table $objects 1 externref))
+                (import "env" "name" (func $name (param externref) (result i32)))
+                (func $store (param externref)
+                  i32.const 0
+                  local.get 0
+                  table.set $objects)
+                (func $load (result externref)
+                  i32.const 0
+                  table.get $objects)
+                (func $name_of_stored (result i32)
+                  call $load
+                  call $name)
+                (export "store" (func $store))
+                (export "load" (func $load))
+                (export "name_of_stored" (func $name_of_stored))
+            )"#;
+        struct Object(i32);
+
+        let mut linker = Linker::new();
+        linker
+            .table("env", "objects", &table(1, RefType::ExternRef))
+            .func(
+                "env",
+                "name",
+                (vec![ValueType::ExternRef], vec![I32]),
+                |_, args| match args {
+                    [Value::ExternRef(Some(object))] => {
+                        Ok(vec![Value::I32(object.downcast_ref::<Object>().unwrap().0)])
+                    }
+                    _ => Ok(vec![Value::I32(-1)]),
+                },
+            );
+        let mut instance = linker.instantiate(compile(wat), ()).unwrap();
+
+        let object = ExternRef::new(Object(42));
+        assert_eq!(
+            Ok(vec![Value::ExternRef(None)]),
+            instance.invoke("load", &[])
+        );
+        assert_eq!(
+            Ok(vec![Value::I32(-1)]),
+            instance.invoke("name_of_stored", &[])
+        );
+        instance
+            .invoke("store", &[Value::ExternRef(Some(object.clone()))])
+            .unwrap();
+        assert_eq!(
+            Ok(vec![Value::I32(42)]),
+            instance.invoke("name_of_stored", &[])
+        );
+        assert_eq!(
+            Ok(vec![Value::ExternRef(Some(object))]),
+            instance.invoke("load", &[])
+        );
+    }
+
+    #[test]
+    fn element_expressions() {
+        let wat = r#"(module
+                (import "env" "object" (global $object externref))
+                (table $funcs funcref (elem (ref.func $one) (ref.null func)))
+                (table $objects 2 externref)
+                (elem $passive externref (global.get $object) (item ref.null extern))
+                (func $one (result i32) i32.const 1)
+                (func $call (param i32) (result i32)
+                  local.get 0
+                  call_indirect $funcs (result i32))
+                (func $init
+                  i32.const 0
+                  i32.const 0
+                  i32.const 2
+                  table.init $objects $passive)
+                (func $load (param i32) (result externref)
+                  local.get 0
+                  table.get $objects)
+                (export "call" (func $call))
+                (export "init" (func $init))
+                (export "load" (func $load))
+            )"#;
+        let object = ExternRef::new(7);
+        let mut linker = Linker::new();
+        let global = Global::new(Value::ExternRef(Some(object.clone())), false);
+        linker.global("env", "object", &global);
+        let mut instance = linker.instantiate(compile(wat), ()).unwrap();
+
+        assert_eq!(
+            Ok(vec![Value::I32(1)]),
+            instance.invoke("call", &[Value::I32(0)])
+        );
+        assert_eq!(
+            Err(RuntimeError::UninitializedElement),
+            instance.invoke("call", &[Value::I32(1)])
+        );
+        instance.invoke("init", &[]).unwrap();
+        assert_eq!(
+            Ok(vec![Value::ExternRef(Some(object))]),
+            instance.invoke("load", &[Value::I32(0)])
+        );
+        assert_eq!(
+            Ok(vec![Value::ExternRef(None)]),
+            instance.invoke("load", &[Value::I32(1)])
+        );
+    }
+
+    #[test]
+    fn exceptions_cross_host_boundary() {
+        let wat = r#"(module
+                (import "env" "fail" (func $fail (param i32)))
+                (tag $error (param i32))
+                (func $throw (param i32)
+                  local.get 0
+                  throw $error)
+                (func $catch (param i32) (result i32)
+                  block $handler (result i32)
+                    try_table (catch $error $handler)
+                      local.get 0
+                      call $fail
+                    end
+                    i32.const -1
+                  end)
+                (export "throw" (func $throw))
+                (export "catch" (func $catch))
+                (export "error" (tag $error))
+            )"#;
+        let exception = |value| {
+            Arc::new(Exception {
+                tag: 0,
+                values: vec![Value::I32(value)],
+            })
+        };
+
+        // Host functions throw by returning the exception.
+        let mut linker = Linker::new();
+        linker.func(
+            "env",
+            "fail",
+            (vec![I32], vec![]),
+            move |_, args| match args {
+                [Value::I32(0)] => Ok(vec![]),
+                [Value::I32(code)] => Err(RuntimeError::UncaughtException(exception(code * 2))),
+                _ => unreachable!(),
+            },
+        );
+        let mut instance = linker.instantiate(compile(wat), ()).unwrap();
+
+        assert_eq!(
+            Err(RuntimeError::UncaughtException(exception(3))),
+            instance.invoke("throw", &[Value::I32(3)])
+        );
+        assert_eq!(
+            Ok(vec![Value::I32(8)]),
+            instance.invoke("catch", &[Value::I32(4)])
+        );
+        assert_eq!(
+            Ok(vec![Value::I32(-1)]),
+            instance.invoke("catch", &[Value::I32(0)])
+        );
+    }
+
+    #[test]
+    fn share_memory_between_threads() {
+        let wat = r#"(module
+                (import "env" "mem" (memory 1 1 shared))
+                (func $add (param i32)
+                  loop $l
+                    i32.const 0
+                    i32.const 1
+                    i32.atomic.rmw.add
+                    drop
+                    local.get 0
+                    i32.const 1
+                    i32.sub
+                    local.tee 0
+                    br_if $l
+                  end)
+                (func $wait (result i32)
+                  i32.const 4
+                  i32.const 0
+                  i64.const -1
+                  memory.atomic.wait32)
+                (func $wake (result i32)
+                  i32.const 4
+                  i32.const 1
+                  memory.atomic.notify)
+                (export "add" (func $add))
+                (export "wait" (func $wait))
+                (export "wake" (func $wake))
+                (export "mem" (memory 0))
+            )"#;
+        let mut memory = Memory::shared(&Limits {
+            min: 1,
+            max: Some(1),
+        })
+        .unwrap();
+        let mut linker = Linker::new();
+        linker.memory("env", "mem", &memory);
+        let wasm = compile(wat);
+        let instantiate = || linker.instantiate(wasm.clone(), ()).unwrap();
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| instantiate().invoke("add", &[Value::I32(1000)]));
+            }
+        });
+        assert_eq!(Ok([0xa0, 0x0f, 0, 0]), memory.load::<4>(0, 0));
+
+        let mut instance = instantiate();
+        std::thread::scope(|scope| {
+            let waiter = scope.spawn(|| instantiate().invoke("wait", &[]));
+            while instance.invoke("wake", &[]) != Ok(vec![Value::I32(1)]) {
+                std::thread::yield_now();
+            }
+            assert_eq!(Ok(vec![Value::I32(0)]), waiter.join().unwrap());
+        });
+        let mut exported = instance.memory("mem").unwrap().clone();
+        assert_eq!(Ok([0xa0, 0x0f]), exported.load::<2>(0, 0));
+
+        // Shared imports need a shared memory and vice versa.
+        let mut linker = Linker::new();
+        linker.memory("env", "mem", &self::memory(1));
+        assert_eq!(
+            Err(IncompatibleImportType),
+            linker.instantiate(wasm, ()).map(|_| ())
+        );
+        let mut linker = env_linker();
+        linker.memory("env", "mem", &memory);
+        assert_eq!(
+            Err(IncompatibleImportType),
+            linker.instantiate(compile(MODULE), 0).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn limit_call_depth() {
+        let wat = r#"(module
+                (func $depth (param i32) (result i32)
+                  local.get 0
+                  if (result i32)
+                    local.get 0
+                    i32.const 1
+                    i32.sub
+                    call $depth
+                    i32.const 1
+                    i32.add
+                  else
+                    i32.const 0
+                  end)
+                (export "depth" (func $depth))
+            )"#;
+
+        // Only calls count, not the `if` block around them.
+        let mut linker = Linker::new();
+        linker.max_call_depth(10);
+        let mut instance = linker.instantiate(compile(wat), ()).unwrap();
+        assert_eq!(
+            Ok(vec![Value::I32(9)]),
+            instance.invoke("depth", &[Value::I32(9)])
+        );
+        assert_eq!(
+            Err(RuntimeError::CallStackExhausted),
+            instance.invoke("depth", &[Value::I32(10)])
+        );
+
+        let mut instance = Linker::new().instantiate(compile(wat), ()).unwrap();
+        assert_eq!(
+            Ok(vec![Value::I32(1000)]),
+            instance.invoke("depth", &[Value::I32(1000)])
+        );
+    }
+
+    #[test]
+    fn host_sees_guest_writes() {
+        let wat = r#"(module
+                (import "env" "count" (global $count (mut i32)))
+                (import "env" "mem" (memory 1))
+                (import "env" "table" (table $t 1 externref))
+                (import "env" "peek" (func $peek (result i32)))
+                (func $inc (result i32)
+                  global.get $count
+                  i32.const 1
+                  i32.add
+                  global.set $count
+                  i32.const 4
+                  global.get $count
+                  i32.store
+                  call $peek)
+                (func $keep (param externref)
+                  i32.const 0
+                  local.get 0
+                  table.set $t)
+                (export "inc" (func $inc))
+                (export "keep" (func $keep))
+            )"#;
+        let mut count = Global::new(Value::I32(5), true);
+        let mut mem = memory(1);
+        let mut objects = table(1, RefType::ExternRef);
+        let mut linker = Linker::new();
+        linker
+            .global("env", "count", &count)
+            .memory("env", "mem", &mem)
+            .table("env", "table", &objects)
+            .func("env", "peek", (vec![], vec![I32]), |caller, _| {
+                let count = caller.global(0).expect("global is imported").get();
+                let table = caller.table(0).expect("table is imported");
+                let kept = table.get(0)? != Value::ExternRef(None);
+                match count {
+                    Value::I32(count) => Ok(vec![Value::I32(count * 2 + kept as i32)]),
+                    _ => unreachable!(),
+                }
+            });
+
+        // Every instance writes to the objects of the host.
+        let mut first = linker.instantiate(compile(wat), ()).unwrap();
+        let mut second = linker.instantiate(compile(wat), ()).unwrap();
+        assert_eq!(Ok(vec![Value::I32(12)]), first.invoke("inc", &[]));
+        assert_eq!(Ok(vec![Value::I32(14)]), second.invoke("inc", &[]));
+        assert_eq!(Value::I32(7), count.get());
+        assert_eq!(Ok([7, 0, 0, 0]), mem.load::<4>(4, 0));
+
+        let object = ExternRef::new(1);
+        first
+            .invoke("keep", &[Value::ExternRef(Some(object.clone()))])
+            .unwrap();
+        assert_eq!(Ok(Value::ExternRef(Some(object))), objects.get(0));
+
+        // And sees what the host sets.
+        count.set(Value::I32(20)).unwrap();
+        assert_eq!(Ok(vec![Value::I32(43)]), second.invoke("inc", &[]));
+        assert_eq!(Value::I32(21), count.get());
+    }
+
+    #[test]
+    fn host_func_refs_are_checked() {
+        let wat = r#"(module
+                (import "env" "f" (global $f funcref))
+                (import "env" "make" (func $make (result funcref)))
+                (func $take (param funcref) (result i32)
+                  local.get 0
+                  ref.is_null)
+                (func $made (result funcref)
+                  call $make)
+                (export "take" (func $take))
+                (export "made" (func $made))
+            )"#;
+        let linker = |global| {
+            let mut linker = Linker::new();
+            linker
+                .global("env", "f", &Global::new(Value::FuncRef(global), false))
+                .func("env", "make", (vec![], vec![FuncRef]), |caller, _| {
+                    Ok(vec![Value::FuncRef(*caller.data())])
+                });
+            linker
+        };
+
+        let mut instance = linker(Some(2)).instantiate(compile(wat), Some(1)).unwrap();
+        let mut take = |f| instance.invoke("take", &[Value::FuncRef(f)]);
+        assert_eq!(Ok(vec![Value::I32(0)]), take(Some(2)));
+        assert_eq!(Err(RuntimeError::InvalidArgType), take(Some(3)));
+        assert_eq!(Err(RuntimeError::InvalidArgType), take(Some(1000)));
+        assert_eq!(
+            Ok(vec![Value::FuncRef(Some(1))]),
+            instance.invoke("made", &[])
+        );
+
+        *instance.data_mut() = Some(1000);
+        assert_eq!(
+            Err(RuntimeError::InvalidHostResult),
+            instance.invoke("made", &[])
+        );
+
+        assert_eq!(
+            Err(IncompatibleImportType),
+            linker(Some(1000))
+                .instantiate(compile(wat), None)
+                .map(|_| ())
+        );
+    }
+
+    #[test]
+    fn typed_func_refs() {
+        let wat = r#"(module
+                (type $unary (func (param i32) (result i32)))
+                (type $caller (func (param (ref $unary) i32) (result i32)))
+                (func $inc (param $x i32) (result i32)
+                  local.get $x
+                  i32.const 1
+                  i32.add)
+                (func $double (type $unary)
+                  local.get 0
+                  i32.const 2
+                  i32.mul)
+                (elem $e func $inc $double)
+                (func $apply (param $f (ref null $unary)) (param $x i32) (result i32)
+                  (local $g (ref $unary))
+                  block $null
+                    local.get $f
+                    br_on_null $null
+                    local.set $g
+                    local.get $x
+                    local.get $g
+                    return_call_ref $unary
+                  end
+                  i32.const -1)
+                (func $pick (param $n i32) (result (ref null $unary))
+                  local.get $n
+                  if (result (ref null $unary))
+                    ref.func $double
+                  else
+                    ref.null $unary
+                  end)
+                (func $run (param $n i32) (param $x i32) (result i32)
+                  local.get $n
+                  call $pick
+                  local.get $x
+                  call $apply)
+                (func $call (type $caller)
+                  local.get 1
+                  local.get 0
+                  call_ref $unary)
+                (export "apply" (func $apply))
+                (export "run" (func $run))
+                (export "call" (func $call))
+            )"#;
+        let mut instance = Linker::new().instantiate(compile(wat), ()).unwrap();
+
+        let mut run = |n| instance.invoke("run", &[Value::I32(n), Value::I32(5)]);
+        assert_eq!(Ok(vec![Value::I32(10)]), run(1));
+        assert_eq!(Ok(vec![Value::I32(-1)]), run(0));
+
+        let mut apply = |f| instance.invoke("apply", &[Value::FuncRef(f), Value::I32(5)]);
+        assert_eq!(Ok(vec![Value::I32(6)]), apply(Some(0)));
+        assert_eq!(Ok(vec![Value::I32(-1)]), apply(None));
+
+        // Only nullable parameters accept null references.
+        let mut call = |f| instance.invoke("call", &[Value::FuncRef(f), Value::I32(5)]);
+        assert_eq!(Ok(vec![Value::I32(10)]), call(Some(1)));
+        assert_eq!(Err(RuntimeError::InvalidArgType), call(None));
+
+        // References of the host have to refer to a function of the type.
+        assert_eq!(Err(RuntimeError::InvalidArgType), call(Some(2)));
+        assert_eq!(Err(RuntimeError::InvalidArgType), call(Some(1000)));
+
+        // Neither can they be passed inside the module.
+        let wat = r#"(module
+                (type $unary (func (param i32) (result i32)))
+                (func $call (param $f (ref $unary)))
+                (func $null
+                  ref.null $unary
+                  call $call)
+            )"#;
+        let instance = Linker::new().instantiate(compile(wat), ());
+        assert_eq!(Err(RuntimeError::TypeMismatch), instance.map(|_| ()));
+    }
+
+    #[test]
+    fn separate_guest_and_host_memories() {
+        let wat = r#"(module
+                (import "env" "buf" (memory $buf 1 1 shared))
+                (import "env" "sum" (func $sum (result i32)))
+                (memory $scratch 1)
+                (data (memory $scratch) (i32.const 8) "\01\02\03")
+                (func $publish (param i32) (result i32)
+                  local.get 0
+                  i32.const 8
+                  i32.const 3
+                  memory.copy $buf $scratch
+                  call $sum)
+                (func $peek (param i32) (result i32)
+                  local.get 0
+                  i32.load8_u $scratch)
+                (export "publish" (func $publish))
+                (export "peek" (func $peek))
+                (export "buf" (memory $buf))
+                (export "scratch" (memory $scratch))
+            )"#;
+        let mut buf = Memory::shared(&Limits {
+            min: 1,
+            max: Some(1),
+        })
+        .unwrap();
+        let mut linker = Linker::new();
+        linker
+            .memory("env", "buf", &buf)
+            .func("env", "sum", (vec![], vec![I32]), |caller, _| {
+                let scratch = caller.memory_at(1).expect("memory is defined");
+                let bytes = scratch.read(8, 3)?;
+                Ok(vec![Value::I32(bytes.iter().map(|b| *b as i32).sum())])
+            });
+        let mut instance = linker.instantiate(compile(wat), ()).unwrap();
+
+        assert_eq!(
+            Ok(vec![Value::I32(6)]),
+            instance.invoke("publish", &[Value::I32(100)])
+        );
+        assert_eq!(Ok([1, 2, 3]), buf.load::<3>(100, 0));
+        assert_eq!(
+            Ok(vec![Value::I32(0)]),
+            instance.invoke("peek", &[Value::I32(100)])
+        );
+        assert_eq!(
+            Ok(vec![Value::I32(2)]),
+            instance.invoke("peek", &[Value::I32(9)])
+        );
+
+        let memories = instance.memories().collect::<Vec<_>>();
+        assert_eq!(2, memories.len());
+        assert_eq!(("buf", true), (memories[0].0, memories[0].1.is_shared()));
+        assert_eq!(
+            ("scratch", false),
+            (memories[1].0, memories[1].1.is_shared())
+        );
+    }
+
+    #[test]
+    fn instances_are_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Instance<u32>>();
+        assert_send::<Linker<u32>>();
+    }
+
+    #[test]
+    fn match_limits() {
+        let limits = |min, max| Limits { min, max };
+        assert!(limits_match(&limits(1, None), &limits(1, None)));
+        assert!(limits_match(&limits(2, Some(3)), &limits(1, None)));
+        assert!(limits_match(&limits(2, Some(3)), &limits(2, Some(4))));
+        assert!(!limits_match(&limits(0, None), &limits(1, None)));
+        assert!(!limits_match(&limits(1, None), &limits(1, Some(2))));
+        assert!(!limits_match(&limits(1, Some(3)), &limits(1, Some(2))));
+    }
+}
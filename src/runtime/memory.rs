@@ -0,0 +1,448 @@
+use crate::ast::{self, Limits};
+use crate::runtime::error::RuntimeError;
+use crate::runtime::error::RuntimeError::{
+    ExpectedSharedMemory, MemoryOutOfBounds, MemoryTooLarge, UnalignedAtomic,
+};
+use crate::runtime::shared::Access;
+use std::collections::VecDeque;
+use std::convert::{TryFrom, TryInto};
+use std::ops::Range;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+pub const PAGE_SIZE: usize = 65536;
+/// Upper bound for the number of pages of a memory.
+pub const MAX_PAGES: u64 = 65536;
+/// Upper bound for the number of pages of a 64-bit memory, which fills the
+/// whole address space.
+pub const MAX_PAGES64: u64 = 1 << 48;
+
+/// A linear memory, which stores values in little-endian byte order. Clones
+/// refer to the same memory, so a shared memory can be handed to instances
+/// on other threads. Accesses only lock the memory while other clones of it
+/// exist, so the memory of a single instance is accessed without locking.
+#[derive(Clone)]
+pub struct Memory {
+    inner: Arc<Inner>,
+    max: Option<u64>,
+    shared: bool,
+    memory64: bool,
+}
+
+struct Inner {
+    state: Mutex<State>,
+    /// Signalled when waiting threads are notified.
+    notified: Condvar,
+}
+
+struct State {
+    data: Vec<u8>,
+    /// Effective address and ticket of every waiting thread in the order in
+    /// which they started to wait.
+    waiters: VecDeque<(usize, u64)>,
+    next_ticket: u64,
+}
+
+impl Memory {
+    pub fn new(limits: &Limits) -> Result<Self, RuntimeError> {
+        Self::with_type(&ast::Memory {
+            limits: *limits,
+            shared: false,
+            memory64: false,
+        })
+    }
+
+    /// Creates a memory that can be waited on by several threads.
+    pub fn shared(limits: &Limits) -> Result<Self, RuntimeError> {
+        Self::with_type(&ast::Memory {
+            limits: *limits,
+            shared: true,
+            memory64: false,
+        })
+    }
+
+    /// Creates a memory of the given type, e.g. a 64-bit one. Fails if the
+    /// host is out of memory for its initial pages.
+    pub fn with_type(memory: &ast::Memory) -> Result<Self, RuntimeError> {
+        let len = usize::try_from(memory.limits.min)
+            .ok()
+            .and_then(|min| min.checked_mul(PAGE_SIZE))
+            .ok_or(MemoryTooLarge)?;
+        let mut data = Vec::new();
+        data.try_reserve_exact(len).map_err(|_| MemoryTooLarge)?;
+        data.resize(len, 0);
+
+        let state = State {
+            data,
+            waiters: VecDeque::new(),
+            next_ticket: 0,
+        };
+        Ok(Self {
+            inner: Arc::new(Inner {
+                state: Mutex::new(state),
+                notified: Condvar::new(),
+            }),
+            max: memory.limits.max,
+            shared: memory.shared,
+            memory64: memory.memory64,
+        })
+    }
+
+    pub fn is_shared(&self) -> bool {
+        self.shared
+    }
+
+    /// Whether the memory is addressed by `i64` operands.
+    pub fn is_64(&self) -> bool {
+        self.memory64
+    }
+
+    /// Current limits, whose minimum is the current size.
+    pub fn limits(&self) -> Limits {
+        Limits {
+            min: self.size(),
+            max: self.max,
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        // The state stays consistent even if a thread panicked while holding
+        // the lock.
+        self.inner.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The state for an access through this handle, which is only locked if
+    /// there are other handles. No handle can be cloned while this one is
+    /// borrowed mutably, so a unique handle stays unique during the access.
+    fn access(&mut self) -> Access<'_, State> {
+        if Arc::strong_count(&self.inner) > 1 {
+            return Access::Locked(self.state());
+        }
+        let inner = Arc::get_mut(&mut self.inner).expect("the handle is unique");
+        Access::Exclusive(inner.state.get_mut().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Current size in pages.
+    pub fn size(&self) -> u64 {
+        (self.state().data.len() / PAGE_SIZE) as u64
+    }
+
+    /// Grows the memory by `delta` pages and returns the previous size, or
+    /// `None` if the memory can't grow that much, also if the host is out
+    /// of memory.
+    pub fn grow(&mut self, delta: u64) -> Option<u64> {
+        let memory64 = self.memory64;
+        let max = self.max;
+        let mut state = self.access();
+        let size = (state.data.len() / PAGE_SIZE) as u64;
+        let new_size = size.checked_add(delta)?;
+        let max_pages = if memory64 { MAX_PAGES64 } else { MAX_PAGES };
+        if new_size > max.unwrap_or(max_pages) {
+            return None;
+        }
+
+        let len = usize::try_from(new_size).ok()?.checked_mul(PAGE_SIZE)?;
+        let additional = len - state.data.len();
+        state.data.try_reserve_exact(additional).ok()?;
+        state.data.resize(len, 0);
+        Some(size)
+    }
+
+    /// Reads `N` bytes at the effective address `addr + offset`.
+    pub fn load<const N: usize>(
+        &mut self,
+        addr: u64,
+        offset: u64,
+    ) -> Result<[u8; N], RuntimeError> {
+        let state = self.access();
+        let range = range(&state.data, addr, offset, N as u64)?;
+        Ok(state.data[range].try_into().unwrap())
+    }
+
+    /// Writes `bytes` at the effective address `addr + offset`.
+    pub fn store(&mut self, addr: u64, offset: u64, bytes: &[u8]) -> Result<(), RuntimeError> {
+        let mut state = self.access();
+        let range = range(&state.data, addr, offset, bytes.len() as u64)?;
+        state.data[range].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Reads `len` bytes at `addr`, e.g. to copy them into another memory.
+    pub fn read(&mut self, addr: u64, len: u64) -> Result<Vec<u8>, RuntimeError> {
+        let state = self.access();
+        let range = range(&state.data, addr, 0, len)?;
+        Ok(state.data[range].to_vec())
+    }
+
+    /// Copies `len` bytes from `src` to `dst`. The ranges may overlap.
+    pub fn copy(&mut self, dst: u64, src: u64, len: u64) -> Result<(), RuntimeError> {
+        let mut state = self.access();
+        let src = range(&state.data, src, 0, len)?;
+        let dst = range(&state.data, dst, 0, len)?;
+        state.data.copy_within(src, dst.start);
+        Ok(())
+    }
+
+    /// Sets `len` bytes at `dst` to `value`.
+    pub fn fill(&mut self, dst: u64, value: u8, len: u64) -> Result<(), RuntimeError> {
+        let mut state = self.access();
+        let range = range(&state.data, dst, 0, len)?;
+        state.data[range].fill(value);
+        Ok(())
+    }
+
+    /// Replaces the little-endian value of `len` bytes at `addr + offset`
+    /// by `f` of it and returns the previous value. The effective address
+    /// has to be a multiple of `len`.
+    pub fn atomic_rmw(
+        &mut self,
+        addr: u64,
+        offset: u64,
+        len: usize,
+        f: impl FnOnce(u64) -> u64,
+    ) -> Result<u64, RuntimeError> {
+        let mut state = self.access();
+        let range = atomic_range(&state.data, addr, offset, len)?;
+        let mut bytes = [0; 8];
+        bytes[..len].copy_from_slice(&state.data[range.clone()]);
+        let old = u64::from_le_bytes(bytes);
+        state.data[range].copy_from_slice(&f(old).to_le_bytes()[..len]);
+        Ok(old)
+    }
+
+    /// Blocks until another thread notifies the address `addr + offset`, if
+    /// `expected` is stored there, or until the `timeout` expires. Returns 0
+    /// if the thread was notified, 1 if the value was different and 2 on
+    /// timeout.
+    pub fn wait(
+        &self,
+        addr: u64,
+        offset: u64,
+        expected: &[u8],
+        timeout: Option<Duration>,
+    ) -> Result<u32, RuntimeError> {
+        if !self.shared {
+            return Err(ExpectedSharedMemory);
+        }
+        let mut state = self.state();
+        let range = atomic_range(&state.data, addr, offset, expected.len())?;
+        if state.data[range.clone()] != *expected {
+            return Ok(1);
+        }
+
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        state.waiters.push_back((range.start, ticket));
+        let waiting = |state: &mut State| state.waiters.iter().any(|(_, t)| *t == ticket);
+        let notified = &self.inner.notified;
+        let mut state = match timeout {
+            Some(timeout) => {
+                let result = notified.wait_timeout_while(state, timeout, waiting);
+                result.unwrap_or_else(|e| e.into_inner()).0
+            }
+            None => notified
+                .wait_while(state, waiting)
+                .unwrap_or_else(|e| e.into_inner()),
+        };
+
+        match state.waiters.iter().position(|(_, t)| *t == ticket) {
+            Some(i) => {
+                state.waiters.remove(i);
+                Ok(2)
+            }
+            None => Ok(0),
+        }
+    }
+
+    /// Wakes up at most `count` threads waiting on `addr + offset` and
+    /// returns how many were woken.
+    pub fn notify(&self, addr: u64, offset: u64, count: u32) -> Result<u32, RuntimeError> {
+        let mut state = self.state();
+        let ea = atomic_range(&state.data, addr, offset, 4)?.start;
+        let mut woken = 0;
+        state.waiters.retain(|(a, _)| {
+            let wake = *a == ea && woken < count;
+            woken += wake as u32;
+            !wake
+        });
+        if woken > 0 {
+            self.inner.notified.notify_all();
+        }
+        Ok(woken)
+    }
+}
+
+/// Checks that `len` bytes at `addr + offset` are inside `data`. The
+/// effective address is computed without wrapping around, so huge offsets
+/// are out of bounds.
+fn range(data: &[u8], addr: u64, offset: u64, len: u64) -> Result<Range<usize>, RuntimeError> {
+    let start = addr.checked_add(offset).ok_or(MemoryOutOfBounds)?;
+    let end = start.checked_add(len).ok_or(MemoryOutOfBounds)?;
+
+    if end > data.len() as u64 {
+        return Err(MemoryOutOfBounds);
+    }
+
+    Ok(start as usize..end as usize)
+}
+
+/// Like `range`, but atomic accesses also have to be aligned to their size.
+fn atomic_range(
+    data: &[u8],
+    addr: u64,
+    offset: u64,
+    len: usize,
+) -> Result<Range<usize>, RuntimeError> {
+    let range = range(data, addr, offset, len as u64)?;
+    if range.start % len != 0 {
+        return Err(UnalignedAtomic);
+    }
+    Ok(range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_memory(min: u64, max: Option<u64>) -> Memory {
+        Memory::new(&Limits { min, max }).unwrap()
+    }
+
+    #[test]
+    fn load_store() {
+        let mut memory = new_memory(1, None);
+        assert_eq!(Ok([0; 4]), memory.load::<4>(0, 0));
+
+        memory.store(8, 2, &0x1234_5678u32.to_le_bytes()).unwrap();
+        assert_eq!(Ok([0x78, 0x56, 0x34, 0x12]), memory.load::<4>(10, 0));
+        assert_eq!(Ok([0x34]), memory.load::<1>(4, 8));
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        let mut memory = new_memory(1, None);
+        let last = PAGE_SIZE as u64 - 4;
+        assert!(memory.load::<4>(last, 0).is_ok());
+        assert_eq!(Err(MemoryOutOfBounds), memory.load::<4>(last, 1));
+        assert_eq!(Err(MemoryOutOfBounds), memory.load::<8>(last, 0));
+        assert_eq!(Err(MemoryOutOfBounds), memory.load::<1>(u32::MAX as u64, 1));
+        assert_eq!(
+            Err(MemoryOutOfBounds),
+            memory.store(1, u32::MAX as u64, &[0])
+        );
+        // The effective address of 64-bit memories may overflow.
+        assert_eq!(Err(MemoryOutOfBounds), memory.load::<1>(u64::MAX, 1));
+        assert_eq!(Err(MemoryOutOfBounds), memory.load::<4>(u64::MAX - 1, 0));
+        assert_eq!(Err(MemoryOutOfBounds), memory.store(0, u64::MAX, &[0]));
+        assert_eq!(Err(MemoryOutOfBounds), memory.fill(u64::MAX, 0, 2));
+        assert_eq!(
+            Err(MemoryOutOfBounds),
+            memory.atomic_rmw(8, u64::MAX - 7, 8, |v| v)
+        );
+        assert_eq!(Err(MemoryOutOfBounds), new_memory(0, None).load::<1>(0, 0));
+    }
+
+    #[test]
+    fn grow() {
+        let mut memory = new_memory(1, Some(3));
+        assert_eq!(Some(1), memory.grow(0));
+        assert_eq!(Some(1), memory.grow(2));
+        assert_eq!(3, memory.size());
+        assert_eq!(None, memory.grow(1));
+        assert_eq!(None, memory.grow(u64::MAX));
+        assert!(memory.load::<1>(3 * PAGE_SIZE as u64 - 1, 0).is_ok());
+
+        assert_eq!(None, new_memory(0, None).grow(MAX_PAGES + 1));
+    }
+
+    #[test]
+    fn memory64() {
+        let mut memory = Memory::with_type(&ast::Memory {
+            limits: Limits { min: 1, max: None },
+            shared: false,
+            memory64: true,
+        })
+        .unwrap();
+        assert!(memory.is_64());
+        assert_eq!(Some(1), memory.grow(1));
+        assert_eq!(None, memory.grow(MAX_PAGES64));
+        assert_eq!(None, memory.grow(u64::MAX));
+        assert_eq!(2, memory.size());
+        assert!(!new_memory(1, None).is_64());
+
+        // Initial pages the host can't allocate fail instead of aborting.
+        let huge = |min| {
+            Memory::with_type(&ast::Memory {
+                limits: Limits { min, max: None },
+                shared: false,
+                memory64: true,
+            })
+        };
+        assert_eq!(Some(MemoryTooLarge), huge(1 << 32).err());
+        assert_eq!(Some(MemoryTooLarge), huge(u64::MAX).err());
+    }
+
+    #[test]
+    fn copy_fill() {
+        let mut memory = new_memory(1, None);
+        memory.store(0, 0, &[1, 2, 3, 4]).unwrap();
+        memory.copy(1, 0, 3).unwrap();
+        assert_eq!(Ok([1, 1, 2, 3]), memory.load::<4>(0, 0));
+        memory.copy(0, 1, 3).unwrap();
+        assert_eq!(Ok([1, 2, 3, 3]), memory.load::<4>(0, 0));
+
+        memory.fill(2, 9, 2).unwrap();
+        assert_eq!(Ok([1, 2, 9, 9]), memory.load::<4>(0, 0));
+        assert_eq!(Ok(vec![2, 9]), memory.read(1, 2));
+
+        let end = PAGE_SIZE as u64;
+        assert_eq!(Ok(()), memory.fill(end, 0, 0));
+        assert_eq!(Err(MemoryOutOfBounds), memory.fill(end, 0, 1));
+        assert_eq!(Err(MemoryOutOfBounds), memory.copy(0, end - 1, 2));
+        assert_eq!(Err(MemoryOutOfBounds), memory.copy(end - 1, 0, 2));
+        assert_eq!(Err(MemoryOutOfBounds), memory.read(end - 1, 2));
+    }
+
+    #[test]
+    fn atomic_rmw() {
+        let mut memory = new_memory(1, None);
+        memory.store(8, 0, &[1, 2, 3, 4]).unwrap();
+        assert_eq!(Ok(0x0201), memory.atomic_rmw(8, 0, 2, |v| v + 1));
+        assert_eq!(Ok([2, 2, 3, 4]), memory.load::<4>(8, 0));
+        assert_eq!(Ok(0x04030202), memory.atomic_rmw(4, 4, 4, |_| u64::MAX));
+        assert_eq!(Ok([0xff; 4]), memory.load::<4>(8, 0));
+
+        assert_eq!(Err(UnalignedAtomic), memory.atomic_rmw(8, 2, 4, |v| v));
+        assert_eq!(Ok(0xff), memory.atomic_rmw(9, 0, 1, |v| v));
+        let last = PAGE_SIZE as u64 - 8;
+        assert_eq!(Err(MemoryOutOfBounds), memory.atomic_rmw(last, 8, 8, |v| v));
+    }
+
+    #[test]
+    fn wait_notify() {
+        let limits = Limits {
+            min: 1,
+            max: Some(1),
+        };
+        assert_eq!(
+            Err(ExpectedSharedMemory),
+            Memory::new(&limits).unwrap().wait(0, 0, &[0; 4], None)
+        );
+
+        let memory = Memory::shared(&limits).unwrap();
+        let timeout = Some(Duration::from_millis(1));
+        assert_eq!(Ok(1), memory.wait(0, 0, &[1, 0, 0, 0], None));
+        assert_eq!(Ok(2), memory.wait(0, 0, &[0; 8], timeout));
+        assert_eq!(Err(UnalignedAtomic), memory.wait(2, 0, &[0; 4], None));
+        assert_eq!(Ok(0), memory.notify(0, 0, 1));
+
+        let waiter = {
+            let memory = memory.clone();
+            std::thread::spawn(move || memory.wait(0, 16, &[0; 4], None))
+        };
+        while memory.notify(8, 8, 2) != Ok(1) {
+            std::thread::yield_now();
+        }
+        assert_eq!(Ok(0), waiter.join().unwrap());
+    }
+}
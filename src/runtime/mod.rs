@@ -1,18 +1,42 @@
 use crate::runtime::disassembler::parse_wasm;
-use crate::runtime::error::RuntimeError;
 use crate::runtime::reader::Reader;
 
+pub use crate::runtime::error::RuntimeError;
+pub use crate::runtime::global::Global;
+pub use crate::runtime::instance::Instance;
+pub use crate::runtime::linker::{Caller, Linker};
+pub use crate::runtime::memory::Memory;
+pub use crate::runtime::table::Table;
+pub use crate::runtime::value::{Exception, ExternRef, Value};
+
 mod disassembler;
 mod error;
+mod float;
+mod global;
+mod instance;
 mod interpreter;
+mod linker;
+mod memory;
 mod processor;
 mod reader;
+mod shared;
+mod simd;
 mod stack;
+mod table;
+mod validator;
+mod value;
 
-pub fn invoke_function(wasm: Vec<u8>, f_name: &str, params: &[i32]) -> Result<i32, RuntimeError> {
+pub fn instantiate(wasm: Vec<u8>) -> Result<Instance, RuntimeError> {
     let reader = Reader::new(wasm);
-    let ast = parse_wasm(&reader)?;
-    interpreter::invoke_function(&ast, f_name, params)
+    Instance::new(parse_wasm(&reader)?)
+}
+
+pub fn invoke_function(
+    wasm: Vec<u8>,
+    f_name: &str,
+    params: &[Value],
+) -> Result<Vec<Value>, RuntimeError> {
+    instantiate(wasm)?.invoke(f_name, params)
 }
 
 #[cfg(test)]
@@ -75,8 +99,8 @@ mod tests {
             0x0b, // end
         ];
 
-        let result = invoke_function(wasm, "add", &[1, 4]).unwrap();
+        let result = invoke_function(wasm, "add", &[Value::I32(1), Value::I32(4)]).unwrap();
 
-        assert_eq!(5, result);
+        assert_eq!(vec![Value::I32(5)], result);
     }
 }
@@ -1,34 +1,2980 @@
-use crate::ast::{Func, Instr};
-use crate::runtime::stack::Stack;
+use crate::ast::{
+    AtomicRmwOp, AtomicWidth, BlockType, Catch, Func, FuncType, Instr, MemArg, Module, ValueType,
+};
+use crate::runtime::error::RuntimeError;
+use crate::runtime::error::RuntimeError::{
+    CallStackExhausted, IndirectCallTypeMismatch, IntegerDivideByZero, IntegerOverflow,
+    InvalidHostResult, MemoryOutOfBounds, NullReference, TableOutOfBounds, UncaughtException,
+    UninitializedElement, Unreachable,
+};
+use crate::runtime::float;
+use crate::runtime::instance::Store;
+use crate::runtime::linker::Caller;
+use crate::runtime::simd::{self, extend, Lane};
+use crate::runtime::stack::{Stack, Stackable};
+use crate::runtime::value::{Exception, Value};
+use std::convert::TryFrom;
+use std::sync::atomic::{fence, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
-pub struct Processor {
+// Exclusive bounds of the float values that truncate into the integer range.
+const I32_MIN_EXCLUSIVE: f64 = -2147483649.0;
+const I32_MAX_EXCLUSIVE: f64 = 2147483648.0;
+const U32_MAX_EXCLUSIVE: f64 = 4294967296.0;
+const I64_MIN_EXCLUSIVE: f64 = -9223372036854777856.0;
+const I64_MAX_EXCLUSIVE: f64 = 9223372036854775808.0;
+const U64_MAX_EXCLUSIVE: f64 = 18446744073709551616.0;
+
+/// Default for the number of calls that may be active at once before
+/// execution traps, e.g. a recursive function can call itself 1199 times.
+/// Tail calls don't count, since they replace the calling function.
+pub const MAX_CALL_DEPTH: usize = 1200;
+
+/// Number of blocks that the active calls together may nest before execution
+/// traps, which is one block for every call at the default call depth. Both
+/// limits together fit on a thread with a 2 MiB stack, even in debug builds.
+const MAX_BLOCK_DEPTH: usize = 1200;
+
+/// How execution continues after a sequence of instructions.
+enum Flow {
+    Continue,
+    /// Branch to the label with the given relative depth.
+    Branch(usize),
+    Return,
+    /// Return and call the function with the given index in place of the
+    /// returning one. Its arguments are on top of the stack.
+    TailCall(usize),
+}
+
+/// The activation of a function call.
+#[derive(Default)]
+struct Frame {
+    locals: Vec<Value>,
+    /// Number of values the function returns.
+    arity: usize,
+    /// Height of the stack below the operands of the call.
+    height: usize,
+}
+
+impl Frame {
+    /// Creates the frame of a call of `func`, whose locals start with the
+    /// arguments, when the stack has the given height.
+    fn new(module: &Module, func: &Func, args: Vec<Value>, height: usize) -> Self {
+        let locals = func.locals.iter().map(Value::zero);
+        Self {
+            locals: args.into_iter().chain(locals).collect(),
+            arity: module.types[func.f_type as usize].1.len(),
+            height,
+        }
+    }
+}
+
+pub struct Processor<'a, D> {
+    module: &'a Module,
+    store: &'a mut Store<D>,
     stack: Stack,
+    /// Number of active calls.
+    depth: usize,
+    max_call_depth: usize,
+    /// Number of blocks entered by the active calls.
+    blocks: usize,
 }
 
-impl Processor {
-    pub fn new() -> Self {
+impl<'a, D> Processor<'a, D> {
+    pub fn new(module: &'a Module, store: &'a mut Store<D>, max_call_depth: usize) -> Self {
         Self {
+            module,
+            store,
             stack: Stack::new(),
+            depth: 0,
+            max_call_depth,
+            blocks: 0,
+        }
+    }
+
+    /// Calls the function at `idx` and leaves its results on the stack.
+    pub fn execute_func(&mut self, idx: usize, params: &[Value]) -> Result<(), RuntimeError> {
+        for param in params {
+            self.push_value(param);
+        }
+        self.call(idx).map(|_| ())
+    }
+
+    /// Calls the function at `idx`, whose arguments are on top of the stack.
+    /// Only the nesting of calls is handled here, and everything else in
+    /// `enter` and `leave`, so that every call takes little of the host
+    /// stack.
+    fn call(&mut self, idx: usize) -> Result<Flow, RuntimeError> {
+        if self.depth == self.max_call_depth {
+            return Err(CallStackExhausted);
+        }
+
+        let mut frame = Frame::default();
+        let mut idx = idx;
+        // Tail calls return to here before the callee runs, so they don't
+        // nest on the host stack.
+        loop {
+            let body = match self.enter(idx, &mut frame) {
+                Ok(Some(body)) => body,
+                Ok(None) => return Ok(Flow::Continue),
+                Err(e) => return Err(e),
+            };
+
+            self.depth += 1;
+            let flow = self.execute_instrs(body, &mut frame);
+            self.depth -= 1;
+
+            match self.leave(flow, &frame) {
+                Ok(Some(callee)) => idx = callee,
+                Ok(None) => return Ok(Flow::Continue),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Starts a call of the function at `idx` by popping its arguments.
+    /// Returns the body of a function of the module, for which `frame`
+    /// becomes the frame of the call, whereas an imported function is
+    /// called right away.
+    #[inline(never)]
+    fn enter(
+        &mut self,
+        idx: usize,
+        frame: &mut Frame,
+    ) -> Result<Option<&'a [Instr]>, RuntimeError> {
+        let args = self.pop_args(idx);
+        let num_imported = self.store.host_funcs.len();
+        if idx < num_imported {
+            self.execute_host_func(idx, &args)?;
+            return Ok(None);
+        }
+
+        let module = self.module;
+        let func = &module.funcs[idx - num_imported];
+        *frame = Frame::new(module, func, args, self.stack.len());
+        Ok(Some(&func.body))
+    }
+
+    /// Returns from the call of `frame`, which ended with `flow`. A tail
+    /// call instead leaves only its arguments on the stack and returns the
+    /// function to call in place of the returning one.
+    #[inline(never)]
+    fn leave(
+        &mut self,
+        flow: Result<Flow, RuntimeError>,
+        frame: &Frame,
+    ) -> Result<Option<usize>, RuntimeError> {
+        match flow? {
+            Flow::Continue => Ok(None),
+            // Branching to the outermost label is the same as returning.
+            Flow::Branch(_) | Flow::Return => {
+                self.stack.unwind(frame.height, frame.arity);
+                Ok(None)
+            }
+            Flow::TailCall(callee) => {
+                let (params, _) = self.func_type(callee);
+                self.stack.unwind(frame.height, params.len());
+                Ok(Some(callee))
+            }
+        }
+    }
+
+    /// Calls the imported function at `idx` and pushes its results.
+    fn execute_host_func(&mut self, idx: usize, params: &[Value]) -> Result<(), RuntimeError> {
+        let host = self.store.host_funcs[idx].clone();
+        let results = (host.func)(&mut Caller::new(self.store), params)?;
+
+        let (_, result_types) = &host.f_type;
+        if results.len() != result_types.len()
+            || results
+                .iter()
+                .zip(result_types)
+                .any(|(r, t)| !r.matches(t, self.module))
+        {
+            return Err(InvalidHostResult);
+        }
+        for result in &results {
+            self.push_value(result);
+        }
+        Ok(())
+    }
+
+    /// Type of the function at `idx` in the function index space.
+    fn func_type(&self, idx: usize) -> &'a FuncType {
+        let module = self.module;
+        let type_idx = match idx.checked_sub(self.store.host_funcs.len()) {
+            Some(defined) => module.funcs[defined].f_type as usize,
+            None => module.func_types()[idx],
+        };
+        &module.types[type_idx]
+    }
+
+    /// Evaluates the constant expression `init`, e.g. of a global.
+    pub fn eval_const(
+        &mut self,
+        init: &[Instr],
+        value_type: &ValueType,
+    ) -> Result<Value, RuntimeError> {
+        self.execute_instrs(init, &mut Frame::default())?;
+        Ok(self.pop_value(value_type))
+    }
+
+    /// Returns the function that a call instruction calls.
+    fn callee(&mut self, instr: &Instr) -> Result<usize, RuntimeError> {
+        match instr {
+            Instr::Call(idx) | Instr::ReturnCall(idx) => Ok(*idx),
+            Instr::CallIndirect(type_idx, table) | Instr::ReturnCallIndirect(type_idx, table) => {
+                self.indirect_callee(*type_idx, *table)
+            }
+            Instr::CallRef(type_idx) | Instr::ReturnCallRef(type_idx) => self.ref_callee(*type_idx),
+            _ => unreachable!("not a call instruction"),
+        }
+    }
+
+    /// Pops the arguments for a call of the function at `idx`.
+    fn pop_args(&mut self, idx: usize) -> Vec<Value> {
+        let (params, _) = self.func_type(idx);
+        self.pop_values(params)
+    }
+
+    /// Returns the function referenced by the table element on top of the
+    /// stack, which has to be of type `type_idx`.
+    fn indirect_callee(&mut self, type_idx: usize, table: usize) -> Result<usize, RuntimeError> {
+        let elem = self.stack.pop::<i32>() as u32;
+        match self.store.tables[table].get(elem)? {
+            Value::FuncRef(Some(func)) => self.check_callee(func, type_idx),
+            _ => Err(UninitializedElement),
+        }
+    }
+
+    /// Returns the function referenced on top of the stack, which has to be
+    /// of type `type_idx`.
+    fn ref_callee(&mut self, type_idx: usize) -> Result<usize, RuntimeError> {
+        let func = self.stack.pop::<Option<usize>>().ok_or(NullReference)?;
+        self.check_callee(func, type_idx)
+    }
+
+    /// Checks that `func` is a function of type `type_idx`. Tables and
+    /// globals are shared with the host, so it may not even be a function.
+    fn check_callee(&self, func: usize, type_idx: usize) -> Result<usize, RuntimeError> {
+        let num_funcs = self.store.host_funcs.len() + self.module.funcs.len();
+        if func >= num_funcs || *self.func_type(func) != self.module.types[type_idx] {
+            return Err(IndirectCallTypeMismatch);
+        }
+
+        Ok(func)
+    }
+
+    /// Numbers of parameters and results of a block.
+    fn block_arity(&self, bt: &BlockType) -> (usize, usize) {
+        match bt {
+            BlockType::Empty => (0, 0),
+            BlockType::Value(_) => (0, 1),
+            BlockType::TypeIdx(idx) => {
+                let (params, results) = &self.module.types[*idx];
+                (params.len(), results.len())
+            }
+        }
+    }
+
+    /// Executes a block, which traps once the blocks of all active calls
+    /// together nest deeper than `MAX_BLOCK_DEPTH`.
+    fn execute_block(
+        &mut self,
+        bt: &BlockType,
+        body: &[Instr],
+        frame: &mut Frame,
+        is_loop: bool,
+    ) -> Result<Flow, RuntimeError> {
+        if self.blocks == MAX_BLOCK_DEPTH {
+            return Err(CallStackExhausted);
+        }
+
+        let (params, results) = self.block_arity(bt);
+        // The parameters are already on the stack and belong to the block.
+        let height = self.stack.len() - params;
+
+        self.blocks += 1;
+        let mut flow = self.execute_instrs(body, frame);
+        // A branch to a loop jumps back to its start, which takes the
+        // parameters again.
+        while is_loop && matches!(flow, Ok(Flow::Branch(0))) {
+            self.stack.unwind(height, params);
+            flow = self.execute_instrs(body, frame);
+        }
+        self.blocks -= 1;
+
+        match flow {
+            Ok(Flow::Branch(0)) => {
+                self.stack.unwind(height, results);
+                Ok(Flow::Continue)
+            }
+            Ok(Flow::Branch(depth)) => Ok(Flow::Branch(depth - 1)),
+            flow => flow,
+        }
+    }
+
+    /// Executes the body of a `try_table`. An exception thrown inside of it,
+    /// even by a called function, unwinds the stack to the block and
+    /// branches to the label of the first catch clause that matches it.
+    fn execute_try_table(
+        &mut self,
+        bt: &BlockType,
+        catches: &[Catch],
+        body: &[Instr],
+        frame: &mut Frame,
+    ) -> Result<Flow, RuntimeError> {
+        let (params, _) = self.block_arity(bt);
+        let height = self.stack.len() - params;
+        let exn = match self.execute_block(bt, body, frame, false) {
+            Err(UncaughtException(exn)) => exn,
+            result => return result,
+        };
+
+        let catch = catches.iter().find(|catch| match catch {
+            Catch::Tag(tag, _) | Catch::TagRef(tag, _) => *tag == exn.tag,
+            Catch::All(_) | Catch::AllRef(_) => true,
+        });
+        let catch = match catch {
+            Some(catch) => catch,
+            None => return Err(UncaughtException(exn)),
+        };
+
+        self.stack.unwind(height, 0);
+        if let Catch::Tag(..) | Catch::TagRef(..) = catch {
+            for value in &exn.values {
+                self.push_value(value);
+            }
+        }
+        let label = match catch {
+            Catch::Tag(_, label) | Catch::All(label) => label,
+            Catch::TagRef(_, label) | Catch::AllRef(label) => {
+                self.collect_handles();
+                let handle = self.store.exception_handle(&exn);
+                self.stack.push(Some(handle));
+                label
+            }
+        };
+        Ok(Flow::Branch(*label))
+    }
+
+    /// Pops the payload of an exception with the given tag, which is then
+    /// thrown by returning it as an error.
+    fn throw(&mut self, tag: usize) -> RuntimeError {
+        let (params, _) = &self.module.types[self.module.tags[tag]];
+        let values = self.pop_values(params);
+        UncaughtException(Arc::new(Exception { tag, values }))
+    }
+
+    /// Rethrows the exception referenced on top of the stack.
+    fn throw_ref(&mut self) -> RuntimeError {
+        match self.stack.pop::<Option<usize>>() {
+            Some(handle) => UncaughtException(self.store.exception(handle)),
+            None => NullReference,
+        }
+    }
+
+    /// Executes a sequence of instructions. Only the instructions that
+    /// enter blocks and calls are handled here, and everything else in
+    /// `execute_instr`, so that every level of nesting takes little of the
+    /// host stack.
+    fn execute_instrs(
+        &mut self,
+        instrs: &[Instr],
+        frame: &mut Frame,
+    ) -> Result<Flow, RuntimeError> {
+        for instr in instrs {
+            let flow = match instr {
+                Instr::Block(bt, body) => self.execute_block(bt, body, frame, false),
+                Instr::Loop(bt, body) => self.execute_block(bt, body, frame, true),
+                Instr::If(bt, then, els) => {
+                    let body = if self.stack.pop::<i32>() != 0 {
+                        then
+                    } else {
+                        els
+                    };
+                    self.execute_block(bt, body, frame, false)
+                }
+                Instr::Call(_) | Instr::CallIndirect(_, _) | Instr::CallRef(_) => {
+                    match self.callee(instr) {
+                        Ok(idx) => self.call(idx),
+                        Err(e) => Err(e),
+                    }
+                }
+                Instr::TryTable(bt, catches, body) => {
+                    self.execute_try_table(bt, catches, body, frame)
+                }
+                _ => self.execute_instr(instr, frame),
+            };
+            match flow {
+                Ok(Flow::Continue) => {}
+                flow => return flow,
+            }
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    /// Executes an instruction that doesn't nest.
+    #[inline(never)]
+    fn execute_instr(&mut self, instr: &Instr, frame: &mut Frame) -> Result<Flow, RuntimeError> {
+        match instr {
+            Instr::Unreachable => return Err(Unreachable),
+            Instr::Nop => {}
+            Instr::Br(label) => return Ok(Flow::Branch(*label)),
+            Instr::BrIf(label) => {
+                if self.stack.pop::<i32>() != 0 {
+                    return Ok(Flow::Branch(*label));
+                }
+            }
+            Instr::BrTable(labels, default) => {
+                let i = self.stack.pop::<i32>() as u32 as usize;
+                return Ok(Flow::Branch(*labels.get(i).unwrap_or(default)));
+            }
+            Instr::Return => return Ok(Flow::Return),
+            Instr::ReturnCall(_) | Instr::ReturnCallIndirect(_, _) | Instr::ReturnCallRef(_) => {
+                return Ok(Flow::TailCall(self.callee(instr)?))
+            }
+            Instr::Throw(tag) => return Err(self.throw(*tag)),
+            Instr::ThrowRef => return Err(self.throw_ref()),
+            Instr::Drop => self.stack.unwind(self.stack.len() - 1, 0),
+            Instr::Select(_) => {
+                // Operands are dropped without knowing their type.
+                let height = self.stack.len() - 3;
+                if self.stack.pop::<i32>() != 0 {
+                    self.stack.unwind(height + 1, 0);
+                } else {
+                    self.stack.unwind(height, 1);
+                }
+            }
+
+            Instr::RefNull(_)
+            | Instr::RefIsNull
+            | Instr::RefFunc(_)
+            | Instr::RefAsNonNull
+            | Instr::BrOnNull(_)
+            | Instr::BrOnNonNull(_) => return self.execute_ref_instr(instr),
+
+            Instr::LocalGet(_)
+            | Instr::LocalSet(_)
+            | Instr::LocalTee(_)
+            | Instr::GlobalGet(_)
+            | Instr::GlobalSet(_) => self.execute_var_instr(instr, frame)?,
+            Instr::TableGet(_)
+            | Instr::TableSet(_)
+            | Instr::TableSize(_)
+            | Instr::TableGrow(_)
+            | Instr::TableFill(_)
+            | Instr::TableCopy(_, _)
+            | Instr::TableInit(_, _)
+            | Instr::ElemDrop(_) => self.execute_table_instr(instr)?,
+
+            Instr::I32Load(_)
+            | Instr::I64Load(_)
+            | Instr::F32Load(_)
+            | Instr::F64Load(_)
+            | Instr::I32Load8S(_)
+            | Instr::I32Load8U(_)
+            | Instr::I32Load16S(_)
+            | Instr::I32Load16U(_)
+            | Instr::I64Load8S(_)
+            | Instr::I64Load8U(_)
+            | Instr::I64Load16S(_)
+            | Instr::I64Load16U(_)
+            | Instr::I64Load32S(_)
+            | Instr::I64Load32U(_)
+            | Instr::I32Store(_)
+            | Instr::I64Store(_)
+            | Instr::F32Store(_)
+            | Instr::F64Store(_)
+            | Instr::I32Store8(_)
+            | Instr::I32Store16(_)
+            | Instr::I64Store8(_)
+            | Instr::I64Store16(_)
+            | Instr::I64Store32(_)
+            | Instr::MemorySize(_)
+            | Instr::MemoryGrow(_)
+            | Instr::MemoryInit(_, _)
+            | Instr::DataDrop(_)
+            | Instr::MemoryCopy(_, _)
+            | Instr::MemoryFill(_) => self.execute_memory_instr(instr)?,
+
+            Instr::Simd(op) => simd::execute(op, &mut self.stack),
+            Instr::V128Load(_)
+            | Instr::V128Load8x8S(_)
+            | Instr::V128Load8x8U(_)
+            | Instr::V128Load16x4S(_)
+            | Instr::V128Load16x4U(_)
+            | Instr::V128Load32x2S(_)
+            | Instr::V128Load32x2U(_)
+            | Instr::V128Load8Splat(_)
+            | Instr::V128Load16Splat(_)
+            | Instr::V128Load32Splat(_)
+            | Instr::V128Load64Splat(_)
+            | Instr::V128Load32Zero(_)
+            | Instr::V128Load64Zero(_)
+            | Instr::V128Store(_)
+            | Instr::V128Load8Lane(_, _)
+            | Instr::V128Load16Lane(_, _)
+            | Instr::V128Load32Lane(_, _)
+            | Instr::V128Load64Lane(_, _)
+            | Instr::V128Store8Lane(_, _)
+            | Instr::V128Store16Lane(_, _)
+            | Instr::V128Store32Lane(_, _)
+            | Instr::V128Store64Lane(_, _)
+            | Instr::V128Const(_)
+            | Instr::I8x16Shuffle(_)
+            | Instr::I8x16ExtractLaneS(_)
+            | Instr::I8x16ExtractLaneU(_)
+            | Instr::I8x16ReplaceLane(_)
+            | Instr::I16x8ExtractLaneS(_)
+            | Instr::I16x8ExtractLaneU(_)
+            | Instr::I16x8ReplaceLane(_)
+            | Instr::I32x4ExtractLane(_)
+            | Instr::I32x4ReplaceLane(_)
+            | Instr::I64x2ExtractLane(_)
+            | Instr::I64x2ReplaceLane(_)
+            | Instr::F32x4ExtractLane(_)
+            | Instr::F32x4ReplaceLane(_)
+            | Instr::F64x2ExtractLane(_)
+            | Instr::F64x2ReplaceLane(_) => self.execute_simd_instr(instr)?,
+
+            Instr::MemoryAtomicNotify(_)
+            | Instr::MemoryAtomicWait32(_)
+            | Instr::MemoryAtomicWait64(_)
+            | Instr::AtomicFence
+            | Instr::AtomicLoad(_, _)
+            | Instr::AtomicStore(_, _)
+            | Instr::AtomicRmw(_, _, _) => self.execute_atomic_instr(instr)?,
+
+            _ => self.execute_numeric_instr(instr)?,
         }
+
+        Ok(Flow::Continue)
     }
 
-    pub fn execute_func(&mut self, func: &Func, params: &[i32]) {
-        for instr in &func.body {
-            match instr {
-                Instr::LocalGet(i) => {
-                    self.stack.push(params[*i]);
+    /// Executes the numeric instructions, which only work on the stack.
+    fn execute_numeric_instr(&mut self, instr: &Instr) -> Result<(), RuntimeError> {
+        match instr {
+            Instr::I32Const(value) => self.stack.push(*value),
+            Instr::I64Const(value) => self.stack.push(*value),
+            Instr::F32Const(value) => self.stack.push(*value),
+            Instr::F64Const(value) => self.stack.push(*value),
+
+            Instr::I32Eqz => self.unop(|a: i32| (a == 0) as i32),
+            Instr::I32Eq => self.relop(|a: i32, b: i32| a == b),
+            Instr::I32Ne => self.relop(|a: i32, b: i32| a != b),
+            Instr::I32LtS => self.relop(|a: i32, b: i32| a < b),
+            Instr::I32LtU => self.relop(|a: i32, b: i32| (a as u32) < (b as u32)),
+            Instr::I32GtS => self.relop(|a: i32, b: i32| a > b),
+            Instr::I32GtU => self.relop(|a: i32, b: i32| (a as u32) > (b as u32)),
+            Instr::I32LeS => self.relop(|a: i32, b: i32| a <= b),
+            Instr::I32LeU => self.relop(|a: i32, b: i32| (a as u32) <= (b as u32)),
+            Instr::I32GeS => self.relop(|a: i32, b: i32| a >= b),
+            Instr::I32GeU => self.relop(|a: i32, b: i32| (a as u32) >= (b as u32)),
+
+            Instr::I32Clz => self.unop(|a: i32| a.leading_zeros() as i32),
+            Instr::I32Ctz => self.unop(|a: i32| a.trailing_zeros() as i32),
+            Instr::I32Popcnt => self.unop(|a: i32| a.count_ones() as i32),
+            Instr::I32Add => self.binop(|a: i32, b: i32| a.wrapping_add(b)),
+            Instr::I32Sub => self.binop(|a: i32, b: i32| a.wrapping_sub(b)),
+            Instr::I32Mul => self.binop(|a: i32, b: i32| a.wrapping_mul(b)),
+            Instr::I32DivS => self.try_binop(|a: i32, b: i32| match b {
+                0 => Err(IntegerDivideByZero),
+                _ => a.checked_div(b).ok_or(IntegerOverflow),
+            })?,
+            Instr::I32DivU => self.try_binop(|a: i32, b: i32| match b {
+                0 => Err(IntegerDivideByZero),
+                _ => Ok(((a as u32) / (b as u32)) as i32),
+            })?,
+            Instr::I32RemS => self.try_binop(|a: i32, b: i32| match b {
+                0 => Err(IntegerDivideByZero),
+                _ => Ok(a.wrapping_rem(b)),
+            })?,
+            Instr::I32RemU => self.try_binop(|a: i32, b: i32| match b {
+                0 => Err(IntegerDivideByZero),
+                _ => Ok(((a as u32) % (b as u32)) as i32),
+            })?,
+            Instr::I32And => self.binop(|a: i32, b: i32| a & b),
+            Instr::I32Or => self.binop(|a: i32, b: i32| a | b),
+            Instr::I32Xor => self.binop(|a: i32, b: i32| a ^ b),
+            Instr::I32Shl => self.binop(|a: i32, b: i32| a.wrapping_shl(b as u32)),
+            Instr::I32ShrS => self.binop(|a: i32, b: i32| a.wrapping_shr(b as u32)),
+            Instr::I32ShrU => self.binop(|a: i32, b: i32| (a as u32).wrapping_shr(b as u32) as i32),
+            Instr::I32Rotl => self.binop(|a: i32, b: i32| a.rotate_left(b as u32)),
+            Instr::I32Rotr => self.binop(|a: i32, b: i32| a.rotate_right(b as u32)),
+
+            Instr::I64Eqz => self.unop(|a: i64| (a == 0) as i32),
+            Instr::I64Eq => self.relop(|a: i64, b: i64| a == b),
+            Instr::I64Ne => self.relop(|a: i64, b: i64| a != b),
+            Instr::I64LtS => self.relop(|a: i64, b: i64| a < b),
+            Instr::I64LtU => self.relop(|a: i64, b: i64| (a as u64) < (b as u64)),
+            Instr::I64GtS => self.relop(|a: i64, b: i64| a > b),
+            Instr::I64GtU => self.relop(|a: i64, b: i64| (a as u64) > (b as u64)),
+            Instr::I64LeS => self.relop(|a: i64, b: i64| a <= b),
+            Instr::I64LeU => self.relop(|a: i64, b: i64| (a as u64) <= (b as u64)),
+            Instr::I64GeS => self.relop(|a: i64, b: i64| a >= b),
+            Instr::I64GeU => self.relop(|a: i64, b: i64| (a as u64) >= (b as u64)),
+
+            Instr::I64Clz => self.unop(|a: i64| a.leading_zeros() as i64),
+            Instr::I64Ctz => self.unop(|a: i64| a.trailing_zeros() as i64),
+            Instr::I64Popcnt => self.unop(|a: i64| a.count_ones() as i64),
+            Instr::I64Add => self.binop(|a: i64, b: i64| a.wrapping_add(b)),
+            Instr::I64Sub => self.binop(|a: i64, b: i64| a.wrapping_sub(b)),
+            Instr::I64Mul => self.binop(|a: i64, b: i64| a.wrapping_mul(b)),
+            Instr::I64DivS => self.try_binop(|a: i64, b: i64| match b {
+                0 => Err(IntegerDivideByZero),
+                _ => a.checked_div(b).ok_or(IntegerOverflow),
+            })?,
+            Instr::I64DivU => self.try_binop(|a: i64, b: i64| match b {
+                0 => Err(IntegerDivideByZero),
+                _ => Ok(((a as u64) / (b as u64)) as i64),
+            })?,
+            Instr::I64RemS => self.try_binop(|a: i64, b: i64| match b {
+                0 => Err(IntegerDivideByZero),
+                _ => Ok(a.wrapping_rem(b)),
+            })?,
+            Instr::I64RemU => self.try_binop(|a: i64, b: i64| match b {
+                0 => Err(IntegerDivideByZero),
+                _ => Ok(((a as u64) % (b as u64)) as i64),
+            })?,
+            Instr::I64And => self.binop(|a: i64, b: i64| a & b),
+            Instr::I64Or => self.binop(|a: i64, b: i64| a | b),
+            Instr::I64Xor => self.binop(|a: i64, b: i64| a ^ b),
+            Instr::I64Shl => self.binop(|a: i64, b: i64| a.wrapping_shl(b as u32)),
+            Instr::I64ShrS => self.binop(|a: i64, b: i64| a.wrapping_shr(b as u32)),
+            Instr::I64ShrU => self.binop(|a: i64, b: i64| (a as u64).wrapping_shr(b as u32) as i64),
+            Instr::I64Rotl => self.binop(|a: i64, b: i64| a.rotate_left(b as u32)),
+            Instr::I64Rotr => self.binop(|a: i64, b: i64| a.rotate_right(b as u32)),
+
+            Instr::F32Eq => self.relop(|a: f32, b: f32| a == b),
+            Instr::F32Ne => self.relop(|a: f32, b: f32| a != b),
+            Instr::F32Lt => self.relop(|a: f32, b: f32| a < b),
+            Instr::F32Gt => self.relop(|a: f32, b: f32| a > b),
+            Instr::F32Le => self.relop(|a: f32, b: f32| a <= b),
+            Instr::F32Ge => self.relop(|a: f32, b: f32| a >= b),
+
+            Instr::F64Eq => self.relop(|a: f64, b: f64| a == b),
+            Instr::F64Ne => self.relop(|a: f64, b: f64| a != b),
+            Instr::F64Lt => self.relop(|a: f64, b: f64| a < b),
+            Instr::F64Gt => self.relop(|a: f64, b: f64| a > b),
+            Instr::F64Le => self.relop(|a: f64, b: f64| a <= b),
+            Instr::F64Ge => self.relop(|a: f64, b: f64| a >= b),
+
+            Instr::F32Abs => self.unop(|a: f32| a.abs()),
+            Instr::F32Neg => self.unop(|a: f32| -a),
+            Instr::F32Ceil => self.unop(|a: f32| a.ceil()),
+            Instr::F32Floor => self.unop(|a: f32| a.floor()),
+            Instr::F32Trunc => self.unop(|a: f32| a.trunc()),
+            Instr::F32Nearest => self.unop(float::f32_nearest),
+            Instr::F32Sqrt => self.unop(|a: f32| a.sqrt()),
+            Instr::F32Add => self.binop(|a: f32, b: f32| a + b),
+            Instr::F32Sub => self.binop(|a: f32, b: f32| a - b),
+            Instr::F32Mul => self.binop(|a: f32, b: f32| a * b),
+            Instr::F32Div => self.binop(|a: f32, b: f32| a / b),
+            Instr::F32Min => self.binop(float::f32_min),
+            Instr::F32Max => self.binop(float::f32_max),
+            Instr::F32Copysign => self.binop(|a: f32, b: f32| a.copysign(b)),
+
+            Instr::F64Abs => self.unop(|a: f64| a.abs()),
+            Instr::F64Neg => self.unop(|a: f64| -a),
+            Instr::F64Ceil => self.unop(|a: f64| a.ceil()),
+            Instr::F64Floor => self.unop(|a: f64| a.floor()),
+            Instr::F64Trunc => self.unop(|a: f64| a.trunc()),
+            Instr::F64Nearest => self.unop(float::f64_nearest),
+            Instr::F64Sqrt => self.unop(|a: f64| a.sqrt()),
+            Instr::F64Add => self.binop(|a: f64, b: f64| a + b),
+            Instr::F64Sub => self.binop(|a: f64, b: f64| a - b),
+            Instr::F64Mul => self.binop(|a: f64, b: f64| a * b),
+            Instr::F64Div => self.binop(|a: f64, b: f64| a / b),
+            Instr::F64Min => self.binop(float::f64_min),
+            Instr::F64Max => self.binop(float::f64_max),
+            Instr::F64Copysign => self.binop(|a: f64, b: f64| a.copysign(b)),
+
+            Instr::I32WrapI64 => self.unop(|a: i64| a as i32),
+            Instr::I32TruncF32S => self.try_unop(|a: f32| {
+                float::trunc(a as f64, I32_MIN_EXCLUSIVE, I32_MAX_EXCLUSIVE).map(|a| a as i32)
+            })?,
+            Instr::I32TruncF32U => self.try_unop(|a: f32| {
+                float::trunc(a as f64, -1.0, U32_MAX_EXCLUSIVE).map(|a| a as u32 as i32)
+            })?,
+            Instr::I32TruncF64S => self.try_unop(|a: f64| {
+                float::trunc(a, I32_MIN_EXCLUSIVE, I32_MAX_EXCLUSIVE).map(|a| a as i32)
+            })?,
+            Instr::I32TruncF64U => self.try_unop(|a: f64| {
+                float::trunc(a, -1.0, U32_MAX_EXCLUSIVE).map(|a| a as u32 as i32)
+            })?,
+            Instr::I64ExtendI32S => self.unop(|a: i32| a as i64),
+            Instr::I64ExtendI32U => self.unop(|a: i32| a as u32 as i64),
+            Instr::I64TruncF32S => self.try_unop(|a: f32| {
+                float::trunc(a as f64, I64_MIN_EXCLUSIVE, I64_MAX_EXCLUSIVE).map(|a| a as i64)
+            })?,
+            Instr::I64TruncF32U => self.try_unop(|a: f32| {
+                float::trunc(a as f64, -1.0, U64_MAX_EXCLUSIVE).map(|a| a as u64 as i64)
+            })?,
+            Instr::I64TruncF64S => self.try_unop(|a: f64| {
+                float::trunc(a, I64_MIN_EXCLUSIVE, I64_MAX_EXCLUSIVE).map(|a| a as i64)
+            })?,
+            Instr::I64TruncF64U => self.try_unop(|a: f64| {
+                float::trunc(a, -1.0, U64_MAX_EXCLUSIVE).map(|a| a as u64 as i64)
+            })?,
+            Instr::F32ConvertI32S => self.unop(|a: i32| a as f32),
+            Instr::F32ConvertI32U => self.unop(|a: i32| a as u32 as f32),
+            Instr::F32ConvertI64S => self.unop(|a: i64| a as f32),
+            Instr::F32ConvertI64U => self.unop(|a: i64| a as u64 as f32),
+            Instr::F32DemoteF64 => self.unop(|a: f64| a as f32),
+            Instr::F64ConvertI32S => self.unop(|a: i32| a as f64),
+            Instr::F64ConvertI32U => self.unop(|a: i32| a as u32 as f64),
+            Instr::F64ConvertI64S => self.unop(|a: i64| a as f64),
+            Instr::F64ConvertI64U => self.unop(|a: i64| a as u64 as f64),
+            Instr::F64PromoteF32 => self.unop(|a: f32| a as f64),
+            Instr::I32ReinterpretF32 => self.unop(|a: f32| a.to_bits() as i32),
+            Instr::I64ReinterpretF64 => self.unop(|a: f64| a.to_bits() as i64),
+            Instr::F32ReinterpretI32 => self.unop(|a: i32| f32::from_bits(a as u32)),
+            Instr::F64ReinterpretI64 => self.unop(|a: i64| f64::from_bits(a as u64)),
+
+            Instr::I32Extend8S => self.unop(|a: i32| a as i8 as i32),
+            Instr::I32Extend16S => self.unop(|a: i32| a as i16 as i32),
+            Instr::I64Extend8S => self.unop(|a: i64| a as i8 as i64),
+            Instr::I64Extend16S => self.unop(|a: i64| a as i16 as i64),
+            Instr::I64Extend32S => self.unop(|a: i64| a as i32 as i64),
+
+            // Rust's float to integer casts saturate and map NaN to 0.
+            Instr::I32TruncSatF32S => self.unop(|a: f32| a as i32),
+            Instr::I32TruncSatF32U => self.unop(|a: f32| a as u32 as i32),
+            Instr::I32TruncSatF64S => self.unop(|a: f64| a as i32),
+            Instr::I32TruncSatF64U => self.unop(|a: f64| a as u32 as i32),
+            Instr::I64TruncSatF32S => self.unop(|a: f32| a as i64),
+            Instr::I64TruncSatF32U => self.unop(|a: f32| a as u64 as i64),
+            Instr::I64TruncSatF64S => self.unop(|a: f64| a as i64),
+            Instr::I64TruncSatF64U => self.unop(|a: f64| a as u64 as i64),
+            _ => unreachable!("not a numeric instruction"),
+        }
+        Ok(())
+    }
+
+    /// Executes the reference instructions, of which `br_on_null` and
+    /// `br_on_non_null` may branch.
+    fn execute_ref_instr(&mut self, instr: &Instr) -> Result<Flow, RuntimeError> {
+        match instr {
+            Instr::RefNull(_) => self.stack.push(None::<usize>),
+            Instr::RefIsNull => self.unop(|r: Option<usize>| r.is_none() as i32),
+            Instr::RefFunc(idx) => self.stack.push(Some(*idx)),
+            Instr::RefAsNonNull => {
+                let r = self.stack.pop::<Option<usize>>().ok_or(NullReference)?;
+                self.stack.push(Some(r));
+            }
+            Instr::BrOnNull(label) => match self.stack.pop::<Option<usize>>() {
+                None => return Ok(Flow::Branch(*label)),
+                r => self.stack.push(r),
+            },
+            Instr::BrOnNonNull(label) => {
+                if let r @ Some(_) = self.stack.pop::<Option<usize>>() {
+                    self.stack.push(r);
+                    return Ok(Flow::Branch(*label));
                 }
-                Instr::I32Add => {
-                    let a: i32 = self.stack.pop();
-                    let b: i32 = self.stack.pop();
-                    let result = a + b;
-                    self.stack.push(result);
+            }
+            _ => unreachable!("not a reference instruction"),
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    fn execute_var_instr(&mut self, instr: &Instr, frame: &mut Frame) -> Result<(), RuntimeError> {
+        match instr {
+            Instr::LocalGet(i) => self.push_value(&frame.locals[*i]),
+            Instr::LocalSet(i) => {
+                let value_type = frame.locals[*i].value_type();
+                frame.locals[*i] = self.pop_value(&value_type);
+            }
+            Instr::LocalTee(i) => {
+                let value_type = frame.locals[*i].value_type();
+                frame.locals[*i] = self.pop_value(&value_type);
+                self.push_value(&frame.locals[*i]);
+            }
+            Instr::GlobalGet(i) => {
+                let value = self.store.globals[*i].get();
+                self.push_value(&value);
+            }
+            Instr::GlobalSet(i) => {
+                let value_type = self.store.globals[*i].global_type().value_type;
+                let value = self.pop_value(&value_type);
+                self.store.globals[*i].set(value)?;
+            }
+            _ => unreachable!("not a variable instruction"),
+        }
+        Ok(())
+    }
+
+    fn execute_table_instr(&mut self, instr: &Instr) -> Result<(), RuntimeError> {
+        match instr {
+            Instr::TableGet(table) => {
+                let idx = self.stack.pop::<i32>() as u32;
+                // Unlike for `call_indirect`, a missing element is out of
+                // bounds.
+                let element = self.store.tables[*table]
+                    .get(idx)
+                    .map_err(|_| TableOutOfBounds)?;
+                self.push_value(&element);
+            }
+            Instr::TableSet(table) => {
+                let element = self.pop_element(*table);
+                let idx = self.stack.pop::<i32>() as u32;
+                self.store.tables[*table].set(idx, element)?;
+            }
+            Instr::TableSize(table) => {
+                let size = self.store.tables[*table].size();
+                self.stack.push(size as i32);
+            }
+            Instr::TableGrow(table) => {
+                let delta = self.stack.pop::<i32>() as u32;
+                let init = self.pop_element(*table);
+                let size = self.store.tables[*table].grow(delta, init);
+                self.stack.push(size.map_or(-1, |s| s as i32));
+            }
+            Instr::TableFill(table) => {
+                let n = self.stack.pop::<i32>() as u32;
+                let element = self.pop_element(*table);
+                let d = self.stack.pop::<i32>() as u32;
+                self.store.tables[*table].fill(d, element, n)?;
+            }
+            Instr::TableCopy(dst, src) => {
+                let n = self.stack.pop::<i32>() as u32;
+                let s = self.stack.pop::<i32>() as u32;
+                let d = self.stack.pop::<i32>() as u32;
+                let elements = self.store.tables[*src].read(s, n)?;
+                self.store.tables[*dst].write(d, &elements)?;
+            }
+            Instr::TableInit(table, elem) => {
+                let n = self.stack.pop::<i32>() as u32 as usize;
+                let s = self.stack.pop::<i32>() as u32 as usize;
+                let d = self.stack.pop::<i32>() as u32;
+                let store = &mut *self.store;
+                let elements = store.elems[*elem].get(s..s + n).ok_or(TableOutOfBounds)?;
+                store.tables[*table].write(d, elements)?;
+            }
+            Instr::ElemDrop(elem) => self.store.elems[*elem].clear(),
+            _ => unreachable!("not a table instruction"),
+        }
+        Ok(())
+    }
+
+    /// Pops a reference of the element type of `table`.
+    fn pop_element(&mut self, table: usize) -> Value {
+        let ref_type = self.store.tables[table].ref_type();
+        self.pop_value(&ref_type.into())
+    }
+
+    fn execute_simd_instr(&mut self, instr: &Instr) -> Result<(), RuntimeError> {
+        match instr {
+            Instr::V128Load(m) => self.mem_load(m, u128::from_le_bytes)?,
+            Instr::V128Load8x8S(m) => self.mem_load(m, |b: [u8; 8]| extend::<i8, i16>(&b))?,
+            Instr::V128Load8x8U(m) => self.mem_load(m, |b: [u8; 8]| extend::<u8, u16>(&b))?,
+            Instr::V128Load16x4S(m) => self.mem_load(m, |b: [u8; 8]| extend::<i16, i32>(&b))?,
+            Instr::V128Load16x4U(m) => self.mem_load(m, |b: [u8; 8]| extend::<u16, u32>(&b))?,
+            Instr::V128Load32x2S(m) => self.mem_load(m, |b: [u8; 8]| extend::<i32, i64>(&b))?,
+            Instr::V128Load32x2U(m) => self.mem_load(m, |b: [u8; 8]| extend::<u32, u64>(&b))?,
+            Instr::V128Load8Splat(m) => self.mem_load(m, |b| simd::splat(u8::from_le_bytes(b)))?,
+            Instr::V128Load16Splat(m) => {
+                self.mem_load(m, |b| simd::splat(u16::from_le_bytes(b)))?
+            }
+            Instr::V128Load32Splat(m) => {
+                self.mem_load(m, |b| simd::splat(u32::from_le_bytes(b)))?
+            }
+            Instr::V128Load64Splat(m) => {
+                self.mem_load(m, |b| simd::splat(u64::from_le_bytes(b)))?
+            }
+            Instr::V128Load32Zero(m) => self.mem_load(m, |b| u32::from_le_bytes(b) as u128)?,
+            Instr::V128Load64Zero(m) => self.mem_load(m, |b| u64::from_le_bytes(b) as u128)?,
+            Instr::V128Store(m) => self.mem_store(m, u128::to_le_bytes)?,
+            Instr::V128Load8Lane(m, lane) => self.load_lane::<1>(m, *lane)?,
+            Instr::V128Load16Lane(m, lane) => self.load_lane::<2>(m, *lane)?,
+            Instr::V128Load32Lane(m, lane) => self.load_lane::<4>(m, *lane)?,
+            Instr::V128Load64Lane(m, lane) => self.load_lane::<8>(m, *lane)?,
+            Instr::V128Store8Lane(m, lane) => self.store_lane::<1>(m, *lane)?,
+            Instr::V128Store16Lane(m, lane) => self.store_lane::<2>(m, *lane)?,
+            Instr::V128Store32Lane(m, lane) => self.store_lane::<4>(m, *lane)?,
+            Instr::V128Store64Lane(m, lane) => self.store_lane::<8>(m, *lane)?,
+
+            Instr::V128Const(v) => self.stack.push(*v),
+            Instr::I8x16Shuffle(indices) => self.binop(|a, b| simd::shuffle(a, b, indices)),
+            Instr::I8x16ExtractLaneS(l) => self.unop(|v| simd::lanes::<i8>(v)[*l as usize] as i32),
+            Instr::I8x16ExtractLaneU(l) => self.unop(|v| simd::lanes::<u8>(v)[*l as usize] as i32),
+            Instr::I8x16ReplaceLane(l) => self.replace_lane(*l, |x: i32| x as i8),
+            Instr::I16x8ExtractLaneS(l) => self.unop(|v| simd::lanes::<i16>(v)[*l as usize] as i32),
+            Instr::I16x8ExtractLaneU(l) => self.unop(|v| simd::lanes::<u16>(v)[*l as usize] as i32),
+            Instr::I16x8ReplaceLane(l) => self.replace_lane(*l, |x: i32| x as i16),
+            Instr::I32x4ExtractLane(l) => self.unop(|v| simd::lanes::<i32>(v)[*l as usize]),
+            Instr::I32x4ReplaceLane(l) => self.replace_lane(*l, |x: i32| x),
+            Instr::I64x2ExtractLane(l) => self.unop(|v| simd::lanes::<i64>(v)[*l as usize]),
+            Instr::I64x2ReplaceLane(l) => self.replace_lane(*l, |x: i64| x),
+            Instr::F32x4ExtractLane(l) => self.unop(|v| simd::lanes::<f32>(v)[*l as usize]),
+            Instr::F32x4ReplaceLane(l) => self.replace_lane(*l, |x: f32| x),
+            Instr::F64x2ExtractLane(l) => self.unop(|v| simd::lanes::<f64>(v)[*l as usize]),
+            Instr::F64x2ReplaceLane(l) => self.replace_lane(*l, |x: f64| x),
+            _ => unreachable!("not a SIMD instruction"),
+        }
+        Ok(())
+    }
+
+    fn execute_memory_instr(&mut self, instr: &Instr) -> Result<(), RuntimeError> {
+        match instr {
+            Instr::I32Load(m) => self.mem_load(m, i32::from_le_bytes)?,
+            Instr::I64Load(m) => self.mem_load(m, i64::from_le_bytes)?,
+            Instr::F32Load(m) => self.mem_load(m, f32::from_le_bytes)?,
+            Instr::F64Load(m) => self.mem_load(m, f64::from_le_bytes)?,
+            Instr::I32Load8S(m) => self.mem_load(m, |b| i8::from_le_bytes(b) as i32)?,
+            Instr::I32Load8U(m) => self.mem_load(m, |b| u8::from_le_bytes(b) as i32)?,
+            Instr::I32Load16S(m) => self.mem_load(m, |b| i16::from_le_bytes(b) as i32)?,
+            Instr::I32Load16U(m) => self.mem_load(m, |b| u16::from_le_bytes(b) as i32)?,
+            Instr::I64Load8S(m) => self.mem_load(m, |b| i8::from_le_bytes(b) as i64)?,
+            Instr::I64Load8U(m) => self.mem_load(m, |b| u8::from_le_bytes(b) as i64)?,
+            Instr::I64Load16S(m) => self.mem_load(m, |b| i16::from_le_bytes(b) as i64)?,
+            Instr::I64Load16U(m) => self.mem_load(m, |b| u16::from_le_bytes(b) as i64)?,
+            Instr::I64Load32S(m) => self.mem_load(m, |b| i32::from_le_bytes(b) as i64)?,
+            Instr::I64Load32U(m) => self.mem_load(m, |b| u32::from_le_bytes(b) as i64)?,
+            Instr::I32Store(m) => self.mem_store(m, i32::to_le_bytes)?,
+            Instr::I64Store(m) => self.mem_store(m, i64::to_le_bytes)?,
+            Instr::F32Store(m) => self.mem_store(m, f32::to_le_bytes)?,
+            Instr::F64Store(m) => self.mem_store(m, f64::to_le_bytes)?,
+            Instr::I32Store8(m) => self.mem_store(m, |v: i32| (v as u8).to_le_bytes())?,
+            Instr::I32Store16(m) => self.mem_store(m, |v: i32| (v as u16).to_le_bytes())?,
+            Instr::I64Store8(m) => self.mem_store(m, |v: i64| (v as u8).to_le_bytes())?,
+            Instr::I64Store16(m) => self.mem_store(m, |v: i64| (v as u16).to_le_bytes())?,
+            Instr::I64Store32(m) => self.mem_store(m, |v: i64| (v as u32).to_le_bytes())?,
+            Instr::MemorySize(memory) => {
+                let size = self.store.memories[*memory].size();
+                self.push_addr(*memory, size);
+            }
+            Instr::MemoryGrow(memory) => {
+                let delta = self.pop_addr(*memory);
+                let size = self.store.memories[*memory].grow(delta);
+                self.push_addr(*memory, size.unwrap_or(u64::MAX));
+            }
+            Instr::MemoryInit(memory, data) => {
+                let n = self.stack.pop::<i32>() as u32;
+                let s = self.stack.pop::<i32>() as u32 as usize;
+                let d = self.pop_addr(*memory);
+                let store = &mut *self.store;
+                let bytes = store.datas[*data]
+                    .get(s..s + n as usize)
+                    .ok_or(MemoryOutOfBounds)?;
+                store.memories[*memory].store(d, 0, bytes)?;
+            }
+            Instr::DataDrop(idx) => self.store.datas[*idx].clear(),
+            Instr::MemoryCopy(dst, src) => {
+                // The length is an `i64` only if both memories are 64-bit.
+                let n = match self.store.memories[*src].is_64() {
+                    true => self.pop_addr(*dst),
+                    false => self.pop_addr(*src),
+                };
+                let s = self.pop_addr(*src);
+                let d = self.pop_addr(*dst);
+                if dst == src {
+                    self.store.memories[*dst].copy(d, s, n)?;
+                } else {
+                    let bytes = self.store.memories[*src].read(s, n)?;
+                    self.store.memories[*dst].store(d, 0, &bytes)?;
                 }
             }
+            Instr::MemoryFill(memory) => {
+                let n = self.pop_addr(*memory);
+                let value = self.stack.pop::<i32>() as u8;
+                let d = self.pop_addr(*memory);
+                self.store.memories[*memory].fill(d, value, n)?;
+            }
+            _ => unreachable!("not a memory instruction"),
+        }
+        Ok(())
+    }
+
+    fn execute_atomic_instr(&mut self, instr: &Instr) -> Result<(), RuntimeError> {
+        match instr {
+            Instr::MemoryAtomicNotify(m) => {
+                let count = self.stack.pop::<i32>() as u32;
+                let addr = self.pop_addr(m.memory);
+                let woken = self.store.memories[m.memory].notify(addr, m.offset, count)?;
+                self.stack.push(woken as i32);
+            }
+            Instr::MemoryAtomicWait32(m) => {
+                let timeout = wait_timeout(self.stack.pop::<i64>());
+                let expected = self.stack.pop::<i32>().to_le_bytes();
+                let addr = self.pop_addr(m.memory);
+                let result =
+                    self.store.memories[m.memory].wait(addr, m.offset, &expected, timeout)?;
+                self.stack.push(result as i32);
+            }
+            Instr::MemoryAtomicWait64(m) => {
+                let timeout = wait_timeout(self.stack.pop::<i64>());
+                let expected = self.stack.pop::<i64>().to_le_bytes();
+                let addr = self.pop_addr(m.memory);
+                let result =
+                    self.store.memories[m.memory].wait(addr, m.offset, &expected, timeout)?;
+                self.stack.push(result as i32);
+            }
+            Instr::AtomicFence => fence(Ordering::SeqCst),
+            Instr::AtomicLoad(width, m) => {
+                let addr = self.pop_addr(m.memory);
+                let value = self.store.memories[m.memory].atomic_rmw(
+                    addr,
+                    m.offset,
+                    width.bytes(),
+                    |v| v,
+                )?;
+                self.push_atomic(width, value);
+            }
+            Instr::AtomicStore(width, m) => {
+                let value = self.pop_atomic(width);
+                let addr = self.pop_addr(m.memory);
+                self.store.memories[m.memory]
+                    .atomic_rmw(addr, m.offset, width.bytes(), |_| value)?;
+            }
+            Instr::AtomicRmw(op, width, m) => {
+                let operand = self.pop_atomic(width);
+                // The expected value is compared with the zero-extended one
+                // in memory, so it is wrapped to the width of the access.
+                let mask = u64::MAX >> (64 - 8 * width.bytes());
+                let expected = match op {
+                    AtomicRmwOp::Cmpxchg => self.pop_atomic(width) & mask,
+                    _ => 0,
+                };
+                let addr = self.pop_addr(m.memory);
+                let f = |old: u64| match op {
+                    AtomicRmwOp::Add => old.wrapping_add(operand),
+                    AtomicRmwOp::Sub => old.wrapping_sub(operand),
+                    AtomicRmwOp::And => old & operand,
+                    AtomicRmwOp::Or => old | operand,
+                    AtomicRmwOp::Xor => old ^ operand,
+                    AtomicRmwOp::Xchg => operand,
+                    AtomicRmwOp::Cmpxchg if old == expected => operand,
+                    AtomicRmwOp::Cmpxchg => old,
+                };
+                let old =
+                    self.store.memories[m.memory].atomic_rmw(addr, m.offset, width.bytes(), f)?;
+                self.push_atomic(width, old);
+            }
+            _ => unreachable!("not an atomic instruction"),
+        }
+        Ok(())
+    }
+
+    /// Pops an address, or a size in pages or bytes, of `memory`, which is
+    /// an `i64` for 64-bit memories.
+    fn pop_addr(&mut self, memory: usize) -> u64 {
+        if self.store.memories[memory].is_64() {
+            self.stack.pop::<i64>() as u64
+        } else {
+            self.stack.pop::<i32>() as u32 as u64
+        }
+    }
+
+    /// Pushes an address or a size, the counterpart of `pop_addr`. The
+    /// `u64::MAX` of a failed `memory.grow` becomes -1 either way.
+    fn push_addr(&mut self, memory: usize, value: u64) {
+        if self.store.memories[memory].is_64() {
+            self.stack.push(value as i64);
+        } else {
+            self.stack.push(value as i32);
+        }
+    }
+
+    /// Pops the operand of an atomic access, zero-extended to 64 bits.
+    fn pop_atomic(&mut self, width: &AtomicWidth) -> u64 {
+        match width.value_type() {
+            ValueType::I32 => self.stack.pop::<i32>() as u32 as u64,
+            _ => self.stack.pop::<i64>() as u64,
+        }
+    }
+
+    fn push_atomic(&mut self, width: &AtomicWidth, value: u64) {
+        match width.value_type() {
+            ValueType::I32 => self.stack.push(value as i32),
+            _ => self.stack.push(value as i64),
+        }
+    }
+
+    fn replace_lane<T: Stackable, L: Lane>(&mut self, lane: u8, f: impl Fn(T) -> L) {
+        let x = self.stack.pop::<T>();
+        let v = self.stack.pop::<u128>();
+        self.stack.push(simd::replace_lane(v, lane as usize, f(x)));
+    }
+
+    /// Replaces lane `lane` of the `v128` on the stack with `N` bytes loaded
+    /// from memory.
+    fn load_lane<const N: usize>(&mut self, memarg: &MemArg, lane: u8) -> Result<(), RuntimeError> {
+        let v = self.stack.pop::<u128>();
+        let addr = self.pop_addr(memarg.memory);
+        let bytes = self.store.memories[memarg.memory].load::<N>(addr, memarg.offset)?;
+        let mut lanes = v.to_le_bytes();
+        let start = lane as usize * N;
+        lanes[start..start + N].copy_from_slice(&bytes);
+        self.stack.push(u128::from_le_bytes(lanes));
+        Ok(())
+    }
+
+    fn store_lane<const N: usize>(
+        &mut self,
+        memarg: &MemArg,
+        lane: u8,
+    ) -> Result<(), RuntimeError> {
+        let v = self.stack.pop::<u128>();
+        let addr = self.pop_addr(memarg.memory);
+        let start = lane as usize * N;
+        let bytes = &v.to_le_bytes()[start..start + N];
+        self.store.memories[memarg.memory].store(addr, memarg.offset, bytes)
+    }
+
+    /// Pushes `value`. Host objects are put on the stack by their handle.
+    fn push_value(&mut self, value: &Value) {
+        match value {
+            Value::I32(v) => self.stack.push(*v),
+            Value::I64(v) => self.stack.push(*v),
+            Value::F32(v) => self.stack.push(*v),
+            Value::F64(v) => self.stack.push(*v),
+            Value::V128(v) => self.stack.push(*v),
+            Value::FuncRef(r) => self.stack.push(*r),
+            Value::ExternRef(r) => {
+                self.collect_handles();
+                let handle = r.as_ref().map(|r| self.store.extern_handle(r));
+                self.stack.push(handle);
+            }
+            Value::ExnRef(r) => {
+                self.collect_handles();
+                let handle = r.as_ref().map(|r| self.store.exception_handle(r));
+                self.stack.push(handle);
+            }
+        }
+    }
+
+    /// Frees the handles that aren't on the operand stack once a handle
+    /// table is full, so that a loop dropping references doesn't grow it
+    /// without bound. Everything else holds the objects themselves. Any
+    /// value of the size of a reference counts, e.g. an `i64`, which may
+    /// only keep a few more handles.
+    fn collect_handles(&mut self) {
+        if self.store.handles_full() {
+            let reachable = self.stack.refs().collect();
+            self.store.retain_handles(&reachable);
         }
     }
 
-    pub fn get_result(&mut self) -> i32 {
-        self.stack.pop::<i32>()
+    fn pop_value(&mut self, value_type: &ValueType) -> Value {
+        match value_type {
+            ValueType::I32 => Value::I32(self.stack.pop()),
+            ValueType::I64 => Value::I64(self.stack.pop()),
+            ValueType::F32 => Value::F32(self.stack.pop()),
+            ValueType::F64 => Value::F64(self.stack.pop()),
+            ValueType::V128 => Value::V128(self.stack.pop()),
+            ValueType::FuncRef | ValueType::Ref { .. } => Value::FuncRef(self.stack.pop()),
+            ValueType::ExternRef => {
+                let handle = self.stack.pop::<Option<usize>>();
+                Value::ExternRef(handle.map(|h| self.store.extern_ref(h)))
+            }
+            ValueType::ExnRef => {
+                let handle = self.stack.pop::<Option<usize>>();
+                Value::ExnRef(handle.map(|h| self.store.exception(h)))
+            }
+        }
+    }
+
+    /// Pops values of the given types, which are in stack order.
+    pub fn pop_values(&mut self, types: &[ValueType]) -> Vec<Value> {
+        let mut values = types
+            .iter()
+            .rev()
+            .map(|t| self.pop_value(t))
+            .collect::<Vec<_>>();
+        values.reverse();
+        values
+    }
+
+    fn mem_load<const N: usize, R: Stackable>(
+        &mut self,
+        memarg: &MemArg,
+        f: impl Fn([u8; N]) -> R,
+    ) -> Result<(), RuntimeError> {
+        let addr = self.pop_addr(memarg.memory);
+        let bytes = self.store.memories[memarg.memory].load(addr, memarg.offset)?;
+        self.stack.push(f(bytes));
+        Ok(())
+    }
+
+    fn mem_store<T: Stackable, const N: usize>(
+        &mut self,
+        memarg: &MemArg,
+        f: impl Fn(T) -> [u8; N],
+    ) -> Result<(), RuntimeError> {
+        let value = self.stack.pop::<T>();
+        let addr = self.pop_addr(memarg.memory);
+        self.store.memories[memarg.memory].store(addr, memarg.offset, &f(value))
+    }
+
+    fn unop<T: Stackable, R: Stackable>(&mut self, f: impl Fn(T) -> R) {
+        let a = self.stack.pop::<T>();
+        self.stack.push(f(a));
+    }
+
+    fn try_unop<T: Stackable, R: Stackable>(
+        &mut self,
+        f: impl Fn(T) -> Result<R, RuntimeError>,
+    ) -> Result<(), RuntimeError> {
+        let a = self.stack.pop::<T>();
+        self.stack.push(f(a)?);
+        Ok(())
+    }
+
+    fn binop<T: Stackable>(&mut self, f: impl Fn(T, T) -> T) {
+        let b = self.stack.pop::<T>();
+        let a = self.stack.pop::<T>();
+        self.stack.push(f(a, b));
+    }
+
+    fn try_binop<T: Stackable>(
+        &mut self,
+        f: impl Fn(T, T) -> Result<T, RuntimeError>,
+    ) -> Result<(), RuntimeError> {
+        let b = self.stack.pop::<T>();
+        let a = self.stack.pop::<T>();
+        self.stack.push(f(a, b)?);
+        Ok(())
+    }
+
+    fn relop<T: Stackable>(&mut self, f: impl Fn(T, T) -> bool) {
+        let b = self.stack.pop::<T>();
+        let a = self.stack.pop::<T>();
+        self.stack.push(f(a, b) as i32);
+    }
+}
+
+/// Converts the timeout of a wait in nanoseconds. Negative timeouts never
+/// expire.
+fn wait_timeout(nanos: i64) -> Option<Duration> {
+    u64::try_from(nanos).ok().map(Duration::from_nanos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Instr::*;
+    use crate::ast::{self, HeapType, Limits, RefType, SimdOp};
+    use crate::runtime::error::RuntimeError::InvalidConversionToInteger;
+    use crate::runtime::error::RuntimeError::UndefinedElement;
+    use crate::runtime::memory::Memory;
+    use crate::runtime::table::Table;
+    use crate::runtime::value::ExternRef;
+
+    fn execute(
+        body: Vec<Instr>,
+        params: &[Value],
+        result: ValueType,
+    ) -> Result<Value, RuntimeError> {
+        let module = Module {
+            types: vec![(params.iter().map(Value::value_type).collect(), vec![result])],
+            imports: vec![],
+            funcs: vec![Func {
+                f_type: 0,
+                locals: vec![],
+                body,
+            }],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![],
+            start: None,
+        };
+        let mut store = Store::new(());
+        let mut processor = Processor::new(&module, &mut store, MAX_CALL_DEPTH);
+        processor.execute_func(0, params)?;
+        Ok(processor.pop_values(&[result]).remove(0))
+    }
+
+    fn run(body: Vec<Instr>, params: &[i32]) -> Result<i32, RuntimeError> {
+        let params = params.iter().map(|p| Value::I32(*p)).collect::<Vec<_>>();
+        run_typed(body, &params)
+    }
+
+    fn run_typed(body: Vec<Instr>, params: &[Value]) -> Result<i32, RuntimeError> {
+        match execute(body, params, ValueType::I32)? {
+            Value::I32(v) => Ok(v),
+            v => panic!("Unexpected result {:?}", v),
+        }
+    }
+
+    fn binary(instr: Instr, a: i32, b: i32) -> Result<i32, RuntimeError> {
+        run(vec![LocalGet(0), LocalGet(1), instr], &[a, b])
+    }
+
+    fn unary(instr: Instr, a: i32) -> Result<i32, RuntimeError> {
+        run(vec![LocalGet(0), instr], &[a])
+    }
+
+    fn binary64(instr: Instr, a: i64, b: i64) -> Result<Value, RuntimeError> {
+        let params = [Value::I64(a), Value::I64(b)];
+        let result = match instr {
+            I64Eq | I64Ne | I64LtS | I64LtU | I64GtS | I64GtU | I64LeS | I64LeU | I64GeS
+            | I64GeU => ValueType::I32,
+            _ => ValueType::I64,
+        };
+        execute(vec![LocalGet(0), LocalGet(1), instr], &params, result)
+    }
+
+    fn unary64(instr: Instr, a: i64) -> Result<Value, RuntimeError> {
+        let result = match instr {
+            I64Eqz => ValueType::I32,
+            _ => ValueType::I64,
+        };
+        execute(vec![LocalGet(0), instr], &[Value::I64(a)], result)
+    }
+
+    #[test]
+    fn consts() {
+        assert_eq!(Ok(-5), run(vec![I32Const(-5)], &[]));
+        assert_eq!(Ok(2), run(vec![I32Const(7), I32Const(5), I32Sub], &[]));
+
+        let module = Module {
+            types: vec![(vec![], vec![ValueType::I32, ValueType::I64])],
+            imports: vec![],
+            funcs: vec![Func {
+                f_type: 0,
+                locals: vec![],
+                body: vec![I32Const(1), I64Const(i64::MIN)],
+            }],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![],
+            start: None,
+        };
+        let mut store = Store::new(());
+        let mut processor = Processor::new(&module, &mut store, MAX_CALL_DEPTH);
+        processor.execute_func(0, &[]).unwrap();
+        assert_eq!(i64::MIN, processor.stack.pop::<i64>());
+        assert_eq!(1, processor.stack.pop::<i32>());
+    }
+
+    #[test]
+    fn i32_arithmetic() {
+        assert_eq!(Ok(i32::MIN), binary(I32Add, i32::MAX, 1));
+        assert_eq!(Ok(i32::MAX), binary(I32Sub, i32::MIN, 1));
+        assert_eq!(Ok(-2), binary(I32Sub, 3, 5));
+        assert_eq!(Ok(0), binary(I32Mul, 0x1000_0000, 0x10));
+        assert_eq!(Ok(-3), binary(I32DivS, -7, 2));
+        assert_eq!(Ok(0x7fff_fffc), binary(I32DivU, -7, 2));
+        assert_eq!(Ok(-1), binary(I32RemS, -7, 2));
+        assert_eq!(Ok(1), binary(I32RemU, -7, 2));
+        assert_eq!(Ok(0), binary(I32RemS, i32::MIN, -1));
+    }
+
+    #[test]
+    fn i32_division_traps() {
+        assert_eq!(Err(IntegerDivideByZero), binary(I32DivS, 1, 0));
+        assert_eq!(Err(IntegerDivideByZero), binary(I32DivU, 1, 0));
+        assert_eq!(Err(IntegerDivideByZero), binary(I32RemS, 1, 0));
+        assert_eq!(Err(IntegerDivideByZero), binary(I32RemU, 1, 0));
+        assert_eq!(Err(IntegerOverflow), binary(I32DivS, i32::MIN, -1));
+    }
+
+    #[test]
+    fn i32_bitwise() {
+        assert_eq!(Ok(0b1000), binary(I32And, 0b1100, 0b1010));
+        assert_eq!(Ok(0b1110), binary(I32Or, 0b1100, 0b1010));
+        assert_eq!(Ok(0b0110), binary(I32Xor, 0b1100, 0b1010));
+        assert_eq!(Ok(i32::MIN), binary(I32Shl, 1, 31));
+        assert_eq!(Ok(2), binary(I32Shl, 1, 33));
+        assert_eq!(Ok(-1), binary(I32ShrS, i32::MIN, 31));
+        assert_eq!(Ok(1), binary(I32ShrU, i32::MIN, 31));
+        assert_eq!(Ok(1), binary(I32Rotl, i32::MIN, 1));
+        assert_eq!(Ok(i32::MIN), binary(I32Rotr, 1, 33));
+        assert_eq!(Ok(32), unary(I32Clz, 0));
+        assert_eq!(Ok(31), unary(I32Clz, 1));
+        assert_eq!(Ok(32), unary(I32Ctz, 0));
+        assert_eq!(Ok(31), unary(I32Ctz, i32::MIN));
+        assert_eq!(Ok(32), unary(I32Popcnt, -1));
+    }
+
+    #[test]
+    fn i32_comparison() {
+        assert_eq!(Ok(1), unary(I32Eqz, 0));
+        assert_eq!(Ok(0), unary(I32Eqz, 1));
+        assert_eq!(Ok(1), binary(I32Eq, 3, 3));
+        assert_eq!(Ok(1), binary(I32Ne, 3, 4));
+        assert_eq!(Ok(1), binary(I32LtS, -1, 0));
+        assert_eq!(Ok(0), binary(I32LtU, -1, 0));
+        assert_eq!(Ok(0), binary(I32GtS, -1, 0));
+        assert_eq!(Ok(1), binary(I32GtU, -1, 0));
+        assert_eq!(Ok(1), binary(I32LeS, 0, 0));
+        assert_eq!(Ok(0), binary(I32LeU, -1, 0));
+        assert_eq!(Ok(1), binary(I32GeS, 0, -1));
+        assert_eq!(Ok(0), binary(I32GeU, 0, -1));
+    }
+
+    #[test]
+    fn i64_arithmetic() {
+        use Value::I64 as V;
+        assert_eq!(Ok(V(i64::MIN)), binary64(I64Add, i64::MAX, 1));
+        assert_eq!(Ok(V(i64::MAX)), binary64(I64Sub, i64::MIN, 1));
+        assert_eq!(Ok(V(1 << 40)), binary64(I64Mul, 1 << 20, 1 << 20));
+        assert_eq!(Ok(V(0)), binary64(I64Mul, 1 << 32, 1 << 32));
+        assert_eq!(Ok(V(-3)), binary64(I64DivS, -7, 2));
+        assert_eq!(Ok(V(0x7fff_ffff_ffff_fffc)), binary64(I64DivU, -7, 2));
+        assert_eq!(Ok(V(-1)), binary64(I64RemS, -7, 2));
+        assert_eq!(Ok(V(1)), binary64(I64RemU, -7, 2));
+        assert_eq!(Ok(V(0)), binary64(I64RemS, i64::MIN, -1));
+        assert_eq!(Err(IntegerDivideByZero), binary64(I64DivU, 1, 0));
+        assert_eq!(Err(IntegerDivideByZero), binary64(I64RemS, 1, 0));
+        assert_eq!(Err(IntegerOverflow), binary64(I64DivS, i64::MIN, -1));
+    }
+
+    #[test]
+    fn i64_bitwise() {
+        use Value::I64 as V;
+        assert_eq!(Ok(V(0b1000)), binary64(I64And, 0b1100, 0b1010));
+        assert_eq!(Ok(V(0b1110)), binary64(I64Or, 0b1100, 0b1010));
+        assert_eq!(Ok(V(0b0110)), binary64(I64Xor, 0b1100, 0b1010));
+        assert_eq!(Ok(V(1 << 32)), binary64(I64Shl, 1, 32));
+        assert_eq!(Ok(V(2)), binary64(I64Shl, 1, 65));
+        assert_eq!(Ok(V(-1)), binary64(I64ShrS, i64::MIN, 63));
+        assert_eq!(Ok(V(1)), binary64(I64ShrU, i64::MIN, 63));
+        assert_eq!(Ok(V(1)), binary64(I64Rotl, i64::MIN, 1));
+        assert_eq!(Ok(V(i64::MIN)), binary64(I64Rotr, 1, 65));
+        assert_eq!(Ok(V(64)), unary64(I64Clz, 0));
+        assert_eq!(Ok(V(31)), unary64(I64Clz, 1 << 32));
+        assert_eq!(Ok(V(64)), unary64(I64Ctz, 0));
+        assert_eq!(Ok(V(32)), unary64(I64Ctz, 1 << 32));
+        assert_eq!(Ok(V(64)), unary64(I64Popcnt, -1));
+    }
+
+    #[test]
+    fn i64_comparison() {
+        use Value::I32 as V;
+        assert_eq!(Ok(V(1)), unary64(I64Eqz, 0));
+        assert_eq!(Ok(V(0)), unary64(I64Eqz, 1 << 32));
+        assert_eq!(Ok(V(0)), binary64(I64Eq, 1, 1 << 32 | 1));
+        assert_eq!(Ok(V(1)), binary64(I64Ne, 1, 1 << 32 | 1));
+        assert_eq!(Ok(V(1)), binary64(I64LtS, -1, 0));
+        assert_eq!(Ok(V(0)), binary64(I64LtU, -1, 0));
+        assert_eq!(Ok(V(0)), binary64(I64GtS, -1, 0));
+        assert_eq!(Ok(V(1)), binary64(I64GtU, -1, 0));
+        assert_eq!(Ok(V(1)), binary64(I64LeS, i64::MIN, i64::MIN));
+        assert_eq!(Ok(V(0)), binary64(I64LeU, -1, 0));
+        assert_eq!(Ok(V(1)), binary64(I64GeS, 0, -1));
+        assert_eq!(Ok(V(0)), binary64(I64GeU, 0, -1));
+    }
+
+    #[test]
+    fn float_arithmetic() {
+        let f32_binary = |instr, a: f32, b: f32| {
+            let params = [Value::F32(a), Value::F32(b)];
+            execute(
+                vec![LocalGet(0), LocalGet(1), instr],
+                &params,
+                ValueType::F32,
+            )
+        };
+        let f64_unary =
+            |instr, a: f64| execute(vec![LocalGet(0), instr], &[Value::F64(a)], ValueType::F64);
+
+        assert_eq!(Ok(Value::F32(3.5)), f32_binary(F32Add, 1.25, 2.25));
+        assert_eq!(Ok(Value::F32(-1.0)), f32_binary(F32Sub, 1.25, 2.25));
+        assert_eq!(Ok(Value::F32(f32::INFINITY)), f32_binary(F32Div, 1.0, 0.0));
+        assert_eq!(Ok(Value::F32(-0.0)), f32_binary(F32Min, 0.0, -0.0));
+        assert_eq!(Ok(Value::F32(-2.0)), f32_binary(F32Copysign, 2.0, -0.0));
+        assert_eq!(Ok(Value::F64(3.0)), f64_unary(F64Sqrt, 9.0));
+        assert_eq!(Ok(Value::F64(-1.0)), f64_unary(F64Ceil, -1.5));
+        assert_eq!(Ok(Value::F64(-2.0)), f64_unary(F64Floor, -1.5));
+        assert_eq!(Ok(Value::F64(-1.0)), f64_unary(F64Trunc, -1.5));
+        assert_eq!(Ok(Value::F64(-2.0)), f64_unary(F64Nearest, -1.5));
+        assert_eq!(Ok(Value::F64(1.5)), f64_unary(F64Abs, -1.5));
+
+        match f32_binary(F32Max, f32::NAN, 1.0) {
+            Ok(Value::F32(v)) => assert!(v.is_nan()),
+            r => panic!("Unexpected result {:?}", r),
+        }
+        match f64_unary(F64Sqrt, -1.0) {
+            Ok(Value::F64(v)) => assert!(v.is_nan()),
+            r => panic!("Unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn float_sign_operations_preserve_nan_payload() {
+        let nan = f32::from_bits(0x7fa0_0001);
+        let result = execute(vec![F32Const(nan), F32Neg], &[], ValueType::F32);
+        assert_eq!(
+            Ok(0xffa0_0001),
+            result.map(|v| match v {
+                Value::F32(v) => v.to_bits(),
+                v => panic!("Unexpected result {:?}", v),
+            })
+        );
+
+        let nan = f64::from_bits(0xfff0_0000_0000_0abc);
+        let result = execute(vec![F64Const(nan), F64Abs], &[], ValueType::F64);
+        assert_eq!(
+            Ok(0x7ff0_0000_0000_0abc),
+            result.map(|v| match v {
+                Value::F64(v) => v.to_bits(),
+                v => panic!("Unexpected result {:?}", v),
+            })
+        );
+    }
+
+    #[test]
+    fn float_comparison() {
+        let f64_binary = |instr, a: f64, b: f64| {
+            let params = [Value::F64(a), Value::F64(b)];
+            run_typed(vec![LocalGet(0), LocalGet(1), instr], &params)
+        };
+
+        assert_eq!(Ok(1), f64_binary(F64Eq, 0.0, -0.0));
+        assert_eq!(Ok(0), f64_binary(F64Eq, f64::NAN, f64::NAN));
+        assert_eq!(Ok(1), f64_binary(F64Ne, f64::NAN, f64::NAN));
+        assert_eq!(Ok(0), f64_binary(F64Lt, f64::NAN, 1.0));
+        assert_eq!(Ok(1), f64_binary(F64Lt, -1.0, 1.0));
+        assert_eq!(Ok(0), f64_binary(F64Gt, -1.0, 1.0));
+        assert_eq!(Ok(1), f64_binary(F64Le, 1.0, 1.0));
+        assert_eq!(Ok(0), f64_binary(F64Ge, f64::NAN, 1.0));
+    }
+
+    #[test]
+    fn conversions() {
+        let convert =
+            |instr, param: Value, result| execute(vec![LocalGet(0), instr], &[param], result);
+        use ValueType::*;
+
+        assert_eq!(
+            Ok(Value::I32(-1)),
+            convert(I32WrapI64, Value::I64(0xffff_ffff), I32)
+        );
+        assert_eq!(
+            Ok(Value::I64(-1)),
+            convert(I64ExtendI32S, Value::I32(-1), I64)
+        );
+        assert_eq!(
+            Ok(Value::I64(0xffff_ffff)),
+            convert(I64ExtendI32U, Value::I32(-1), I64)
+        );
+        assert_eq!(
+            Ok(Value::I32(-3)),
+            convert(I32TruncF32S, Value::F32(-3.9), I32)
+        );
+        assert_eq!(
+            Ok(Value::I32(-1)),
+            convert(I32TruncF64U, Value::F64(4294967295.9), I32)
+        );
+        assert_eq!(
+            Ok(Value::I64(i64::MIN)),
+            convert(I64TruncF64S, Value::F64(-9223372036854775808.0), I64)
+        );
+        assert_eq!(
+            Ok(Value::I64(-(1 << 40))),
+            convert(I64TruncF32U, Value::F32(18446742974197923840.0), I64)
+        );
+        assert_eq!(
+            Ok(Value::F32(4294967296.0)),
+            convert(F32ConvertI32U, Value::I32(-1), F32)
+        );
+        assert_eq!(
+            Ok(Value::F32(9223372036854775808.0)),
+            convert(F32ConvertI64S, Value::I64(i64::MAX), F32)
+        );
+        assert_eq!(
+            Ok(Value::F64(18446744073709551616.0)),
+            convert(F64ConvertI64U, Value::I64(-1), F64)
+        );
+        assert_eq!(
+            Ok(Value::F64(-1.0)),
+            convert(F64ConvertI32S, Value::I32(-1), F64)
+        );
+        assert_eq!(
+            Ok(Value::F32(f32::INFINITY)),
+            convert(F32DemoteF64, Value::F64(1e300), F32)
+        );
+        assert_eq!(
+            Ok(Value::F64(1.5)),
+            convert(F64PromoteF32, Value::F32(1.5), F64)
+        );
+        assert_eq!(
+            Ok(Value::I32(0x3fc0_0000)),
+            convert(I32ReinterpretF32, Value::F32(1.5), I32)
+        );
+        assert_eq!(
+            Ok(Value::F64(-0.0)),
+            convert(F64ReinterpretI64, Value::I64(i64::MIN), F64)
+        );
+    }
+
+    #[test]
+    fn trapping_truncation() {
+        let convert =
+            |instr, param: Value, result| execute(vec![LocalGet(0), instr], &[param], result);
+        use ValueType::*;
+
+        assert_eq!(
+            Err(InvalidConversionToInteger),
+            convert(I32TruncF32S, Value::F32(f32::NAN), I32)
+        );
+        assert_eq!(
+            Err(IntegerOverflow),
+            convert(I32TruncF32S, Value::F32(2147483648.0), I32)
+        );
+        assert_eq!(
+            Err(IntegerOverflow),
+            convert(I32TruncF64S, Value::F64(-2147483649.0), I32)
+        );
+        assert_eq!(
+            Err(IntegerOverflow),
+            convert(I32TruncF64U, Value::F64(-1.0), I32)
+        );
+        assert_eq!(
+            Ok(Value::I32(0)),
+            convert(I32TruncF64U, Value::F64(-0.9), I32)
+        );
+        assert_eq!(
+            Err(IntegerOverflow),
+            convert(I64TruncF64U, Value::F64(18446744073709551616.0), I64)
+        );
+        assert_eq!(
+            Err(IntegerOverflow),
+            convert(I64TruncF32S, Value::F32(f32::INFINITY), I64)
+        );
+        assert_eq!(
+            Err(InvalidConversionToInteger),
+            convert(I64TruncF64U, Value::F64(f64::NAN), I64)
+        );
+    }
+
+    #[test]
+    fn saturating_truncation() {
+        let convert =
+            |instr, param: Value, result| execute(vec![LocalGet(0), instr], &[param], result);
+        use ValueType::*;
+
+        assert_eq!(
+            Ok(Value::I32(0)),
+            convert(I32TruncSatF32S, Value::F32(f32::NAN), I32)
+        );
+        assert_eq!(
+            Ok(Value::I32(i32::MAX)),
+            convert(I32TruncSatF32S, Value::F32(3e9), I32)
+        );
+        assert_eq!(
+            Ok(Value::I32(i32::MIN)),
+            convert(I32TruncSatF64S, Value::F64(-3e9), I32)
+        );
+        assert_eq!(
+            Ok(Value::I32(-1)),
+            convert(I32TruncSatF64U, Value::F64(1e10), I32)
+        );
+        assert_eq!(
+            Ok(Value::I32(0)),
+            convert(I32TruncSatF32U, Value::F32(-1.0), I32)
+        );
+        assert_eq!(
+            Ok(Value::I64(i64::MAX)),
+            convert(I64TruncSatF64S, Value::F64(f64::INFINITY), I64)
+        );
+        assert_eq!(
+            Ok(Value::I64(-1)),
+            convert(I64TruncSatF32U, Value::F32(f32::INFINITY), I64)
+        );
+        assert_eq!(
+            Ok(Value::I64(0)),
+            convert(I64TruncSatF64U, Value::F64(-f64::INFINITY), I64)
+        );
+        assert_eq!(
+            Ok(Value::I64(-5)),
+            convert(I64TruncSatF32S, Value::F32(-5.5), I64)
+        );
+    }
+
+    #[test]
+    fn sign_extension() {
+        assert_eq!(Ok(0), unary(I32Extend8S, 0));
+        assert_eq!(Ok(127), unary(I32Extend8S, 0x7f));
+        assert_eq!(Ok(-128), unary(I32Extend8S, 0x80));
+        assert_eq!(Ok(-1), unary(I32Extend8S, 0xff));
+        assert_eq!(Ok(0), unary(I32Extend8S, 0x0123_4500));
+        assert_eq!(Ok(-0x80), unary(I32Extend8S, 0xfedc_ba80_u32 as i32));
+        assert_eq!(Ok(-1), unary(I32Extend8S, -1));
+
+        assert_eq!(Ok(0), unary(I32Extend16S, 0));
+        assert_eq!(Ok(32767), unary(I32Extend16S, 0x7fff));
+        assert_eq!(Ok(-32768), unary(I32Extend16S, 0x8000));
+        assert_eq!(Ok(-1), unary(I32Extend16S, 0xffff));
+        assert_eq!(Ok(0), unary(I32Extend16S, 0x0123_0000));
+        assert_eq!(Ok(-0x8000), unary(I32Extend16S, 0xfedc_8000_u32 as i32));
+        assert_eq!(Ok(-1), unary(I32Extend16S, -1));
+
+        use Value::I64 as V;
+        assert_eq!(Ok(V(0)), unary64(I64Extend8S, 0));
+        assert_eq!(Ok(V(127)), unary64(I64Extend8S, 0x7f));
+        assert_eq!(Ok(V(-128)), unary64(I64Extend8S, 0x80));
+        assert_eq!(Ok(V(-1)), unary64(I64Extend8S, 0xff));
+        assert_eq!(Ok(V(0)), unary64(I64Extend8S, 0x0123_4567_89ab_cd00));
+        assert_eq!(
+            Ok(V(-0x80)),
+            unary64(I64Extend8S, 0xfedc_ba98_7654_3280_u64 as i64)
+        );
+        assert_eq!(Ok(V(-1)), unary64(I64Extend8S, -1));
+
+        assert_eq!(Ok(V(0)), unary64(I64Extend16S, 0));
+        assert_eq!(Ok(V(32767)), unary64(I64Extend16S, 0x7fff));
+        assert_eq!(Ok(V(-32768)), unary64(I64Extend16S, 0x8000));
+        assert_eq!(Ok(V(-1)), unary64(I64Extend16S, 0xffff));
+        assert_eq!(Ok(V(0)), unary64(I64Extend16S, 0x1234_5678_9abc_0000));
+        assert_eq!(
+            Ok(V(-0x8000)),
+            unary64(I64Extend16S, 0xfedc_ba98_7654_8000_u64 as i64)
+        );
+        assert_eq!(Ok(V(-1)), unary64(I64Extend16S, -1));
+
+        assert_eq!(Ok(V(0)), unary64(I64Extend32S, 0));
+        assert_eq!(Ok(V(32767)), unary64(I64Extend32S, 0x7fff));
+        assert_eq!(Ok(V(32768)), unary64(I64Extend32S, 0x8000));
+        assert_eq!(Ok(V(65535)), unary64(I64Extend32S, 0xffff));
+        assert_eq!(Ok(V(0x7fff_ffff)), unary64(I64Extend32S, 0x7fff_ffff));
+        assert_eq!(Ok(V(-0x8000_0000)), unary64(I64Extend32S, 0x8000_0000));
+        assert_eq!(Ok(V(-1)), unary64(I64Extend32S, 0xffff_ffff));
+        assert_eq!(Ok(V(0)), unary64(I64Extend32S, 0x0123_4567_0000_0000));
+        assert_eq!(
+            Ok(V(-0x8000_0000)),
+            unary64(I64Extend32S, 0xfedc_ba98_8000_0000_u64 as i64)
+        );
+        assert_eq!(Ok(V(-1)), unary64(I64Extend32S, -1));
+    }
+
+    #[test]
+    fn parametric() {
+        let body = vec![I32Const(2), F64Const(1.0), Drop, Nop];
+        assert_eq!(Ok(2), run(body, &[]));
+
+        // Untyped select works on operands of any type.
+        let body = vec![
+            I64Const(3),
+            I64Const(4),
+            LocalGet(0),
+            Select(None),
+            I32WrapI64,
+        ];
+        assert_eq!(Ok(3), run(body.clone(), &[1]));
+        assert_eq!(Ok(4), run(body, &[0]));
+
+        let body = vec![F32Const(1.0), F32Const(2.0), LocalGet(0), Select(None)];
+        assert_eq!(
+            Ok(Value::F32(2.0)),
+            execute(body, &[Value::I32(0)], ValueType::F32)
+        );
+
+        let body = vec![
+            I32Const(5),
+            I32Const(6),
+            LocalGet(0),
+            Select(Some(ValueType::I32)),
+        ];
+        assert_eq!(Ok(5), run(body, &[-1]));
+    }
+
+    #[test]
+    fn unreachable_traps() {
+        let body = vec![
+            I32Const(1),
+            Block(BlockType::Empty, vec![Instr::Unreachable]),
+            Drop,
+        ];
+        assert_eq!(Err(RuntimeError::Unreachable), run(body, &[]));
+    }
+
+    #[test]
+    fn block_branch_unwinds_operands() {
+        let block = Block(
+            BlockType::Value(ValueType::I32),
+            vec![I64Const(1), I32Const(2), I32Const(3), Br(0), I32Const(4)],
+        );
+        assert_eq!(Ok(13), run(vec![I32Const(10), block, I32Add], &[]));
+    }
+
+    #[test]
+    fn nested_block_branch() {
+        let inner = Block(BlockType::Empty, vec![I32Const(1), Br(1), I32Const(2)]);
+        let outer = Block(BlockType::Value(ValueType::I32), vec![inner, I32Const(3)]);
+        assert_eq!(Ok(1), run(vec![outer], &[]));
+
+        let inner = Block(BlockType::Empty, vec![LocalGet(0), BrIf(0), Br(1)]);
+        let outer = Block(BlockType::Empty, vec![inner, I32Const(5), Return]);
+        let body = vec![outer, I32Const(6)];
+        assert_eq!(Ok(5), run(body.clone(), &[1]));
+        assert_eq!(Ok(6), run(body, &[0]));
+    }
+
+    #[test]
+    fn if_else() {
+        let body = vec![
+            LocalGet(0),
+            If(
+                BlockType::Value(ValueType::I32),
+                vec![I32Const(1)],
+                vec![I32Const(2)],
+            ),
+        ];
+        assert_eq!(Ok(1), run(body.clone(), &[-1]));
+        assert_eq!(Ok(2), run(body, &[0]));
+
+        let body = vec![
+            I32Const(3),
+            LocalGet(0),
+            If(BlockType::Empty, vec![Return], vec![]),
+            I32Const(4),
+            I32Add,
+        ];
+        assert_eq!(Ok(3), run(body.clone(), &[1]));
+        assert_eq!(Ok(7), run(body, &[0]));
+    }
+
+    #[test]
+    fn br_table() {
+        let switch = |index| {
+            let b0 = Block(BlockType::Empty, vec![LocalGet(0), BrTable(vec![0, 1], 2)]);
+            let b1 = Block(BlockType::Empty, vec![b0, I32Const(10), Return]);
+            let b2 = Block(BlockType::Empty, vec![b1, I32Const(11), Return]);
+            run(vec![b2, I32Const(12)], &[index])
+        };
+        assert_eq!(Ok(10), switch(0));
+        assert_eq!(Ok(11), switch(1));
+        assert_eq!(Ok(12), switch(2));
+        assert_eq!(Ok(12), switch(-1));
+    }
+
+    #[test]
+    fn loop_branch() {
+        let lp = Loop(BlockType::Value(ValueType::I32), vec![I32Const(7)]);
+        assert_eq!(Ok(7), run(vec![lp], &[]));
+
+        let lp = Loop(
+            BlockType::Empty,
+            vec![I32Const(8), LocalGet(0), BrIf(1), Br(0)],
+        );
+        let block = Block(BlockType::Value(ValueType::I32), vec![lp, I32Const(9)]);
+        assert_eq!(Ok(8), run(vec![block], &[1]));
+    }
+
+    fn call_module(funcs: Vec<Vec<Instr>>) -> Module {
+        Module {
+            types: vec![(vec![ValueType::I32], vec![ValueType::I32])],
+            imports: vec![],
+            funcs: funcs
+                .into_iter()
+                .map(|body| Func {
+                    f_type: 0,
+                    locals: vec![],
+                    body,
+                })
+                .collect(),
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![],
+            start: None,
+        }
+    }
+
+    #[test]
+    fn call() {
+        let module = call_module(vec![
+            vec![I32Const(1), LocalGet(0), Call(1), I32Add],
+            vec![LocalGet(0), I32Const(10), I32Mul, Return, I32Const(-1)],
+        ]);
+        let mut store = Store::new(());
+        let mut processor = Processor::new(&module, &mut store, MAX_CALL_DEPTH);
+        processor.execute_func(0, &[Value::I32(4)]).unwrap();
+        assert_eq!(Value::I32(41), processor.pop_values(&[ValueType::I32])[0]);
+        assert_eq!(0, processor.stack.len());
+    }
+
+    #[test]
+    fn recursive_call() {
+        let factorial = vec![
+            LocalGet(0),
+            I32Eqz,
+            If(
+                BlockType::Value(ValueType::I32),
+                vec![I32Const(1)],
+                vec![
+                    LocalGet(0),
+                    LocalGet(0),
+                    I32Const(1),
+                    I32Sub,
+                    Call(0),
+                    I32Mul,
+                ],
+            ),
+        ];
+        let module = call_module(vec![factorial]);
+        let mut store = Store::new(());
+        let mut processor = Processor::new(&module, &mut store, MAX_CALL_DEPTH);
+        processor.execute_func(0, &[Value::I32(10)]).unwrap();
+        assert_eq!(
+            Value::I32(3628800),
+            processor.pop_values(&[ValueType::I32])[0]
+        );
+    }
+
+    #[test]
+    fn call_stack_exhausted() {
+        let module = call_module(vec![vec![LocalGet(0), Call(0)]]);
+        let mut store = Store::new(());
+        let mut processor = Processor::new(&module, &mut store, MAX_CALL_DEPTH);
+        assert_eq!(
+            Err(CallStackExhausted),
+            processor.execute_func(0, &[Value::I32(0)])
+        );
+
+        let mut store = Store::new(());
+
+        let mut processor = Processor::new(&module, &mut store, 3);
+        assert_eq!(
+            Err(CallStackExhausted),
+            processor.execute_func(0, &[Value::I32(0)])
+        );
+        assert_eq!(0, processor.depth);
+
+        // Only calls count against the call depth, not blocks.
+        let block = |body| Block(BlockType::Empty, body);
+        let module = call_module(vec![vec![block(vec![block(vec![])]), LocalGet(0)]]);
+        let mut store = Store::new(());
+        let mut processor = Processor::new(&module, &mut store, 1);
+        assert_eq!(Ok(()), processor.execute_func(0, &[Value::I32(0)]));
+
+        // A recursive function can call itself until the default depth,
+        // although every call is nested in a block.
+        let countdown = vec![
+            LocalGet(0),
+            If(
+                BlockType::Empty,
+                vec![LocalGet(0), I32Const(1), I32Sub, Call(0), Drop],
+                vec![],
+            ),
+            LocalGet(0),
+        ];
+        let module = call_module(vec![countdown]);
+        let mut store = Store::new(());
+        let mut processor = Processor::new(&module, &mut store, MAX_CALL_DEPTH);
+        let depth = MAX_CALL_DEPTH as i32;
+        assert_eq!(Ok(()), processor.execute_func(0, &[Value::I32(depth - 1)]));
+        assert_eq!(
+            Err(CallStackExhausted),
+            processor.execute_func(0, &[Value::I32(depth)])
+        );
+
+        // Blocks are bounded across calls, so both limits fit on the stack
+        // of a test thread.
+        let recurse = vec![block(vec![block(vec![LocalGet(0), Call(0), Drop])])];
+        let module = call_module(vec![recurse]);
+        let mut store = Store::new(());
+        let mut processor = Processor::new(&module, &mut store, MAX_CALL_DEPTH);
+        assert_eq!(
+            Err(CallStackExhausted),
+            processor.execute_func(0, &[Value::I32(0)])
+        );
+        assert_eq!((0, 0), (processor.depth, processor.blocks));
+    }
+
+    #[test]
+    fn multi_value() {
+        use ValueType::I32;
+        let mut module = call_module(vec![
+            vec![
+                LocalGet(0),
+                I32Const(1),
+                // Block parameters are taken from the enclosing stack.
+                Block(BlockType::TypeIdx(1), vec![I32Add, I32Const(2)]),
+                I32Add,
+                // A branch to a loop passes its parameters again.
+                Loop(
+                    BlockType::TypeIdx(2),
+                    vec![I32Const(-1), I32Add, LocalTee(0), LocalGet(0), BrIf(0)],
+                ),
+                I32Const(10),
+                I32Add,
+                Block(
+                    BlockType::TypeIdx(3),
+                    vec![I32Const(7), I32Const(8), I32Const(9), Br(0)],
+                ),
+                I32Add,
+            ],
+            vec![LocalGet(0), Call(0), I32Sub],
+        ]);
+        module.types = vec![
+            (vec![I32], vec![I32, I32]),
+            (vec![I32, I32], vec![I32, I32]),
+            (vec![I32], vec![I32]),
+            (vec![], vec![I32, I32]),
+        ];
+        module.funcs[1].f_type = 2;
+
+        let mut store = Store::new(());
+        let mut processor = Processor::new(&module, &mut store, MAX_CALL_DEPTH);
+        processor.execute_func(0, &[Value::I32(4)]).unwrap();
+        assert_eq!(
+            vec![Value::I32(10), Value::I32(17)],
+            processor.pop_values(&[I32, I32])
+        );
+
+        processor.execute_func(1, &[Value::I32(4)]).unwrap();
+        assert_eq!(vec![Value::I32(-7)], processor.pop_values(&[I32]));
+        assert_eq!(0, processor.stack.len());
+    }
+
+    #[test]
+    fn locals() {
+        // Sums up 1..=n in a loop.
+        let body = vec![
+            Loop(
+                BlockType::Empty,
+                vec![
+                    LocalGet(1),
+                    LocalGet(0),
+                    I32Add,
+                    LocalSet(1),
+                    LocalGet(0),
+                    I32Const(1),
+                    I32Sub,
+                    LocalTee(0),
+                    BrIf(0),
+                ],
+            ),
+            LocalGet(1),
+        ];
+        let module = Module {
+            types: vec![(vec![ValueType::I32], vec![ValueType::I32])],
+            imports: vec![],
+            funcs: vec![Func {
+                f_type: 0,
+                locals: vec![ValueType::I32, ValueType::F64],
+                body,
+            }],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![],
+            start: None,
+        };
+        let mut store = Store::new(());
+        let mut processor = Processor::new(&module, &mut store, MAX_CALL_DEPTH);
+        processor.execute_func(0, &[Value::I32(100)]).unwrap();
+        assert_eq!(Value::I32(5050), processor.pop_values(&[ValueType::I32])[0]);
+
+        let module = Module {
+            types: vec![(vec![], vec![ValueType::F64])],
+            imports: vec![],
+            funcs: vec![Func {
+                f_type: 0,
+                locals: vec![ValueType::I64, ValueType::F64],
+                body: vec![LocalGet(1)],
+            }],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![],
+            start: None,
+        };
+        let mut store = Store::new(());
+        let mut processor = Processor::new(&module, &mut store, MAX_CALL_DEPTH);
+        processor.execute_func(0, &[]).unwrap();
+        assert_eq!(Value::F64(0.0), processor.pop_values(&[ValueType::F64])[0]);
+    }
+
+    fn run_memory(body: Vec<Instr>, result: ValueType) -> Result<Value, RuntimeError> {
+        run_memory_with_data(body, vec![], result)
+    }
+
+    fn run_memory_with_data(
+        body: Vec<Instr>,
+        datas: Vec<Vec<u8>>,
+        result: ValueType,
+    ) -> Result<Value, RuntimeError> {
+        let memory = Memory::new(&Limits {
+            min: 1,
+            max: Some(2),
+        })
+        .unwrap();
+        run_with_memories(body, vec![memory], datas, result)
+    }
+
+    fn run_with_memories(
+        body: Vec<Instr>,
+        memories: Vec<Memory>,
+        datas: Vec<Vec<u8>>,
+        result: ValueType,
+    ) -> Result<Value, RuntimeError> {
+        let module = Module {
+            types: vec![(vec![], vec![result])],
+            imports: vec![],
+            funcs: vec![Func {
+                f_type: 0,
+                locals: vec![],
+                body,
+            }],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: vec![],
+            elems: vec![],
+            datas: vec![],
+            exports: vec![],
+            start: None,
+        };
+        let mut store = Store::new(());
+        store.memories = memories;
+        store.datas = datas;
+        let mut processor = Processor::new(&module, &mut store, MAX_CALL_DEPTH);
+        processor.execute_func(0, &[])?;
+        Ok(processor.pop_values(&[result]).remove(0))
+    }
+
+    #[test]
+    fn load_store() {
+        let m = |offset| MemArg {
+            align: 0,
+            offset,
+            memory: 0,
+        };
+        let store = vec![
+            I32Const(8),
+            I64Const(-0x0123_4567_89ab_cdf0),
+            I64Store(m(0)),
+        ];
+        let load = |instr: Instr, result| {
+            run_memory([store.clone(), vec![I32Const(0), instr]].concat(), result)
+        };
+
+        assert_eq!(
+            Ok(Value::I64(-0x0123_4567_89ab_cdf0)),
+            load(I64Load(m(8)), ValueType::I64)
+        );
+        assert_eq!(
+            Ok(Value::I32(0x7654_3210)),
+            load(I32Load(m(8)), ValueType::I32)
+        );
+        assert_eq!(Ok(Value::I32(0x10)), load(I32Load8S(m(8)), ValueType::I32));
+        assert_eq!(Ok(Value::I32(-2)), load(I32Load8S(m(15)), ValueType::I32));
+        assert_eq!(Ok(Value::I32(0xfe)), load(I32Load8U(m(15)), ValueType::I32));
+        assert_eq!(
+            Ok(Value::I32(-0x0124)),
+            load(I32Load16S(m(14)), ValueType::I32)
+        );
+        assert_eq!(
+            Ok(Value::I32(0xfedc)),
+            load(I32Load16U(m(14)), ValueType::I32)
+        );
+        assert_eq!(
+            Ok(Value::I64(-0x0123_4568)),
+            load(I64Load32S(m(12)), ValueType::I64)
+        );
+        assert_eq!(
+            Ok(Value::I64(0xfedc_ba98)),
+            load(I64Load32U(m(12)), ValueType::I64)
+        );
+        assert_eq!(Ok(Value::I64(0xba)), load(I64Load8U(m(13)), ValueType::I64));
+        assert_eq!(
+            Ok(Value::I64(-0x4568)),
+            load(I64Load16S(m(12)), ValueType::I64)
+        );
+
+        let body = vec![
+            I32Const(4),
+            F32Const(1.5),
+            F32Store(m(0)),
+            I32Const(0),
+            F32Load(m(4)),
+        ];
+        assert_eq!(Ok(Value::F32(1.5)), run_memory(body, ValueType::F32));
+
+        let body = vec![
+            I32Const(0),
+            I32Const(0x1234_5678),
+            I32Store8(m(1)),
+            I32Const(0),
+            I32Load(m(0)),
+        ];
+        assert_eq!(Ok(Value::I32(0x7800)), run_memory(body, ValueType::I32));
+    }
+
+    #[test]
+    fn memory_out_of_bounds() {
+        let m = |offset| MemArg {
+            align: 0,
+            offset,
+            memory: 0,
+        };
+        let body = vec![I32Const(65532), I32Load(m(0))];
+        assert_eq!(Ok(Value::I32(0)), run_memory(body, ValueType::I32));
+
+        let body = vec![I32Const(65533), I32Load(m(0))];
+        assert_eq!(Err(MemoryOutOfBounds), run_memory(body, ValueType::I32));
+
+        let body = vec![I32Const(-1), I32Load8U(m(1))];
+        assert_eq!(Err(MemoryOutOfBounds), run_memory(body, ValueType::I32));
+
+        let body = vec![I32Const(0), I64Const(0), I64Store(m(65529)), I32Const(0)];
+        assert_eq!(Err(MemoryOutOfBounds), run_memory(body, ValueType::I32));
+    }
+
+    #[test]
+    fn memory_size_grow() {
+        let body = vec![I32Const(1), MemoryGrow(0), MemorySize(0), I32Add];
+        assert_eq!(Ok(Value::I32(3)), run_memory(body, ValueType::I32));
+
+        let body = vec![I32Const(2), MemoryGrow(0)];
+        assert_eq!(Ok(Value::I32(-1)), run_memory(body, ValueType::I32));
+
+        let m = MemArg {
+            align: 0,
+            offset: 0,
+            memory: 0,
+        };
+        let body = vec![
+            I32Const(1),
+            MemoryGrow(0),
+            I32Const(131071),
+            I32Load8U(m),
+            I32Add,
+        ];
+        assert_eq!(Ok(Value::I32(1)), run_memory(body, ValueType::I32));
+    }
+
+    #[test]
+    fn memory64() {
+        let run = |body| {
+            let memory = Memory::with_type(&ast::Memory {
+                limits: Limits {
+                    min: 1,
+                    max: Some(2),
+                },
+                shared: false,
+                memory64: true,
+            })
+            .unwrap();
+            run_with_memories(body, vec![memory], vec![vec![7]], ValueType::I64)
+        };
+        let m = |offset| MemArg {
+            align: 0,
+            offset,
+            memory: 0,
+        };
+
+        let body = vec![I64Const(1), MemoryGrow(0), MemorySize(0), I64Add];
+        assert_eq!(Ok(Value::I64(3)), run(body));
+        let body = vec![I64Const(2), MemoryGrow(0)];
+        assert_eq!(Ok(Value::I64(-1)), run(body));
+
+        let body = vec![
+            I64Const(8),
+            I64Const(0x1234),
+            I64Store16(m(0)),
+            I64Const(4),
+            I64Load16U(m(4)),
+        ];
+        assert_eq!(Ok(Value::I64(0x1234)), run(body));
+
+        // Neither the address nor the offset wraps around.
+        let body = vec![I64Const(-1), I64Load8U(m(1))];
+        assert_eq!(Err(MemoryOutOfBounds), run(body));
+        let body = vec![I64Const(1), I64Load8U(m(u64::MAX))];
+        assert_eq!(Err(MemoryOutOfBounds), run(body));
+        let body = vec![I64Const(0), I64Load8U(m(1 << 32))];
+        assert_eq!(Err(MemoryOutOfBounds), run(body));
+
+        let body = vec![
+            I64Const(16),
+            I32Const(0),
+            I32Const(1),
+            MemoryInit(0, 0),
+            I64Const(0),
+            I64Load8U(m(16)),
+        ];
+        assert_eq!(Ok(Value::I64(7)), run(body));
+        let body = vec![
+            I64Const(-1),
+            I32Const(0),
+            I64Const(2),
+            MemoryFill(0),
+            I64Const(0),
+        ];
+        assert_eq!(Err(MemoryOutOfBounds), run(body));
+    }
+
+    #[test]
+    fn multiple_memories() {
+        let run = |body| {
+            let limits = Limits { min: 1, max: None };
+            let memory64 = Memory::with_type(&ast::Memory {
+                limits,
+                shared: false,
+                memory64: true,
+            })
+            .unwrap();
+            let memories = vec![Memory::new(&limits).unwrap(), memory64];
+            run_with_memories(body, memories, vec![vec![1, 2, 3]], ValueType::I32)
+        };
+        let m = |memory| MemArg {
+            align: 0,
+            offset: 0,
+            memory,
+        };
+
+        let body = vec![
+            I64Const(8),
+            I32Const(0),
+            I32Const(3),
+            MemoryInit(1, 0),
+            I32Const(4),
+            I32Load8U(m(0)),
+            I64Const(9),
+            I32Load8U(m(1)),
+            I32Add,
+        ];
+        assert_eq!(Ok(Value::I32(2)), run(body));
+
+        // The length of a copy between a 32-bit and a 64-bit memory is an
+        // `i32`.
+        let body = vec![
+            I64Const(8),
+            I32Const(0),
+            I32Const(3),
+            MemoryInit(1, 0),
+            I32Const(100),
+            I64Const(8),
+            I32Const(3),
+            MemoryCopy(0, 1),
+            I32Const(102),
+            I32Load8U(m(0)),
+        ];
+        assert_eq!(Ok(Value::I32(3)), run(body));
+        let body = vec![
+            I64Const(1),
+            MemoryGrow(1),
+            I32WrapI64,
+            MemorySize(0),
+            I32Add,
+        ];
+        assert_eq!(Ok(Value::I32(2)), run(body));
+        let body = vec![
+            I64Const(-1),
+            I32Const(0),
+            I32Const(1),
+            MemoryCopy(1, 0),
+            I32Const(0),
+        ];
+        assert_eq!(Err(MemoryOutOfBounds), run(body));
+    }
+
+    #[test]
+    fn bulk_memory() {
+        let m = MemArg {
+            align: 0,
+            offset: 0,
+            memory: 0,
+        };
+        let load = |addr| vec![I32Const(addr), I32Load(m)];
+        let data = || vec![vec![1, 2, 3, 4, 5]];
+        let run = |body| run_memory_with_data(body, data(), ValueType::I32);
+
+        let init = vec![I32Const(100), I32Const(1), I32Const(3), MemoryInit(0, 0)];
+        let body = [init.clone(), load(100)].concat();
+        assert_eq!(Ok(Value::I32(0x0004_0302)), run(body));
+
+        let body = vec![I32Const(0), I32Const(3), I32Const(3), MemoryInit(0, 0)];
+        assert_eq!(Err(MemoryOutOfBounds), run(body));
+        let body = vec![I32Const(65535), I32Const(0), I32Const(2), MemoryInit(0, 0)];
+        assert_eq!(Err(MemoryOutOfBounds), run(body));
+
+        let drop = vec![DataDrop(0), I32Const(0), I32Const(0), I32Const(0)];
+        let body = [drop, vec![MemoryInit(0, 0), I32Const(0)]].concat();
+        assert_eq!(Ok(Value::I32(0)), run(body));
+        let body = vec![
+            DataDrop(0),
+            I32Const(0),
+            I32Const(0),
+            I32Const(1),
+            MemoryInit(0, 0),
+        ];
+        assert_eq!(Err(MemoryOutOfBounds), run(body));
+
+        // Overlapping copies behave as if the source was copied first.
+        let copy = vec![I32Const(101), I32Const(100), I32Const(3), MemoryCopy(0, 0)];
+        let body = [init.clone(), copy, load(100)].concat();
+        assert_eq!(Ok(Value::I32(0x0403_0202)), run(body));
+        let copy = vec![I32Const(0), I32Const(65534), I32Const(3), MemoryCopy(0, 0)];
+        assert_eq!(Err(MemoryOutOfBounds), run([copy, load(0)].concat()));
+
+        let fill = vec![I32Const(101), I32Const(0x1ff), I32Const(2), MemoryFill(0)];
+        assert_eq!(Ok(Value::I32(0x00ff_ff00)), run([fill, load(100)].concat()));
+        let fill = vec![I32Const(65536), I32Const(0), I32Const(0), MemoryFill(0)];
+        assert_eq!(Ok(Value::I32(0)), run([fill, load(0)].concat()));
+        let fill = vec![I32Const(65536), I32Const(0), I32Const(1), MemoryFill(0)];
+        assert_eq!(Err(MemoryOutOfBounds), run([fill, load(0)].concat()));
+    }
+
+    #[test]
+    fn call_indirect() {
+        let mut module = call_module(vec![
+            vec![LocalGet(0), LocalGet(0), CallIndirect(0, 0)],
+            vec![LocalGet(0), I32Const(1), I32Add],
+            vec![LocalGet(0), I32Const(2), I32Mul],
+        ]);
+        module.types.push((vec![], vec![]));
+        module.funcs.push(Func {
+            f_type: 1,
+            locals: vec![],
+            body: vec![],
+        });
+        let run = |module: &Module, elem| {
+            let mut store = Store::new(());
+            let limits = Limits { min: 4, max: None };
+            let mut table = Table::new(&limits, RefType::FuncRef).unwrap();
+            let elements = [1, 2, 3].map(|func| Value::FuncRef(Some(func)));
+            table.write(0, &elements).unwrap();
+            store.tables.push(table);
+            let mut processor = Processor::new(module, &mut store, MAX_CALL_DEPTH);
+            processor.execute_func(0, &[Value::I32(elem)])?;
+            Ok(processor.pop_values(&[ValueType::I32]).remove(0))
+        };
+
+        assert_eq!(Ok(Value::I32(1)), run(&module, 0));
+        assert_eq!(Ok(Value::I32(2)), run(&module, 1));
+        assert_eq!(Err(IndirectCallTypeMismatch), run(&module, 2));
+        assert_eq!(Err(UninitializedElement), run(&module, 3));
+        assert_eq!(Err(UndefinedElement), run(&module, 4));
+        assert_eq!(Err(UndefinedElement), run(&module, -1));
+    }
+
+    #[test]
+    fn call_ref() {
+        let run = |body| {
+            let mut module = call_module(vec![
+                body,
+                vec![LocalGet(0), I32Const(1), I32Add],
+                vec![LocalGet(0), I32Const(2), I32Mul],
+            ]);
+            module.types.push((vec![], vec![]));
+            module.funcs.push(Func {
+                f_type: 1,
+                locals: vec![],
+                body: vec![],
+            });
+            let mut store = Store::new(());
+            let mut processor = Processor::new(&module, &mut store, MAX_CALL_DEPTH);
+            processor.execute_func(0, &[Value::I32(5)])?;
+            Ok(processor.pop_values(&[ValueType::I32]).remove(0))
+        };
+
+        let body = vec![LocalGet(0), RefFunc(1), CallRef(0)];
+        assert_eq!(Ok(Value::I32(6)), run(body));
+        let body = vec![LocalGet(0), RefFunc(2), ReturnCallRef(0)];
+        assert_eq!(Ok(Value::I32(10)), run(body));
+        let body = vec![LocalGet(0), RefNull(HeapType::Func), CallRef(0)];
+        assert_eq!(Err(NullReference), run(body));
+        let body = vec![LocalGet(0), RefFunc(3), CallRef(0)];
+        assert_eq!(Err(IndirectCallTypeMismatch), run(body));
+
+        let body = vec![RefFunc(1), RefAsNonNull, RefIsNull];
+        assert_eq!(Ok(Value::I32(0)), run(body));
+        let body = vec![RefNull(HeapType::Func), RefAsNonNull, RefIsNull];
+        assert_eq!(Err(NullReference), run(body));
+
+        // `br_on_null` drops the null reference when it branches.
+        let br_on_null = |r| {
+            let block = vec![I32Const(7), r, BrOnNull(0), Drop, Drop, I32Const(8)];
+            vec![Block(BlockType::Value(ValueType::I32), block)]
+        };
+        assert_eq!(Ok(Value::I32(7)), run(br_on_null(RefNull(HeapType::Func))));
+        assert_eq!(Ok(Value::I32(8)), run(br_on_null(RefFunc(1))));
+
+        // `br_on_non_null` branches with the reference.
+        let br_on_non_null = |r| {
+            let block = vec![r, BrOnNonNull(0), RefFunc(1)];
+            let bt = BlockType::Value(ValueType::FuncRef);
+            vec![LocalGet(0), Block(bt, block), CallRef(0)]
+        };
+        assert_eq!(Ok(Value::I32(10)), run(br_on_non_null(RefFunc(2))));
+        assert_eq!(
+            Ok(Value::I32(6)),
+            run(br_on_non_null(RefNull(HeapType::Func)))
+        );
+    }
+
+    #[test]
+    fn tail_call() {
+        // Mutual recursion far deeper than the call depth, which only works
+        // if every call reuses the frame.
+        let parity = |base, other| {
+            vec![
+                I32Const(-1),
+                LocalGet(0),
+                I32Eqz,
+                If(
+                    BlockType::Value(ValueType::I32),
+                    vec![I32Const(base)],
+                    vec![LocalGet(0), I32Const(1), I32Sub, ReturnCall(other)],
+                ),
+                I32Add,
+            ]
+        };
+        let module = call_module(vec![parity(1, 1), parity(0, 0)]);
+        let mut store = Store::new(());
+        let mut processor = Processor::new(&module, &mut store, MAX_CALL_DEPTH);
+        processor.execute_func(0, &[Value::I32(100_000)]).unwrap();
+        assert_eq!(Value::I32(0), processor.pop_values(&[ValueType::I32])[0]);
+        assert_eq!(0, processor.stack.len());
+        assert_eq!(0, processor.depth);
+
+        let mut module = call_module(vec![
+            vec![
+                I32Const(5),
+                LocalGet(0),
+                LocalGet(0),
+                ReturnCallIndirect(0, 0),
+            ],
+            vec![LocalGet(0), I32Const(1), I32Add],
+        ]);
+        module.types.push((vec![], vec![]));
+        module.funcs.push(Func {
+            f_type: 1,
+            locals: vec![],
+            body: vec![],
+        });
+        let run = |elem| {
+            let mut store = Store::new(());
+            let limits = Limits { min: 2, max: None };
+            let mut table = Table::new(&limits, RefType::FuncRef).unwrap();
+            let elements = [1, 2].map(|func| Value::FuncRef(Some(func)));
+            table.write(0, &elements).unwrap();
+            store.tables.push(table);
+            let mut processor = Processor::new(&module, &mut store, MAX_CALL_DEPTH);
+            processor.execute_func(0, &[Value::I32(elem)])?;
+            assert_eq!(1, processor.stack.len());
+            Ok(processor.pop_values(&[ValueType::I32]).remove(0))
+        };
+        assert_eq!(Ok(Value::I32(1)), run(0));
+        assert_eq!(Err(IndirectCallTypeMismatch), run(1));
+        assert_eq!(Err(UndefinedElement), run(2));
+    }
+
+    #[test]
+    fn exceptions() {
+        let mut module = call_module(vec![
+            // Catches the payload of an exception thrown by a callee.
+            vec![Block(
+                BlockType::Value(ValueType::I32),
+                vec![TryTable(
+                    BlockType::Value(ValueType::I32),
+                    vec![Catch::Tag(0, 0)],
+                    vec![LocalGet(0), Call(1)],
+                )],
+            )],
+            vec![I32Const(7), LocalGet(0), Throw(0)],
+            // Catches any exception and rethrows it.
+            vec![
+                Block(
+                    BlockType::Value(ValueType::ExnRef),
+                    vec![
+                        TryTable(
+                            BlockType::Empty,
+                            vec![Catch::AllRef(0)],
+                            vec![LocalGet(0), Call(1), Drop],
+                        ),
+                        RefNull(HeapType::Exn),
+                    ],
+                ),
+                ThrowRef,
+            ],
+            vec![Block(
+                BlockType::Value(ValueType::I32),
+                vec![TryTable(
+                    BlockType::Value(ValueType::I32),
+                    vec![Catch::Tag(1, 0)],
+                    vec![LocalGet(0), Call(1)],
+                )],
+            )],
+            vec![
+                Block(
+                    BlockType::Empty,
+                    vec![TryTable(
+                        BlockType::Empty,
+                        vec![Catch::All(0)],
+                        vec![Instr::Unreachable],
+                    )],
+                ),
+                LocalGet(0),
+            ],
+            vec![RefNull(HeapType::Exn), ThrowRef],
+        ]);
+        module.types.push((vec![ValueType::I32], vec![]));
+        module.types.push((vec![], vec![]));
+        module.tags = vec![1, 2];
+        let run = |idx, arg| {
+            let mut store = Store::new(());
+            let mut processor = Processor::new(&module, &mut store, MAX_CALL_DEPTH);
+            let result = processor.execute_func(idx, &[Value::I32(arg)]);
+            assert_eq!(0, processor.depth);
+            result?;
+            assert_eq!(1, processor.stack.len());
+            Ok(processor.pop_values(&[ValueType::I32]).remove(0))
+        };
+        let uncaught = |values| Err(UncaughtException(Arc::new(Exception { tag: 0, values })));
+
+        assert_eq!(Ok(Value::I32(5)), run(0, 5));
+        assert_eq!(uncaught(vec![Value::I32(5)]), run(1, 5));
+        assert_eq!(uncaught(vec![Value::I32(3)]), run(2, 3));
+        assert_eq!(uncaught(vec![Value::I32(4)]), run(3, 4));
+        // Traps can't be caught.
+        assert_eq!(Err(RuntimeError::Unreachable), run(4, 0));
+        assert_eq!(Err(NullReference), run(5, 0));
+    }
+
+    #[test]
+    fn references() {
+        assert_eq!(Ok(1), run(vec![RefNull(HeapType::Extern), RefIsNull], &[]));
+        assert_eq!(Ok(0), run(vec![RefFunc(0), RefIsNull], &[]));
+        assert_eq!(
+            Ok(Value::FuncRef(Some(3))),
+            execute(vec![RefFunc(3)], &[], ValueType::FuncRef)
+        );
+
+        // Host objects come back as the same object.
+        let object = Value::ExternRef(Some(ExternRef::new(String::from("host"))));
+        let body = vec![LocalGet(0), LocalGet(0), Drop];
+        let result = execute(body, std::slice::from_ref(&object), ValueType::ExternRef);
+        assert_eq!(Ok(object), result);
+        let result = execute(
+            vec![LocalGet(0)],
+            &[Value::ExternRef(None)],
+            ValueType::ExternRef,
+        );
+        assert_eq!(Ok(Value::ExternRef(None)), result);
+    }
+
+    #[test]
+    fn table_instrs() {
+        // Returns the result and the elements of the second table.
+        let run = |body| -> Result<(i32, Vec<Option<usize>>), RuntimeError> {
+            let module = call_module(vec![body]);
+            let mut store = Store::new(());
+            let limits = Limits {
+                min: 2,
+                max: Some(5),
+            };
+            store
+                .tables
+                .push(Table::new(&limits, RefType::FuncRef).unwrap());
+            let limits = Limits { min: 3, max: None };
+            store
+                .tables
+                .push(Table::new(&limits, RefType::FuncRef).unwrap());
+            let elems = [7, 8, 9].map(|func| Value::FuncRef(Some(func)));
+            store.elems.push(elems.to_vec());
+            let mut processor = Processor::new(&module, &mut store, MAX_CALL_DEPTH);
+            processor.execute_func(0, &[Value::I32(0)])?;
+            let result = match processor.pop_values(&[ValueType::I32])[..] {
+                [Value::I32(result)] => result,
+                _ => unreachable!(),
+            };
+            let table = &mut store.tables[1];
+            let elements = table.read(0, table.size())?.into_iter();
+            let funcs = elements.map(|element| match element {
+                Value::FuncRef(func) => func,
+                _ => unreachable!(),
+            });
+            Ok((result, funcs.collect()))
+        };
+
+        let get_set = vec![
+            I32Const(1),
+            RefFunc(4),
+            TableSet(1),
+            I32Const(1),
+            TableGet(1),
+            RefIsNull,
+        ];
+        assert_eq!(Ok((0, vec![None, Some(4), None])), run(get_set));
+
+        let grow = vec![
+            RefNull(HeapType::Func),
+            I32Const(2),
+            TableGrow(0),
+            RefNull(HeapType::Func),
+            I32Const(2),
+            TableGrow(0),
+            I32Add,
+            TableSize(0),
+            I32Add,
+        ];
+        assert_eq!(Ok((2 - 1 + 4, vec![None; 3])), run(grow));
+
+        let fill = vec![
+            I32Const(1),
+            RefFunc(5),
+            I32Const(2),
+            TableFill(1),
+            I32Const(0),
+        ];
+        assert_eq!(Ok((0, vec![None, Some(5), Some(5)])), run(fill));
+
+        let init_copy = vec![
+            I32Const(0),
+            I32Const(1),
+            I32Const(2),
+            TableInit(1, 0),
+            I32Const(1),
+            I32Const(0),
+            I32Const(2),
+            TableCopy(1, 1),
+            I32Const(0),
+        ];
+        assert_eq!(Ok((0, vec![Some(8), Some(8), Some(9)])), run(init_copy));
+
+        let drop_init = vec![
+            ElemDrop(0),
+            I32Const(0),
+            I32Const(0),
+            I32Const(1),
+            TableInit(1, 0),
+            I32Const(0),
+        ];
+        assert_eq!(Err(TableOutOfBounds), run(drop_init));
+        assert_eq!(Err(TableOutOfBounds), run(vec![I32Const(3), TableGet(1)]));
+        let fill = vec![
+            I32Const(2),
+            RefFunc(0),
+            I32Const(2),
+            TableFill(1),
+            I32Const(0),
+        ];
+        assert_eq!(Err(TableOutOfBounds), run(fill));
+        let copy = vec![
+            I32Const(0),
+            I32Const(0),
+            I32Const(3),
+            TableCopy(1, 0),
+            I32Const(0),
+        ];
+        assert_eq!(Err(TableOutOfBounds), run(copy));
+    }
+
+    #[test]
+    fn simd() {
+        let m = |offset| MemArg {
+            align: 0,
+            offset,
+            memory: 0,
+        };
+        let store = vec![
+            I32Const(0),
+            V128Const(0x8f8e8d8c_8b8a8988_07060504_03020100),
+            V128Store(m(0)),
+        ];
+        let load = |instrs: Vec<Instr>| match run_memory(
+            [store.clone(), instrs].concat(),
+            ValueType::V128,
+        ) {
+            Ok(Value::V128(v)) => Ok(v),
+            Ok(v) => panic!("Unexpected result {:?}", v),
+            Err(e) => Err(e),
+        };
+
+        assert_eq!(
+            Ok(0x07060504_03020100),
+            load(vec![I32Const(0), V128Load64Zero(m(0))])
+        );
+        assert_eq!(
+            Ok(0xff8bff8a_ff89ff88_00070006_00050004),
+            load(vec![I32Const(4), V128Load8x8S(m(0))])
+        );
+        assert_eq!(
+            Ok(0x0504_0504_0504_0504_0504_0504_0504_0504),
+            load(vec![I32Const(0), V128Load16Splat(m(4))])
+        );
+        assert_eq!(
+            Ok(0x8f00),
+            load(vec![I32Const(0), V128Const(0), V128Load8Lane(m(15), 1)])
+        );
+        assert_eq!(
+            Ok(0x8fff8d8c_8b8a8988_07060504_03020100),
+            load(vec![
+                I32Const(0),
+                V128Const(0xff00),
+                V128Store8Lane(m(14), 1),
+                I32Const(0),
+                V128Load(m(0)),
+            ])
+        );
+        assert_eq!(
+            Err(MemoryOutOfBounds),
+            load(vec![I32Const(0), V128Load(m(0xfff8))])
+        );
+
+        let lane =
+            |instrs: Vec<Instr>, result| run_memory([store.clone(), instrs].concat(), result);
+        let v = || vec![I32Const(0), V128Load(m(0))];
+        assert_eq!(
+            Ok(Value::I32(-0x71)),
+            lane([v(), vec![I8x16ExtractLaneS(15)]].concat(), ValueType::I32)
+        );
+        assert_eq!(
+            Ok(Value::I32(0x8f8e)),
+            lane([v(), vec![I16x8ExtractLaneU(7)]].concat(), ValueType::I32)
+        );
+        assert_eq!(
+            Ok(Value::I64(0x07060504_03020100)),
+            lane([v(), vec![I64x2ExtractLane(0)]].concat(), ValueType::I64)
+        );
+        let replaced = vec![F64Const(1.5), F64x2ReplaceLane(1), F64x2ExtractLane(1)];
+        assert_eq!(
+            Ok(Value::F64(1.5)),
+            lane([v(), replaced].concat(), ValueType::F64)
+        );
+        let sum = vec![
+            I32Const(2),
+            Simd(SimdOp::I32x4Splat),
+            Simd(SimdOp::I32x4Add),
+        ];
+        let bitmask = vec![Simd(SimdOp::I32x4Bitmask)];
+        assert_eq!(
+            Ok(Value::I32(0b1100)),
+            lane([v(), sum, bitmask].concat(), ValueType::I32)
+        );
+    }
+
+    #[test]
+    fn atomics() {
+        use crate::ast::{AtomicRmwOp::*, AtomicWidth};
+        use crate::runtime::error::RuntimeError::{ExpectedSharedMemory, UnalignedAtomic};
+
+        let m = |offset| MemArg {
+            align: 0,
+            offset,
+            memory: 0,
+        };
+        let store = vec![
+            I32Const(8),
+            I64Const(-0x0123_4567_89ab_cdf0),
+            I64Store(m(0)),
+        ];
+        let run = |instrs: Vec<Instr>, result| run_memory([store.clone(), instrs].concat(), result);
+        let load = |width| vec![I32Const(0), AtomicLoad(width, m(8))];
+
+        assert_eq!(
+            Ok(Value::I32(0x10)),
+            run(load(AtomicWidth::I32U8), ValueType::I32)
+        );
+        assert_eq!(
+            Ok(Value::I64(0xfedc_ba98)),
+            run(
+                vec![I32Const(4), AtomicLoad(AtomicWidth::I64U32, m(8))],
+                ValueType::I64
+            )
+        );
+
+        let rmw = |op, width, operands: Vec<Instr>, result| {
+            let instrs = vec![I32Const(0)];
+            run(
+                [instrs, operands, vec![AtomicRmw(op, width, m(8))]].concat(),
+                result,
+            )
+        };
+        assert_eq!(
+            Ok(Value::I32(0x7654_3210)),
+            rmw(Add, AtomicWidth::I32, vec![I32Const(1)], ValueType::I32)
+        );
+        assert_eq!(
+            Ok(Value::I64(0x10)),
+            rmw(
+                Sub,
+                AtomicWidth::I64U8,
+                vec![I64Const(0x11)],
+                ValueType::I64
+            )
+        );
+        let add = vec![
+            I32Const(0),
+            I32Const(1),
+            AtomicRmw(Add, AtomicWidth::I32, m(8)),
+        ];
+        let sub = vec![
+            I32Const(0),
+            I32Const(0x12),
+            AtomicRmw(Sub, AtomicWidth::I32U8, m(8)),
+        ];
+        assert_eq!(
+            Ok(Value::I32(0x7654_32ff)),
+            run(
+                [add, vec![Drop], sub, vec![Drop], load(AtomicWidth::I32)].concat(),
+                ValueType::I32
+            )
+        );
+
+        // The expected value is wrapped to the width of the access.
+        let cmpxchg = |expected| {
+            let instrs = vec![
+                I32Const(0),
+                I32Const(expected),
+                I32Const(0x1234),
+                AtomicRmw(Cmpxchg, AtomicWidth::I32U16, m(8)),
+                Drop,
+            ];
+            run([instrs, load(AtomicWidth::I32)].concat(), ValueType::I32)
+        };
+        assert_eq!(Ok(Value::I32(0x7654_1234)), cmpxchg(0x1_3210));
+        assert_eq!(Ok(Value::I32(0x7654_3210)), cmpxchg(0x3211));
+
+        let store32 = vec![
+            I32Const(0),
+            I64Const(-1),
+            AtomicStore(AtomicWidth::I64U32, m(8)),
+            AtomicFence,
+        ];
+        assert_eq!(
+            Ok(Value::I64(-0x0123_4567_0000_0001)),
+            run([store32, load(AtomicWidth::I64)].concat(), ValueType::I64)
+        );
+
+        assert_eq!(
+            Err(UnalignedAtomic),
+            run(
+                vec![I32Const(2), AtomicLoad(AtomicWidth::I32, m(8))],
+                ValueType::I32
+            )
+        );
+        assert_eq!(
+            Ok(Value::I32(0)),
+            run(
+                vec![I32Const(0), I32Const(1), MemoryAtomicNotify(m(8))],
+                ValueType::I32
+            )
+        );
+        let wait = vec![
+            I32Const(0),
+            I32Const(0x7654_3210),
+            I64Const(-1),
+            MemoryAtomicWait32(m(8)),
+        ];
+        assert_eq!(Err(ExpectedSharedMemory), run(wait, ValueType::I32));
     }
 }
@@ -1,3 +1,4 @@
+use crate::runtime::error::RuntimeError;
 use std::{cell::Cell, convert::TryInto};
 
 pub struct Reader {
@@ -17,11 +18,23 @@ impl Reader {
         self.data.len()
     }
 
+    pub fn is_at_end(&self) -> bool {
+        self.pos.get() >= self.data.len()
+    }
+
     pub fn dword(&self) -> u32 {
         let prev = self.pos.replace(self.pos.get() + 4);
         u32::from_le_bytes(self.data[prev..self.pos.get()].try_into().unwrap())
     }
 
+    pub fn f32(&self) -> f32 {
+        f32::from_le_bytes(self.bytes(4).try_into().unwrap())
+    }
+
+    pub fn f64(&self) -> f64 {
+        f64::from_le_bytes(self.bytes(8).try_into().unwrap())
+    }
+
     pub fn bytes(&self, num: usize) -> &[u8] {
         let prev = self.pos.replace(self.pos.get() + num);
         &self.data[prev..self.pos.get()]
@@ -31,4 +44,152 @@ impl Reader {
         let prev = self.pos.replace(self.pos.get() + 1);
         self.data[prev]
     }
+
+    /// Steps back one byte, e.g. after peeking at an opcode.
+    pub fn back(&self) {
+        self.pos.set(self.pos.get() - 1);
+    }
+
+    pub fn leb_u32(&self) -> Result<u32, RuntimeError> {
+        Ok(self.leb(32, false)? as u32)
+    }
+
+    pub fn leb_u64(&self) -> Result<u64, RuntimeError> {
+        self.leb(64, false)
+    }
+
+    pub fn leb_i32(&self) -> Result<i32, RuntimeError> {
+        Ok(self.leb(32, true)? as i32)
+    }
+
+    pub fn leb_i64(&self) -> Result<i64, RuntimeError> {
+        Ok(self.leb(64, true)? as i64)
+    }
+
+    /// Decodes a LEB128 integer of at most `bits` bits. An encoding may use at
+    /// most `ceil(bits / 7)` bytes and the unused bits of its last byte have to
+    /// be zero, or a sign extension for signed integers.
+    fn leb(&self, bits: u32, signed: bool) -> Result<u64, RuntimeError> {
+        let max_bytes = bits.div_ceil(7);
+        let mut result = 0u64;
+        let mut shift = 0;
+
+        for i in 0..max_bytes {
+            let byte = self.byte();
+            let payload = (byte & 0x7f) as u64;
+            result |= payload << shift;
+            shift += 7;
+
+            if byte & 0x80 == 0 {
+                if i == max_bytes - 1 {
+                    let used = bits - 7 * i;
+                    let valid = if signed {
+                        let rest = payload >> (used - 1);
+                        rest == 0 || rest == 0x7f >> (used - 1)
+                    } else {
+                        payload >> used == 0
+                    };
+                    if !valid {
+                        return Err(RuntimeError::IntegerTooLarge);
+                    }
+                }
+                if signed && shift < 64 && byte & 0x40 != 0 {
+                    result |= !0u64 << shift;
+                }
+                return Ok(result);
+            }
+        }
+
+        Err(RuntimeError::IntegerRepresentationTooLong)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reader(bytes: &[u8]) -> Reader {
+        Reader::new(bytes.to_vec())
+    }
+
+    #[test]
+    fn leb_u32_test() {
+        assert_eq!(Ok(0), reader(&[0x00]).leb_u32());
+        assert_eq!(Ok(624485), reader(&[0xe5, 0x8e, 0x26]).leb_u32());
+        assert_eq!(Ok(3), reader(&[0x83, 0x80, 0x00]).leb_u32());
+        assert_eq!(
+            Ok(u32::MAX),
+            reader(&[0xff, 0xff, 0xff, 0xff, 0x0f]).leb_u32()
+        );
+        assert_eq!(
+            Err(RuntimeError::IntegerTooLarge),
+            reader(&[0xff, 0xff, 0xff, 0xff, 0x1f]).leb_u32()
+        );
+        assert_eq!(
+            Err(RuntimeError::IntegerRepresentationTooLong),
+            reader(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x00]).leb_u32()
+        );
+    }
+
+    #[test]
+    fn leb_u64_test() {
+        assert_eq!(Ok(624485), reader(&[0xe5, 0x8e, 0x26]).leb_u64());
+        assert_eq!(
+            Ok(u64::MAX),
+            reader(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]).leb_u64()
+        );
+        assert_eq!(
+            Err(RuntimeError::IntegerTooLarge),
+            reader(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x03]).leb_u64()
+        );
+    }
+
+    #[test]
+    fn leb_i32_test() {
+        assert_eq!(Ok(-1), reader(&[0x7f]).leb_i32());
+        assert_eq!(Ok(64), reader(&[0xc0, 0x00]).leb_i32());
+        assert_eq!(Ok(-123456), reader(&[0xc0, 0xbb, 0x78]).leb_i32());
+        assert_eq!(Ok(-1), reader(&[0xff, 0xff, 0xff, 0xff, 0x7f]).leb_i32());
+        assert_eq!(
+            Ok(i32::MIN),
+            reader(&[0x80, 0x80, 0x80, 0x80, 0x78]).leb_i32()
+        );
+        assert_eq!(
+            Ok(i32::MAX),
+            reader(&[0xff, 0xff, 0xff, 0xff, 0x07]).leb_i32()
+        );
+        assert_eq!(
+            Err(RuntimeError::IntegerTooLarge),
+            reader(&[0xff, 0xff, 0xff, 0xff, 0x4f]).leb_i32()
+        );
+        assert_eq!(
+            Err(RuntimeError::IntegerTooLarge),
+            reader(&[0x80, 0x80, 0x80, 0x80, 0x08]).leb_i32()
+        );
+        assert_eq!(
+            Err(RuntimeError::IntegerRepresentationTooLong),
+            reader(&[0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]).leb_i32()
+        );
+    }
+
+    #[test]
+    fn leb_i64_test() {
+        assert_eq!(Ok(-1), reader(&[0x7f]).leb_i64());
+        assert_eq!(
+            Ok(i64::MIN),
+            reader(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7f]).leb_i64()
+        );
+        assert_eq!(
+            Ok(i64::MAX),
+            reader(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00]).leb_i64()
+        );
+        assert_eq!(
+            Err(RuntimeError::IntegerTooLarge),
+            reader(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]).leb_i64()
+        );
+        assert_eq!(
+            Err(RuntimeError::IntegerRepresentationTooLong),
+            reader(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00]).leb_i64()
+        );
+    }
 }
@@ -0,0 +1,63 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// State behind a handle. Clones of the handle refer to the same state, so
+/// the host and the instances it is imported into see each other's changes.
+pub struct Shared<T>(Arc<Mutex<T>>);
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Shared<T> {
+    pub fn new(state: T) -> Self {
+        Self(Arc::new(Mutex::new(state)))
+    }
+
+    /// Locks the state, e.g. to read it through a shared reference.
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        // The state stays consistent even if a thread panicked while holding
+        // the lock.
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The state for an access through this handle, which is only locked if
+    /// there are other handles. No handle can be cloned while this one is
+    /// borrowed mutably, so a unique handle stays unique during the access.
+    pub fn access(&mut self) -> Access<'_, T> {
+        if Arc::strong_count(&self.0) > 1 {
+            return Access::Locked(self.lock());
+        }
+        let state = Arc::get_mut(&mut self.0).expect("the handle is unique");
+        Access::Exclusive(state.get_mut().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// Access to the state behind a handle, which is locked if other handles
+/// can access it at the same time.
+pub enum Access<'a, T> {
+    Exclusive(&'a mut T),
+    Locked(MutexGuard<'a, T>),
+}
+
+impl<T> Deref for Access<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match self {
+            Access::Exclusive(state) => state,
+            Access::Locked(state) => state,
+        }
+    }
+}
+
+impl<T> DerefMut for Access<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        match self {
+            Access::Exclusive(state) => state,
+            Access::Locked(state) => state,
+        }
+    }
+}
@@ -0,0 +1,643 @@
+//! Portable implementation of the SIMD instructions. A `v128` is kept as a
+//! `u128` whose lanes are numbered from the least significant bits, which
+//! matches the little-endian layout of the value in memory.
+
+use crate::ast::SimdOp;
+use crate::runtime::float;
+use crate::runtime::stack::{Stack, Stackable};
+use std::convert::TryInto;
+
+/// A scalar type that a `v128` can be split into.
+pub trait Lane: Copy {
+    const BYTES: usize;
+    fn read(bytes: &[u8]) -> Self;
+    fn write(self, bytes: &mut [u8]);
+}
+
+macro_rules! impl_lane {
+    ($($t:ty),*) => {
+        $(
+            impl Lane for $t {
+                const BYTES: usize = std::mem::size_of::<$t>();
+
+                fn read(bytes: &[u8]) -> Self {
+                    <$t>::from_le_bytes(bytes.try_into().unwrap())
+                }
+
+                fn write(self, bytes: &mut [u8]) {
+                    bytes.copy_from_slice(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_lane!(i8, u8, i16, u16, i32, u32, i64, u64, f32, f64);
+
+/// Reads lanes of type `T` from `bytes`.
+pub fn read_lanes<T: Lane>(bytes: &[u8]) -> Vec<T> {
+    bytes.chunks(T::BYTES).map(T::read).collect()
+}
+
+pub fn lanes<T: Lane>(v: u128) -> Vec<T> {
+    read_lanes(&v.to_le_bytes())
+}
+
+/// Combines `lanes` into a `v128`. Missing upper lanes are zero.
+pub fn from_lanes<T: Lane>(lanes: &[T]) -> u128 {
+    let mut bytes = [0; 16];
+    for (lane, chunk) in lanes.iter().zip(bytes.chunks_mut(T::BYTES)) {
+        lane.write(chunk);
+    }
+    u128::from_le_bytes(bytes)
+}
+
+pub fn splat<T: Lane>(x: T) -> u128 {
+    from_lanes(&vec![x; 16 / T::BYTES])
+}
+
+pub fn replace_lane<T: Lane>(v: u128, lane: usize, x: T) -> u128 {
+    let mut lanes = lanes(v);
+    lanes[lane] = x;
+    from_lanes(&lanes)
+}
+
+/// Applies `f` to each of `lanes`.
+pub fn map_lanes<T: Lane, R: Lane>(lanes: Vec<T>, f: impl Fn(T) -> R) -> u128 {
+    from_lanes(&lanes.into_iter().map(f).collect::<Vec<_>>())
+}
+
+/// Widens each lane read from `bytes`.
+pub fn extend<T: Lane, R: Lane + From<T>>(bytes: &[u8]) -> u128 {
+    map_lanes(read_lanes::<T>(bytes), R::from)
+}
+
+fn map<T: Lane, R: Lane>(v: u128, f: impl Fn(T) -> R) -> u128 {
+    map_lanes(lanes(v), f)
+}
+
+fn zip_lanes<T: Lane, R: Lane>(a: Vec<T>, b: Vec<T>, f: impl Fn(T, T) -> R) -> u128 {
+    let lanes = a.into_iter().zip(b).map(|(a, b)| f(a, b));
+    from_lanes(&lanes.collect::<Vec<_>>())
+}
+
+fn zip<T: Lane, R: Lane>(a: u128, b: u128, f: impl Fn(T, T) -> R) -> u128 {
+    zip_lanes(lanes(a), lanes(b), f)
+}
+
+/// The lower or upper half of the lanes of `v`.
+fn half<T: Lane>(v: u128, high: bool) -> Vec<T> {
+    let mut lanes = lanes(v);
+    let upper = lanes.split_off(lanes.len() / 2);
+    if high {
+        upper
+    } else {
+        lanes
+    }
+}
+
+/// Combines adjacent lanes of `v` with `f`.
+fn pairwise<T: Lane, R: Lane>(v: u128, f: impl Fn(T, T) -> R) -> u128 {
+    let lanes = lanes(v);
+    let pairs = lanes.chunks(2).map(|pair| f(pair[0], pair[1]));
+    from_lanes(&pairs.collect::<Vec<_>>())
+}
+
+/// Converts the lanes of `a` followed by those of `b` into lanes of half
+/// the size.
+fn narrow<T: Lane, R: Lane>(a: u128, b: u128, f: impl Fn(T) -> R) -> u128 {
+    let lanes = lanes(a).into_iter().chain(lanes(b)).map(f);
+    from_lanes(&lanes.collect::<Vec<_>>())
+}
+
+/// A lane with all bits set if `condition` holds and no bit set otherwise.
+fn mask<T: Lane + From<i8>>(condition: bool) -> T {
+    T::from(-(condition as i8))
+}
+
+fn all_true<T: Lane + PartialEq + Default>(v: u128) -> i32 {
+    lanes::<T>(v).iter().all(|x| *x != T::default()) as i32
+}
+
+/// Collects the sign bits of the lanes, lane 0 in the least significant bit.
+fn bitmask<T: Lane + PartialOrd + Default>(v: u128) -> i32 {
+    let signs = lanes::<T>(v).into_iter().map(|x| x < T::default());
+    signs
+        .enumerate()
+        .fold(0, |mask, (i, sign)| mask | (sign as i32) << i)
+}
+
+fn swizzle(v: u128, s: u128) -> u128 {
+    let bytes = lanes::<u8>(v);
+    map(s, |i: u8| bytes.get(i as usize).copied().unwrap_or(0))
+}
+
+/// Selects each byte of the result by its index into the bytes of `a`
+/// followed by those of `b`.
+pub fn shuffle(a: u128, b: u128, indices: &[u8; 16]) -> u128 {
+    let bytes = [lanes::<u8>(a), lanes::<u8>(b)].concat();
+    map_lanes(indices.to_vec(), |i| bytes[i as usize])
+}
+
+fn dot(a: u128, b: u128) -> u128 {
+    let products = lanes::<i16>(a).into_iter().zip(lanes::<i16>(b));
+    let products = products
+        .map(|(x, y)| x as i32 * y as i32)
+        .collect::<Vec<_>>();
+    let sums = products.chunks(2).map(|p| p[0].wrapping_add(p[1]));
+    from_lanes(&sums.collect::<Vec<_>>())
+}
+
+fn q15mulr_sat(x: i16, y: i16) -> i16 {
+    let product = (x as i32 * y as i32 + 0x4000) >> 15;
+    product.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
+
+fn unop(stack: &mut Stack, f: impl Fn(u128) -> u128) {
+    let v = stack.pop::<u128>();
+    stack.push(f(v));
+}
+
+fn binop(stack: &mut Stack, f: impl Fn(u128, u128) -> u128) {
+    let b = stack.pop::<u128>();
+    let a = stack.pop::<u128>();
+    stack.push(f(a, b));
+}
+
+fn test(stack: &mut Stack, f: impl Fn(u128) -> i32) {
+    let v = stack.pop::<u128>();
+    stack.push(f(v));
+}
+
+/// Shifts by the scalar on top of the stack. Shifting lanes wraps the shift
+/// count around the lane width.
+fn shift(stack: &mut Stack, f: impl Fn(u128, u32) -> u128) {
+    let count = stack.pop::<i32>() as u32;
+    let v = stack.pop::<u128>();
+    stack.push(f(v, count));
+}
+
+fn splat_scalar<T: Stackable, L: Lane>(stack: &mut Stack, f: impl Fn(T) -> L) {
+    let x = stack.pop::<T>();
+    stack.push(splat(f(x)));
+}
+
+/// Executes `op` on the operands on top of `stack`.
+pub fn execute(op: &SimdOp, stack: &mut Stack) {
+    use SimdOp::*;
+
+    match op {
+        I8x16Swizzle => binop(stack, swizzle),
+        I8x16Splat => splat_scalar(stack, |x: i32| x as i8),
+        I16x8Splat => splat_scalar(stack, |x: i32| x as i16),
+        I32x4Splat => splat_scalar(stack, |x: i32| x),
+        I64x2Splat => splat_scalar(stack, |x: i64| x),
+        F32x4Splat => splat_scalar(stack, |x: f32| x),
+        F64x2Splat => splat_scalar(stack, |x: f64| x),
+
+        I8x16Eq => binop(stack, |a, b| zip(a, b, |x: i8, y| mask::<i8>(x == y))),
+        I8x16Ne => binop(stack, |a, b| zip(a, b, |x: i8, y| mask::<i8>(x != y))),
+        I8x16LtS => binop(stack, |a, b| zip(a, b, |x: i8, y| mask::<i8>(x < y))),
+        I8x16LtU => binop(stack, |a, b| zip(a, b, |x: u8, y| mask::<i8>(x < y))),
+        I8x16GtS => binop(stack, |a, b| zip(a, b, |x: i8, y| mask::<i8>(x > y))),
+        I8x16GtU => binop(stack, |a, b| zip(a, b, |x: u8, y| mask::<i8>(x > y))),
+        I8x16LeS => binop(stack, |a, b| zip(a, b, |x: i8, y| mask::<i8>(x <= y))),
+        I8x16LeU => binop(stack, |a, b| zip(a, b, |x: u8, y| mask::<i8>(x <= y))),
+        I8x16GeS => binop(stack, |a, b| zip(a, b, |x: i8, y| mask::<i8>(x >= y))),
+        I8x16GeU => binop(stack, |a, b| zip(a, b, |x: u8, y| mask::<i8>(x >= y))),
+        I16x8Eq => binop(stack, |a, b| zip(a, b, |x: i16, y| mask::<i16>(x == y))),
+        I16x8Ne => binop(stack, |a, b| zip(a, b, |x: i16, y| mask::<i16>(x != y))),
+        I16x8LtS => binop(stack, |a, b| zip(a, b, |x: i16, y| mask::<i16>(x < y))),
+        I16x8LtU => binop(stack, |a, b| zip(a, b, |x: u16, y| mask::<i16>(x < y))),
+        I16x8GtS => binop(stack, |a, b| zip(a, b, |x: i16, y| mask::<i16>(x > y))),
+        I16x8GtU => binop(stack, |a, b| zip(a, b, |x: u16, y| mask::<i16>(x > y))),
+        I16x8LeS => binop(stack, |a, b| zip(a, b, |x: i16, y| mask::<i16>(x <= y))),
+        I16x8LeU => binop(stack, |a, b| zip(a, b, |x: u16, y| mask::<i16>(x <= y))),
+        I16x8GeS => binop(stack, |a, b| zip(a, b, |x: i16, y| mask::<i16>(x >= y))),
+        I16x8GeU => binop(stack, |a, b| zip(a, b, |x: u16, y| mask::<i16>(x >= y))),
+        I32x4Eq => binop(stack, |a, b| zip(a, b, |x: i32, y| mask::<i32>(x == y))),
+        I32x4Ne => binop(stack, |a, b| zip(a, b, |x: i32, y| mask::<i32>(x != y))),
+        I32x4LtS => binop(stack, |a, b| zip(a, b, |x: i32, y| mask::<i32>(x < y))),
+        I32x4LtU => binop(stack, |a, b| zip(a, b, |x: u32, y| mask::<i32>(x < y))),
+        I32x4GtS => binop(stack, |a, b| zip(a, b, |x: i32, y| mask::<i32>(x > y))),
+        I32x4GtU => binop(stack, |a, b| zip(a, b, |x: u32, y| mask::<i32>(x > y))),
+        I32x4LeS => binop(stack, |a, b| zip(a, b, |x: i32, y| mask::<i32>(x <= y))),
+        I32x4LeU => binop(stack, |a, b| zip(a, b, |x: u32, y| mask::<i32>(x <= y))),
+        I32x4GeS => binop(stack, |a, b| zip(a, b, |x: i32, y| mask::<i32>(x >= y))),
+        I32x4GeU => binop(stack, |a, b| zip(a, b, |x: u32, y| mask::<i32>(x >= y))),
+        F32x4Eq => binop(stack, |a, b| zip(a, b, |x: f32, y| mask::<i32>(x == y))),
+        F32x4Ne => binop(stack, |a, b| zip(a, b, |x: f32, y| mask::<i32>(x != y))),
+        F32x4Lt => binop(stack, |a, b| zip(a, b, |x: f32, y| mask::<i32>(x < y))),
+        F32x4Gt => binop(stack, |a, b| zip(a, b, |x: f32, y| mask::<i32>(x > y))),
+        F32x4Le => binop(stack, |a, b| zip(a, b, |x: f32, y| mask::<i32>(x <= y))),
+        F32x4Ge => binop(stack, |a, b| zip(a, b, |x: f32, y| mask::<i32>(x >= y))),
+        F64x2Eq => binop(stack, |a, b| zip(a, b, |x: f64, y| mask::<i64>(x == y))),
+        F64x2Ne => binop(stack, |a, b| zip(a, b, |x: f64, y| mask::<i64>(x != y))),
+        F64x2Lt => binop(stack, |a, b| zip(a, b, |x: f64, y| mask::<i64>(x < y))),
+        F64x2Gt => binop(stack, |a, b| zip(a, b, |x: f64, y| mask::<i64>(x > y))),
+        F64x2Le => binop(stack, |a, b| zip(a, b, |x: f64, y| mask::<i64>(x <= y))),
+        F64x2Ge => binop(stack, |a, b| zip(a, b, |x: f64, y| mask::<i64>(x >= y))),
+
+        V128Not => unop(stack, |v| !v),
+        V128And => binop(stack, |a, b| a & b),
+        V128AndNot => binop(stack, |a, b| a & !b),
+        V128Or => binop(stack, |a, b| a | b),
+        V128Xor => binop(stack, |a, b| a ^ b),
+        V128Bitselect => {
+            let c = stack.pop::<u128>();
+            binop(stack, |a, b| (a & c) | (b & !c));
+        }
+        V128AnyTrue => test(stack, |v| (v != 0) as i32),
+
+        F32x4DemoteF64x2Zero => unop(stack, |v| map(v, |x: f64| x as f32)),
+        F64x2PromoteLowF32x4 => unop(stack, |v| map_lanes(half::<f32>(v, false), f64::from)),
+
+        I8x16Abs => unop(stack, |v| map(v, i8::wrapping_abs)),
+        I8x16Neg => unop(stack, |v| map(v, i8::wrapping_neg)),
+        I8x16Popcnt => unop(stack, |v| map(v, |x: u8| x.count_ones() as u8)),
+        I8x16AllTrue => test(stack, all_true::<i8>),
+        I8x16Bitmask => test(stack, bitmask::<i8>),
+        I8x16NarrowI16x8S => binop(stack, |a, b| {
+            narrow(a, b, |x: i16| x.clamp(i8::MIN as i16, i8::MAX as i16) as i8)
+        }),
+        I8x16NarrowI16x8U => binop(stack, |a, b| {
+            narrow(a, b, |x: i16| x.clamp(0, u8::MAX as i16) as u8)
+        }),
+        F32x4Ceil => unop(stack, |v| map(v, f32::ceil)),
+        F32x4Floor => unop(stack, |v| map(v, f32::floor)),
+        F32x4Trunc => unop(stack, |v| map(v, f32::trunc)),
+        F32x4Nearest => unop(stack, |v| map(v, float::f32_nearest)),
+        I8x16Shl => shift(stack, |v, s| map(v, |x: i8| x.wrapping_shl(s))),
+        I8x16ShrS => shift(stack, |v, s| map(v, |x: i8| x.wrapping_shr(s))),
+        I8x16ShrU => shift(stack, |v, s| map(v, |x: u8| x.wrapping_shr(s))),
+        I8x16Add => binop(stack, |a, b| zip(a, b, i8::wrapping_add)),
+        I8x16AddSatS => binop(stack, |a, b| zip(a, b, i8::saturating_add)),
+        I8x16AddSatU => binop(stack, |a, b| zip(a, b, u8::saturating_add)),
+        I8x16Sub => binop(stack, |a, b| zip(a, b, i8::wrapping_sub)),
+        I8x16SubSatS => binop(stack, |a, b| zip(a, b, i8::saturating_sub)),
+        I8x16SubSatU => binop(stack, |a, b| zip(a, b, u8::saturating_sub)),
+        F64x2Ceil => unop(stack, |v| map(v, f64::ceil)),
+        F64x2Floor => unop(stack, |v| map(v, f64::floor)),
+        I8x16MinS => binop(stack, |a, b| zip(a, b, |x: i8, y| x.min(y))),
+        I8x16MinU => binop(stack, |a, b| zip(a, b, |x: u8, y| x.min(y))),
+        I8x16MaxS => binop(stack, |a, b| zip(a, b, |x: i8, y| x.max(y))),
+        I8x16MaxU => binop(stack, |a, b| zip(a, b, |x: u8, y| x.max(y))),
+        F64x2Trunc => unop(stack, |v| map(v, f64::trunc)),
+        I8x16AvgrU => binop(stack, |a, b| {
+            zip(a, b, |x: u8, y: u8| (x as u16 + y as u16).div_ceil(2) as u8)
+        }),
+        I16x8ExtaddPairwiseI8x16S => {
+            unop(stack, |v| pairwise(v, |x: i8, y: i8| x as i16 + y as i16))
+        }
+        I16x8ExtaddPairwiseI8x16U => {
+            unop(stack, |v| pairwise(v, |x: u8, y: u8| x as u16 + y as u16))
+        }
+        I32x4ExtaddPairwiseI16x8S => {
+            unop(stack, |v| pairwise(v, |x: i16, y: i16| x as i32 + y as i32))
+        }
+        I32x4ExtaddPairwiseI16x8U => {
+            unop(stack, |v| pairwise(v, |x: u16, y: u16| x as u32 + y as u32))
+        }
+
+        I16x8Abs => unop(stack, |v| map(v, i16::wrapping_abs)),
+        I16x8Neg => unop(stack, |v| map(v, i16::wrapping_neg)),
+        I16x8Q15mulrSatS => binop(stack, |a, b| zip(a, b, q15mulr_sat)),
+        I16x8AllTrue => test(stack, all_true::<i16>),
+        I16x8Bitmask => test(stack, bitmask::<i16>),
+        I16x8NarrowI32x4S => binop(stack, |a, b| {
+            narrow(a, b, |x: i32| {
+                x.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+            })
+        }),
+        I16x8NarrowI32x4U => binop(stack, |a, b| {
+            narrow(a, b, |x: i32| x.clamp(0, u16::MAX as i32) as u16)
+        }),
+        I16x8ExtendLowI8x16S => unop(stack, |v| map_lanes(half::<i8>(v, false), i16::from)),
+        I16x8ExtendHighI8x16S => unop(stack, |v| map_lanes(half::<i8>(v, true), i16::from)),
+        I16x8ExtendLowI8x16U => unop(stack, |v| map_lanes(half::<u8>(v, false), u16::from)),
+        I16x8ExtendHighI8x16U => unop(stack, |v| map_lanes(half::<u8>(v, true), u16::from)),
+        I16x8Shl => shift(stack, |v, s| map(v, |x: i16| x.wrapping_shl(s))),
+        I16x8ShrS => shift(stack, |v, s| map(v, |x: i16| x.wrapping_shr(s))),
+        I16x8ShrU => shift(stack, |v, s| map(v, |x: u16| x.wrapping_shr(s))),
+        I16x8Add => binop(stack, |a, b| zip(a, b, i16::wrapping_add)),
+        I16x8AddSatS => binop(stack, |a, b| zip(a, b, i16::saturating_add)),
+        I16x8AddSatU => binop(stack, |a, b| zip(a, b, u16::saturating_add)),
+        I16x8Sub => binop(stack, |a, b| zip(a, b, i16::wrapping_sub)),
+        I16x8SubSatS => binop(stack, |a, b| zip(a, b, i16::saturating_sub)),
+        I16x8SubSatU => binop(stack, |a, b| zip(a, b, u16::saturating_sub)),
+        F64x2Nearest => unop(stack, |v| map(v, float::f64_nearest)),
+        I16x8Mul => binop(stack, |a, b| zip(a, b, i16::wrapping_mul)),
+        I16x8MinS => binop(stack, |a, b| zip(a, b, |x: i16, y| x.min(y))),
+        I16x8MinU => binop(stack, |a, b| zip(a, b, |x: u16, y| x.min(y))),
+        I16x8MaxS => binop(stack, |a, b| zip(a, b, |x: i16, y| x.max(y))),
+        I16x8MaxU => binop(stack, |a, b| zip(a, b, |x: u16, y| x.max(y))),
+        I16x8AvgrU => binop(stack, |a, b| {
+            zip(a, b, |x: u16, y: u16| {
+                (x as u32 + y as u32).div_ceil(2) as u16
+            })
+        }),
+        I16x8ExtmulLowI8x16S => binop(stack, |a, b| {
+            zip_lanes(half(a, false), half(b, false), |x: i8, y| {
+                x as i16 * y as i16
+            })
+        }),
+        I16x8ExtmulHighI8x16S => binop(stack, |a, b| {
+            zip_lanes(half(a, true), half(b, true), |x: i8, y| x as i16 * y as i16)
+        }),
+        I16x8ExtmulLowI8x16U => binop(stack, |a, b| {
+            zip_lanes(half(a, false), half(b, false), |x: u8, y| {
+                x as u16 * y as u16
+            })
+        }),
+        I16x8ExtmulHighI8x16U => binop(stack, |a, b| {
+            zip_lanes(half(a, true), half(b, true), |x: u8, y| x as u16 * y as u16)
+        }),
+
+        I32x4Abs => unop(stack, |v| map(v, i32::wrapping_abs)),
+        I32x4Neg => unop(stack, |v| map(v, i32::wrapping_neg)),
+        I32x4AllTrue => test(stack, all_true::<i32>),
+        I32x4Bitmask => test(stack, bitmask::<i32>),
+        I32x4ExtendLowI16x8S => unop(stack, |v| map_lanes(half::<i16>(v, false), i32::from)),
+        I32x4ExtendHighI16x8S => unop(stack, |v| map_lanes(half::<i16>(v, true), i32::from)),
+        I32x4ExtendLowI16x8U => unop(stack, |v| map_lanes(half::<u16>(v, false), u32::from)),
+        I32x4ExtendHighI16x8U => unop(stack, |v| map_lanes(half::<u16>(v, true), u32::from)),
+        I32x4Shl => shift(stack, |v, s| map(v, |x: i32| x.wrapping_shl(s))),
+        I32x4ShrS => shift(stack, |v, s| map(v, |x: i32| x.wrapping_shr(s))),
+        I32x4ShrU => shift(stack, |v, s| map(v, |x: u32| x.wrapping_shr(s))),
+        I32x4Add => binop(stack, |a, b| zip(a, b, i32::wrapping_add)),
+        I32x4Sub => binop(stack, |a, b| zip(a, b, i32::wrapping_sub)),
+        I32x4Mul => binop(stack, |a, b| zip(a, b, i32::wrapping_mul)),
+        I32x4MinS => binop(stack, |a, b| zip(a, b, |x: i32, y| x.min(y))),
+        I32x4MinU => binop(stack, |a, b| zip(a, b, |x: u32, y| x.min(y))),
+        I32x4MaxS => binop(stack, |a, b| zip(a, b, |x: i32, y| x.max(y))),
+        I32x4MaxU => binop(stack, |a, b| zip(a, b, |x: u32, y| x.max(y))),
+        I32x4DotI16x8S => binop(stack, dot),
+        I32x4ExtmulLowI16x8S => binop(stack, |a, b| {
+            zip_lanes(half(a, false), half(b, false), |x: i16, y| {
+                x as i32 * y as i32
+            })
+        }),
+        I32x4ExtmulHighI16x8S => binop(stack, |a, b| {
+            zip_lanes(half(a, true), half(b, true), |x: i16, y| {
+                x as i32 * y as i32
+            })
+        }),
+        I32x4ExtmulLowI16x8U => binop(stack, |a, b| {
+            zip_lanes(half(a, false), half(b, false), |x: u16, y| {
+                x as u32 * y as u32
+            })
+        }),
+        I32x4ExtmulHighI16x8U => binop(stack, |a, b| {
+            zip_lanes(half(a, true), half(b, true), |x: u16, y| {
+                x as u32 * y as u32
+            })
+        }),
+
+        I64x2Abs => unop(stack, |v| map(v, i64::wrapping_abs)),
+        I64x2Neg => unop(stack, |v| map(v, i64::wrapping_neg)),
+        I64x2AllTrue => test(stack, all_true::<i64>),
+        I64x2Bitmask => test(stack, bitmask::<i64>),
+        I64x2ExtendLowI32x4S => unop(stack, |v| map_lanes(half::<i32>(v, false), i64::from)),
+        I64x2ExtendHighI32x4S => unop(stack, |v| map_lanes(half::<i32>(v, true), i64::from)),
+        I64x2ExtendLowI32x4U => unop(stack, |v| map_lanes(half::<u32>(v, false), u64::from)),
+        I64x2ExtendHighI32x4U => unop(stack, |v| map_lanes(half::<u32>(v, true), u64::from)),
+        I64x2Shl => shift(stack, |v, s| map(v, |x: i64| x.wrapping_shl(s))),
+        I64x2ShrS => shift(stack, |v, s| map(v, |x: i64| x.wrapping_shr(s))),
+        I64x2ShrU => shift(stack, |v, s| map(v, |x: u64| x.wrapping_shr(s))),
+        I64x2Add => binop(stack, |a, b| zip(a, b, i64::wrapping_add)),
+        I64x2Sub => binop(stack, |a, b| zip(a, b, i64::wrapping_sub)),
+        I64x2Mul => binop(stack, |a, b| zip(a, b, i64::wrapping_mul)),
+        I64x2Eq => binop(stack, |a, b| zip(a, b, |x: i64, y| mask::<i64>(x == y))),
+        I64x2Ne => binop(stack, |a, b| zip(a, b, |x: i64, y| mask::<i64>(x != y))),
+        I64x2LtS => binop(stack, |a, b| zip(a, b, |x: i64, y| mask::<i64>(x < y))),
+        I64x2GtS => binop(stack, |a, b| zip(a, b, |x: i64, y| mask::<i64>(x > y))),
+        I64x2LeS => binop(stack, |a, b| zip(a, b, |x: i64, y| mask::<i64>(x <= y))),
+        I64x2GeS => binop(stack, |a, b| zip(a, b, |x: i64, y| mask::<i64>(x >= y))),
+        I64x2ExtmulLowI32x4S => binop(stack, |a, b| {
+            zip_lanes(half(a, false), half(b, false), |x: i32, y| {
+                x as i64 * y as i64
+            })
+        }),
+        I64x2ExtmulHighI32x4S => binop(stack, |a, b| {
+            zip_lanes(half(a, true), half(b, true), |x: i32, y| {
+                x as i64 * y as i64
+            })
+        }),
+        I64x2ExtmulLowI32x4U => binop(stack, |a, b| {
+            zip_lanes(half(a, false), half(b, false), |x: u32, y| {
+                x as u64 * y as u64
+            })
+        }),
+        I64x2ExtmulHighI32x4U => binop(stack, |a, b| {
+            zip_lanes(half(a, true), half(b, true), |x: u32, y| {
+                x as u64 * y as u64
+            })
+        }),
+
+        F32x4Abs => unop(stack, |v| map(v, f32::abs)),
+        F32x4Neg => unop(stack, |v| map(v, |x: f32| -x)),
+        F32x4Sqrt => unop(stack, |v| map(v, f32::sqrt)),
+        F32x4Add => binop(stack, |a, b| zip(a, b, |x: f32, y| x + y)),
+        F32x4Sub => binop(stack, |a, b| zip(a, b, |x: f32, y| x - y)),
+        F32x4Mul => binop(stack, |a, b| zip(a, b, |x: f32, y| x * y)),
+        F32x4Div => binop(stack, |a, b| zip(a, b, |x: f32, y| x / y)),
+        F32x4Min => binop(stack, |a, b| zip(a, b, float::f32_min)),
+        F32x4Max => binop(stack, |a, b| zip(a, b, float::f32_max)),
+        // The pseudo-minimum and -maximum are defined by a single comparison,
+        // so they return the first operand for NaN and signed zeros.
+        F32x4Pmin => binop(stack, |a, b| {
+            zip(a, b, |x: f32, y| if y < x { y } else { x })
+        }),
+        F32x4Pmax => binop(stack, |a, b| {
+            zip(a, b, |x: f32, y| if x < y { y } else { x })
+        }),
+        F64x2Abs => unop(stack, |v| map(v, f64::abs)),
+        F64x2Neg => unop(stack, |v| map(v, |x: f64| -x)),
+        F64x2Sqrt => unop(stack, |v| map(v, f64::sqrt)),
+        F64x2Add => binop(stack, |a, b| zip(a, b, |x: f64, y| x + y)),
+        F64x2Sub => binop(stack, |a, b| zip(a, b, |x: f64, y| x - y)),
+        F64x2Mul => binop(stack, |a, b| zip(a, b, |x: f64, y| x * y)),
+        F64x2Div => binop(stack, |a, b| zip(a, b, |x: f64, y| x / y)),
+        F64x2Min => binop(stack, |a, b| zip(a, b, float::f64_min)),
+        F64x2Max => binop(stack, |a, b| zip(a, b, float::f64_max)),
+        F64x2Pmin => binop(stack, |a, b| {
+            zip(a, b, |x: f64, y| if y < x { y } else { x })
+        }),
+        F64x2Pmax => binop(stack, |a, b| {
+            zip(a, b, |x: f64, y| if x < y { y } else { x })
+        }),
+
+        // Rust's float to integer casts saturate and map NaN to 0.
+        I32x4TruncSatF32x4S => unop(stack, |v| map(v, |x: f32| x as i32)),
+        I32x4TruncSatF32x4U => unop(stack, |v| map(v, |x: f32| x as u32)),
+        F32x4ConvertI32x4S => unop(stack, |v| map(v, |x: i32| x as f32)),
+        F32x4ConvertI32x4U => unop(stack, |v| map(v, |x: u32| x as f32)),
+        I32x4TruncSatF64x2SZero => unop(stack, |v| map(v, |x: f64| x as i32)),
+        I32x4TruncSatF64x2UZero => unop(stack, |v| map(v, |x: f64| x as u32)),
+        F64x2ConvertLowI32x4S => unop(stack, |v| map_lanes(half::<i32>(v, false), f64::from)),
+        F64x2ConvertLowI32x4U => unop(stack, |v| map_lanes(half::<u32>(v, false), f64::from)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use SimdOp::*;
+
+    fn run<R: Stackable>(op: SimdOp, operands: &[u128]) -> R {
+        let mut stack = Stack::new();
+        for operand in operands {
+            stack.push(*operand);
+        }
+        execute(&op, &mut stack);
+        stack.pop()
+    }
+
+    #[test]
+    fn lane_order() {
+        let v = 0x0f0e0d0c_0b0a0908_07060504_03020100;
+        assert_eq!((0..16).collect::<Vec<u8>>(), lanes::<u8>(v));
+        assert_eq!(vec![0x03020100, 0x07060504], lanes::<u32>(v)[..2]);
+        assert_eq!(v, from_lanes(&lanes::<i16>(v)));
+        assert_eq!(0x2_0000_0001, from_lanes(&[1u32, 2]));
+        assert_eq!(0x7f7f7f7f_7f7f7f7f_7f7f7f7f_7f7f7f7f, splat(0x7fu8));
+        assert_eq!(
+            from_lanes(&[1, 9, 3, 4]),
+            replace_lane(from_lanes(&[1, 2, 3, 4]), 1, 9)
+        );
+        assert_eq!(
+            from_lanes(&[-1i16, 2, 3, 4]),
+            extend::<i8, i16>(&[0xff, 2, 3, 4])
+        );
+    }
+
+    #[test]
+    fn integer_lanes() {
+        let a = from_lanes(&[1i8, -1, 127, -128]);
+        let b = from_lanes(&[1i8, 1, 1, -1]);
+        assert_eq!(
+            from_lanes(&[2i8, 0, -128, 127]),
+            run::<u128>(I8x16Add, &[a, b])
+        );
+        assert_eq!(
+            from_lanes(&[2i8, 0, 127, -128]),
+            run::<u128>(I8x16AddSatS, &[a, b])
+        );
+        assert_eq!(
+            from_lanes(&[0i8, -1, 0, -1]),
+            run::<u128>(I8x16LtS, &[a, b])
+        );
+        let le = lanes::<i8>(run(I8x16LeU, &[a, b]));
+        assert_eq!(vec![-1, 0, 0, -1, -1], le[..5]);
+        assert_eq!(0b1010, run::<i32>(I8x16Bitmask, &[a]));
+        assert_eq!(0, run::<i32>(I8x16AllTrue, &[a]));
+        assert_eq!(1, run::<i32>(I8x16AllTrue, &[splat(3u8)]));
+        assert_eq!(1, run::<i32>(V128AnyTrue, &[a]));
+
+        let a = from_lanes(&[-32768i16, 32767, 3, 4, 5, 6, 7, 8]);
+        let b = from_lanes(&[-32768i16, 32767, -3, 4, 5, 6, 7, 8]);
+        assert_eq!(
+            from_lanes(&[32767i16, 32766, 0, 0, 0, 0, 0, 0]),
+            run::<u128>(I16x8Q15mulrSatS, &[a, b])
+        );
+        assert_eq!(
+            from_lanes(&[0x7fff_0001, 25, 61, 113]),
+            run::<u128>(I32x4DotI16x8S, &[a, a])
+        );
+        assert_eq!(
+            from_lanes(&[-128i8, 127, 3, 4, 5, 6, 7, 8, -128, 127, -3, 4, 5, 6, 7, 8]),
+            run::<u128>(I8x16NarrowI16x8S, &[a, b])
+        );
+        assert_eq!(
+            from_lanes(&[-32768, 32767, 3, 4]),
+            run::<u128>(I32x4ExtendLowI16x8S, &[a])
+        );
+        assert_eq!(
+            from_lanes(&[32768, 32767, 3, 4]),
+            run::<u128>(I32x4ExtendLowI16x8U, &[a])
+        );
+        assert_eq!(
+            from_lanes(&[-1, 7, 11, 15]),
+            run::<u128>(I32x4ExtaddPairwiseI16x8S, &[a])
+        );
+        assert_eq!(
+            from_lanes(&[25, 36, 49, 64]),
+            run::<u128>(I32x4ExtmulHighI16x8S, &[a, b])
+        );
+        assert_eq!(
+            splat(0x8000u16),
+            run::<u128>(I16x8AvgrU, &[splat(0xffffu16), splat(1u16)])
+        );
+
+        // The shift count is taken modulo the lane width.
+        let mut stack = Stack::new();
+        stack.push(from_lanes(&[1, -8, i32::MIN, 0]));
+        stack.push(33);
+        execute(&I32x4ShrS, &mut stack);
+        assert_eq!(from_lanes(&[0, -4, -0x4000_0000, 0]), stack.pop::<u128>());
+    }
+
+    #[test]
+    fn float_lanes() {
+        let a = from_lanes(&[1.5f32, -0.0, f32::NAN, 2.5]);
+        let b = from_lanes(&[-1.0f32, 0.0, 1.0, 2.5]);
+        let min = lanes::<f32>(run(F32x4Min, &[a, b]));
+        assert_eq!(-1.0, min[0]);
+        assert!(min[1].is_sign_negative());
+        assert!(min[2].is_nan());
+        let pmin = lanes::<f32>(run(F32x4Pmin, &[a, b]));
+        assert!(pmin[1].is_sign_negative());
+        assert!(pmin[2].is_nan());
+        let pmax = lanes::<f32>(run(F32x4Pmax, &[b, a]));
+        assert!(pmax[1].is_sign_positive());
+        assert_eq!(1.0, pmax[2]);
+        assert_eq!(from_lanes(&[0, -1, 0, -1]), run::<u128>(F32x4Eq, &[a, b]));
+        assert_eq!(
+            vec![2.0, -0.0, 2.0],
+            lanes::<f32>(run(F32x4Nearest, &[a]))
+                .into_iter()
+                .filter(|f| !f.is_nan())
+                .collect::<Vec<_>>()
+        );
+
+        let big = from_lanes(&[f64::MAX, -1.5]);
+        assert_eq!(
+            from_lanes(&[1, 0, 0, 2]),
+            run::<u128>(I32x4TruncSatF32x4S, &[a])
+        );
+        assert_eq!(
+            from_lanes(&[i32::MAX, -1, 0, 0]),
+            run::<u128>(I32x4TruncSatF64x2SZero, &[big])
+        );
+        assert_eq!(
+            from_lanes(&[u32::MAX, 0, 0, 0]),
+            run::<u128>(I32x4TruncSatF64x2UZero, &[big])
+        );
+        assert_eq!(
+            from_lanes(&[1.5f64, -0.0]),
+            run::<u128>(F64x2PromoteLowF32x4, &[a])
+        );
+        assert_eq!(
+            from_lanes(&[f32::INFINITY, -1.5, 0.0, 0.0]),
+            run::<u128>(F32x4DemoteF64x2Zero, &[big])
+        );
+        assert_eq!(
+            from_lanes(&[4294967295.0f64, 2.0]),
+            run::<u128>(F64x2ConvertLowI32x4U, &[from_lanes(&[-1, 2, 3, 4])])
+        );
+    }
+
+    #[test]
+    fn shuffle_swizzle() {
+        let a = from_lanes(&(0..16).collect::<Vec<u8>>());
+        let b = from_lanes(&(16..32).collect::<Vec<u8>>());
+        let indices = [31, 0, 17, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14];
+        assert_eq!(from_lanes(&indices), shuffle(a, b, &indices));
+
+        let s = from_lanes(&[15u8, 16, 255, 0]);
+        assert_eq!(
+            from_lanes(&[15u8, 0, 0, 0]),
+            run::<u128>(I8x16Swizzle, &[a, s])
+        );
+    }
+}
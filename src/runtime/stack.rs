@@ -1,53 +1,241 @@
-use std::cell::Cell;
+use std::convert::TryInto;
 
 pub struct Stack {
-    stack: Cell<Vec<u8>>,
+    stack: Vec<u8>,
+    // Start offset of every value in `stack`, so values can be counted and
+    // unwound without knowing their types.
+    offsets: Vec<usize>,
 }
 
 impl Stack {
     pub fn new() -> Self {
         Self {
-            stack: Cell::new(Vec::<u8>::new()),
+            stack: Vec::new(),
+            offsets: Vec::new(),
         }
     }
 
     pub fn push<T: Stackable>(&mut self, arg: T) {
-        let mut bytes = arg.to_bytes();
-        self.stack.get_mut().append(&mut bytes);
+        self.offsets.push(self.stack.len());
+        self.stack.extend_from_slice(arg.to_bytes().as_ref());
     }
 
     pub fn pop<T: Stackable>(&mut self) -> T {
-        use std::convert::TryInto;
-        let bytes = self
-            .stack
-            .get_mut()
+        let stack = &mut self.stack;
+        let top = self.offsets.pop().unwrap();
+        debug_assert_eq!(stack.len() - top, T::byte_size());
+        let value = T::from_bytes(&stack[top..]);
+        stack.truncate(top);
+        value
+    }
+
+    /// Number of values on the stack.
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// The non-null values of the size of a reference as references, which
+    /// includes every reference on the stack.
+    pub fn refs(&self) -> impl Iterator<Item = usize> + '_ {
+        let ends = self.offsets.iter().skip(1).copied();
+        let ends = ends.chain([self.stack.len()]);
+        self.offsets
             .iter()
-            .rev()
-            .take(T::byte_size())
-            .copied()
-            .collect::<Vec<u8>>();
-        let value = bytes.as_slice().try_into().unwrap();
-        self.stack.get_mut().truncate(T::byte_size());
-        T::from_bytes(value)
+            .zip(ends)
+            .filter(|(start, end)| end - *start == Option::<usize>::byte_size())
+            .filter_map(move |(start, end)| Option::<usize>::from_bytes(&self.stack[*start..end]))
+    }
+
+    /// Removes all values above `height` except for the topmost `arity`
+    /// values, which are kept on top of the stack.
+    pub fn unwind(&mut self, height: usize, arity: usize) {
+        let stack = &mut self.stack;
+        let keep = self.offsets.len() - arity;
+        let to = self.offsets.get(height).copied().unwrap_or(stack.len());
+        let from = self.offsets.get(keep).copied().unwrap_or(stack.len());
+
+        stack.drain(to..from);
+        self.offsets.drain(height..keep);
+        for offset in &mut self.offsets[height..] {
+            *offset -= from - to;
+        }
     }
 }
 
 pub trait Stackable {
-    fn to_bytes(&self) -> Vec<u8>;
-    fn from_bytes(stack: &[u8; 4]) -> Self;
+    /// The bytes of a value, which are an array so that pushing doesn't
+    /// allocate.
+    type Bytes: AsRef<[u8]>;
+
+    fn to_bytes(&self) -> Self::Bytes;
+    fn from_bytes(stack: &[u8]) -> Self;
     fn byte_size() -> usize;
 }
 
 impl Stackable for i32 {
-    fn to_bytes(&self) -> Vec<u8> {
-        self.to_ne_bytes().to_vec()
+    type Bytes = [u8; 4];
+
+    fn to_bytes(&self) -> Self::Bytes {
+        self.to_ne_bytes()
     }
 
-    fn from_bytes(stack: &[u8; 4]) -> Self {
-        i32::from_ne_bytes(*stack)
+    fn from_bytes(stack: &[u8]) -> Self {
+        i32::from_ne_bytes(stack.try_into().unwrap())
     }
 
     fn byte_size() -> usize {
         4
     }
 }
+
+impl Stackable for i64 {
+    type Bytes = [u8; 8];
+
+    fn to_bytes(&self) -> Self::Bytes {
+        self.to_ne_bytes()
+    }
+
+    fn from_bytes(stack: &[u8]) -> Self {
+        i64::from_ne_bytes(stack.try_into().unwrap())
+    }
+
+    fn byte_size() -> usize {
+        8
+    }
+}
+
+impl Stackable for f32 {
+    type Bytes = [u8; 4];
+
+    fn to_bytes(&self) -> Self::Bytes {
+        self.to_ne_bytes()
+    }
+
+    fn from_bytes(stack: &[u8]) -> Self {
+        f32::from_ne_bytes(stack.try_into().unwrap())
+    }
+
+    fn byte_size() -> usize {
+        4
+    }
+}
+
+impl Stackable for f64 {
+    type Bytes = [u8; 8];
+
+    fn to_bytes(&self) -> Self::Bytes {
+        self.to_ne_bytes()
+    }
+
+    fn from_bytes(stack: &[u8]) -> Self {
+        f64::from_ne_bytes(stack.try_into().unwrap())
+    }
+
+    fn byte_size() -> usize {
+        8
+    }
+}
+
+impl Stackable for u128 {
+    type Bytes = [u8; 16];
+
+    fn to_bytes(&self) -> Self::Bytes {
+        self.to_ne_bytes()
+    }
+
+    fn from_bytes(stack: &[u8]) -> Self {
+        u128::from_ne_bytes(stack.try_into().unwrap())
+    }
+
+    fn byte_size() -> usize {
+        16
+    }
+}
+
+/// References are function indices or host object handles, with the maximum
+/// value standing for null.
+impl Stackable for Option<usize> {
+    type Bytes = [u8; 8];
+
+    fn to_bytes(&self) -> Self::Bytes {
+        self.map_or(u64::MAX, |r| r as u64).to_ne_bytes()
+    }
+
+    fn from_bytes(stack: &[u8]) -> Self {
+        match u64::from_ne_bytes(stack.try_into().unwrap()) {
+            u64::MAX => None,
+            r => Some(r as usize),
+        }
+    }
+
+    fn byte_size() -> usize {
+        8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_pop() {
+        let mut stack = Stack::new();
+        stack.push(1);
+        stack.push(-2);
+        stack.push(i32::MAX);
+        assert_eq!(i32::MAX, stack.pop::<i32>());
+        assert_eq!(-2, stack.pop::<i32>());
+        assert_eq!(1, stack.pop::<i32>());
+    }
+
+    #[test]
+    fn push_pop_mixed() {
+        let mut stack = Stack::new();
+        stack.push(7);
+        stack.push(-1i64);
+        stack.push(i64::MIN);
+        assert_eq!(i64::MIN, stack.pop::<i64>());
+        assert_eq!(-1, stack.pop::<i64>());
+        assert_eq!(7, stack.pop::<i32>());
+    }
+
+    #[test]
+    fn refs() {
+        let mut stack = Stack::new();
+        stack.push(Some(3usize));
+        stack.push(1);
+        stack.push(None::<usize>);
+        stack.push(7u128);
+        stack.push(5i64);
+        assert_eq!(vec![3, 5], stack.refs().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn unwind() {
+        let mut stack = Stack::new();
+        stack.push(1);
+        stack.push(2i64);
+        stack.push(3.0f32);
+        stack.push(4i64);
+        stack.push(5);
+        stack.unwind(1, 2);
+        assert_eq!(3, stack.len());
+        assert_eq!(5, stack.pop::<i32>());
+        assert_eq!(4, stack.pop::<i64>());
+        assert_eq!(1, stack.pop::<i32>());
+
+        stack.push(6);
+        stack.push(7);
+        stack.unwind(0, 0);
+        assert_eq!(0, stack.len());
+    }
+
+    #[test]
+    fn push_pop_refs() {
+        let mut stack = Stack::new();
+        stack.push(Some(3usize));
+        stack.push(None::<usize>);
+        assert_eq!(None, stack.pop::<Option<usize>>());
+        assert_eq!(Some(3), stack.pop::<Option<usize>>());
+    }
+}
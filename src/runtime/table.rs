@@ -0,0 +1,213 @@
+use crate::ast::{Limits, RefType, ValueType};
+use crate::runtime::error::RuntimeError;
+use crate::runtime::error::RuntimeError::{
+    RefTypeMismatch, TableOutOfBounds, TableTooLarge, UndefinedElement,
+};
+use crate::runtime::shared::Shared;
+use crate::runtime::value::Value;
+use std::ops::Range;
+
+/// Upper bound for the number of elements of a table.
+pub const MAX_ELEMENTS: u32 = 10_000_000;
+
+/// A table of references of type `ref_type`. Clones refer to the same table,
+/// so a table can be imported into instances while the host keeps a handle
+/// to it. Function references are indices into the functions of the
+/// instance that uses them.
+#[derive(Clone)]
+pub struct Table {
+    elements: Shared<Vec<Value>>,
+    ref_type: RefType,
+    max: Option<u32>,
+}
+
+impl Table {
+    /// Creates a table of `limits.min` null elements, which may not be more
+    /// than `MAX_ELEMENTS`.
+    pub fn new(limits: &Limits, ref_type: RefType) -> Result<Self, RuntimeError> {
+        if limits.min > MAX_ELEMENTS as u64 {
+            return Err(TableTooLarge);
+        }
+
+        let null = Value::zero(&ref_type.into());
+        Ok(Self {
+            elements: Shared::new(vec![null; limits.min as usize]),
+            ref_type,
+            // Validation bounds the limits of tables by `u32::MAX`.
+            max: limits.max.map(|max| max as u32),
+        })
+    }
+
+    pub fn ref_type(&self) -> RefType {
+        self.ref_type
+    }
+
+    /// Current limits, whose minimum is the current size.
+    pub fn limits(&self) -> Limits {
+        Limits {
+            min: self.size() as u64,
+            max: self.max.map(u64::from),
+        }
+    }
+
+    /// Current number of elements.
+    pub fn size(&self) -> u32 {
+        self.elements.lock().len() as u32
+    }
+
+    /// Grows the table by `delta` elements set to `init` and returns the
+    /// previous size, or `None` if the table can't grow that much or `init`
+    /// isn't a reference of its type.
+    pub fn grow(&mut self, delta: u32, init: Value) -> Option<u32> {
+        self.check(&init).ok()?;
+        let max = self.max.unwrap_or(MAX_ELEMENTS).min(MAX_ELEMENTS);
+        let mut elements = self.elements.access();
+        let size = elements.len() as u32;
+        let new_size = size.checked_add(delta)?;
+        if new_size > max {
+            return None;
+        }
+
+        elements.resize(new_size as usize, init);
+        Some(size)
+    }
+
+    /// Returns the element at `idx`.
+    pub fn get(&mut self, idx: u32) -> Result<Value, RuntimeError> {
+        self.elements
+            .access()
+            .get(idx as usize)
+            .cloned()
+            .ok_or(UndefinedElement)
+    }
+
+    /// Sets the element at `idx`.
+    pub fn set(&mut self, idx: u32, element: Value) -> Result<(), RuntimeError> {
+        self.check(&element)?;
+        let mut elements = self.elements.access();
+        let slot = elements.get_mut(idx as usize).ok_or(TableOutOfBounds)?;
+        *slot = element;
+        Ok(())
+    }
+
+    /// Returns `len` elements starting at `offset`.
+    pub fn read(&mut self, offset: u32, len: u32) -> Result<Vec<Value>, RuntimeError> {
+        let elements = self.elements.access();
+        let range = range(&elements, offset, len as usize)?;
+        Ok(elements[range].to_vec())
+    }
+
+    /// Writes `elements` into the table starting at `offset`.
+    pub fn write(&mut self, offset: u32, elements: &[Value]) -> Result<(), RuntimeError> {
+        for element in elements {
+            self.check(element)?;
+        }
+        let mut table = self.elements.access();
+        let range = range(&table, offset, elements.len())?;
+        table[range].clone_from_slice(elements);
+        Ok(())
+    }
+
+    /// Sets `len` elements starting at `offset` to `element`.
+    pub fn fill(&mut self, offset: u32, element: Value, len: u32) -> Result<(), RuntimeError> {
+        self.check(&element)?;
+        let mut elements = self.elements.access();
+        let range = range(&elements, offset, len as usize)?;
+        elements[range].fill(element);
+        Ok(())
+    }
+
+    /// Checks that `element` is a reference of the type of the table.
+    fn check(&self, element: &Value) -> Result<(), RuntimeError> {
+        match element.value_type() == ValueType::from(self.ref_type) {
+            true => Ok(()),
+            false => Err(RefTypeMismatch),
+        }
+    }
+}
+
+/// Checks that `len` elements starting at `offset` are inside `elements`.
+fn range(elements: &[Value], offset: u32, len: usize) -> Result<Range<usize>, RuntimeError> {
+    let start = offset as usize;
+    let end = start + len;
+    if end > elements.len() {
+        return Err(TableOutOfBounds);
+    }
+    Ok(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn func(idx: usize) -> Value {
+        Value::FuncRef(Some(idx))
+    }
+
+    const NULL: Value = Value::FuncRef(None);
+
+    #[test]
+    fn write_get() {
+        let limits = Limits { min: 3, max: None };
+        let mut table = Table::new(&limits, RefType::FuncRef).unwrap();
+        assert_eq!(Ok(NULL), table.get(0));
+
+        table.write(1, &[func(7), func(8)]).unwrap();
+        assert_eq!(Ok(func(7)), table.get(1));
+        assert_eq!(Ok(func(8)), table.get(2));
+        assert_eq!(Err(UndefinedElement), table.get(3));
+        assert_eq!(Err(UndefinedElement), table.get(u32::MAX));
+
+        assert_eq!(Ok(()), table.write(3, &[]));
+        assert_eq!(Err(TableOutOfBounds), table.write(2, &[func(1), func(2)]));
+        assert_eq!(Err(TableOutOfBounds), table.write(u32::MAX, &[func(1)]));
+
+        let limits = Limits {
+            min: MAX_ELEMENTS as u64 + 1,
+            max: None,
+        };
+        assert_eq!(
+            Err(TableTooLarge),
+            Table::new(&limits, RefType::FuncRef).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn grow_fill_write() {
+        let limits = Limits {
+            min: 1,
+            max: Some(4),
+        };
+        let mut table = Table::new(&limits, RefType::FuncRef).unwrap();
+        assert_eq!(Some(1), table.grow(2, func(5)));
+        assert_eq!(3, table.size());
+        assert_eq!(None, table.grow(2, NULL));
+        assert_eq!(None, table.grow(u32::MAX, NULL));
+        assert_eq!(Ok(vec![NULL, func(5), func(5)]), table.read(0, 3));
+
+        table.fill(0, func(1), 2).unwrap();
+        table.set(2, NULL).unwrap();
+        assert_eq!(Err(TableOutOfBounds), table.set(3, NULL));
+        assert_eq!(Ok(vec![func(1), func(1), NULL]), table.read(0, 3));
+        assert_eq!(Err(TableOutOfBounds), table.fill(2, NULL, 2));
+
+        table.write(1, &[func(9), func(8)]).unwrap();
+        assert_eq!(Ok(vec![func(1), func(9), func(8)]), table.read(0, 3));
+        assert_eq!(Err(TableOutOfBounds), table.write(3, &[NULL]));
+        assert_eq!(Err(TableOutOfBounds), table.read(u32::MAX, 2));
+    }
+
+    #[test]
+    fn clones_share_elements() {
+        let limits = Limits { min: 1, max: None };
+        let mut table = Table::new(&limits, RefType::ExternRef).unwrap();
+        let mut clone = table.clone();
+        assert_eq!(Some(1), clone.grow(1, Value::ExternRef(None)));
+        assert_eq!(2, table.size());
+
+        // Elements have to be references of the type of the table.
+        assert_eq!(Err(RefTypeMismatch), table.set(0, func(1)));
+        assert_eq!(Err(RefTypeMismatch), table.fill(0, Value::I32(1), 1));
+        assert_eq!(None, table.grow(1, NULL));
+    }
+}
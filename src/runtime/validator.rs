@@ -0,0 +1,2207 @@
+use crate::ast::{
+    AtomicRmwOp, BlockType, Catch, DataMode, EDesc, ElemMode, FuncType, GlobalType, HeapType,
+    ImportDesc, Instr, Limits, MemArg, Memory, Module, RefType, SimdOp, Table, ValueType,
+};
+use crate::runtime::error::RuntimeError;
+use crate::runtime::error::RuntimeError::{
+    ImmutableGlobal, InvalidAlignment, InvalidConstExpr, InvalidLaneIndex, InvalidLimits,
+    InvalidStartFunction, InvalidTagType, NestingTooDeep, OffsetTooLarge, RefTypeMismatch,
+    TailCallTypeMismatch, TypeMismatch, UnboundedSharedMemory, UndeclaredFuncRef,
+    UninitializedLocal, UnknownData, UnknownElem, UnknownFunc, UnknownGlobal, UnknownLabel,
+    UnknownLocal, UnknownMemory, UnknownTable, UnknownTag, UnknownType,
+};
+use crate::runtime::memory::{MAX_PAGES, MAX_PAGES64};
+use std::collections::HashSet;
+
+/// The deepest that blocks may be nested, counting the function body or
+/// constant expression. Decoding, validation and execution recurse for
+/// every level, so deeper nesting would overflow the stack.
+pub const MAX_NESTING_DEPTH: usize = 500;
+
+/// Sizes of the index spaces of a module, which include the imports.
+struct Context<'a> {
+    module: &'a Module,
+    /// Type indices of the functions.
+    funcs: Vec<usize>,
+    tables: Vec<Table>,
+    memories: Vec<Memory>,
+    globals: Vec<GlobalType>,
+    /// Functions that are referenced outside of function bodies, which
+    /// `ref.func` may refer to.
+    refs: HashSet<usize>,
+}
+
+/// Checks the rules of a module that decoding alone doesn't enforce.
+pub fn validate(module: &Module) -> Result<(), RuntimeError> {
+    let ctx = Context {
+        module,
+        funcs: module.func_types(),
+        tables: module.table_types(),
+        memories: module.memory_types(),
+        globals: module.global_types(),
+        refs: declared_refs(module),
+    };
+
+    for import in &module.imports {
+        if let ImportDesc::Func(type_idx) = import.desc {
+            if type_idx >= module.types.len() {
+                return Err(UnknownType);
+            }
+        }
+    }
+
+    if module
+        .funcs
+        .iter()
+        .any(|f| f.f_type as usize >= module.types.len())
+    {
+        return Err(UnknownType);
+    }
+
+    let value_types = module
+        .types
+        .iter()
+        .flat_map(|(params, results)| params.iter().chain(results))
+        .chain(module.funcs.iter().flat_map(|f| &f.locals))
+        .chain(ctx.globals.iter().map(|g| &g.value_type));
+    for value_type in value_types {
+        validate_value_type(&ctx, value_type)?;
+    }
+
+    for table in &ctx.tables {
+        validate_limits(&table.limits, u32::MAX as u64)?;
+    }
+
+    for memory in &ctx.memories {
+        let bound = match memory.memory64 {
+            true => MAX_PAGES64,
+            false => MAX_PAGES,
+        };
+        validate_limits(&memory.limits, bound)?;
+        if memory.shared && memory.limits.max.is_none() {
+            return Err(UnboundedSharedMemory);
+        }
+    }
+
+    // Exceptions only carry a payload, tags can't have results.
+    for type_idx in &module.tags {
+        match module.types.get(*type_idx) {
+            None => return Err(UnknownType),
+            Some((_, results)) if !results.is_empty() => return Err(InvalidTagType),
+            Some(_) => {}
+        }
+    }
+
+    let num_imported_globals = ctx.globals.len() - module.globals.len();
+    for (i, global) in module.globals.iter().enumerate() {
+        validate_const_expr(&ctx, &global.init, num_imported_globals + i)?;
+        let value_type = global.g_type.value_type;
+        validate_instrs(&ctx, &[], vec![], &[value_type], &global.init)?;
+    }
+
+    for export in &module.exports {
+        match export.e_desc {
+            EDesc::FuncExport(idx) if idx >= ctx.funcs.len() => return Err(UnknownFunc),
+            EDesc::TableExport(idx) if idx >= ctx.tables.len() => return Err(UnknownTable),
+            EDesc::MemoryExport(idx) if idx >= ctx.memories.len() => return Err(UnknownMemory),
+            EDesc::TagExport(idx) if idx >= module.tags.len() => return Err(UnknownTag),
+            EDesc::GlobalExport(idx) => {
+                global(&ctx, idx)?;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(start) = module.start {
+        if start >= ctx.funcs.len() {
+            return Err(UnknownFunc);
+        }
+        let f_type = &module.types[ctx.funcs[start]];
+        if !f_type.0.is_empty() || !f_type.1.is_empty() {
+            return Err(InvalidStartFunction);
+        }
+    }
+
+    for elem in &module.elems {
+        if let ElemMode::Active { table, offset } = &elem.mode {
+            if table_type(&ctx, *table)? != elem.ref_type {
+                return Err(RefTypeMismatch);
+            }
+            validate_const_expr(&ctx, offset, ctx.globals.len())?;
+            validate_instrs(&ctx, &[], vec![], &[ValueType::I32], offset)?;
+        }
+        for init in &elem.init {
+            validate_const_expr(&ctx, init, ctx.globals.len())?;
+            validate_instrs(&ctx, &[], vec![], &[elem.ref_type.into()], init)?;
+        }
+    }
+
+    for data in &module.datas {
+        if let DataMode::Active { memory, offset } = &data.mode {
+            let address = address_type(memory_type(&ctx, *memory)?);
+            validate_const_expr(&ctx, offset, ctx.globals.len())?;
+            validate_instrs(&ctx, &[], vec![], &[address], offset)?;
+        }
+    }
+
+    for func in &module.funcs {
+        let (params, results) = &module.types[func.f_type as usize];
+        // Parameters are always set, other locals only if they have a
+        // default value.
+        let inits = params
+            .iter()
+            .map(|_| true)
+            .chain(func.locals.iter().map(ValueType::is_defaultable))
+            .collect::<Vec<_>>();
+        let locals = [params.as_slice(), &func.locals].concat();
+        validate_instrs(&ctx, &locals, inits, results, &func.body)?;
+    }
+
+    Ok(())
+}
+
+fn validate_limits(limits: &Limits, bound: u64) -> Result<(), RuntimeError> {
+    let max = limits.max.unwrap_or(limits.min);
+    if limits.min > max || max > bound {
+        return Err(InvalidLimits);
+    }
+
+    Ok(())
+}
+
+/// Checks that the type referenced by `value_type` exists.
+fn validate_value_type(ctx: &Context, value_type: &ValueType) -> Result<(), RuntimeError> {
+    match value_type {
+        ValueType::Ref { type_idx, .. } if *type_idx >= ctx.module.types.len() => Err(UnknownType),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that the accessed memory exists and that the offset fits into its
+/// addresses, which have 32 bits unless the memory is a 64-bit one.
+fn validate_offset(ctx: &Context, memarg: &MemArg) -> Result<(), RuntimeError> {
+    let memory = memory_type(ctx, memarg.memory)?;
+    if !memory.memory64 && memarg.offset > u32::MAX as u64 {
+        return Err(OffsetTooLarge);
+    }
+
+    Ok(())
+}
+
+/// Returns the memory immediate of a load or store together with the
+/// exponent of its natural alignment.
+fn memarg(instr: &Instr) -> Option<(&MemArg, u32)> {
+    match instr {
+        Instr::I32Load8S(m)
+        | Instr::I32Load8U(m)
+        | Instr::V128Load8Splat(m)
+        | Instr::V128Load8Lane(m, _)
+        | Instr::V128Store8Lane(m, _)
+        | Instr::I64Load8S(m)
+        | Instr::I64Load8U(m)
+        | Instr::I32Store8(m)
+        | Instr::I64Store8(m) => Some((m, 0)),
+        Instr::I32Load16S(m)
+        | Instr::I32Load16U(m)
+        | Instr::I64Load16S(m)
+        | Instr::I64Load16U(m)
+        | Instr::I32Store16(m)
+        | Instr::I64Store16(m)
+        | Instr::V128Load16Splat(m)
+        | Instr::V128Load16Lane(m, _)
+        | Instr::V128Store16Lane(m, _) => Some((m, 1)),
+        Instr::I32Load(m)
+        | Instr::F32Load(m)
+        | Instr::I64Load32S(m)
+        | Instr::I64Load32U(m)
+        | Instr::I32Store(m)
+        | Instr::F32Store(m)
+        | Instr::I64Store32(m)
+        | Instr::V128Load32Splat(m)
+        | Instr::V128Load32Zero(m)
+        | Instr::V128Load32Lane(m, _)
+        | Instr::V128Store32Lane(m, _) => Some((m, 2)),
+        Instr::I64Load(m)
+        | Instr::F64Load(m)
+        | Instr::I64Store(m)
+        | Instr::F64Store(m)
+        | Instr::V128Load8x8S(m)
+        | Instr::V128Load8x8U(m)
+        | Instr::V128Load16x4S(m)
+        | Instr::V128Load16x4U(m)
+        | Instr::V128Load32x2S(m)
+        | Instr::V128Load32x2U(m)
+        | Instr::V128Load64Splat(m)
+        | Instr::V128Load64Zero(m)
+        | Instr::V128Load64Lane(m, _)
+        | Instr::V128Store64Lane(m, _) => Some((m, 3)),
+        Instr::V128Load(m) | Instr::V128Store(m) => Some((m, 4)),
+        _ => None,
+    }
+}
+
+/// Returns the memory argument of an atomic instruction together with its
+/// natural alignment, which is the only valid one.
+fn atomic_memarg(instr: &Instr) -> Option<(&MemArg, u32)> {
+    match instr {
+        Instr::MemoryAtomicNotify(m) | Instr::MemoryAtomicWait32(m) => Some((m, 2)),
+        Instr::MemoryAtomicWait64(m) => Some((m, 3)),
+        Instr::AtomicLoad(width, m)
+        | Instr::AtomicStore(width, m)
+        | Instr::AtomicRmw(_, width, m) => Some((m, width.bytes().trailing_zeros())),
+        _ => None,
+    }
+}
+
+/// Returns the lane immediate of a SIMD instruction together with the
+/// number of lanes it selects from.
+fn lane(instr: &Instr) -> Option<(u8, u8)> {
+    match instr {
+        Instr::I8x16ExtractLaneS(l)
+        | Instr::I8x16ExtractLaneU(l)
+        | Instr::I8x16ReplaceLane(l)
+        | Instr::V128Load8Lane(_, l)
+        | Instr::V128Store8Lane(_, l) => Some((*l, 16)),
+        Instr::I16x8ExtractLaneS(l)
+        | Instr::I16x8ExtractLaneU(l)
+        | Instr::I16x8ReplaceLane(l)
+        | Instr::V128Load16Lane(_, l)
+        | Instr::V128Store16Lane(_, l) => Some((*l, 8)),
+        Instr::I32x4ExtractLane(l)
+        | Instr::I32x4ReplaceLane(l)
+        | Instr::F32x4ExtractLane(l)
+        | Instr::F32x4ReplaceLane(l)
+        | Instr::V128Load32Lane(_, l)
+        | Instr::V128Store32Lane(_, l) => Some((*l, 4)),
+        Instr::I64x2ExtractLane(l)
+        | Instr::I64x2ReplaceLane(l)
+        | Instr::F64x2ExtractLane(l)
+        | Instr::F64x2ReplaceLane(l)
+        | Instr::V128Load64Lane(_, l)
+        | Instr::V128Store64Lane(_, l) => Some((*l, 2)),
+        _ => None,
+    }
+}
+
+fn global(ctx: &Context, idx: usize) -> Result<GlobalType, RuntimeError> {
+    ctx.globals.get(idx).copied().ok_or(UnknownGlobal)
+}
+
+fn table_type(ctx: &Context, idx: usize) -> Result<RefType, RuntimeError> {
+    ctx.tables.get(idx).map(|t| t.ref_type).ok_or(UnknownTable)
+}
+
+fn memory_type<'a>(ctx: &'a Context, idx: usize) -> Result<&'a Memory, RuntimeError> {
+    ctx.memories.get(idx).ok_or(UnknownMemory)
+}
+
+fn elem_type(ctx: &Context, idx: usize) -> Result<RefType, RuntimeError> {
+    ctx.module
+        .elems
+        .get(idx)
+        .map(|e| e.ref_type)
+        .ok_or(UnknownElem)
+}
+
+/// Collects the functions referenced by element segments, exports and
+/// global initializers.
+fn declared_refs(module: &Module) -> HashSet<usize> {
+    let elems = module.elems.iter().flat_map(|e| e.init.iter());
+    let globals = module.globals.iter().map(|g| &g.init);
+    let refs = elems
+        .chain(globals)
+        .flatten()
+        .filter_map(|instr| match instr {
+            Instr::RefFunc(idx) => Some(*idx),
+            _ => None,
+        });
+    let exports = module.exports.iter().filter_map(|e| match e.e_desc {
+        EDesc::FuncExport(idx) => Some(idx),
+        _ => None,
+    });
+    refs.chain(exports).collect()
+}
+
+/// A constant expression may only contain constants and read immutable
+/// globals defined before the first `num_globals`.
+fn validate_const_expr(
+    ctx: &Context,
+    init: &[Instr],
+    num_globals: usize,
+) -> Result<(), RuntimeError> {
+    for instr in init {
+        match instr {
+            Instr::I32Const(_)
+            | Instr::I64Const(_)
+            | Instr::F32Const(_)
+            | Instr::F64Const(_)
+            | Instr::V128Const(_) => {}
+            Instr::RefNull(HeapType::Type(idx)) if *idx >= ctx.module.types.len() => {
+                return Err(UnknownType)
+            }
+            Instr::RefNull(_) => {}
+            Instr::RefFunc(idx) if *idx >= ctx.funcs.len() => return Err(UnknownFunc),
+            Instr::RefFunc(_) => {}
+            Instr::GlobalGet(idx) if *idx < num_globals => {
+                if ctx.globals[*idx].mutable {
+                    return Err(InvalidConstExpr);
+                }
+            }
+            _ => return Err(InvalidConstExpr),
+        }
+    }
+
+    Ok(())
+}
+
+/// Type of an operand, which is unknown if unreachable code pushed it.
+type Operand = Option<ValueType>;
+
+/// A block whose instructions are being type checked.
+struct Frame {
+    /// Types of the operands that branches to the block pass, which are the
+    /// parameters of a loop and the results of other blocks.
+    labels: Vec<ValueType>,
+    results: Vec<ValueType>,
+    /// Height of the operand stack when the block was entered.
+    height: usize,
+    /// Set after instructions that never continue, like `br`. The rest of
+    /// the block can pop operands of any type.
+    unreachable: bool,
+    /// Which locals were set when the block was entered, locals set inside
+    /// of it are unset again at its end.
+    inits: Vec<bool>,
+}
+
+/// Validates instructions in a single pass. Each instruction has to refer
+/// to existing indices and find operands of the right types on the stack,
+/// and blocks have to leave their results.
+struct InstrValidator<'a> {
+    ctx: &'a Context<'a>,
+    locals: &'a [ValueType],
+    /// Which locals are set, parameters and locals with a default value
+    /// always are.
+    inits: Vec<bool>,
+    operands: Vec<Operand>,
+    frames: Vec<Frame>,
+}
+
+/// Validates a function body or a constant expression, which has to leave
+/// `results` on the stack. The function body is the outermost block that
+/// branches can target.
+fn validate_instrs(
+    ctx: &Context,
+    locals: &[ValueType],
+    inits: Vec<bool>,
+    results: &[ValueType],
+    instrs: &[Instr],
+) -> Result<(), RuntimeError> {
+    let mut validator = InstrValidator {
+        ctx,
+        locals,
+        inits,
+        operands: vec![],
+        frames: vec![],
+    };
+    validator.enter(results.to_vec(), &[], results.to_vec());
+    validator.check(instrs)?;
+    validator.end()
+}
+
+/// Whether a value of type `actual` can be used where `expected` is. A
+/// reference to a function type is a `funcref`, and a non-nullable one
+/// also a nullable one. Type indices match if they have the same type.
+fn is_subtype(ctx: &Context, actual: ValueType, expected: ValueType) -> bool {
+    match (actual, expected) {
+        _ if actual == expected => true,
+        (
+            ValueType::Ref { nullable, type_idx },
+            ValueType::Ref {
+                nullable: other,
+                type_idx: other_idx,
+            },
+        ) => (other || !nullable) && ctx.module.types[type_idx] == ctx.module.types[other_idx],
+        (ValueType::Ref { .. }, ValueType::FuncRef) => true,
+        _ => false,
+    }
+}
+
+fn is_ref(value_type: ValueType) -> bool {
+    matches!(
+        value_type,
+        ValueType::FuncRef | ValueType::ExternRef | ValueType::ExnRef | ValueType::Ref { .. }
+    )
+}
+
+fn non_null(value_type: ValueType) -> ValueType {
+    match value_type {
+        ValueType::Ref { type_idx, .. } => ValueType::Ref {
+            nullable: false,
+            type_idx,
+        },
+        _ => value_type,
+    }
+}
+
+/// Type of the addresses of a memory.
+fn address_type(memory: &Memory) -> ValueType {
+    match memory.memory64 {
+        true => ValueType::I64,
+        false => ValueType::I32,
+    }
+}
+
+impl<'a> InstrValidator<'a> {
+    fn push(&mut self, value_type: ValueType) {
+        self.operands.push(Some(value_type));
+    }
+
+    fn push_all(&mut self, types: &[ValueType]) {
+        self.operands.extend(types.iter().copied().map(Some));
+    }
+
+    fn frame(&mut self) -> &mut Frame {
+        self.frames
+            .last_mut()
+            .expect("Blocks are entered before checking")
+    }
+
+    /// Pops an operand of the current block, which is only unknown if the
+    /// rest of the block is unreachable.
+    fn pop(&mut self) -> Result<Operand, RuntimeError> {
+        let frame = self
+            .frames
+            .last()
+            .expect("Blocks are entered before checking");
+        match (self.operands.len() == frame.height, frame.unreachable) {
+            (true, true) => Ok(None),
+            (true, false) => Err(TypeMismatch),
+            (false, _) => Ok(self.operands.pop().flatten()),
+        }
+    }
+
+    fn pop_type(&mut self, expected: ValueType) -> Result<Operand, RuntimeError> {
+        match self.pop()? {
+            Some(actual) if !is_subtype(self.ctx, actual, expected) => Err(TypeMismatch),
+            operand => Ok(operand),
+        }
+    }
+
+    fn pop_ref(&mut self) -> Result<Operand, RuntimeError> {
+        match self.pop()? {
+            Some(actual) if !is_ref(actual) => Err(TypeMismatch),
+            operand => Ok(operand),
+        }
+    }
+
+    /// Pops operands of the given types and returns them in stack order.
+    fn pop_all(&mut self, types: &[ValueType]) -> Result<Vec<Operand>, RuntimeError> {
+        let mut operands = types
+            .iter()
+            .rev()
+            .map(|t| self.pop_type(*t))
+            .collect::<Result<Vec<_>, _>>()?;
+        operands.reverse();
+        Ok(operands)
+    }
+
+    fn set_unreachable(&mut self) {
+        let height = self.frame().height;
+        self.operands.truncate(height);
+        self.frame().unreachable = true;
+    }
+
+    fn label(&self, label: usize) -> Vec<ValueType> {
+        self.frames[self.frames.len() - 1 - label].labels.clone()
+    }
+
+    /// Checks that branches with operands of `types` can target the label.
+    fn check_label(&self, label: usize, types: &[ValueType]) -> Result<(), RuntimeError> {
+        let labels = self.label(label);
+        let matching = labels.len() == types.len()
+            && types
+                .iter()
+                .zip(&labels)
+                .all(|(t, l)| is_subtype(self.ctx, *t, *l));
+        match matching {
+            true => Ok(()),
+            false => Err(TypeMismatch),
+        }
+    }
+
+    fn block_type(&self, block_type: &BlockType) -> FuncType {
+        match block_type {
+            BlockType::Empty => (vec![], vec![]),
+            BlockType::Value(value_type) => (vec![], vec![*value_type]),
+            BlockType::TypeIdx(idx) => self.ctx.module.types[*idx].clone(),
+        }
+    }
+
+    fn func_type(&self, func: usize) -> &'a FuncType {
+        &self.ctx.module.types[self.ctx.funcs[func]]
+    }
+
+    fn tag_params(&self, tag: usize) -> Vec<ValueType> {
+        self.ctx.module.types[self.ctx.module.tags[tag]].0.clone()
+    }
+
+    /// Enters a block whose `params` were already popped.
+    fn enter(&mut self, labels: Vec<ValueType>, params: &[ValueType], results: Vec<ValueType>) {
+        self.frames.push(Frame {
+            labels,
+            results,
+            height: self.operands.len(),
+            unreachable: false,
+            inits: self.inits.clone(),
+        });
+        self.push_all(params);
+    }
+
+    /// Leaves the current block, which has to end with exactly its results
+    /// on the stack.
+    fn end(&mut self) -> Result<(), RuntimeError> {
+        let results = self.frame().results.clone();
+        self.pop_all(&results)?;
+        let frame = self
+            .frames
+            .pop()
+            .expect("Blocks are entered before checking");
+        self.inits = frame.inits;
+        match self.operands.len() == frame.height {
+            true => Ok(()),
+            false => Err(TypeMismatch),
+        }
+    }
+
+    /// Checks a block, which takes its parameters from the stack and pushes
+    /// its results.
+    fn check_block(
+        &mut self,
+        (params, results): FuncType,
+        is_loop: bool,
+        bodies: &[&[Instr]],
+    ) -> Result<(), RuntimeError> {
+        if self.frames.len() >= MAX_NESTING_DEPTH {
+            return Err(NestingTooDeep);
+        }
+        self.pop_all(&params)?;
+        let labels = match is_loop {
+            true => params.clone(),
+            false => results.clone(),
+        };
+        for body in bodies {
+            self.enter(labels.clone(), &params, results.clone());
+            self.check(body)?;
+            self.end()?;
+        }
+        self.push_all(&results);
+        Ok(())
+    }
+
+    fn check(&mut self, instrs: &[Instr]) -> Result<(), RuntimeError> {
+        for instr in instrs {
+            self.check_instr(instr)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks the immediates of an instruction: the indices it uses have to
+    /// exist and branches may only target enclosing blocks. Locals of a
+    /// non-nullable type may only be read after a `local.set` or `local.tee`
+    /// in the same or an enclosing block.
+    fn check_immediates(&mut self, instr: &Instr) -> Result<(), RuntimeError> {
+        match instr {
+            Instr::Block(BlockType::TypeIdx(idx), _)
+            | Instr::Loop(BlockType::TypeIdx(idx), _)
+            | Instr::If(BlockType::TypeIdx(idx), _, _)
+            | Instr::TryTable(BlockType::TypeIdx(idx), _, _)
+                if *idx >= self.ctx.module.types.len() =>
+            {
+                return Err(UnknownType)
+            }
+            Instr::Block(BlockType::Value(vt), _)
+            | Instr::Loop(BlockType::Value(vt), _)
+            | Instr::If(BlockType::Value(vt), _, _)
+            | Instr::TryTable(BlockType::Value(vt), _, _)
+            | Instr::Select(Some(vt))
+                if validate_value_type(self.ctx, vt).is_err() =>
+            {
+                return Err(UnknownType)
+            }
+            Instr::TryTable(_, catches, _) => {
+                for catch in catches {
+                    if let Catch::Tag(tag, _) | Catch::TagRef(tag, _) = catch {
+                        if *tag >= self.ctx.module.tags.len() {
+                            return Err(UnknownTag);
+                        }
+                    }
+                    // The labels of catch clauses are outside of the block.
+                    let (Catch::Tag(_, label)
+                    | Catch::TagRef(_, label)
+                    | Catch::All(label)
+                    | Catch::AllRef(label)) = catch;
+                    if *label >= self.frames.len() {
+                        return Err(UnknownLabel);
+                    }
+                }
+            }
+            Instr::Br(label)
+            | Instr::BrIf(label)
+            | Instr::BrOnNull(label)
+            | Instr::BrOnNonNull(label)
+                if *label >= self.frames.len() =>
+            {
+                return Err(UnknownLabel)
+            }
+            Instr::BrTable(targets, default)
+                if targets
+                    .iter()
+                    .chain([default])
+                    .any(|l| *l >= self.frames.len()) =>
+            {
+                return Err(UnknownLabel)
+            }
+            Instr::Throw(tag) if *tag >= self.ctx.module.tags.len() => return Err(UnknownTag),
+            Instr::Call(idx) | Instr::ReturnCall(idx) if *idx >= self.ctx.funcs.len() => {
+                return Err(UnknownFunc)
+            }
+            Instr::CallIndirect(type_idx, _)
+            | Instr::ReturnCallIndirect(type_idx, _)
+            | Instr::CallRef(type_idx)
+            | Instr::ReturnCallRef(type_idx)
+            | Instr::RefNull(HeapType::Type(type_idx))
+                if *type_idx >= self.ctx.module.types.len() =>
+            {
+                return Err(UnknownType)
+            }
+            Instr::CallIndirect(_, table) => {
+                if table_type(self.ctx, *table)? != RefType::FuncRef {
+                    return Err(RefTypeMismatch);
+                }
+            }
+            // The results of the callee are returned to the caller.
+            Instr::ReturnCall(idx) => {
+                if self.ctx.module.types[self.ctx.funcs[*idx]].1 != self.frames[0].results {
+                    return Err(TailCallTypeMismatch);
+                }
+            }
+            Instr::ReturnCallIndirect(type_idx, table) => {
+                if table_type(self.ctx, *table)? != RefType::FuncRef {
+                    return Err(RefTypeMismatch);
+                }
+                if self.ctx.module.types[*type_idx].1 != self.frames[0].results {
+                    return Err(TailCallTypeMismatch);
+                }
+            }
+            Instr::ReturnCallRef(type_idx) => {
+                if self.ctx.module.types[*type_idx].1 != self.frames[0].results {
+                    return Err(TailCallTypeMismatch);
+                }
+            }
+            Instr::RefFunc(idx) if *idx >= self.ctx.funcs.len() => return Err(UnknownFunc),
+            Instr::RefFunc(idx) if !self.ctx.refs.contains(idx) => return Err(UndeclaredFuncRef),
+            Instr::TableGet(table)
+            | Instr::TableSet(table)
+            | Instr::TableSize(table)
+            | Instr::TableGrow(table)
+            | Instr::TableFill(table) => {
+                table_type(self.ctx, *table)?;
+            }
+            Instr::TableCopy(dst, src) => {
+                if table_type(self.ctx, *dst)? != table_type(self.ctx, *src)? {
+                    return Err(RefTypeMismatch);
+                }
+            }
+            Instr::TableInit(table, elem) => {
+                if table_type(self.ctx, *table)? != elem_type(self.ctx, *elem)? {
+                    return Err(RefTypeMismatch);
+                }
+            }
+            Instr::ElemDrop(elem) => {
+                elem_type(self.ctx, *elem)?;
+            }
+            Instr::LocalGet(idx) | Instr::LocalSet(idx) | Instr::LocalTee(idx)
+                if *idx >= self.inits.len() =>
+            {
+                return Err(UnknownLocal)
+            }
+            Instr::LocalGet(idx) if !self.inits[*idx] => return Err(UninitializedLocal),
+            Instr::LocalSet(idx) | Instr::LocalTee(idx) => self.inits[*idx] = true,
+            Instr::GlobalGet(idx) => {
+                global(self.ctx, *idx)?;
+            }
+            Instr::GlobalSet(idx) if !global(self.ctx, *idx)?.mutable => {
+                return Err(ImmutableGlobal)
+            }
+            Instr::MemoryInit(_, data) | Instr::DataDrop(data)
+                if *data >= self.ctx.module.datas.len() =>
+            {
+                return Err(UnknownData)
+            }
+            Instr::MemorySize(memory)
+            | Instr::MemoryGrow(memory)
+            | Instr::MemoryInit(memory, _)
+            | Instr::MemoryFill(memory) => {
+                memory_type(self.ctx, *memory)?;
+            }
+            Instr::MemoryCopy(dst, src) => {
+                memory_type(self.ctx, *dst)?;
+                memory_type(self.ctx, *src)?;
+            }
+            // Shuffle indices select from the lanes of both operands.
+            Instr::I8x16Shuffle(indices) if indices.iter().any(|i| *i >= 32) => {
+                return Err(InvalidLaneIndex)
+            }
+            instr => {
+                if let Some((lane, count)) = lane(instr) {
+                    if lane >= count {
+                        return Err(InvalidLaneIndex);
+                    }
+                }
+                if let Some((memarg, natural)) = memarg(instr) {
+                    validate_offset(self.ctx, memarg)?;
+                    if memarg.align > natural {
+                        return Err(InvalidAlignment);
+                    }
+                }
+                if let Some((memarg, natural)) = atomic_memarg(instr) {
+                    validate_offset(self.ctx, memarg)?;
+                    if memarg.align != natural {
+                        return Err(InvalidAlignment);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_instr(&mut self, instr: &Instr) -> Result<(), RuntimeError> {
+        self.check_immediates(instr)?;
+        match instr {
+            Instr::Block(block_type, body) => {
+                self.check_block(self.block_type(block_type), false, &[body])?
+            }
+            Instr::Loop(block_type, body) => {
+                self.check_block(self.block_type(block_type), true, &[body])?
+            }
+            Instr::If(block_type, then, els) => {
+                self.pop_type(ValueType::I32)?;
+                self.check_block(self.block_type(block_type), false, &[then, els])?;
+            }
+            Instr::TryTable(block_type, catches, body) => {
+                for catch in catches {
+                    match catch {
+                        Catch::Tag(tag, label) => {
+                            self.check_label(*label, &self.tag_params(*tag))?
+                        }
+                        Catch::TagRef(tag, label) => {
+                            let types = [self.tag_params(*tag), vec![ValueType::ExnRef]].concat();
+                            self.check_label(*label, &types)?
+                        }
+                        Catch::All(label) => self.check_label(*label, &[])?,
+                        Catch::AllRef(label) => self.check_label(*label, &[ValueType::ExnRef])?,
+                    }
+                }
+                self.check_block(self.block_type(block_type), false, &[body])?;
+            }
+            instr => self.check_plain_instr(instr)?,
+        }
+
+        Ok(())
+    }
+
+    /// Checks an instruction without a body. It is kept out of
+    /// `check_instr` so that its large frame is not on the stack for every
+    /// level of nesting.
+    #[inline(never)]
+    fn check_plain_instr(&mut self, instr: &Instr) -> Result<(), RuntimeError> {
+        use ValueType::{I32, I64};
+
+        match instr {
+            Instr::Block(..) | Instr::Loop(..) | Instr::If(..) | Instr::TryTable(..) => {
+                self.check_instr(instr)?
+            }
+            Instr::Unreachable => self.set_unreachable(),
+            Instr::Br(label) => {
+                self.pop_all(&self.label(*label))?;
+                self.set_unreachable();
+            }
+            Instr::BrIf(label) => {
+                self.pop_type(I32)?;
+                let operands = self.pop_all(&self.label(*label))?;
+                self.operands.extend(operands);
+            }
+            Instr::BrTable(targets, default) => {
+                self.pop_type(I32)?;
+                let arity = self.label(*default).len();
+                for label in targets.iter().chain([default]) {
+                    let types = self.label(*label);
+                    if types.len() != arity {
+                        return Err(TypeMismatch);
+                    }
+                    let operands = self.pop_all(&types)?;
+                    self.operands.extend(operands);
+                }
+                self.set_unreachable();
+            }
+            Instr::Return => {
+                self.pop_all(&self.frames[0].results.clone())?;
+                self.set_unreachable();
+            }
+            Instr::Call(func) => {
+                let (params, results) = self.func_type(*func);
+                self.pop_all(params)?;
+                self.push_all(results);
+            }
+            Instr::CallIndirect(type_idx, _) => {
+                let (params, results) = &self.ctx.module.types[*type_idx];
+                self.pop_type(I32)?;
+                self.pop_all(params)?;
+                self.push_all(results);
+            }
+            Instr::CallRef(type_idx) => {
+                let (params, results) = &self.ctx.module.types[*type_idx];
+                self.pop_type(ValueType::Ref {
+                    nullable: true,
+                    type_idx: *type_idx,
+                })?;
+                self.pop_all(params)?;
+                self.push_all(results);
+            }
+            // The results were already checked to be the caller's.
+            Instr::ReturnCall(func) => {
+                self.pop_all(&self.func_type(*func).0)?;
+                self.set_unreachable();
+            }
+            Instr::ReturnCallIndirect(type_idx, _) => {
+                self.pop_type(I32)?;
+                self.pop_all(&self.ctx.module.types[*type_idx].0)?;
+                self.set_unreachable();
+            }
+            Instr::ReturnCallRef(type_idx) => {
+                self.pop_type(ValueType::Ref {
+                    nullable: true,
+                    type_idx: *type_idx,
+                })?;
+                self.pop_all(&self.ctx.module.types[*type_idx].0)?;
+                self.set_unreachable();
+            }
+            Instr::Throw(tag) => {
+                self.pop_all(&self.tag_params(*tag))?;
+                self.set_unreachable();
+            }
+            Instr::ThrowRef => {
+                self.pop_type(ValueType::ExnRef)?;
+                self.set_unreachable();
+            }
+            Instr::Drop => {
+                self.pop()?;
+            }
+            // Without a type, only numeric and vector operands can be
+            // selected.
+            Instr::Select(None) => {
+                self.pop_type(I32)?;
+                let operands = [self.pop()?, self.pop()?];
+                match operands {
+                    [Some(a), _] | [_, Some(a)] if is_ref(a) => return Err(TypeMismatch),
+                    [Some(a), Some(b)] if a != b => return Err(TypeMismatch),
+                    [a, b] => self.operands.push(a.or(b)),
+                }
+            }
+            Instr::Select(Some(value_type)) => {
+                self.pop_type(I32)?;
+                self.pop_type(*value_type)?;
+                self.pop_type(*value_type)?;
+                self.push(*value_type);
+            }
+            Instr::RefNull(heap_type) => self.push((*heap_type).into()),
+            Instr::RefIsNull => {
+                self.pop_ref()?;
+                self.push(I32);
+            }
+            Instr::RefFunc(func) => self.push(ValueType::Ref {
+                nullable: false,
+                type_idx: self.ctx.funcs[*func],
+            }),
+            Instr::RefAsNonNull => {
+                let operand = self.pop_ref()?;
+                self.operands.push(operand.map(non_null));
+            }
+            Instr::BrOnNull(label) => {
+                let operand = self.pop_ref()?;
+                let operands = self.pop_all(&self.label(*label))?;
+                self.operands.extend(operands);
+                self.operands.push(operand.map(non_null));
+            }
+            // The label takes the reference as its last operand.
+            Instr::BrOnNonNull(label) => {
+                let operand = self.pop_ref()?;
+                let mut types = self.label(*label);
+                match (types.pop(), operand) {
+                    (None, _) => return Err(TypeMismatch),
+                    (Some(t), Some(operand)) if !is_subtype(self.ctx, non_null(operand), t) => {
+                        return Err(TypeMismatch)
+                    }
+                    _ => {}
+                }
+                let operands = self.pop_all(&types)?;
+                self.operands.extend(operands);
+            }
+            Instr::LocalGet(idx) => self.push(self.locals[*idx]),
+            Instr::LocalSet(idx) => {
+                self.pop_type(self.locals[*idx])?;
+            }
+            Instr::LocalTee(idx) => {
+                self.pop_type(self.locals[*idx])?;
+                self.push(self.locals[*idx]);
+            }
+            Instr::GlobalGet(idx) => self.push(self.ctx.globals[*idx].value_type),
+            Instr::GlobalSet(idx) => {
+                self.pop_type(self.ctx.globals[*idx].value_type)?;
+            }
+            Instr::TableGet(table) => {
+                self.pop_type(I32)?;
+                self.push(self.ctx.tables[*table].ref_type.into());
+            }
+            Instr::TableSet(table) => {
+                self.pop_type(self.ctx.tables[*table].ref_type.into())?;
+                self.pop_type(I32)?;
+            }
+            Instr::TableSize(_) => self.push(I32),
+            Instr::TableGrow(table) => {
+                self.pop_type(I32)?;
+                self.pop_type(self.ctx.tables[*table].ref_type.into())?;
+                self.push(I32);
+            }
+            Instr::TableFill(table) => {
+                self.pop_type(I32)?;
+                self.pop_type(self.ctx.tables[*table].ref_type.into())?;
+                self.pop_type(I32)?;
+            }
+            Instr::TableCopy(_, _) | Instr::TableInit(_, _) => {
+                self.pop_all(&[I32, I32, I32])?;
+            }
+            Instr::MemorySize(memory) => self.push(address_type(&self.ctx.memories[*memory])),
+            Instr::MemoryGrow(memory) => {
+                let address = address_type(&self.ctx.memories[*memory]);
+                self.pop_type(address)?;
+                self.push(address);
+            }
+            Instr::MemoryInit(memory, _) => {
+                let address = address_type(&self.ctx.memories[*memory]);
+                self.pop_all(&[address, I32, I32])?;
+            }
+            // The length is only 64-bit if both memories are.
+            Instr::MemoryCopy(dst, src) => {
+                let dst = address_type(&self.ctx.memories[*dst]);
+                let src = address_type(&self.ctx.memories[*src]);
+                let len = match (dst, src) {
+                    (I64, I64) => I64,
+                    _ => I32,
+                };
+                self.pop_all(&[dst, src, len])?;
+            }
+            Instr::MemoryFill(memory) => {
+                let address = address_type(&self.ctx.memories[*memory]);
+                self.pop_all(&[address, I32, address])?;
+            }
+            instr => match memarg(instr).or_else(|| atomic_memarg(instr)) {
+                // Memory accesses take an address before their other
+                // operands.
+                Some((memarg, _)) => {
+                    let (params, results) = access_type(instr);
+                    self.pop_all(&params)?;
+                    self.pop_type(address_type(&self.ctx.memories[memarg.memory]))?;
+                    self.push_all(&results);
+                }
+                None => {
+                    let (params, results) = signature(instr);
+                    self.pop_all(params)?;
+                    self.push_all(results);
+                }
+            },
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the operands of a memory access that follow the address, and
+/// its results.
+fn access_type(instr: &Instr) -> FuncType {
+    use ValueType::{F32, F64, I32, I64, V128};
+
+    match instr {
+        Instr::I32Load(_)
+        | Instr::I32Load8S(_)
+        | Instr::I32Load8U(_)
+        | Instr::I32Load16S(_)
+        | Instr::I32Load16U(_) => (vec![], vec![I32]),
+        Instr::I64Load(_)
+        | Instr::I64Load8S(_)
+        | Instr::I64Load8U(_)
+        | Instr::I64Load16S(_)
+        | Instr::I64Load16U(_)
+        | Instr::I64Load32S(_)
+        | Instr::I64Load32U(_) => (vec![], vec![I64]),
+        Instr::F32Load(_) => (vec![], vec![F32]),
+        Instr::F64Load(_) => (vec![], vec![F64]),
+        Instr::I32Store(_) | Instr::I32Store8(_) | Instr::I32Store16(_) => (vec![I32], vec![]),
+        Instr::I64Store(_) | Instr::I64Store8(_) | Instr::I64Store16(_) | Instr::I64Store32(_) => {
+            (vec![I64], vec![])
+        }
+        Instr::F32Store(_) => (vec![F32], vec![]),
+        Instr::F64Store(_) => (vec![F64], vec![]),
+        Instr::V128Load8Lane(_, _)
+        | Instr::V128Load16Lane(_, _)
+        | Instr::V128Load32Lane(_, _)
+        | Instr::V128Load64Lane(_, _) => (vec![V128], vec![V128]),
+        Instr::V128Store(_)
+        | Instr::V128Store8Lane(_, _)
+        | Instr::V128Store16Lane(_, _)
+        | Instr::V128Store32Lane(_, _)
+        | Instr::V128Store64Lane(_, _) => (vec![V128], vec![]),
+        Instr::MemoryAtomicNotify(_) => (vec![I32], vec![I32]),
+        Instr::MemoryAtomicWait32(_) => (vec![I32, I64], vec![I32]),
+        Instr::MemoryAtomicWait64(_) => (vec![I64, I64], vec![I32]),
+        Instr::AtomicLoad(width, _) => (vec![], vec![width.value_type()]),
+        Instr::AtomicStore(width, _) => (vec![width.value_type()], vec![]),
+        Instr::AtomicRmw(AtomicRmwOp::Cmpxchg, width, _) => {
+            let value_type = width.value_type();
+            (vec![value_type, value_type], vec![value_type])
+        }
+        Instr::AtomicRmw(_, width, _) => (vec![width.value_type()], vec![width.value_type()]),
+        // The other vector loads.
+        _ => (vec![], vec![V128]),
+    }
+}
+
+/// Returns the operand and result types of the instructions that don't
+/// depend on the module, e.g. numeric instructions.
+fn signature(instr: &Instr) -> (&'static [ValueType], &'static [ValueType]) {
+    use ValueType::{F32, F64, I32, I64, V128};
+
+    match instr {
+        Instr::I32Const(_) => (&[], &[I32]),
+        Instr::I64Const(_) => (&[], &[I64]),
+        Instr::F32Const(_) => (&[], &[F32]),
+        Instr::F64Const(_) => (&[], &[F64]),
+        Instr::V128Const(_) => (&[], &[V128]),
+        Instr::I32Eqz
+        | Instr::I32Clz
+        | Instr::I32Ctz
+        | Instr::I32Popcnt
+        | Instr::I32Extend8S
+        | Instr::I32Extend16S => (&[I32], &[I32]),
+        Instr::I32Eq
+        | Instr::I32Ne
+        | Instr::I32LtS
+        | Instr::I32LtU
+        | Instr::I32GtS
+        | Instr::I32GtU
+        | Instr::I32LeS
+        | Instr::I32LeU
+        | Instr::I32GeS
+        | Instr::I32GeU
+        | Instr::I32Add
+        | Instr::I32Sub
+        | Instr::I32Mul
+        | Instr::I32DivS
+        | Instr::I32DivU
+        | Instr::I32RemS
+        | Instr::I32RemU
+        | Instr::I32And
+        | Instr::I32Or
+        | Instr::I32Xor
+        | Instr::I32Shl
+        | Instr::I32ShrS
+        | Instr::I32ShrU
+        | Instr::I32Rotl
+        | Instr::I32Rotr => (&[I32, I32], &[I32]),
+        Instr::I64Eqz | Instr::I32WrapI64 => (&[I64], &[I32]),
+        Instr::I64Eq
+        | Instr::I64Ne
+        | Instr::I64LtS
+        | Instr::I64LtU
+        | Instr::I64GtS
+        | Instr::I64GtU
+        | Instr::I64LeS
+        | Instr::I64LeU
+        | Instr::I64GeS
+        | Instr::I64GeU => (&[I64, I64], &[I32]),
+        Instr::I64Clz
+        | Instr::I64Ctz
+        | Instr::I64Popcnt
+        | Instr::I64Extend8S
+        | Instr::I64Extend16S
+        | Instr::I64Extend32S => (&[I64], &[I64]),
+        Instr::I64Add
+        | Instr::I64Sub
+        | Instr::I64Mul
+        | Instr::I64DivS
+        | Instr::I64DivU
+        | Instr::I64RemS
+        | Instr::I64RemU
+        | Instr::I64And
+        | Instr::I64Or
+        | Instr::I64Xor
+        | Instr::I64Shl
+        | Instr::I64ShrS
+        | Instr::I64ShrU
+        | Instr::I64Rotl
+        | Instr::I64Rotr => (&[I64, I64], &[I64]),
+        Instr::F32Eq | Instr::F32Ne | Instr::F32Lt | Instr::F32Gt | Instr::F32Le | Instr::F32Ge => {
+            (&[F32, F32], &[I32])
+        }
+        Instr::F64Eq | Instr::F64Ne | Instr::F64Lt | Instr::F64Gt | Instr::F64Le | Instr::F64Ge => {
+            (&[F64, F64], &[I32])
+        }
+        Instr::F32Abs
+        | Instr::F32Neg
+        | Instr::F32Ceil
+        | Instr::F32Floor
+        | Instr::F32Trunc
+        | Instr::F32Nearest
+        | Instr::F32Sqrt => (&[F32], &[F32]),
+        Instr::F32Add
+        | Instr::F32Sub
+        | Instr::F32Mul
+        | Instr::F32Div
+        | Instr::F32Min
+        | Instr::F32Max
+        | Instr::F32Copysign => (&[F32, F32], &[F32]),
+        Instr::F64Abs
+        | Instr::F64Neg
+        | Instr::F64Ceil
+        | Instr::F64Floor
+        | Instr::F64Trunc
+        | Instr::F64Nearest
+        | Instr::F64Sqrt => (&[F64], &[F64]),
+        Instr::F64Add
+        | Instr::F64Sub
+        | Instr::F64Mul
+        | Instr::F64Div
+        | Instr::F64Min
+        | Instr::F64Max
+        | Instr::F64Copysign => (&[F64, F64], &[F64]),
+        Instr::I32TruncF32S
+        | Instr::I32TruncF32U
+        | Instr::I32TruncSatF32S
+        | Instr::I32TruncSatF32U
+        | Instr::I32ReinterpretF32 => (&[F32], &[I32]),
+        Instr::I32TruncF64S
+        | Instr::I32TruncF64U
+        | Instr::I32TruncSatF64S
+        | Instr::I32TruncSatF64U => (&[F64], &[I32]),
+        Instr::I64ExtendI32S | Instr::I64ExtendI32U => (&[I32], &[I64]),
+        Instr::I64TruncF32S
+        | Instr::I64TruncF32U
+        | Instr::I64TruncSatF32S
+        | Instr::I64TruncSatF32U => (&[F32], &[I64]),
+        Instr::I64TruncF64S
+        | Instr::I64TruncF64U
+        | Instr::I64TruncSatF64S
+        | Instr::I64TruncSatF64U
+        | Instr::I64ReinterpretF64 => (&[F64], &[I64]),
+        Instr::F32ConvertI32S | Instr::F32ConvertI32U | Instr::F32ReinterpretI32 => {
+            (&[I32], &[F32])
+        }
+        Instr::F32ConvertI64S | Instr::F32ConvertI64U => (&[I64], &[F32]),
+        Instr::F32DemoteF64 => (&[F64], &[F32]),
+        Instr::F64ConvertI32S | Instr::F64ConvertI32U => (&[I32], &[F64]),
+        Instr::F64ConvertI64S | Instr::F64ConvertI64U | Instr::F64ReinterpretI64 => {
+            (&[I64], &[F64])
+        }
+        Instr::F64PromoteF32 => (&[F32], &[F64]),
+        Instr::I8x16ExtractLaneS(_)
+        | Instr::I8x16ExtractLaneU(_)
+        | Instr::I16x8ExtractLaneS(_)
+        | Instr::I16x8ExtractLaneU(_)
+        | Instr::I32x4ExtractLane(_) => (&[V128], &[I32]),
+        Instr::I64x2ExtractLane(_) => (&[V128], &[I64]),
+        Instr::F32x4ExtractLane(_) => (&[V128], &[F32]),
+        Instr::F64x2ExtractLane(_) => (&[V128], &[F64]),
+        Instr::I8x16ReplaceLane(_) | Instr::I16x8ReplaceLane(_) | Instr::I32x4ReplaceLane(_) => {
+            (&[V128, I32], &[V128])
+        }
+        Instr::I64x2ReplaceLane(_) => (&[V128, I64], &[V128]),
+        Instr::F32x4ReplaceLane(_) => (&[V128, F32], &[V128]),
+        Instr::F64x2ReplaceLane(_) => (&[V128, F64], &[V128]),
+        Instr::I8x16Shuffle(_) => (&[V128, V128], &[V128]),
+        Instr::Simd(op) => simd_signature(op),
+        // Instructions without operands, like `nop`.
+        _ => (&[], &[]),
+    }
+}
+
+fn simd_signature(op: &SimdOp) -> (&'static [ValueType], &'static [ValueType]) {
+    use ValueType::{F32, F64, I32, I64, V128};
+
+    match op {
+        SimdOp::I8x16Splat | SimdOp::I16x8Splat | SimdOp::I32x4Splat => (&[I32], &[V128]),
+        SimdOp::I64x2Splat => (&[I64], &[V128]),
+        SimdOp::F32x4Splat => (&[F32], &[V128]),
+        SimdOp::F64x2Splat => (&[F64], &[V128]),
+        SimdOp::V128AnyTrue
+        | SimdOp::I8x16AllTrue
+        | SimdOp::I8x16Bitmask
+        | SimdOp::I16x8AllTrue
+        | SimdOp::I16x8Bitmask
+        | SimdOp::I32x4AllTrue
+        | SimdOp::I32x4Bitmask
+        | SimdOp::I64x2AllTrue
+        | SimdOp::I64x2Bitmask => (&[V128], &[I32]),
+        SimdOp::I8x16Shl
+        | SimdOp::I8x16ShrS
+        | SimdOp::I8x16ShrU
+        | SimdOp::I16x8Shl
+        | SimdOp::I16x8ShrS
+        | SimdOp::I16x8ShrU
+        | SimdOp::I32x4Shl
+        | SimdOp::I32x4ShrS
+        | SimdOp::I32x4ShrU
+        | SimdOp::I64x2Shl
+        | SimdOp::I64x2ShrS
+        | SimdOp::I64x2ShrU => (&[V128, I32], &[V128]),
+        SimdOp::V128Bitselect => (&[V128, V128, V128], &[V128]),
+        SimdOp::V128Not
+        | SimdOp::F32x4DemoteF64x2Zero
+        | SimdOp::F64x2PromoteLowF32x4
+        | SimdOp::I8x16Abs
+        | SimdOp::I8x16Neg
+        | SimdOp::I8x16Popcnt
+        | SimdOp::F32x4Ceil
+        | SimdOp::F32x4Floor
+        | SimdOp::F32x4Trunc
+        | SimdOp::F32x4Nearest
+        | SimdOp::F64x2Ceil
+        | SimdOp::F64x2Floor
+        | SimdOp::F64x2Trunc
+        | SimdOp::F64x2Nearest
+        | SimdOp::I16x8ExtaddPairwiseI8x16S
+        | SimdOp::I16x8ExtaddPairwiseI8x16U
+        | SimdOp::I32x4ExtaddPairwiseI16x8S
+        | SimdOp::I32x4ExtaddPairwiseI16x8U
+        | SimdOp::I16x8Abs
+        | SimdOp::I16x8Neg
+        | SimdOp::I16x8ExtendLowI8x16S
+        | SimdOp::I16x8ExtendHighI8x16S
+        | SimdOp::I16x8ExtendLowI8x16U
+        | SimdOp::I16x8ExtendHighI8x16U
+        | SimdOp::I32x4Abs
+        | SimdOp::I32x4Neg
+        | SimdOp::I32x4ExtendLowI16x8S
+        | SimdOp::I32x4ExtendHighI16x8S
+        | SimdOp::I32x4ExtendLowI16x8U
+        | SimdOp::I32x4ExtendHighI16x8U
+        | SimdOp::I64x2Abs
+        | SimdOp::I64x2Neg
+        | SimdOp::I64x2ExtendLowI32x4S
+        | SimdOp::I64x2ExtendHighI32x4S
+        | SimdOp::I64x2ExtendLowI32x4U
+        | SimdOp::I64x2ExtendHighI32x4U
+        | SimdOp::F32x4Abs
+        | SimdOp::F32x4Neg
+        | SimdOp::F32x4Sqrt
+        | SimdOp::F64x2Abs
+        | SimdOp::F64x2Neg
+        | SimdOp::F64x2Sqrt
+        | SimdOp::I32x4TruncSatF32x4S
+        | SimdOp::I32x4TruncSatF32x4U
+        | SimdOp::F32x4ConvertI32x4S
+        | SimdOp::F32x4ConvertI32x4U
+        | SimdOp::I32x4TruncSatF64x2SZero
+        | SimdOp::I32x4TruncSatF64x2UZero
+        | SimdOp::F64x2ConvertLowI32x4S
+        | SimdOp::F64x2ConvertLowI32x4U => (&[V128], &[V128]),
+        // Comparisons and other lane-wise binary operations.
+        _ => (&[V128, V128], &[V128]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::*;
+
+    fn module(globals: Vec<(bool, Vec<Instr>)>, body: Vec<Instr>) -> Module {
+        Module {
+            types: vec![(vec![], vec![])],
+            imports: vec![],
+            funcs: vec![Func {
+                f_type: 0,
+                locals: vec![],
+                body,
+            }],
+            tables: vec![],
+            memories: vec![],
+            tags: vec![],
+            globals: globals
+                .into_iter()
+                .map(|(mutable, init)| Global {
+                    g_type: GlobalType {
+                        value_type: ValueType::I32,
+                        mutable,
+                    },
+                    init,
+                })
+                .collect(),
+            elems: vec![],
+            datas: vec![],
+            exports: vec![],
+            start: None,
+        }
+    }
+
+    #[test]
+    fn validate_global_set() {
+        let globals = vec![
+            (false, vec![Instr::I32Const(1)]),
+            (true, vec![Instr::I32Const(2)]),
+        ];
+        let body = vec![Instr::I32Const(0), Instr::GlobalSet(1)];
+        assert_eq!(Ok(()), validate(&module(globals.clone(), body)));
+
+        let set = Instr::GlobalSet(0);
+        let body = vec![Instr::Block(
+            BlockType::Empty,
+            vec![Instr::I32Const(0), set],
+        )];
+        assert_eq!(
+            Err(ImmutableGlobal),
+            validate(&module(globals.clone(), body))
+        );
+
+        let body = vec![Instr::GlobalGet(2)];
+        assert_eq!(Err(UnknownGlobal), validate(&module(globals, body)));
+    }
+
+    #[test]
+    fn validate_labels() {
+        let block = |body| Instr::Block(BlockType::Empty, body);
+        let valid = [
+            vec![Instr::Br(0)],
+            vec![block(vec![Instr::Br(1)])],
+            vec![Instr::I32Const(0), Instr::BrTable(vec![0], 0)],
+            vec![Instr::TryTable(
+                BlockType::Empty,
+                vec![Catch::All(0)],
+                vec![],
+            )],
+        ];
+        for body in valid {
+            assert_eq!(Ok(()), validate(&module(vec![], body)));
+        }
+
+        let invalid = [
+            vec![Instr::Br(1)],
+            vec![block(vec![Instr::I32Const(0), Instr::BrIf(2)])],
+            vec![Instr::I32Const(0), Instr::BrTable(vec![0, 1], 0)],
+            vec![Instr::I32Const(0), Instr::BrTable(vec![], 3)],
+            vec![Instr::RefNull(HeapType::Func), Instr::BrOnNull(1)],
+            vec![Instr::TryTable(
+                BlockType::Empty,
+                vec![Catch::All(1)],
+                vec![],
+            )],
+        ];
+        for body in invalid {
+            assert_eq!(Err(UnknownLabel), validate(&module(vec![], body)));
+        }
+    }
+
+    #[test]
+    fn validate_operand_types() {
+        let block = |value_type, body| Instr::Block(BlockType::Value(value_type), body);
+        let valid = [
+            vec![
+                Instr::I32Const(1),
+                Instr::I32Const(2),
+                Instr::I32Add,
+                Instr::Drop,
+            ],
+            vec![block(ValueType::I64, vec![Instr::I64Const(1)]), Instr::Drop],
+            vec![
+                block(ValueType::F32, vec![Instr::F32Const(1.0), Instr::Br(0)]),
+                Instr::Drop,
+            ],
+            // Operands of any type can be popped after unreachable code.
+            vec![Instr::Unreachable, Instr::I64Add, Instr::Drop],
+            vec![block(ValueType::I32, vec![Instr::Unreachable]), Instr::Drop],
+            vec![
+                Instr::I32Const(1),
+                Instr::I32Const(2),
+                Instr::I32Const(0),
+                Instr::Select(None),
+                Instr::Drop,
+            ],
+        ];
+        for body in valid {
+            assert_eq!(Ok(()), validate(&module(vec![], body)));
+        }
+
+        let invalid = [
+            vec![Instr::I32Add],
+            vec![Instr::I32Const(1), Instr::I32Const(2), Instr::I64Add],
+            vec![Instr::I32Const(1)],
+            vec![block(ValueType::I32, vec![])],
+            vec![block(ValueType::I32, vec![Instr::I64Const(1)]), Instr::Drop],
+            vec![block(ValueType::I32, vec![Instr::Br(0)]), Instr::Drop],
+            // Blocks can't pop the operands of the enclosing block.
+            vec![
+                Instr::I32Const(1),
+                Instr::Block(BlockType::Empty, vec![Instr::Drop]),
+            ],
+            // Without an else, an if has to produce its results from its
+            // parameters.
+            vec![
+                Instr::I32Const(0),
+                Instr::If(
+                    BlockType::Value(ValueType::I32),
+                    vec![Instr::I32Const(1)],
+                    vec![],
+                ),
+                Instr::Drop,
+            ],
+            vec![
+                Instr::I32Const(1),
+                Instr::I64Const(2),
+                Instr::I32Const(0),
+                Instr::Select(None),
+                Instr::Drop,
+            ],
+            vec![
+                Instr::RefNull(HeapType::Func),
+                Instr::RefNull(HeapType::Func),
+                Instr::I32Const(0),
+                Instr::Select(None),
+                Instr::Drop,
+            ],
+            // All targets of br_table need the same number of operands.
+            vec![block(
+                ValueType::I32,
+                vec![
+                    Instr::I32Const(1),
+                    Instr::I32Const(0),
+                    Instr::BrTable(vec![0], 1),
+                ],
+            )],
+        ];
+        for body in invalid {
+            assert_eq!(Err(TypeMismatch), validate(&module(vec![], body)));
+        }
+    }
+
+    #[test]
+    fn validate_unreachable_indices() {
+        let memarg = MemArg {
+            align: 2,
+            offset: 0,
+            memory: 0,
+        };
+        let invalid = [
+            (Err(UnknownFunc), Instr::Call(1)),
+            (Err(UnknownGlobal), Instr::GlobalGet(0)),
+            (Err(UnknownLocal), Instr::LocalGet(0)),
+            (Err(UnknownMemory), Instr::I32Load(memarg)),
+            (Err(UnknownTable), Instr::TableGet(0)),
+            (Err(UnknownLabel), Instr::Br(1)),
+        ];
+        for (result, instr) in invalid {
+            let body = vec![Instr::Unreachable, instr];
+            assert_eq!(result, validate(&module(vec![], body)));
+        }
+    }
+
+    #[test]
+    fn validate_locals() {
+        let mut module = module(vec![], vec![Instr::LocalGet(0), Instr::Drop]);
+        module.types[0].0 = vec![ValueType::I32];
+        module.funcs[0].locals = vec![ValueType::I64];
+        assert_eq!(Ok(()), validate(&module));
+
+        for instr in [Instr::LocalGet(2), Instr::LocalSet(2), Instr::LocalTee(5)] {
+            module.funcs[0].body = vec![Instr::I32Const(0), instr];
+            assert_eq!(Err(UnknownLocal), validate(&module));
+        }
+    }
+
+    #[test]
+    fn validate_const_exprs() {
+        let globals = vec![
+            (false, vec![Instr::I32Const(1)]),
+            (false, vec![Instr::GlobalGet(0)]),
+        ];
+        assert_eq!(Ok(()), validate(&module(globals, vec![])));
+
+        // Initializers have to produce a value of the global's type.
+        for init in [
+            vec![],
+            vec![Instr::F64Const(1.0)],
+            vec![Instr::I32Const(1); 2],
+        ] {
+            let globals = vec![(false, init)];
+            assert_eq!(Err(TypeMismatch), validate(&module(globals, vec![])));
+        }
+
+        // A reference in a global initializer declares the function.
+        let globals = vec![(false, vec![Instr::RefFunc(0)])];
+        let body = vec![Instr::RefFunc(0), Instr::Drop];
+        let mut module_with_ref = module(globals, body);
+        module_with_ref.globals[0].g_type.value_type = ValueType::FuncRef;
+        assert_eq!(Ok(()), validate(&module_with_ref));
+        let globals = vec![(false, vec![Instr::RefFunc(1)])];
+        assert_eq!(Err(UnknownFunc), validate(&module(globals, vec![])));
+
+        let globals = vec![(false, vec![Instr::GlobalGet(0)])];
+        assert_eq!(Err(InvalidConstExpr), validate(&module(globals, vec![])));
+
+        let globals = vec![
+            (true, vec![Instr::I32Const(1)]),
+            (false, vec![Instr::GlobalGet(0)]),
+        ];
+        assert_eq!(Err(InvalidConstExpr), validate(&module(globals, vec![])));
+
+        let globals = vec![(
+            false,
+            vec![Instr::I32Const(1), Instr::I32Const(2), Instr::I32Add],
+        )];
+        assert_eq!(Err(InvalidConstExpr), validate(&module(globals, vec![])));
+    }
+
+    #[test]
+    fn validate_func_refs() {
+        let func_ref = |nullable, type_idx| ValueType::Ref { nullable, type_idx };
+        let mut module = module(vec![], vec![]);
+        module
+            .types
+            .push((vec![ValueType::I32], vec![ValueType::I32]));
+
+        // A non-nullable local has to be set in the same or an enclosing
+        // block before it is read.
+        module.funcs[0].locals = vec![func_ref(false, 1)];
+        let set = vec![
+            Instr::RefNull(HeapType::Type(1)),
+            Instr::RefAsNonNull,
+            Instr::LocalSet(0),
+        ];
+        let get = vec![Instr::LocalGet(0), Instr::Drop];
+        let bodies = [
+            (Ok(()), [set.clone(), get.clone()].concat()),
+            (Err(UninitializedLocal), get.clone()),
+            (
+                Ok(()),
+                [
+                    set.clone(),
+                    vec![Instr::Block(BlockType::Empty, get.clone())],
+                ]
+                .concat(),
+            ),
+            (
+                Err(UninitializedLocal),
+                [
+                    vec![Instr::Block(BlockType::Empty, set.clone())],
+                    get.clone(),
+                ]
+                .concat(),
+            ),
+            (
+                Err(UninitializedLocal),
+                vec![
+                    Instr::I32Const(1),
+                    Instr::If(BlockType::Empty, set, get.clone()),
+                ],
+            ),
+        ];
+        for (result, body) in bodies {
+            module.funcs[0].body = body;
+            assert_eq!(result, validate(&module));
+        }
+        module.funcs[0].locals = vec![func_ref(true, 1)];
+        assert_eq!(Ok(()), validate(&module));
+
+        // Nullable references have to be checked before they are used as
+        // non-nullable ones.
+        module.funcs[0].locals = vec![func_ref(true, 1), func_ref(false, 1)];
+        let bodies = [
+            (
+                Err(TypeMismatch),
+                vec![Instr::LocalGet(0), Instr::LocalSet(1)],
+            ),
+            (
+                Ok(()),
+                vec![Instr::LocalGet(0), Instr::RefAsNonNull, Instr::LocalSet(1)],
+            ),
+            (
+                Ok(()),
+                vec![Instr::Block(
+                    BlockType::Empty,
+                    vec![Instr::LocalGet(0), Instr::BrOnNull(0), Instr::LocalSet(1)],
+                )],
+            ),
+            (
+                Err(TypeMismatch),
+                vec![Instr::RefNull(HeapType::Type(1)), Instr::LocalSet(1)],
+            ),
+            // References to other types don't match, even if non-nullable.
+            (
+                Err(TypeMismatch),
+                vec![
+                    Instr::RefNull(HeapType::Type(0)),
+                    Instr::RefAsNonNull,
+                    Instr::LocalSet(1),
+                ],
+            ),
+        ];
+        for (result, body) in bodies {
+            module.funcs[0].body = body;
+            assert_eq!(result, validate(&module));
+        }
+        module.funcs[0].body = vec![];
+
+        module.funcs[0].locals = vec![func_ref(true, 2)];
+        assert_eq!(Err(UnknownType), validate(&module));
+        module.funcs[0].locals = vec![];
+        let invalid = [
+            Instr::CallRef(2),
+            Instr::ReturnCallRef(2),
+            Instr::RefNull(HeapType::Type(2)),
+            Instr::Select(Some(func_ref(false, 2))),
+        ];
+        for instr in invalid {
+            module.funcs[0].body = vec![instr];
+            assert_eq!(Err(UnknownType), validate(&module));
+        }
+        module.funcs[0].body = vec![Instr::ReturnCallRef(1)];
+        assert_eq!(Err(TailCallTypeMismatch), validate(&module));
+        module.funcs[0].body = vec![Instr::RefNull(HeapType::Type(0)), Instr::ReturnCallRef(0)];
+        assert_eq!(Ok(()), validate(&module));
+
+        // Only nullable globals can be initialized with null, and functions
+        // have to be of the referenced type.
+        let globals = [
+            (Ok(()), func_ref(true, 1), Instr::RefNull(HeapType::Type(1))),
+            (
+                Err(TypeMismatch),
+                func_ref(true, 1),
+                Instr::RefNull(HeapType::Func),
+            ),
+            (
+                Err(TypeMismatch),
+                func_ref(false, 1),
+                Instr::RefNull(HeapType::Type(1)),
+            ),
+            (
+                Err(UnknownType),
+                func_ref(true, 1),
+                Instr::RefNull(HeapType::Type(2)),
+            ),
+            (Ok(()), func_ref(false, 0), Instr::RefFunc(0)),
+            (Err(TypeMismatch), func_ref(false, 1), Instr::RefFunc(0)),
+        ];
+        for (result, value_type, init) in globals {
+            module.globals = vec![Global {
+                g_type: GlobalType {
+                    value_type,
+                    mutable: false,
+                },
+                init: vec![init],
+            }];
+            assert_eq!(result, validate(&module));
+        }
+    }
+
+    #[test]
+    fn validate_memory() {
+        let load = Instr::I64Load(MemArg {
+            align: 3,
+            offset: 0,
+            memory: 0,
+        });
+        let mut module = module(vec![], vec![Instr::I32Const(0), load, Instr::Drop]);
+        assert_eq!(Err(UnknownMemory), validate(&module));
+
+        let memory = |min, max| Memory {
+            limits: Limits { min, max },
+            shared: false,
+            memory64: false,
+        };
+        module.memories = vec![memory(1, Some(1))];
+        assert_eq!(Ok(()), validate(&module));
+
+        module.funcs[0].body = vec![
+            Instr::I32Const(0),
+            Instr::I32Load16U(MemArg {
+                align: 2,
+                offset: 0,
+                memory: 0,
+            }),
+        ];
+        assert_eq!(Err(InvalidAlignment), validate(&module));
+
+        module.funcs[0].body = vec![Instr::MemorySize(0), Instr::Drop];
+        module.memories = vec![memory(2, Some(1))];
+        assert_eq!(Err(InvalidLimits), validate(&module));
+        module.memories = vec![memory(MAX_PAGES + 1, None)];
+        assert_eq!(Err(InvalidLimits), validate(&module));
+        module.memories = vec![];
+        assert_eq!(Err(UnknownMemory), validate(&module));
+    }
+
+    #[test]
+    fn validate_multiple_memories() {
+        let memory = Memory {
+            limits: Limits { min: 1, max: None },
+            shared: false,
+            memory64: false,
+        };
+        let load = |memory| {
+            Instr::I32Load(MemArg {
+                align: 2,
+                offset: 0,
+                memory,
+            })
+        };
+        let mut module = module(vec![], vec![Instr::I32Const(0), load(1), Instr::Drop]);
+        module.memories = vec![memory, memory];
+        assert_eq!(Ok(()), validate(&module));
+
+        let invalid = [
+            load(2),
+            Instr::MemorySize(2),
+            Instr::MemoryGrow(2),
+            Instr::MemoryFill(2),
+            Instr::MemoryCopy(0, 2),
+            Instr::MemoryCopy(2, 1),
+            Instr::AtomicLoad(
+                AtomicWidth::I32,
+                MemArg {
+                    align: 2,
+                    offset: 0,
+                    memory: 2,
+                },
+            ),
+        ];
+        for instr in invalid {
+            module.funcs[0].body = vec![instr];
+            assert_eq!(Err(UnknownMemory), validate(&module));
+        }
+    }
+
+    #[test]
+    fn validate_memory64() {
+        let load = Instr::I64Load(MemArg {
+            align: 3,
+            offset: 1 << 32,
+            memory: 0,
+        });
+        let mut module = module(vec![], vec![Instr::I64Const(0), load.clone(), Instr::Drop]);
+        let memory = |min, memory64| Memory {
+            limits: Limits { min, max: None },
+            shared: false,
+            memory64,
+        };
+        module.memories = vec![memory(1, false)];
+        assert_eq!(Err(OffsetTooLarge), validate(&module));
+        module.memories = vec![memory(1, true)];
+        assert_eq!(Ok(()), validate(&module));
+        // 64-bit memories are addressed by `i64` operands.
+        module.funcs[0].body = vec![Instr::I32Const(0), load.clone(), Instr::Drop];
+        assert_eq!(Err(TypeMismatch), validate(&module));
+        module.funcs[0].body = vec![Instr::I64Const(0), load, Instr::Drop];
+
+        module.memories = vec![memory(MAX_PAGES + 1, true)];
+        assert_eq!(Ok(()), validate(&module));
+        module.memories = vec![memory(MAX_PAGES64 + 1, true)];
+        assert_eq!(Err(InvalidLimits), validate(&module));
+    }
+
+    #[test]
+    fn validate_simd() {
+        let memarg = |align| MemArg {
+            align,
+            offset: 0,
+            memory: 0,
+        };
+        let body = vec![Instr::I32Const(0), Instr::V128Load(memarg(4)), Instr::Drop];
+        let mut module = module(vec![], body);
+        assert_eq!(Err(UnknownMemory), validate(&module));
+        module.memories = vec![Memory {
+            limits: Limits { min: 1, max: None },
+            shared: false,
+            memory64: false,
+        }];
+        assert_eq!(Ok(()), validate(&module));
+
+        let invalid = [
+            (Instr::V128Load(memarg(5)), InvalidAlignment),
+            (Instr::V128Load64Zero(memarg(4)), InvalidAlignment),
+            (Instr::V128Load16Lane(memarg(2), 0), InvalidAlignment),
+            (Instr::V128Store32Lane(memarg(2), 4), InvalidLaneIndex),
+            (Instr::I8x16ExtractLaneS(16), InvalidLaneIndex),
+            (Instr::F64x2ReplaceLane(2), InvalidLaneIndex),
+            (Instr::I8x16Shuffle([32; 16]), InvalidLaneIndex),
+        ];
+        for (instr, error) in invalid {
+            module.funcs[0].body = vec![instr];
+            assert_eq!(Err(error), validate(&module));
+        }
+        module.funcs[0].body = vec![
+            Instr::I32Const(0),
+            Instr::V128Const(0),
+            Instr::V128Const(0),
+            Instr::I8x16Shuffle([31; 16]),
+            Instr::V128Store64Lane(memarg(3), 1),
+            Instr::V128Const(0),
+            Instr::I16x8ExtractLaneU(7),
+            Instr::Drop,
+        ];
+        assert_eq!(Ok(()), validate(&module));
+    }
+
+    #[test]
+    fn validate_tail_calls() {
+        let mut module = module(vec![], vec![Instr::ReturnCall(0)]);
+        assert_eq!(Ok(()), validate(&module));
+
+        // Callees have to return the results of the caller.
+        module.types.push((vec![], vec![ValueType::I32]));
+        module.funcs.push(Func {
+            f_type: 1,
+            locals: vec![],
+            body: vec![Instr::ReturnCall(1)],
+        });
+        assert_eq!(Ok(()), validate(&module));
+        module.funcs[0].body = vec![Instr::ReturnCall(1)];
+        assert_eq!(Err(TailCallTypeMismatch), validate(&module));
+        module.funcs[0].body = vec![Instr::ReturnCall(2)];
+        assert_eq!(Err(UnknownFunc), validate(&module));
+
+        module.funcs[0].body = vec![Instr::I32Const(0), Instr::ReturnCallIndirect(0, 0)];
+        assert_eq!(Err(UnknownTable), validate(&module));
+        module.tables = vec![Table {
+            limits: Limits { min: 1, max: None },
+            ref_type: RefType::FuncRef,
+        }];
+        assert_eq!(Ok(()), validate(&module));
+        module.funcs[0].body = vec![Instr::I32Const(0), Instr::ReturnCallIndirect(1, 0)];
+        assert_eq!(Err(TailCallTypeMismatch), validate(&module));
+        module.funcs[0].body = vec![Instr::I32Const(0), Instr::ReturnCallIndirect(2, 0)];
+        assert_eq!(Err(UnknownType), validate(&module));
+    }
+
+    #[test]
+    fn validate_exceptions() {
+        let try_table = |catch| Instr::TryTable(BlockType::Empty, vec![catch], vec![]);
+        let mut module = module(vec![], vec![Instr::Throw(0)]);
+        assert_eq!(Err(UnknownTag), validate(&module));
+        module.tags = vec![0];
+        assert_eq!(Ok(()), validate(&module));
+
+        module.funcs[0].body = vec![try_table(Catch::TagRef(1, 0))];
+        assert_eq!(Err(UnknownTag), validate(&module));
+        // The label of a catch clause takes the caught exception.
+        module.funcs[0].body = vec![try_table(Catch::AllRef(0))];
+        assert_eq!(Err(TypeMismatch), validate(&module));
+        module.funcs[0].body = vec![
+            Instr::Block(
+                BlockType::Value(ValueType::ExnRef),
+                vec![try_table(Catch::AllRef(0)), Instr::RefNull(HeapType::Exn)],
+            ),
+            Instr::Drop,
+        ];
+        assert_eq!(Ok(()), validate(&module));
+        module.funcs[0].body = vec![Instr::TryTable(
+            BlockType::Empty,
+            vec![],
+            vec![Instr::Throw(1)],
+        )];
+        assert_eq!(Err(UnknownTag), validate(&module));
+
+        module.exports = vec![Export {
+            name: "e".to_string(),
+            e_desc: EDesc::TagExport(1),
+        }];
+        module.funcs[0].body = vec![];
+        assert_eq!(Err(UnknownTag), validate(&module));
+        module.exports.clear();
+
+        // Tags can only have parameters.
+        module.types.push((vec![], vec![ValueType::I32]));
+        module.tags = vec![1];
+        assert_eq!(Err(InvalidTagType), validate(&module));
+        module.tags = vec![2];
+        assert_eq!(Err(UnknownType), validate(&module));
+    }
+
+    #[test]
+    fn validate_atomics() {
+        let memarg = |align| MemArg {
+            align,
+            offset: 0,
+            memory: 0,
+        };
+        let load = Instr::AtomicLoad(AtomicWidth::I64U16, memarg(1));
+        let mut module = module(
+            vec![],
+            vec![Instr::AtomicFence, Instr::I32Const(0), load, Instr::Drop],
+        );
+        assert_eq!(Err(UnknownMemory), validate(&module));
+        let memory = |max, shared| Memory {
+            limits: Limits { min: 1, max },
+            shared,
+            memory64: false,
+        };
+        module.memories = vec![memory(Some(1), true)];
+        assert_eq!(Ok(()), validate(&module));
+        module.memories = vec![memory(None, false)];
+        assert_eq!(Ok(()), validate(&module));
+        module.memories = vec![memory(None, true)];
+        assert_eq!(Err(UnboundedSharedMemory), validate(&module));
+        module.memories = vec![memory(Some(1), true)];
+
+        // Atomic accesses have to be naturally aligned.
+        let invalid = [
+            Instr::AtomicLoad(AtomicWidth::I32, memarg(1)),
+            Instr::AtomicStore(AtomicWidth::I64U8, memarg(1)),
+            Instr::AtomicRmw(AtomicRmwOp::Cmpxchg, AtomicWidth::I64, memarg(2)),
+            Instr::MemoryAtomicWait64(memarg(2)),
+        ];
+        for instr in invalid {
+            module.funcs[0].body = vec![instr];
+            assert_eq!(Err(InvalidAlignment), validate(&module));
+        }
+        module.funcs[0].body = vec![
+            Instr::I32Const(0),
+            Instr::I32Const(1),
+            Instr::MemoryAtomicNotify(memarg(2)),
+            Instr::I64Const(0),
+            Instr::I64Const(-1),
+            Instr::MemoryAtomicWait64(memarg(3)),
+            Instr::I64Const(1),
+            Instr::AtomicRmw(AtomicRmwOp::Xchg, AtomicWidth::I64U32, memarg(2)),
+            Instr::Drop,
+        ];
+        assert_eq!(Ok(()), validate(&module));
+    }
+
+    #[test]
+    fn validate_data() {
+        let mut module = module(vec![], vec![Instr::DataDrop(0)]);
+        assert_eq!(Err(UnknownData), validate(&module));
+
+        module.datas = vec![Data {
+            init: vec![1],
+            mode: DataMode::Passive,
+        }];
+        assert_eq!(Ok(()), validate(&module));
+
+        module.funcs[0].body = vec![
+            Instr::I32Const(0),
+            Instr::I32Const(0),
+            Instr::I32Const(1),
+            Instr::MemoryFill(0),
+        ];
+        assert_eq!(Err(UnknownMemory), validate(&module));
+        module.memories = vec![Memory {
+            limits: Limits { min: 1, max: None },
+            shared: false,
+            memory64: false,
+        }];
+        assert_eq!(Ok(()), validate(&module));
+
+        module.datas[0].mode = DataMode::Active {
+            memory: 0,
+            offset: vec![Instr::I32Const(0), Instr::I32Const(1), Instr::I32Add],
+        };
+        assert_eq!(Err(InvalidConstExpr), validate(&module));
+        module.datas[0].mode = DataMode::Active {
+            memory: 1,
+            offset: vec![Instr::I32Const(0)],
+        };
+        assert_eq!(Err(UnknownMemory), validate(&module));
+    }
+
+    #[test]
+    fn validate_tables() {
+        let mut module = module(vec![], vec![Instr::I32Const(0), Instr::CallIndirect(0, 0)]);
+        assert_eq!(Err(UnknownTable), validate(&module));
+
+        let table = |min, max| Table {
+            limits: Limits { min, max },
+            ref_type: RefType::FuncRef,
+        };
+        module.tables = vec![table(1, None)];
+        assert_eq!(Ok(()), validate(&module));
+        module.tables = vec![table(2, Some(1))];
+        assert_eq!(Err(InvalidLimits), validate(&module));
+        module.tables = vec![table(0, None), table(0, None)];
+        assert_eq!(Ok(()), validate(&module));
+        module.tables = vec![table(1, None)];
+
+        module.funcs[0].body = vec![Instr::I32Const(0), Instr::CallIndirect(1, 0)];
+        assert_eq!(Err(UnknownType), validate(&module));
+        module.funcs[0].body = vec![];
+
+        module.elems = vec![Elem {
+            ref_type: RefType::FuncRef,
+            init: vec![vec![Instr::RefFunc(0)]],
+            mode: ElemMode::Active {
+                table: 0,
+                offset: vec![Instr::I32Const(0)],
+            },
+        }];
+        assert_eq!(Ok(()), validate(&module));
+        module.elems[0].init = vec![vec![Instr::RefFunc(1)]];
+        assert_eq!(Err(UnknownFunc), validate(&module));
+        module.elems[0].init = vec![vec![Instr::RefNull(HeapType::Extern)]];
+        assert_eq!(Err(TypeMismatch), validate(&module));
+        module.elems[0].init = vec![vec![Instr::RefFunc(0), Instr::Drop]];
+        assert_eq!(Err(InvalidConstExpr), validate(&module));
+        module.elems[0].init = vec![vec![Instr::RefNull(HeapType::Func)]];
+        assert_eq!(Ok(()), validate(&module));
+        module.elems[0].init = vec![vec![Instr::RefFunc(0)]];
+        module.elems[0].mode = ElemMode::Active {
+            table: 1,
+            offset: vec![Instr::I32Const(0)],
+        };
+        assert_eq!(Err(UnknownTable), validate(&module));
+        module.elems[0].mode = ElemMode::Declarative;
+        assert_eq!(Ok(()), validate(&module));
+
+        module.exports = vec![Export {
+            name: "t".to_string(),
+            e_desc: EDesc::TableExport(1),
+        }];
+        assert_eq!(Err(UnknownTable), validate(&module));
+    }
+
+    #[test]
+    fn validate_references() {
+        let mut module = module(vec![], vec![Instr::RefFunc(0), Instr::Drop]);
+        assert_eq!(Err(UndeclaredFuncRef), validate(&module));
+        module.exports = vec![Export {
+            name: "f".to_string(),
+            e_desc: EDesc::FuncExport(0),
+        }];
+        assert_eq!(Ok(()), validate(&module));
+        module.funcs[0].body = vec![Instr::RefFunc(1), Instr::Drop];
+        assert_eq!(Err(UnknownFunc), validate(&module));
+
+        let table = |ref_type| Table {
+            limits: Limits { min: 1, max: None },
+            ref_type,
+        };
+        module.tables = vec![table(RefType::ExternRef), table(RefType::FuncRef)];
+        module.elems = vec![Elem {
+            ref_type: RefType::FuncRef,
+            init: vec![vec![Instr::RefFunc(0)]],
+            mode: ElemMode::Passive,
+        }];
+        let table_instrs = vec![
+            Instr::TableSize(1),
+            Instr::Drop,
+            Instr::ElemDrop(0),
+            Instr::I32Const(0),
+            Instr::I32Const(0),
+            Instr::I32Const(0),
+            Instr::TableInit(1, 0),
+        ];
+        module.funcs[0].body = table_instrs;
+        assert_eq!(Ok(()), validate(&module));
+
+        module.funcs[0].body = vec![Instr::TableGet(2)];
+        assert_eq!(Err(UnknownTable), validate(&module));
+        module.funcs[0].body = vec![Instr::ElemDrop(1)];
+        assert_eq!(Err(UnknownElem), validate(&module));
+        module.funcs[0].body = vec![Instr::TableInit(0, 0)];
+        assert_eq!(Err(RefTypeMismatch), validate(&module));
+        module.funcs[0].body = vec![Instr::TableCopy(0, 1)];
+        assert_eq!(Err(RefTypeMismatch), validate(&module));
+        module.funcs[0].body = vec![Instr::I32Const(0), Instr::CallIndirect(0, 0)];
+        assert_eq!(Err(RefTypeMismatch), validate(&module));
+
+        module.funcs[0].body = vec![];
+        module.elems[0].mode = ElemMode::Active {
+            table: 0,
+            offset: vec![Instr::I32Const(0)],
+        };
+        assert_eq!(Err(RefTypeMismatch), validate(&module));
+    }
+
+    #[test]
+    fn validate_imports() {
+        let import = |desc| Import {
+            module: "env".to_string(),
+            name: "x".to_string(),
+            desc,
+        };
+        let mut module = module(vec![], vec![Instr::Call(1)]);
+        assert_eq!(Err(UnknownFunc), validate(&module));
+
+        module.imports = vec![import(ImportDesc::Func(0))];
+        assert_eq!(Ok(()), validate(&module));
+
+        module.imports = vec![import(ImportDesc::Func(1))];
+        assert_eq!(Err(UnknownType), validate(&module));
+
+        let global = GlobalType {
+            value_type: ValueType::I32,
+            mutable: true,
+        };
+        module.imports = vec![
+            import(ImportDesc::Func(0)),
+            import(ImportDesc::Global(global)),
+        ];
+        module.funcs[0].body = vec![Instr::I32Const(1), Instr::GlobalSet(0)];
+        assert_eq!(Ok(()), validate(&module));
+
+        let memory = Memory {
+            limits: Limits { min: 1, max: None },
+            shared: false,
+            memory64: false,
+        };
+        module.imports = vec![import(ImportDesc::Memory(memory))];
+        module.memories = vec![memory];
+        module.funcs[0].body = vec![Instr::MemorySize(1), Instr::Drop];
+        assert_eq!(Ok(()), validate(&module));
+        module.funcs[0].body = vec![Instr::MemorySize(2), Instr::Drop];
+        assert_eq!(Err(UnknownMemory), validate(&module));
+    }
+
+    #[test]
+    fn validate_block_types() {
+        let body = vec![Instr::Block(BlockType::TypeIdx(0), vec![])];
+        assert_eq!(Ok(()), validate(&module(vec![], body)));
+
+        let body = vec![Instr::Loop(BlockType::TypeIdx(1), vec![])];
+        assert_eq!(Err(UnknownType), validate(&module(vec![], body)));
+    }
+
+    #[test]
+    fn validate_nesting_depth() {
+        let nested = |n| (0..n).fold(vec![], |body, _| vec![Instr::Block(BlockType::Empty, body)]);
+        let body = nested(MAX_NESTING_DEPTH - 1);
+        assert_eq!(Ok(()), validate(&module(vec![], body)));
+
+        let body = nested(MAX_NESTING_DEPTH);
+        assert_eq!(Err(NestingTooDeep), validate(&module(vec![], body)));
+    }
+
+    #[test]
+    fn validate_start() {
+        let mut module = module(vec![], vec![]);
+        module.start = Some(0);
+        assert_eq!(Ok(()), validate(&module));
+
+        module.start = Some(1);
+        assert_eq!(Err(UnknownFunc), validate(&module));
+
+        module.start = Some(0);
+        module.types[0] = (vec![], vec![ValueType::I32]);
+        module.funcs[0].body = vec![Instr::I32Const(0)];
+        assert_eq!(Err(InvalidStartFunction), validate(&module));
+    }
+}
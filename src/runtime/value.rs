@@ -0,0 +1,126 @@
+use crate::ast::{Module, ValueType};
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+/// An opaque reference to an object of the host, which wasm code can only
+/// pass around.
+#[derive(Clone)]
+pub struct ExternRef(Arc<dyn Any + Send + Sync>);
+
+impl ExternRef {
+    pub fn new<T: Any + Send + Sync>(object: T) -> Self {
+        Self(Arc::new(object))
+    }
+
+    /// Address of the referenced object, which identifies it.
+    pub(crate) fn as_ptr(&self) -> *const () {
+        Arc::as_ptr(&self.0) as *const ()
+    }
+
+    /// The referenced object, if it is a `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.0.downcast_ref()
+    }
+}
+
+/// References are equal if they refer to the same object.
+impl PartialEq for ExternRef {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for ExternRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ExternRef({:p})", Arc::as_ptr(&self.0))
+    }
+}
+
+/// An exception thrown by `throw`, which is identified by the index of its
+/// tag in the module that threw it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Exception {
+    pub tag: usize,
+    /// The payload, whose types are the parameters of the tag type.
+    pub values: Vec<Value>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    V128(u128),
+    /// A function index or `None` for a null reference.
+    FuncRef(Option<usize>),
+    ExternRef(Option<ExternRef>),
+    ExnRef(Option<Arc<Exception>>),
+}
+
+impl Value {
+    /// The default value of a local of type `value_type`. Locals of a
+    /// non-nullable type are null until they are set.
+    pub fn zero(value_type: &ValueType) -> Self {
+        match value_type {
+            ValueType::I32 => Value::I32(0),
+            ValueType::I64 => Value::I64(0),
+            ValueType::F32 => Value::F32(0.0),
+            ValueType::F64 => Value::F64(0.0),
+            ValueType::V128 => Value::V128(0),
+            ValueType::FuncRef | ValueType::Ref { .. } => Value::FuncRef(None),
+            ValueType::ExternRef => Value::ExternRef(None),
+            ValueType::ExnRef => Value::ExnRef(None),
+        }
+    }
+
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            Value::I32(_) => ValueType::I32,
+            Value::I64(_) => ValueType::I64,
+            Value::F32(_) => ValueType::F32,
+            Value::F64(_) => ValueType::F64,
+            Value::V128(_) => ValueType::V128,
+            Value::FuncRef(_) => ValueType::FuncRef,
+            Value::ExternRef(_) => ValueType::ExternRef,
+            Value::ExnRef(_) => ValueType::ExnRef,
+        }
+    }
+
+    /// Whether the value can be passed as a `value_type` of `module`. A
+    /// function reference has to refer to a function of `module` and is
+    /// also a reference to its type, unless it is null and the type isn't
+    /// nullable.
+    pub fn matches(&self, value_type: &ValueType, module: &Module) -> bool {
+        match (self, value_type) {
+            (Value::FuncRef(Some(func)), ValueType::FuncRef) => *func < module.func_types().len(),
+            (Value::FuncRef(Some(func)), ValueType::Ref { type_idx, .. }) => {
+                match module.func_types().get(*func) {
+                    Some(func_type) => module.types[*func_type] == module.types[*type_idx],
+                    None => false,
+                }
+            }
+            (Value::FuncRef(None), ValueType::Ref { nullable, .. }) => *nullable,
+            _ => self.value_type() == *value_type,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::I32(v) => write!(f, "{}", v),
+            Value::I64(v) => write!(f, "{}", v),
+            Value::F32(v) => write!(f, "{}", v),
+            Value::F64(v) => write!(f, "{}", v),
+            Value::V128(v) => write!(f, "{:#034x}", v),
+            Value::FuncRef(Some(idx)) => write!(f, "funcref {}", idx),
+            Value::ExternRef(Some(_)) => write!(f, "externref"),
+            Value::ExnRef(Some(exn)) => write!(f, "exnref {}", exn.tag),
+            Value::FuncRef(None) | Value::ExternRef(None) | Value::ExnRef(None) => {
+                write!(f, "null")
+            }
+        }
+    }
+}